@@ -4,6 +4,28 @@ const API_BASE = '';
 let authToken = localStorage.getItem('authToken');
 // 当前用户信息
 let currentUserRole = null;
+// 当前用户的权限（如 document.write、user.manage）
+let currentPermissions = [];
+// 角色显示名称
+const ROLE_LABELS = {
+    admin: '👑 超级管理员',
+    knowledge_admin: '📚 知识库管理员',
+    support_agent: '🎧 客服',
+    editor: '✏️ 编辑',
+    user: '👤 普通用户',
+    viewer: '👁️ 只读',
+};
+
+function hasPermission(permission) {
+    return currentPermissions.includes(permission);
+}
+
+// 角色下拉选项
+function roleOptions(selected) {
+    return Object.entries(ROLE_LABELS)
+        .map(([value, label]) => `<option value="${value}" ${value === selected ? 'selected' : ''}>${label}</option>`)
+        .join('');
+}
 // 当前选中的文档ID
 let currentDocumentId = null;
 
@@ -38,8 +60,15 @@ async function checkAuth() {
         }
 
         const userData = await response.json();
+        // 需要先修改密码或绑定两步验证，回到登录页完成
+        if (userData.must_change_password || userData.mfa_enrollment_required) {
+            window.location.href = '/login';
+            return false;
+        }
+
         // 保存用户角色
         currentUserRole = userData.role;
+        currentPermissions = userData.permissions || [];
         localStorage.setItem('userRole', userData.role);
         
         // 更新用户信息显示
@@ -70,7 +99,7 @@ function hasSpecialAccess() {
 
 // 更新UI权限显示
 function updateUIPermissions() {
-    const isAdmin = currentUserRole === 'admin';
+    const canManageUsers = hasPermission('user.manage');
     const hasSpecial = hasSpecialAccess();
     
     // 管理员或有特殊访问权限的用户可以看到这些功能
@@ -84,34 +113,31 @@ function updateUIPermissions() {
     if (preambleNavBtn) {
         preambleNavBtn.style.display = canAccessAdmin ? 'block' : 'none';
     }
-    // 用户管理只有真正的管理员才能看到
+    // 用户管理需要 user.manage 权限
     if (usersNavBtn) {
-        usersNavBtn.style.display = isAdmin ? 'block' : 'none';
+        usersNavBtn.style.display = canManageUsers ? 'block' : 'none';
     }
 }
 
 // 登出
-function logout() {
-    localStorage.removeItem('authToken');
-    localStorage.removeItem('username');
-    localStorage.removeItem('userRole');
+async function logout() {
+    await window.rigAuth.logout();
     window.location.href = '/login';
 }
 
 // 显示不同的页面部分
 function showSection(sectionName) {
     // 权限检查
-    const isAdmin = currentUserRole === 'admin';
     const hasSpecial = hasSpecialAccess();
     
-    // Preamble 配置：管理员或有特殊访问权限可以访问
-    if (sectionName === 'preamble' && !isAdmin && !hasSpecial) {
+    // Preamble 配置：有 preamble.write 权限或特殊访问权限可以访问
+    if (sectionName === 'preamble' && !hasPermission('preamble.write') && !hasSpecial) {
         showAlert('您没有权限访问此功能', 'error');
         return;
     }
     
-    // 用户管理：只有管理员可以访问
-    if (sectionName === 'users' && !isAdmin) {
+    // 用户管理：需要 user.manage 权限
+    if (sectionName === 'users' && !hasPermission('user.manage')) {
         showAlert('您没有权限访问此功能', 'error');
         return;
     }
@@ -195,67 +221,6 @@ function showAlert(message, type = 'success') {
     }, 3000);
 }
 
-// 显示密钥输入模态框
-function showSecretKeyModal(callback) {
-    const modal = document.createElement('div');
-    modal.className = 'modal-backdrop';
-    modal.style.cssText = `
-        position: fixed;
-        top: 0;
-        left: 0;
-        width: 100%;
-        height: 100%;
-        background: rgba(0,0,0,0.5);
-        display: flex;
-        justify-content: center;
-        align-items: center;
-        z-index: 1000;
-    `;
-    
-    modal.innerHTML = `
-        <div class="modal-content" style="background: white; padding: 30px; border-radius: 15px; width: 90%; max-width: 400px;">
-            <h3 style="margin-bottom: 20px;">🔑 验证</h3>
-            <form id="secretKeyForm">
-                <div class="form-group">
-                    <input type="password" id="secretKeyInput" class="form-control" 
-                           placeholder="请输入密钥" required autofocus>
-                </div>
-                <div>
-                    <button type="submit" class="btn btn-primary">✅ 确认</button>
-                    <button type="button" class="btn btn-secondary modal-cancel-btn">❌ 取消</button>
-                </div>
-            </form>
-        </div>
-    `;
-    
-    const cancelBtn = modal.querySelector('.modal-cancel-btn');
-    cancelBtn.addEventListener('click', () => modal.remove());
-    
-    modal.addEventListener('click', (e) => {
-        if (e.target === modal) {
-            modal.remove();
-        }
-    });
-    
-    modal.querySelector('.modal-content').addEventListener('click', (e) => {
-        e.stopPropagation();
-    });
-    
-    modal.querySelector('#secretKeyForm').addEventListener('submit', (e) => {
-        e.preventDefault();
-        const secretKey = document.getElementById('secretKeyInput').value.trim();
-        modal.remove();
-        callback(secretKey);
-    });
-    
-    document.body.appendChild(modal);
-    
-    // 自动聚焦到输入框
-    setTimeout(() => {
-        document.getElementById('secretKeyInput').focus();
-    }, 100);
-}
-
 // 加载文档列表
 async function loadDocuments(page = 0) {
     const loading = document.getElementById('documentsLoading');
@@ -619,9 +584,12 @@ async function loadPreamble() {
         
         const data = await response.json();
         document.getElementById('preambleContent').value = data.content;
+        document.getElementById('preambleApprovalHint').style.display = data.require_approval ? 'block' : 'none';
         
         loading.style.display = 'none';
         form.style.display = 'block';
+        loadPreambleDrafts();
+        loadPreambleVersions();
         
     } catch (error) {
         loading.style.display = 'none';
@@ -629,6 +597,248 @@ async function loadPreamble() {
     }
 }
 
+// 加载Preamble历史版本
+async function loadPreambleVersions() {
+    const container = document.getElementById('preambleVersions');
+    try {
+        const response = await fetch(`${API_BASE}/api/preamble/versions`, {
+            headers: getAuthHeaders(),
+        });
+        if (!response.ok) {
+            container.innerHTML = '';
+            return;
+        }
+
+        const versions = await response.json();
+        if (versions.length === 0) {
+            container.innerHTML = '<p style="color: #6c757d;">暂无历史版本，保存后开始记录。</p>';
+            return;
+        }
+
+        const rows = versions.map(v => `
+            <tr style="border-bottom: 1px solid #e9ecef;">
+                <td style="padding: 10px; font-weight: 600;">v${v.version}</td>
+                <td style="padding: 10px;">${escapeHtml(v.author)}</td>
+                <td style="padding: 10px;">${escapeHtml(v.comment || '')}</td>
+                <td style="padding: 10px;">${new Date(v.created_at).toLocaleString('zh-CN')}</td>
+                <td style="padding: 10px;">
+                    ${v.live
+                        ? '<span style="color: #28a745; font-weight: 600;">✅ 当前版本</span>'
+                        : `<button class="btn btn-secondary" onclick="showPreambleDiff(${v.version})">🔍 差异</button>
+                           <button class="btn btn-primary" onclick="rollbackPreamble(${v.version})">⏪ 回滚</button>`}
+                </td>
+            </tr>
+        `).join('');
+
+        container.innerHTML = `
+            <h3 style="margin-bottom: 15px;">🕘 历史版本</h3>
+            <table style="width: 100%; border-collapse: collapse;">
+                <thead>
+                    <tr style="background: #f8f9fa; text-align: left;">
+                        <th style="padding: 10px;">版本</th>
+                        <th style="padding: 10px;">作者</th>
+                        <th style="padding: 10px;">说明</th>
+                        <th style="padding: 10px;">时间</th>
+                        <th style="padding: 10px;">操作</th>
+                    </tr>
+                </thead>
+                <tbody>${rows}</tbody>
+            </table>
+        `;
+    } catch (error) {
+        container.innerHTML = '';
+    }
+}
+
+// 显示差异模态框
+function showDiffModal(title, diff) {
+    const modal = document.createElement('div');
+    modal.className = 'modal-backdrop';
+    modal.style.cssText = 'position: fixed; top: 0; left: 0; width: 100%; height: 100%; background: rgba(0,0,0,0.5); display: flex; justify-content: center; align-items: center; z-index: 1000;';
+    modal.innerHTML = `
+        <div class="modal-content" style="background: white; padding: 30px; border-radius: 15px; width: 90%; max-width: 900px;">
+            <h3 style="margin-bottom: 20px;">🔍 ${escapeHtml(title)}</h3>
+            <pre style="max-height: 60vh; overflow: auto; background: #f8f9fa; padding: 15px; border-radius: 8px; white-space: pre-wrap;">${escapeHtml(diff || '内容相同')}</pre>
+            <button type="button" class="btn btn-secondary modal-cancel-btn">关闭</button>
+        </div>
+    `;
+    modal.querySelector('.modal-cancel-btn').addEventListener('click', () => modal.remove());
+    modal.addEventListener('click', (e) => {
+        if (e.target === modal) {
+            modal.remove();
+        }
+    });
+    document.body.appendChild(modal);
+}
+
+// 显示历史版本与当前版本的差异
+async function showPreambleDiff(version) {
+    try {
+        const response = await fetch(`${API_BASE}/api/preamble/diff?from=${version}`, {
+            headers: getAuthHeaders(),
+        });
+        if (!response.ok) {
+            throw new Error('获取版本差异失败');
+        }
+        const data = await response.json();
+        showDiffModal(`v${data.from} → v${data.to}`, data.diff);
+    } catch (error) {
+        showAlert(error.message, 'error');
+    }
+}
+
+// 回滚到历史版本（保存为新版本并立即生效，需要审批时提交草稿）
+async function rollbackPreamble(version) {
+    if (!confirm(`确定要回滚到 v${version} 吗？`)) {
+        return;
+    }
+
+    try {
+        const response = await fetch(`${API_BASE}/api/preamble/versions/${version}/rollback`, {
+            method: 'POST',
+            headers: getAuthHeaders(),
+            body: JSON.stringify({}),
+        });
+
+        if (response.status === 403) {
+            showAlert('❌ 无权限回滚配置', 'error');
+            return;
+        }
+
+        if (!response.ok) {
+            throw new Error('回滚Preamble失败');
+        }
+
+        if (response.status === 202) {
+            showAlert(`📝 已提交回滚到 v${version} 的草稿，等待其他管理员发布`);
+            loadPreambleDrafts();
+            return;
+        }
+
+        showAlert(`✅ 已回滚到 v${version}`);
+        loadPreamble();
+    } catch (error) {
+        showAlert(error.message, 'error');
+    }
+}
+
+// 待审批的草稿，按 id 缓存用于查看差异
+let preambleDrafts = {};
+
+// 加载待审批的Preamble草稿
+async function loadPreambleDrafts() {
+    const container = document.getElementById('preambleDrafts');
+    try {
+        const response = await fetch(`${API_BASE}/api/preamble/drafts`, {
+            headers: getAuthHeaders(),
+        });
+        if (!response.ok) {
+            container.innerHTML = '';
+            return;
+        }
+
+        const drafts = await response.json();
+        preambleDrafts = Object.fromEntries(drafts.map(d => [d.id, d]));
+        if (drafts.length === 0) {
+            container.innerHTML = '';
+            return;
+        }
+
+        const rows = drafts.map(d => `
+            <tr style="border-bottom: 1px solid #e9ecef;">
+                <td style="padding: 10px; font-weight: 600;">#${d.id}</td>
+                <td style="padding: 10px;">${escapeHtml(d.author)}</td>
+                <td style="padding: 10px;">${escapeHtml(d.comment || '')}</td>
+                <td style="padding: 10px;">${new Date(d.created_at).toLocaleString('zh-CN')}</td>
+                <td style="padding: 10px;">
+                    <button class="btn btn-secondary" onclick="showDraftDiff(${d.id})">🔍 差异</button>
+                    ${d.can_publish
+                        ? `<button class="btn btn-primary" onclick="publishPreambleDraft(${d.id})">🚀 发布</button>`
+                        : ''}
+                    <button class="btn btn-danger" onclick="rejectPreambleDraft(${d.id})">${d.can_publish ? '❎ 拒绝' : '↩️ 撤回'}</button>
+                </td>
+            </tr>
+        `).join('');
+
+        container.innerHTML = `
+            <h3 style="margin-bottom: 15px;">📝 待审批草稿</h3>
+            <table style="width: 100%; border-collapse: collapse;">
+                <thead>
+                    <tr style="background: #f8f9fa; text-align: left;">
+                        <th style="padding: 10px;">草稿</th>
+                        <th style="padding: 10px;">提交者</th>
+                        <th style="padding: 10px;">说明</th>
+                        <th style="padding: 10px;">时间</th>
+                        <th style="padding: 10px;">操作</th>
+                    </tr>
+                </thead>
+                <tbody>${rows}</tbody>
+            </table>
+        `;
+    } catch (error) {
+        container.innerHTML = '';
+    }
+}
+
+// 显示草稿与当前版本的差异
+function showDraftDiff(id) {
+    const draft = preambleDrafts[id];
+    if (draft) {
+        showDiffModal(`草稿 #${id}`, draft.diff);
+    }
+}
+
+// 发布草稿（必须由提交者以外的管理员发布）
+async function publishPreambleDraft(id) {
+    if (!confirm(`确定要发布草稿 #${id} 吗？发布后立即生效。`)) {
+        return;
+    }
+
+    try {
+        const response = await fetch(`${API_BASE}/api/preamble/drafts/${id}/publish`, {
+            method: 'POST',
+            headers: getAuthHeaders(),
+        });
+
+        if (response.status === 403) {
+            showAlert('❌ 不能发布自己提交的草稿', 'error');
+            return;
+        }
+
+        if (!response.ok) {
+            throw new Error('发布草稿失败');
+        }
+
+        showAlert(`✅ 草稿 #${id} 已发布`);
+        loadPreamble();
+    } catch (error) {
+        showAlert(error.message, 'error');
+    }
+}
+
+// 拒绝或撤回草稿
+async function rejectPreambleDraft(id) {
+    if (!confirm(`确定要拒绝草稿 #${id} 吗？`)) {
+        return;
+    }
+
+    try {
+        const response = await fetch(`${API_BASE}/api/preamble/drafts/${id}/reject`, {
+            method: 'POST',
+            headers: getAuthHeaders(),
+        });
+
+        if (!response.ok) {
+            throw new Error('拒绝草稿失败');
+        }
+
+        showAlert(`✅ 草稿 #${id} 已拒绝`);
+        loadPreambleDrafts();
+    } catch (error) {
+        showAlert(error.message, 'error');
+    }
+}
+
 // 验证文件类型
 function validateFileType(file) {
     const allowedTypes = ['.txt', '.md', '.json', '.csv', '.pdf', '.docx', '.xlsx'];
@@ -823,7 +1033,7 @@ async function loadUsers() {
             
             const createdAt = new Date(user.created_at).toLocaleString('zh-CN');
             const updatedAt = new Date(user.updated_at).toLocaleString('zh-CN');
-            const roleText = user.role === 'admin' ? '👑 管理员' : '👤 用户';
+            const roleText = ROLE_LABELS[user.role] || user.role;
             
             // 状态显示
             const statusBadge = user.status === 1 
@@ -888,8 +1098,7 @@ function showCreateUserModal() {
                 <div class="form-group">
                     <label>角色：</label>
                     <select id="newRole" class="form-control">
-                        <option value="user">👤 普通用户</option>
-                        <option value="admin">👑 管理员</option>
+                        ${roleOptions('viewer')}
                     </select>
                 </div>
                 <div class="form-group">
@@ -988,8 +1197,7 @@ async function editUser(id) {
                     <div class="form-group">
                         <label>角色：</label>
                         <select id="editRole" class="form-control">
-                            <option value="user" ${user.role === 'user' ? 'selected' : ''}>👤 普通用户</option>
-                            <option value="admin" ${user.role === 'admin' ? 'selected' : ''}>👑 管理员</option>
+                            ${roleOptions(user.role)}
                         </select>
                     </div>
                     <div class="form-group">
@@ -1097,44 +1305,53 @@ document.addEventListener('DOMContentLoaded', async function() {
         e.preventDefault();
         
         const content = document.getElementById('preambleContent').value.trim();
+        const comment = document.getElementById('preambleComment').value.trim();
         
         if (!content) {
             showAlert('请输入Preamble内容', 'error');
             return;
         }
         
-        // 弹出密钥输入框
-        showSecretKeyModal(async (secretKey) => {
-            if (!secretKey) {
-                showAlert('请输入验证密钥', 'error');
+        try {
+            const response = await fetch(`${API_BASE}/api/preamble`, {
+                method: 'PUT',
+                headers: getAuthHeaders(),
+                body: JSON.stringify({ 
+                    content,
+                    comment: comment || null,
+                }),
+            });
+            
+            if (response.status === 403) {
+                showAlert('❌ 无权限保存配置', 'error');
                 return;
             }
             
-            try {
-                const response = await fetch(`${API_BASE}/api/preamble`, {
-                    method: 'PUT',
-                    headers: getAuthHeaders(),
-                    body: JSON.stringify({ 
-                        content,
-                        secret_key: secretKey 
-                    }),
-                });
-                
-                if (response.status === 403) {
-                    showAlert('❌ 验证失败，无权限保存配置', 'error');
-                    return;
-                }
-                
-                if (!response.ok) {
-                    throw new Error('保存Preamble配置失败');
-                }
-                
-                showAlert('✅ Preamble配置保存成功！');
-                
-            } catch (error) {
-                showAlert(error.message, 'error');
+            // 未知的模板变量
+            if (response.status === 400) {
+                const data = await response.json().catch(() => ({}));
+                showAlert(`❌ ${data.error || 'Preamble内容无效'}`, 'error');
+                return;
             }
-        });
+            
+            if (!response.ok) {
+                throw new Error('保存Preamble配置失败');
+            }
+            
+            document.getElementById('preambleComment').value = '';
+            // 需要审批时只提交了草稿
+            if (response.status === 202) {
+                showAlert('📝 已提交草稿，等待其他管理员发布');
+                loadPreambleDrafts();
+                return;
+            }
+            
+            showAlert('✅ Preamble配置保存成功！');
+            loadPreambleVersions();
+            
+        } catch (error) {
+            showAlert(error.message, 'error');
+        }
     });
     
     // 绑定创建文档表单提交
@@ -0,0 +1,93 @@
+// 登录会话管理：access token 过期后自动用刷新 token 换取新 token
+(function () {
+    const originalFetch = window.fetch.bind(window);
+    // 并发请求共用一次刷新，避免重复使用同一个刷新 token
+    let refreshing = null;
+
+    function saveTokens(data) {
+        localStorage.setItem('authToken', data.token);
+        localStorage.setItem('refreshToken', data.refresh_token);
+        localStorage.setItem('username', data.username);
+        localStorage.setItem('userRole', data.role);
+    }
+
+    function clearTokens() {
+        localStorage.removeItem('authToken');
+        localStorage.removeItem('refreshToken');
+        localStorage.removeItem('username');
+        localStorage.removeItem('userRole');
+    }
+
+    async function refreshAccessToken() {
+        const refreshToken = localStorage.getItem('refreshToken');
+        if (!refreshToken) {
+            return null;
+        }
+
+        const response = await originalFetch('/api/auth/refresh', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ refresh_token: refreshToken }),
+        });
+        if (!response.ok) {
+            clearTokens();
+            return null;
+        }
+
+        const data = await response.json();
+        saveTokens(data);
+        return data.token;
+    }
+
+    // 请求总是带上最新的 access token
+    function withToken(init, token) {
+        const headers = new Headers((init && init.headers) || {});
+        if (!headers.has('Authorization')) {
+            return null;
+        }
+        headers.set('Authorization', `Bearer ${token}`);
+        return { ...init, headers };
+    }
+
+    window.fetch = async function (input, init) {
+        const url = typeof input === 'string' ? input : input.url;
+        const token = localStorage.getItem('authToken');
+        const authInit = token ? withToken(init, token) : null;
+        const response = await originalFetch(input, authInit || init);
+
+        const isSessionCall = /\/api\/auth\/(login|refresh|logout|password|totp)/.test(url);
+        if (response.status !== 401 || !authInit || isSessionCall) {
+            return response;
+        }
+
+        refreshing = refreshing || refreshAccessToken().finally(() => {
+            refreshing = null;
+        });
+        const newToken = await refreshing;
+        if (!newToken) {
+            return response;
+        }
+        return originalFetch(input, withToken(init, newToken));
+    };
+
+    window.rigAuth = {
+        saveTokens,
+        clearTokens,
+        // 退出登录：服务端作废刷新 token
+        async logout() {
+            const refreshToken = localStorage.getItem('refreshToken');
+            clearTokens();
+            if (refreshToken) {
+                try {
+                    await originalFetch('/api/auth/logout', {
+                        method: 'POST',
+                        headers: { 'Content-Type': 'application/json' },
+                        body: JSON.stringify({ refresh_token: refreshToken }),
+                    });
+                } catch (error) {
+                    console.error('Logout error:', error);
+                }
+            }
+        },
+    };
+})();
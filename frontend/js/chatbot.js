@@ -782,6 +782,8 @@ class RigChat {
                 method: 'POST',
                 headers: {
                     'Content-Type': 'application/json',
+                    // 匿名会话 token，服务端据此按访客限流
+                    ...(userId ? { 'X-Chat-Session': userId } : {}),
                 },
                 body: JSON.stringify({ 
                     message,
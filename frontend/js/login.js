@@ -1,9 +1,13 @@
+// 已启用两步验证但未提交验证码时服务端返回的错误
+const MFA_CODE_REQUIRED = 'Two-factor code required';
+
 // Login handling
 document.getElementById('loginForm').addEventListener('submit', async (e) => {
     e.preventDefault();
     
     const username = document.getElementById('username').value.trim();
     const password = document.getElementById('password').value;
+    const otp = document.getElementById('otp').value.trim();
     const loginBtn = document.getElementById('loginBtn');
     const alertBox = document.getElementById('alertBox');
     
@@ -22,7 +26,7 @@ document.getElementById('loginForm').addEventListener('submit', async (e) => {
             headers: {
                 'Content-Type': 'application/json',
             },
-            body: JSON.stringify({ username, password }),
+            body: JSON.stringify({ username, password, otp: otp || undefined }),
         });
         
         const data = await response.json();
@@ -30,8 +34,18 @@ document.getElementById('loginForm').addEventListener('submit', async (e) => {
         if (response.ok) {
             // 保存token到localStorage
             localStorage.setItem('authToken', data.token);
+            localStorage.setItem('refreshToken', data.refresh_token);
             localStorage.setItem('username', data.username);
             localStorage.setItem('userRole', data.role);
+
+            if (data.must_change_password) {
+                showPasswordForm(password);
+                return;
+            }
+            if (data.mfa_enrollment_required) {
+                showEnrollForm();
+                return;
+            }
             
             showAlert('登录成功！正在跳转...', 'success');
             
@@ -39,6 +53,13 @@ document.getElementById('loginForm').addEventListener('submit', async (e) => {
             setTimeout(() => {
                 window.location.href = '/admin';
             }, 500);
+        } else if (data.error === MFA_CODE_REQUIRED) {
+            // 密码正确，显示验证码输入框后重新提交
+            document.getElementById('otpGroup').style.display = 'block';
+            document.getElementById('otp').focus();
+            showAlert('请输入两步验证码', 'error');
+            loginBtn.disabled = false;
+            loginBtn.innerHTML = '登录';
         } else {
             showAlert(data.error || '登录失败，请检查用户名和密码', 'error');
             loginBtn.disabled = false;
@@ -52,6 +73,174 @@ document.getElementById('loginForm').addEventListener('submit', async (e) => {
     }
 });
 
+// 需要修改密码时切换到修改密码表单
+function showPasswordForm(currentPassword) {
+    document.getElementById('loginForm').style.display = 'none';
+    document.getElementById('ssoBtn').style.display = 'none';
+    document.getElementById('passwordForm').style.display = 'block';
+    document.getElementById('currentPassword').value = currentPassword || '';
+    document.getElementById(currentPassword ? 'newPassword' : 'currentPassword').focus();
+}
+
+document.getElementById('passwordForm').addEventListener('submit', async (e) => {
+    e.preventDefault();
+
+    const currentPassword = document.getElementById('currentPassword').value;
+    const newPassword = document.getElementById('newPassword').value;
+    const confirmPassword = document.getElementById('confirmPassword').value;
+    const passwordBtn = document.getElementById('passwordBtn');
+
+    if (newPassword !== confirmPassword) {
+        showAlert('两次输入的新密码不一致', 'error');
+        return;
+    }
+
+    passwordBtn.disabled = true;
+    try {
+        const response = await fetch('/api/auth/password', {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+                'Authorization': `Bearer ${localStorage.getItem('authToken')}`,
+            },
+            body: JSON.stringify({
+                current_password: currentPassword,
+                new_password: newPassword,
+            }),
+        });
+        const data = await response.json();
+
+        if (response.ok) {
+            rigAuth.saveTokens(data);
+            if (data.mfa_enrollment_required) {
+                document.getElementById('passwordForm').style.display = 'none';
+                showEnrollForm();
+                return;
+            }
+            showAlert('密码已修改！正在跳转...', 'success');
+            setTimeout(() => {
+                window.location.href = '/admin';
+            }, 500);
+        } else {
+            showAlert(data.error || '修改密码失败', 'error');
+            passwordBtn.disabled = false;
+        }
+    } catch (error) {
+        console.error('Change password error:', error);
+        showAlert('网络错误，请稍后重试', 'error');
+        passwordBtn.disabled = false;
+    }
+});
+
+// 管理员尚未绑定两步验证：生成密钥并显示绑定表单
+async function showEnrollForm() {
+    document.getElementById('loginForm').style.display = 'none';
+    document.getElementById('ssoBtn').style.display = 'none';
+    document.getElementById('enrollForm').style.display = 'block';
+
+    try {
+        const response = await fetch('/api/auth/totp/setup', {
+            method: 'POST',
+            headers: {
+                'Authorization': `Bearer ${localStorage.getItem('authToken')}`,
+            },
+        });
+        const data = await response.json();
+        if (!response.ok) {
+            showAlert(data.error || '生成两步验证密钥失败', 'error');
+            return;
+        }
+        document.getElementById('totpSecret').value = data.secret;
+        document.getElementById('totpUri').href = data.otpauth_uri;
+        document.getElementById('enrollCode').focus();
+    } catch (error) {
+        console.error('TOTP setup error:', error);
+        showAlert('网络错误，请稍后重试', 'error');
+    }
+}
+
+document.getElementById('enrollForm').addEventListener('submit', async (e) => {
+    e.preventDefault();
+
+    const code = document.getElementById('enrollCode').value.trim();
+    const enrollBtn = document.getElementById('enrollBtn');
+
+    enrollBtn.disabled = true;
+    try {
+        const response = await fetch('/api/auth/totp/confirm', {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+                'Authorization': `Bearer ${localStorage.getItem('authToken')}`,
+            },
+            body: JSON.stringify({ code }),
+        });
+        const data = await response.json();
+
+        if (response.ok) {
+            rigAuth.saveTokens(data);
+            document.getElementById('enrollForm').style.display = 'none';
+            document.getElementById('recoveryCodes').textContent = data.recovery_codes.join('\n');
+            document.getElementById('recoveryBox').style.display = 'block';
+        } else {
+            showAlert(data.error || '验证码错误', 'error');
+            enrollBtn.disabled = false;
+        }
+    } catch (error) {
+        console.error('TOTP confirm error:', error);
+        showAlert('网络错误，请稍后重试', 'error');
+        enrollBtn.disabled = false;
+    }
+});
+
+document.getElementById('recoveryDoneBtn').addEventListener('click', () => {
+    window.location.href = '/admin';
+});
+
+// 单点登录后需要两步验证：用票据和验证码换取 token
+function showMfaForm(ticket) {
+    document.getElementById('loginForm').style.display = 'none';
+    document.getElementById('ssoBtn').style.display = 'none';
+    document.getElementById('mfaForm').style.display = 'block';
+    document.getElementById('mfaForm').dataset.ticket = ticket;
+    document.getElementById('mfaCode').focus();
+}
+
+document.getElementById('mfaForm').addEventListener('submit', async (e) => {
+    e.preventDefault();
+
+    const mfaForm = document.getElementById('mfaForm');
+    const otp = document.getElementById('mfaCode').value.trim();
+    const mfaBtn = document.getElementById('mfaBtn');
+
+    mfaBtn.disabled = true;
+    try {
+        const response = await fetch('/api/auth/login/mfa', {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({ ticket: mfaForm.dataset.ticket, otp }),
+        });
+        const data = await response.json();
+
+        if (response.ok) {
+            rigAuth.saveTokens(data);
+            showAlert('登录成功！正在跳转...', 'success');
+            setTimeout(() => {
+                window.location.href = '/admin';
+            }, 500);
+        } else {
+            showAlert(data.error || '验证码错误', 'error');
+            mfaBtn.disabled = false;
+        }
+    } catch (error) {
+        console.error('MFA login error:', error);
+        showAlert('网络错误，请稍后重试', 'error');
+        mfaBtn.disabled = false;
+    }
+});
+
 // 显示提示信息
 function showAlert(message, type) {
     const alertBox = document.getElementById('alertBox');
@@ -64,8 +253,71 @@ function showAlert(message, type) {
     }, 3000);
 }
 
+// 单点登录回调：URL fragment 中只有一次性交换码，用它领取 token，token 不会出现在地址栏和历史记录中
+function handleSsoCallback() {
+    if (!window.location.hash) {
+        return false;
+    }
+    const params = new URLSearchParams(window.location.hash.slice(1));
+    history.replaceState(null, '', window.location.pathname);
+
+    if (params.get('error')) {
+        showAlert(params.get('error'), 'error');
+        return false;
+    }
+    if (!params.get('sso_code')) {
+        return false;
+    }
+
+    completeSsoLogin(params.get('sso_code'));
+    return true;
+}
+
+async function completeSsoLogin(code) {
+    try {
+        const response = await fetch('/api/auth/oidc/token', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ code }),
+        });
+        const data = await response.json();
+        if (!response.ok) {
+            showAlert(data.error || '单点登录失败', 'error');
+            loadSsoConfig();
+            return;
+        }
+        if (data.mfa_ticket) {
+            showMfaForm(data.mfa_ticket);
+            return;
+        }
+        rigAuth.saveTokens(data);
+        window.location.href = '/admin';
+    } catch (error) {
+        console.error('SSO login error:', error);
+        showAlert('网络错误，请稍后重试', 'error');
+    }
+}
+
+// 配置了 OIDC 时显示单点登录按钮
+async function loadSsoConfig() {
+    try {
+        const response = await fetch('/api/auth/oidc/config');
+        const data = await response.json();
+        if (data.enabled) {
+            document.getElementById('ssoBtn').style.display = 'block';
+        }
+    } catch (error) {
+        console.error('SSO config error:', error);
+    }
+}
+
 // 检查是否已登录
 window.addEventListener('DOMContentLoaded', () => {
+    if (handleSsoCallback()) {
+        return;
+    }
+    loadSsoConfig();
+
     const token = localStorage.getItem('authToken');
     if (token) {
         // 验证token是否有效
@@ -76,8 +328,16 @@ window.addEventListener('DOMContentLoaded', () => {
                 'Authorization': `Bearer ${token}`,
             },
         })
-        .then(response => {
-            if (response.ok) {
+        .then(response => (response.ok ? response.json() : null))
+        .then(claims => {
+            if (!claims) {
+                return;
+            }
+            if (claims.must_change_password) {
+                showPasswordForm('');
+            } else if (claims.mfa_enrollment_required) {
+                showEnrollForm();
+            } else {
                 // token有效，直接跳转到admin页面
                 window.location.href = '/admin';
             }
@@ -0,0 +1,383 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use parking_lot::RwLock;
+use tracing::info;
+
+use super::RigAgent;
+use crate::{
+    config::AppConfig,
+    db::{DEFAULT_WORKSPACE, DocumentStore, KnowledgeBase, KnowledgeBaseStore, WorkspaceStore},
+    utils::{FileBackup, get_file_backup},
+};
+
+/// 默认知识库 id，对应环境变量中配置的集合和 preamble
+pub const DEFAULT_KNOWLEDGE_BASE: &str = "default";
+
+/// 知识库和非默认工作区的备份分别位于全局备份目录下的这两个子目录中
+const KNOWLEDGE_BASE_BACKUP_DIR: &str = "knowledge_bases";
+const WORKSPACE_BACKUP_DIR: &str = "workspaces";
+
+/// 全局知识库注册表
+static KNOWLEDGE_BASES: OnceLock<KnowledgeBaseRegistry> = OnceLock::new();
+
+/// 初始化全局知识库注册表
+pub fn init_knowledge_bases(registry: KnowledgeBaseRegistry) -> Result<()> {
+    KNOWLEDGE_BASES
+        .set(registry)
+        .map_err(|_| anyhow::anyhow!("Knowledge base registry already initialized"))
+}
+
+/// 获取全局知识库注册表
+pub fn get_knowledge_bases() -> Option<&'static KnowledgeBaseRegistry> {
+    KNOWLEDGE_BASES.get()
+}
+
+/// 已加载的知识库：独立的 agent、集合和备份目录
+pub struct KnowledgeBaseHandle {
+    pub id: String,
+    pub workspace: String,
+    pub agent: Arc<RigAgent>,
+    pub document_store: Arc<DocumentStore>,
+    pub chunk_size: usize,
+    backup: Option<FileBackup>,
+}
+
+impl KnowledgeBaseHandle {
+    /// 文件备份，默认知识库使用全局备份
+    pub fn backup(&self) -> Option<&FileBackup> {
+        match &self.backup {
+            Some(backup) => Some(backup),
+            None => get_file_backup(),
+        }
+    }
+}
+
+/// 知识库注册表，按需为每个工作区和知识库构建 agent 并缓存
+///
+/// 每个工作区有一个默认知识库；默认工作区的默认知识库即环境变量配置的集合和 preamble
+pub struct KnowledgeBaseRegistry {
+    /// 基础配置，模型参数可在运行时通过设置接口修改
+    config: RwLock<AppConfig>,
+    store: KnowledgeBaseStore,
+    workspaces: Arc<WorkspaceStore>,
+    default: Arc<KnowledgeBaseHandle>,
+    loaded: RwLock<HashMap<String, Arc<KnowledgeBaseHandle>>>,
+    loaded_workspaces: RwLock<HashMap<String, Arc<KnowledgeBaseHandle>>>,
+}
+
+impl KnowledgeBaseRegistry {
+    pub fn new(
+        config: AppConfig,
+        store: KnowledgeBaseStore,
+        workspaces: Arc<WorkspaceStore>,
+        agent: Arc<RigAgent>,
+        document_store: Arc<DocumentStore>,
+        default_chunk_size: usize,
+    ) -> Self {
+        let default = Arc::new(KnowledgeBaseHandle {
+            id: DEFAULT_KNOWLEDGE_BASE.to_string(),
+            workspace: DEFAULT_WORKSPACE.to_string(),
+            agent,
+            document_store,
+            chunk_size: default_chunk_size,
+            backup: None,
+        });
+
+        Self {
+            config: RwLock::new(config),
+            store,
+            workspaces,
+            default,
+            loaded: RwLock::new(HashMap::new()),
+            loaded_workspaces: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn store(&self) -> &KnowledgeBaseStore {
+        &self.store
+    }
+
+    pub fn config(&self) -> AppConfig {
+        self.config.read().clone()
+    }
+
+    pub fn workspaces(&self) -> &WorkspaceStore {
+        &self.workspaces
+    }
+
+    /// 获取工作区中的知识库，id 为空或 "default" 时返回工作区的默认知识库
+    ///
+    /// 工作区或知识库不存在、知识库不属于该工作区时返回 None
+    pub async fn get(
+        &self,
+        workspace: &str,
+        id: Option<&str>,
+    ) -> Result<Option<Arc<KnowledgeBaseHandle>>> {
+        let id = match id.map(str::trim).filter(|id| !id.is_empty()) {
+            None | Some(DEFAULT_KNOWLEDGE_BASE) => return self.workspace(workspace).await,
+            Some(id) => id,
+        };
+
+        if let Some(handle) = self.loaded.read().get(id) {
+            return Ok((handle.workspace == workspace).then(|| handle.clone()));
+        }
+
+        let Some(knowledge_base) = self.store.get(id).await? else {
+            return Ok(None);
+        };
+        if knowledge_base.workspace_id != workspace {
+            return Ok(None);
+        }
+
+        // 在锁外构建，并发构建时保留先插入的那个
+        let handle = Arc::new(self.build_knowledge_base(&knowledge_base).await?);
+        let mut loaded = self.loaded.write();
+        Ok(Some(loaded.entry(id.to_string()).or_insert(handle).clone()))
+    }
+
+    /// 获取工作区的默认知识库，工作区不存在时返回 None
+    pub async fn workspace(&self, workspace: &str) -> Result<Option<Arc<KnowledgeBaseHandle>>> {
+        if workspace == DEFAULT_WORKSPACE {
+            return Ok(Some(self.default.clone()));
+        }
+
+        if let Some(handle) = self.loaded_workspaces.read().get(workspace) {
+            return Ok(Some(handle.clone()));
+        }
+
+        if self.workspaces.get(workspace).await?.is_none() {
+            return Ok(None);
+        }
+
+        info!("🏢 Loading workspace: {}", workspace);
+        let config = self.app_config_for_workspace(workspace);
+        let handle = Arc::new(
+            self.build_handle(
+                DEFAULT_KNOWLEDGE_BASE,
+                workspace,
+                &config,
+                self.default.chunk_size,
+                PathBuf::from(WORKSPACE_BACKUP_DIR).join(workspace),
+            )
+            .await?,
+        );
+        let mut loaded = self.loaded_workspaces.write();
+        Ok(Some(
+            loaded
+                .entry(workspace.to_string())
+                .or_insert(handle)
+                .clone(),
+        ))
+    }
+
+    /// 修改全局的模型、温度和检索条数，已加载的 agent 在下一次请求时生效
+    ///
+    /// 知识库自己配置的模型和温度保持不变
+    pub async fn update_generation(
+        &self,
+        openai_model: &str,
+        temperature: f64,
+        top_k: Option<usize>,
+    ) -> Result<()> {
+        {
+            let mut config = self.config.write();
+            config.openai_model = openai_model.to_string();
+            config.temperature = temperature;
+            config.top_k = top_k;
+        }
+
+        let workspaces: Vec<Arc<KnowledgeBaseHandle>> = std::iter::once(self.default.clone())
+            .chain(self.loaded_workspaces.read().values().cloned())
+            .collect();
+        for handle in workspaces {
+            handle
+                .agent
+                .update_generation(&self.app_config_for_workspace(&handle.workspace));
+        }
+
+        let loaded: Vec<Arc<KnowledgeBaseHandle>> = self.loaded.read().values().cloned().collect();
+        for handle in loaded {
+            if let Some(knowledge_base) = self.store.get(&handle.id).await? {
+                handle
+                    .agent
+                    .update_generation(&self.app_config_for(&knowledge_base));
+            }
+        }
+        Ok(())
+    }
+
+    /// 已加载的知识库，未加载时返回 None（不会触发构建）
+    pub fn loaded(&self, id: &str) -> Option<Arc<KnowledgeBaseHandle>> {
+        self.loaded.read().get(id).cloned()
+    }
+
+    /// 移除缓存的知识库，下次使用时按最新配置重建
+    pub fn evict(&self, id: &str) {
+        self.loaded.write().remove(id);
+    }
+
+    /// 移除缓存的工作区及其知识库
+    pub fn evict_workspace(&self, workspace: &str) {
+        self.loaded_workspaces.write().remove(workspace);
+        self.loaded
+            .write()
+            .retain(|_, handle| handle.workspace != workspace);
+    }
+
+    /// 非默认工作区的目录（preamble 等）
+    pub fn workspace_dir(&self, workspace: &str) -> PathBuf {
+        PathBuf::from(&self.config.read().workspace_dir).join(workspace)
+    }
+
+    /// 工作区默认知识库的配置：独立的集合和 preamble 文件
+    pub fn app_config_for_workspace(&self, workspace: &str) -> AppConfig {
+        let mut config = self.config();
+        if workspace != DEFAULT_WORKSPACE {
+            config.qdrant.collection_name =
+                format!("{}_ws_{}", config.qdrant.collection_name, workspace);
+            config.preamble_file = self
+                .workspace_dir(workspace)
+                .join("preamble.md")
+                .to_string_lossy()
+                .into_owned();
+        }
+        config
+    }
+
+    /// 知识库的 preamble 文件路径
+    pub fn preamble_file(&self, id: &str) -> PathBuf {
+        PathBuf::from(&self.config.read().knowledge_base_dir)
+            .join(id)
+            .join("preamble.md")
+    }
+
+    /// 读取知识库的 preamble
+    pub async fn load_preamble(&self, id: &str) -> Option<String> {
+        tokio::fs::read_to_string(self.preamble_file(id)).await.ok()
+    }
+
+    /// 删除知识库目录（preamble 等）
+    pub async fn remove_files(&self, id: &str) -> Result<()> {
+        let dir = PathBuf::from(&self.config.read().knowledge_base_dir).join(id);
+        if dir.exists() {
+            tokio::fs::remove_dir_all(&dir)
+                .await
+                .context("Failed to remove knowledge base directory")?;
+        }
+        Ok(())
+    }
+
+    /// 删除知识库的备份目录
+    pub async fn remove_backups(&self, id: &str) -> Result<()> {
+        let Some(global) = get_file_backup() else {
+            return Ok(());
+        };
+        let dir = global.backup_dir().join(KNOWLEDGE_BASE_BACKUP_DIR).join(id);
+        if dir.exists() {
+            tokio::fs::remove_dir_all(&dir)
+                .await
+                .context("Failed to remove knowledge base backups")?;
+        }
+        Ok(())
+    }
+
+    /// 按全局备份的保留策略清理各知识库和非默认工作区的备份，返回删除的文件数
+    ///
+    /// 全局备份自身由调用方清理
+    pub async fn apply_backup_retention(&self) -> Result<usize> {
+        let Some(global) = get_file_backup() else {
+            return Ok(0);
+        };
+        let mut dirs = Vec::new();
+        for subdir in [KNOWLEDGE_BASE_BACKUP_DIR, WORKSPACE_BACKUP_DIR] {
+            let parent = global.backup_dir().join(subdir);
+            if !parent.exists() {
+                continue;
+            }
+            let mut entries = tokio::fs::read_dir(&parent)
+                .await
+                .context("Failed to read backup directory")?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(entry.path());
+                }
+            }
+        }
+
+        let mut deleted = 0;
+        for dir in dirs {
+            deleted += FileBackup::new(dir)
+                .apply_retention(global.retention())
+                .await?;
+        }
+        Ok(deleted)
+    }
+
+    /// 以工作区配置为基础，覆盖知识库自己的集合、preamble 和模型参数
+    pub fn app_config_for(&self, knowledge_base: &KnowledgeBase) -> AppConfig {
+        let mut config = self.app_config_for_workspace(&knowledge_base.workspace_id);
+        config.qdrant.collection_name = knowledge_base.collection_name.clone();
+        config.preamble_file = self
+            .preamble_file(&knowledge_base.id)
+            .to_string_lossy()
+            .into_owned();
+        if let Some(temperature) = knowledge_base.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(model) = &knowledge_base.openai_model {
+            config.openai_model = model.clone();
+        }
+        config
+    }
+
+    async fn build_knowledge_base(
+        &self,
+        knowledge_base: &KnowledgeBase,
+    ) -> Result<KnowledgeBaseHandle> {
+        info!("📚 Loading knowledge base: {}", knowledge_base.id);
+        self.build_handle(
+            &knowledge_base.id,
+            &knowledge_base.workspace_id,
+            &self.app_config_for(knowledge_base),
+            knowledge_base.chunk_size.max(1) as usize,
+            PathBuf::from(KNOWLEDGE_BASE_BACKUP_DIR).join(&knowledge_base.id),
+        )
+        .await
+    }
+
+    /// `backup_subdir` 为全局备份目录下的子目录，避免与默认知识库的备份混在一起
+    async fn build_handle(
+        &self,
+        id: &str,
+        workspace: &str,
+        config: &AppConfig,
+        chunk_size: usize,
+        backup_subdir: PathBuf,
+    ) -> Result<KnowledgeBaseHandle> {
+        let agent = RigAgent::new_from_config(config).await?;
+
+        let backup = match get_file_backup() {
+            Some(global) => {
+                let backup = FileBackup::new(global.backup_dir().join(backup_subdir))
+                    .with_retention(global.retention().clone());
+                backup.init().await?;
+                Some(backup)
+            }
+            None => None,
+        };
+
+        Ok(KnowledgeBaseHandle {
+            id: id.to_string(),
+            workspace: workspace.to_string(),
+            agent: Arc::new(agent),
+            document_store: Arc::new(DocumentStore::with_config(&config.qdrant)),
+            chunk_size,
+            backup,
+        })
+    }
+}
@@ -1,5 +1,16 @@
+mod generation;
+mod knowledge_base;
+mod preamble_template;
+mod prompt_inspector;
 mod rig_agent;
 mod rig_agent_builder;
 
-pub use rig_agent::RigAgent;
+pub use generation::GenerationParams;
+pub use knowledge_base::*;
+pub use preamble_template::{
+    PREAMBLE_VARIABLES, PreambleVariables, language_tag, render_preamble, uses_variable,
+    validate_preamble,
+};
+pub use prompt_inspector::{PromptInspection, PromptMessage, RetrievedChunk};
+pub use rig_agent::{AgentOverrides, RetrievalIndex, RigAgent};
 pub use rig_agent_builder::RigAgentBuilder;
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, anyhow};
+use rig::{
+    completion::{Completion, CompletionModel, Message},
+    message::{AssistantContent, UserContent},
+    prelude::CompletionClient,
+};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use super::{PreambleVariables, RetrievalIndex, RigAgent, render_preamble};
+use crate::db::{Document, DocumentStore, DocumentViewer, MetadataFilter};
+
+/// 检索到的分块及相似度
+#[derive(Debug, Clone, Serialize)]
+pub struct RetrievedChunk {
+    pub score: f64,
+    pub id: String,
+    pub base_id: String,
+    pub chunk_index: Option<u32>,
+    pub source: String,
+    pub content: String,
+}
+
+/// 实际发送给模型的一条消息
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// 一次检索调试的完整结果
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptInspection {
+    pub model: String,
+    pub temperature: Option<f64>,
+    pub top_k: usize,
+    pub retrieved: Vec<RetrievedChunk>,
+    /// 按发送顺序排列：system(preamble) → user(dynamic_context 文档) → 历史 → 问题
+    pub messages: Vec<PromptMessage>,
+    pub answer: String,
+}
+
+impl PromptMessage {
+    fn from_message(message: &Message) -> Self {
+        match message {
+            Message::User { content } => PromptMessage {
+                role: "user".to_string(),
+                content: content
+                    .iter()
+                    .filter_map(|c| match c {
+                        UserContent::Text(text) => Some(text.text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+            Message::Assistant { content, .. } => PromptMessage {
+                role: "assistant".to_string(),
+                content: content
+                    .iter()
+                    .filter_map(|c| match c {
+                        AssistantContent::Text(text) => Some(text.text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            },
+        }
+    }
+}
+
+impl RigAgent {
+    /// 检索调试：用当前配置构建临时 agent，返回检索结果、组装后的提示词和模型回答
+    ///
+    /// 不替换正在使用的 agent，也不写入任何会话记录；preamble 中的变量按传入的取值渲染
+    pub async fn inspect(
+        &self,
+        question: &str,
+        history: Vec<Message>,
+        preamble: Option<String>,
+        filter: &MetadataFilter,
+        viewer: &DocumentViewer,
+        variables: &PreambleVariables,
+    ) -> anyhow::Result<PromptInspection> {
+        let index = self.retrieval_index().await;
+        let mut context = self.context.read().clone();
+        if let Some(preamble) = preamble {
+            context.preamble = preamble;
+        }
+        context.preamble = render_preamble(&context.preamble, variables);
+
+        // 只检索一次：检索结果既用于展示，也作为文档附加到请求中，与 dynamic_context 的格式一致
+        let (retrieved, documents, top_k) = match index {
+            RetrievalIndex::Vector(index) => {
+                let top_k = match context.top_k {
+                    Some(top_k) => top_k,
+                    None => context.collection_size().await,
+                }
+                .max(1);
+                let index = index
+                    .with_filter(filter.clone())
+                    .with_viewer(viewer.clone());
+                let store: DocumentStore = DocumentStore::with_config(&context.qdrant_config);
+                let mut retrieved = Vec::new();
+                let mut documents = Vec::new();
+                for (score, id, payload) in store.search(&index, question, top_k).await? {
+                    let text = serde_json::to_string_pretty(&payload)
+                        .unwrap_or_else(|_| payload.to_string());
+                    let doc: Document = serde_json::from_value(payload)
+                        .context("Failed to parse retrieved document")?;
+                    retrieved.push(RetrievedChunk {
+                        score,
+                        id: doc.id,
+                        base_id: doc.base_id,
+                        chunk_index: doc.chunk_index,
+                        source: doc.source,
+                        content: doc.content,
+                    });
+                    documents.push(rig::completion::Document {
+                        id,
+                        text,
+                        additional_props: HashMap::new(),
+                    });
+                }
+                (retrieved, documents, top_k)
+            }
+            RetrievalIndex::Unavailable => {
+                warn!("No vector index available for inspection");
+                (Vec::new(), Vec::new(), 0)
+            }
+        };
+
+        // 与 chat 相同的 agent 配置构建请求，检索结果作为文档附加，不再重复检索
+        let request = context
+            .build_basic()
+            .completion(question, history)
+            .await
+            .map_err(|e| anyhow!("Failed to build completion request: {}", e))?
+            .documents(documents)
+            .build();
+
+        let mut messages = Vec::new();
+        if let Some(preamble) = &request.preamble {
+            messages.push(PromptMessage {
+                role: "system".to_string(),
+                content: preamble.clone(),
+            });
+        }
+        if !request.documents.is_empty() {
+            messages.push(PromptMessage {
+                role: "user".to_string(),
+                content: request
+                    .documents
+                    .iter()
+                    .map(|doc| doc.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            });
+        }
+        messages.extend(request.chat_history.iter().map(PromptMessage::from_message));
+        let temperature = request.temperature;
+
+        info!(
+            "🔍 Inspecting prompt: {} retrieved chunk(s), {} message(s)",
+            request.documents.len(),
+            messages.len()
+        );
+
+        let response = context
+            .client
+            .completion_model(&context.openai_model)
+            .completions_api()
+            .completion(request)
+            .await
+            .context("Completion request failed")?;
+        let answer = response
+            .choice
+            .iter()
+            .filter_map(|c| match c {
+                AssistantContent::Text(text) => Some(text.text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("");
+
+        Ok(PromptInspection {
+            model: context.openai_model.clone(),
+            temperature,
+            top_k,
+            retrieved,
+            messages,
+            answer,
+        })
+    }
+}
@@ -1,170 +1,235 @@
-use std::sync::atomic::{AtomicPtr, Ordering};
+use std::{
+    ops::Deref,
+    pin::Pin,
+    time::{Duration, Instant},
+};
 
-use super::RigAgentBuilder;
+use super::{PreambleVariables, RigAgentBuilder, render_preamble, uses_variable};
 use crate::{
     config::{AppConfig, QdrantConfig},
-    db::{DocumentStore, SerializableQdrantVectorStore},
+    db::{DocumentStore, DocumentViewer, MetadataFilter, SerializableQdrantVectorStore},
 };
 use async_stream::stream;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
 use parking_lot::RwLock;
 use rig::{
-    agent::{Agent, MultiTurnStreamItem, Text},
-    completion::Chat,
+    agent::{Agent, AgentBuilder, MultiTurnStreamItem, Text},
+    completion::{Chat, Message},
     message::Reasoning,
     prelude::CompletionClient,
     providers::openai::{self},
     streaming::{StreamedAssistantContent, StreamingChat},
 };
 
+/// 检索使用的向量索引，集合不可用时为 `Unavailable`（使用不带检索的 agent）
+#[derive(Clone)]
+pub enum RetrievalIndex {
+    Vector(SerializableQdrantVectorStore<openai::EmbeddingModel>),
+    Unavailable,
+}
+
+/// 创建向量索引失败后的首次重试间隔，之后每次失败翻倍
+const INDEX_RETRY_MIN: Duration = Duration::from_secs(5);
+/// 重试间隔的上限
+const INDEX_RETRY_MAX: Duration = Duration::from_secs(300);
+
+/// 创建向量索引失败后的重试时间
+#[derive(Clone, Copy)]
+struct IndexRetry {
+    next_attempt: Instant,
+    delay: Duration,
+}
+
+/// 每次请求用当前 context（preamble、温度、模型）和缓存的向量索引组装 agent
+///
+/// 修改 preamble、温度或模型只需更新 context，下一次请求立即生效；
+/// 向量索引只在创建成功后缓存，Qdrant 不可用时按退避间隔重试
 pub struct RigAgent {
-    pub agent: AtomicPtr<Agent<openai::CompletionModel>>,
     pub context: RwLock<RigAgentContext>,
+    index: RwLock<Option<SerializableQdrantVectorStore<openai::EmbeddingModel>>>,
+    retry: RwLock<Option<IndexRetry>>,
 }
 
-// 显式实现 Send，因为 AtomicPtr 和 RwLock 都是 Send 的
-unsafe impl Send for RigAgent {}
-unsafe impl Sync for RigAgent {}
+/// 单次请求对 context 的覆盖（如 A/B 实验的变体），未设置的字段使用 context 中的值
+#[derive(Debug, Clone, Default)]
+pub struct AgentOverrides {
+    pub preamble: Option<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u64>,
+    pub top_k: Option<usize>,
+    /// 要求使用指定语言回复，追加在 preamble 之后
+    pub response_language: Option<String>,
+}
 
 #[derive(Clone)]
 pub struct RigAgentContext {
     pub temperature: f64,
+    /// 单次回复的最大 token 数，未设置时由模型决定
+    pub max_tokens: Option<u64>,
+    /// 每次检索的最大文档片段数，未设置时检索集合中的全部文档
+    pub top_k: Option<usize>,
     pub openai_model: String,
     pub client: openai::Client,
     pub embedding_model: openai::EmbeddingModel,
-    pub needs_rebuild: bool,
     pub qdrant_config: QdrantConfig,
     pub preamble_file: String,
     pub preamble: String,
 }
 
 impl RigAgent {
+    pub fn new(context: RigAgentContext) -> Self {
+        Self {
+            context: RwLock::new(context),
+            index: RwLock::new(None),
+            retry: RwLock::new(None),
+        }
+    }
+
     /// 从配置创建新的 RigAgent
     pub async fn new_from_config(config: &AppConfig) -> anyhow::Result<RigAgent> {
         let builder = RigAgentBuilder::from_config(config.clone());
         builder.build().await
     }
 
-    /// 动态聊天 - 使用当前最新的context构建临时agent进行聊天
+    /// 只检索公开文档的聊天
     pub async fn chat(
         &self,
         message: &str,
         history: Vec<rig::completion::Message>,
     ) -> anyhow::Result<String> {
-        // 检查是否需要重建agent
-        let needs_rebuild = {
-            let context = self.context.read();
-            context.needs_rebuild
-        };
-
-        if needs_rebuild {
-            tracing::info!("🔄 Agent needs rebuild, rebuilding with latest documents...");
-            // 重建agent以使用最新的文档
-            self.rebuild_with_sync().await?;
-        }
+        self.chat_with_filter(
+            message,
+            history,
+            &MetadataFilter::default(),
+            &DocumentViewer::Anonymous,
+            &PreambleVariables::default(),
+            &AgentOverrides::default(),
+        )
+        .await
+    }
 
-        // 使用当前（可能已重建）的agent进行聊天
-        let agent_ptr = self.agent.load(Ordering::Acquire);
-        if agent_ptr.is_null() {
-            return Err(anyhow::anyhow!("Agent not initialized"));
-        }
+    /// 只检索公开文档的流式聊天
+    pub async fn stream_chat(
+        &self,
+        message: &str,
+        history: Vec<rig::completion::Message>,
+    ) -> anyhow::Result<Pin<Box<dyn Stream<Item = String> + Send>>> {
+        self.stream_chat_with_filter(
+            message,
+            history,
+            &MetadataFilter::default(),
+            &DocumentViewer::Anonymous,
+            &PreambleVariables::default(),
+            &AgentOverrides::default(),
+        )
+        .await
+    }
 
-        // 安全地解引用原子指针
-        let agent = unsafe { &*agent_ptr };
-        let response = agent
+    /// 按元数据和访问身份限制检索范围的聊天，preamble 中的变量按本次请求渲染
+    pub async fn chat_with_filter(
+        &self,
+        message: &str,
+        history: Vec<Message>,
+        filter: &MetadataFilter,
+        viewer: &DocumentViewer,
+        variables: &PreambleVariables,
+        overrides: &AgentOverrides,
+    ) -> anyhow::Result<String> {
+        let agent = self
+            .request_agent(filter, viewer, variables, overrides)
+            .await;
+        agent
             .chat(message, history)
             .await
-            .map_err(|e| anyhow::anyhow!("Chat error: {}", e))?;
-        Ok(response)
+            .map_err(|e| anyhow::anyhow!("Chat error: {}", e))
     }
 
-    /// 动态流式聊天 - 使用当前最新的context构建临时agent进行流式聊天
-    pub async fn stream_chat(
+    /// 按元数据和访问身份限制检索范围的流式聊天
+    pub async fn stream_chat_with_filter(
         &self,
         message: &str,
-        history: Vec<rig::completion::Message>,
-    ) -> anyhow::Result<impl futures::Stream<Item = String> + Unpin> {
-        // 检查是否需要重建agent
-        let needs_rebuild = {
-            let context = self.context.read();
-            context.needs_rebuild
-        };
+        history: Vec<Message>,
+        filter: &MetadataFilter,
+        viewer: &DocumentViewer,
+        variables: &PreambleVariables,
+        overrides: &AgentOverrides,
+    ) -> anyhow::Result<Pin<Box<dyn Stream<Item = String> + Send>>> {
+        let agent = self
+            .request_agent(filter, viewer, variables, overrides)
+            .await;
+        Ok(Box::pin(text_stream(
+            Box::new(agent),
+            message.to_string(),
+            history,
+        )))
+    }
 
-        if needs_rebuild {
-            tracing::info!("🔄 Agent needs rebuild, rebuilding with latest documents...");
-            // 重建agent以使用最新的文档
-            self.rebuild_with_sync().await?;
+    /// 本次请求实际使用的 preamble 中是否用到了指定变量
+    pub fn preamble_uses(&self, overrides: &AgentOverrides, name: &str) -> bool {
+        match &overrides.preamble {
+            Some(preamble) => uses_variable(preamble, name),
+            None => uses_variable(&self.context.read().preamble, name),
         }
+    }
 
-        // 使用当前（可能已重建）的agent进行流式聊天
-        let agent_ptr = self.agent.load(Ordering::Acquire);
-        if agent_ptr.is_null() {
-            return Err(anyhow::anyhow!("Agent not initialized"));
+    /// 用当前 context 和缓存的向量索引构建本次请求的 agent
+    async fn request_agent(
+        &self,
+        filter: &MetadataFilter,
+        viewer: &DocumentViewer,
+        variables: &PreambleVariables,
+        overrides: &AgentOverrides,
+    ) -> Agent<openai::CompletionModel> {
+        let index = self.retrieval_index().await;
+        let mut context = self.context.read().clone();
+        if let Some(preamble) = &overrides.preamble {
+            context.preamble = preamble.clone();
         }
-
-        // 安全地解引用原子指针
-        let agent = unsafe { &*agent_ptr };
-        let stream_request = agent.stream_chat(message, history);
-
-        // 创建一个简化的流，将复杂的流式响应转换为简单的字符串流
-        let stream = Box::pin(stream! {
-            let mut stream = stream_request.await;
-            while let Some(content) = stream.next().await {
-                match content {
-                    Ok(MultiTurnStreamItem::StreamAssistantItem(StreamedAssistantContent::Text(Text {
-                        text,
-                    }))) => {
-                        yield text;
-                    },
-                    Ok(MultiTurnStreamItem::StreamAssistantItem(StreamedAssistantContent::Reasoning(
-                        Reasoning { reasoning, .. },
-                    ))) => {
-                        // yield reasoning.join("\n");
-                        tracing::debug!("Reasoning: {:?}", reasoning);
-                        yield "Reasoning... Please wait...".to_string();
-                    },
-                    Ok(MultiTurnStreamItem::FinalResponse(res)) => {
-                        tracing::debug!("{:?}", res);
-                    },
-                    Err(e) => {
-                        yield format!("Error: {}", e);
-                        break;
-                    },
-                    _ => {},
-                }
+        if let Some(model) = &overrides.model {
+            context.openai_model = model.clone();
+        }
+        if let Some(temperature) = overrides.temperature {
+            context.temperature = temperature;
+        }
+        if let Some(max_tokens) = overrides.max_tokens {
+            context.max_tokens = Some(max_tokens);
+        }
+        context.preamble = render_preamble(&context.preamble, variables);
+        if let Some(language) = &overrides.response_language {
+            context
+                .preamble
+                .push_str(&format!("\n\nAlways respond in {}.", language));
+        }
+        match index {
+            RetrievalIndex::Vector(index) => {
+                let top_k = match overrides.top_k.or(context.top_k) {
+                    Some(top_k) => top_k,
+                    None => context.collection_size().await,
+                };
+                context.build_with_vector_index(
+                    index
+                        .with_filter(filter.clone())
+                        .with_viewer(viewer.clone()),
+                    top_k,
+                )
             }
-        });
-
-        Ok(stream)
+            RetrievalIndex::Unavailable => context.build_basic(),
+        }
     }
 
-    /// 重新构建整个RigAgent以应用最新的配置
-    pub async fn rebuild_with_sync(&self) -> anyhow::Result<()> {
-        {
-            let preamble = load_preamble(&self.context.read().preamble_file);
-            self.context.write().preamble = preamble;
+    /// 缓存的向量索引，创建失败时不缓存，按退避间隔在之后的请求中重试
+    pub async fn retrieval_index(&self) -> RetrievalIndex {
+        if let Some(index) = self.index.read().clone() {
+            return RetrievalIndex::Vector(index);
         }
-        let new_agent = self.build_agent().await?;
-
-        // 替换 agent - 使用原子指针替换
-        let new_agent_box = Box::new(new_agent);
-        let new_agent_ptr = Box::into_raw(new_agent_box);
-
-        // 原子地替换指针
-        let old_agent_ptr = self.agent.swap(new_agent_ptr, Ordering::AcqRel);
-
-        // 清理旧的 agent（如果存在）
-        if !old_agent_ptr.is_null() {
-            let _ = unsafe { Box::from_raw(old_agent_ptr) };
+        let retry = *self.retry.read();
+        if let Some(retry) = retry
+            && Instant::now() < retry.next_attempt
+        {
+            return RetrievalIndex::Unavailable;
         }
 
-        self.context.write().needs_rebuild = false;
-        Ok(())
-    }
-
-    /// 从当前context构建agent，避免跨越await持有锁
-    async fn build_agent(&self) -> anyhow::Result<Agent<openai::CompletionModel>> {
-        // 提取构建agent所需的最小数据
         let (embedding_model, qdrant_config) = {
             let context = self.context.read();
             (
@@ -172,38 +237,108 @@ impl RigAgent {
                 context.qdrant_config.clone(),
             )
         };
-
-        let index = create_vector_index(&qdrant_config, &embedding_model).await?;
-        let context = self.context.read();
-        let agent = context.build_with_vector_index(index.0, index.1);
-        Ok(agent)
+        match create_vector_index(&qdrant_config, &embedding_model).await {
+            Ok(index) => {
+                tracing::info!("✅ Created vector index");
+                *self.index.write() = Some(index.clone());
+                *self.retry.write() = None;
+                RetrievalIndex::Vector(index)
+            }
+            Err(e) => {
+                let delay = retry
+                    .map(|r| (r.delay * 2).min(INDEX_RETRY_MAX))
+                    .unwrap_or(INDEX_RETRY_MIN);
+                tracing::info!(
+                    "ℹ️ No vector index available ({}), using basic agent, retrying in {:?}",
+                    e,
+                    delay
+                );
+                *self.retry.write() = Some(IndexRetry {
+                    next_attempt: Instant::now() + delay,
+                    delay,
+                });
+                RetrievalIndex::Unavailable
+            }
+        }
     }
 
-    pub async fn set_needs_rebuild(&self, needs_rebuild: bool) {
-        self.context.write().needs_rebuild = needs_rebuild;
+    /// 应用新的模型、温度和检索条数，下一次请求生效
+    pub fn update_generation(&self, config: &AppConfig) {
+        let mut context = self.context.write();
+        context.openai_model = config.openai_model.clone();
+        context.temperature = config.temperature;
+        context.top_k = config.top_k;
     }
 }
 
-impl Drop for RigAgent {
-    fn drop(&mut self) {
-        // 清理原子指针中的 agent
-        let agent_ptr = self.agent.swap(std::ptr::null_mut(), Ordering::AcqRel);
-        if !agent_ptr.is_null() {
-            let _ = unsafe { Box::from_raw(agent_ptr) };
+/// 将 agent 的流式响应转换为简单的字符串流
+fn text_stream<A>(
+    agent: A,
+    message: String,
+    history: Vec<Message>,
+) -> impl Stream<Item = String> + Send + Unpin
+where
+    A: Deref<Target = Agent<openai::CompletionModel>> + Send + Sync + 'static,
+{
+    Box::pin(stream! {
+        let mut stream = agent.stream_chat(&message, history).await;
+        while let Some(content) = stream.next().await {
+            match content {
+                Ok(MultiTurnStreamItem::StreamAssistantItem(StreamedAssistantContent::Text(Text {
+                    text,
+                }))) => {
+                    yield text;
+                },
+                Ok(MultiTurnStreamItem::StreamAssistantItem(StreamedAssistantContent::Reasoning(
+                    Reasoning { reasoning, .. },
+                ))) => {
+                    // yield reasoning.join("\n");
+                    tracing::debug!("Reasoning: {:?}", reasoning);
+                    yield "Reasoning... Please wait...".to_string();
+                },
+                Ok(MultiTurnStreamItem::FinalResponse(res)) => {
+                    tracing::debug!("{:?}", res);
+                },
+                Err(e) => {
+                    yield format!("Error: {}", e);
+                    break;
+                },
+                _ => {},
+            }
         }
-    }
+    })
 }
 
 impl RigAgentContext {
-    /// 构建基础 agent
-    pub fn build_basic(&self) -> Agent<openai::CompletionModel> {
-        self.client
+    fn agent_builder(&self) -> AgentBuilder<openai::CompletionModel> {
+        let builder = self
+            .client
             .completion_model(&self.openai_model)
             .completions_api()
             .into_agent_builder()
             .temperature(self.temperature) // 0.1-0.3 准确性高，0.5-0.7 创造性高
-            .preamble(&self.preamble)
-            .build()
+            .preamble(&self.preamble);
+        match self.max_tokens {
+            Some(max_tokens) => builder.max_tokens(max_tokens),
+            None => builder,
+        }
+    }
+
+    /// 构建基础 agent
+    pub fn build_basic(&self) -> Agent<openai::CompletionModel> {
+        self.agent_builder().build()
+    }
+
+    /// 未设置 top_k 时按请求时集合中的文档数检索，文档增删后无需重建索引
+    pub async fn collection_size(&self) -> usize {
+        let store: DocumentStore = DocumentStore::with_config(&self.qdrant_config);
+        match store.count_documents_async().await {
+            Ok(count) => count.max(1),
+            Err(e) => {
+                tracing::warn!("Failed to count documents for retrieval: {}", e);
+                1
+            }
+        }
     }
 
     /// 构建带有向量索引的RAG agent
@@ -213,34 +348,17 @@ impl RigAgentContext {
         top_k: usize,
     ) -> Agent<openai::CompletionModel> {
         let top_k = top_k.max(1);
-        tracing::info!("✅ Building RAG agent with vector index, top_k={}", top_k);
-        self.client
-            .completion_model(&self.openai_model)
-            .completions_api()
-            .into_agent_builder()
-            .temperature(self.temperature)
-            .preamble(&self.preamble)
+        tracing::debug!("Building RAG agent with vector index, top_k={}", top_k);
+        self.agent_builder()
             .dynamic_context(top_k, vector_index)
             .build()
     }
-
-    /// 构建带有向量索引的RAG agent
-    pub async fn build(&self) -> anyhow::Result<Agent<openai::CompletionModel>> {
-        let index = create_vector_index(&self.qdrant_config, &self.embedding_model).await?;
-        Ok(self.build_with_vector_index(index.0, index.1))
-    }
-
-    pub async fn create_vector_index(
-        &self,
-    ) -> anyhow::Result<(SerializableQdrantVectorStore<openai::EmbeddingModel>, usize)> {
-        create_vector_index(&self.qdrant_config, &self.embedding_model).await
-    }
 }
 
 pub async fn create_vector_index(
     qdrant_config: &QdrantConfig,
     embedding_model: &openai::EmbeddingModel,
-) -> anyhow::Result<(SerializableQdrantVectorStore<openai::EmbeddingModel>, usize)> {
+) -> anyhow::Result<SerializableQdrantVectorStore<openai::EmbeddingModel>> {
     let store: DocumentStore = DocumentStore::with_config(qdrant_config);
     store.create_vector_index(embedding_model.clone()).await
 }
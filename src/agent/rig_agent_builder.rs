@@ -1,6 +1,3 @@
-use std::sync::atomic::AtomicPtr;
-
-use parking_lot::RwLock;
 use rig::prelude::EmbeddingsClient;
 use rig::providers::openai::Client;
 use tracing::info;
@@ -15,8 +12,9 @@ pub struct RigAgentBuilder {
 }
 
 impl RigAgentBuilder {
-    pub fn from_env() -> RigAgentBuilder {
-        let config = AppConfig::from_env();
+    /// 使用全局配置创建
+    pub fn current() -> RigAgentBuilder {
+        let config = AppConfig::current();
         Self::from_config(config)
     }
 
@@ -53,35 +51,20 @@ impl RigAgentBuilder {
             client: client.clone(),
             embedding_model,
             temperature: self.config.temperature,
+            max_tokens: None,
+            top_k: self.config.top_k,
             openai_model: self.config.openai_model.clone(),
             qdrant_config: self.config.qdrant.clone(),
             preamble_file: self.config.preamble_file.clone(),
-            needs_rebuild: false,
             preamble: load_preamble(&self.config.preamble_file),
         };
 
-        let rag_agent = match context.build().await {
-            Ok(agent) => {
-                info!("ℹ️ Building RAG agent with vector index");
-                agent
-            }
-            Err(e) => {
-                info!("ℹ️ No vector index available ({}), using basic agent", e);
-                context.build_basic()
-            }
-        };
+        // 预先创建向量索引，集合不可用时使用不带检索的 agent
+        let agent = RigAgent::new(context);
+        agent.retrieval_index().await;
 
         info!("✅ RigAgent initialized successfully");
-
-        // 将 agent 包装在 Box 中并转换为原始指针
-        let agent_box = Box::new(rag_agent);
-        let agent_ptr = Box::into_raw(agent_box);
-        let agent_atomic = AtomicPtr::new(agent_ptr);
-
-        Ok(RigAgent {
-            agent: agent_atomic,
-            context: RwLock::new(context),
-        })
+        Ok(agent)
     }
 
     /// 初始化OpenAI客户端
@@ -91,7 +74,8 @@ impl RigAgentBuilder {
             .build()
     }
 
-    fn init_embedding_client(&self) -> rig::providers::openai::EmbeddingModel {
+    /// 初始化Embedding模型
+    pub fn init_embedding_client(&self) -> rig::providers::openai::EmbeddingModel {
         let embedding_client = Client::builder(&self.config.embedding_api_key)
             .base_url(&self.config.embedding_url)
             .build();
@@ -0,0 +1,918 @@
+use std::{collections::BTreeMap, env, str::FromStr, sync::OnceLock};
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Utc};
+use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+
+use super::QdrantConfig;
+use crate::{
+    db::{LockoutPolicy, PasswordPolicy, UserRole},
+    utils::logger::LogConfig,
+};
+
+/// 未指定 CONFIG_FILE 时使用的配置文件，不存在时全部使用默认值和环境变量
+const DEFAULT_CONFIG_FILE: &str = "data/config.toml";
+
+/// 示例中的 JWT 密钥，不能用于部署
+const DEFAULT_JWT_SECRET: &str = "your-secret-key-change-in-production";
+
+/// 示例中的默认管理员密码，不能用于部署
+const DEFAULT_ADMIN_PASSWORD: &str = "aaa111";
+
+/// 全局配置，启动时校验后初始化
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// 初始化全局配置
+pub fn init_config(config: Config) -> Result<()> {
+    CONFIG
+        .set(config)
+        .map_err(|_| anyhow::anyhow!("Config already initialized"))
+}
+
+/// 获取全局配置，未初始化时（如测试）按配置文件和环境变量读取，不做校验
+pub fn get_config() -> &'static Config {
+    CONFIG.get_or_init(|| Config::read().0)
+}
+
+/// 统一配置：先读取 TOML 配置文件，再由环境变量覆盖单个配置项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerSection,
+    pub llm: LlmSection,
+    pub embedding: EmbeddingSection,
+    pub qdrant: QdrantSection,
+    pub auth: AuthSection,
+    pub oidc: OidcSection,
+    pub chat: ChatSection,
+    pub storage: StorageSection,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSection {
+    /// 监听地址（SERVER_HOST）
+    pub host: String,
+    /// 允许跨域访问的来源，`*` 表示全部（CORS_ORIGINS），可在运行时设置中覆盖
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for ServerSection {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0:3000".to_string(),
+            cors_origins: vec!["*".to_string()],
+        }
+    }
+}
+
+/// 聊天模型（OPENAI_*、TEMPERATURE、RETRIEVAL_TOP_K、PREAMBLE_*、BUSINESS_HOURS）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmSection {
+    pub api_key: String,
+    pub base_url: String,
+    pub model: String,
+    pub temperature: f64,
+    /// 每次检索的最大文档片段数，不设置时检索集合中的全部文档
+    pub top_k: Option<usize>,
+    pub preamble_file: String,
+    /// preamble 时间变量使用的时区，如 `+08:00`，不设置时使用服务器时区
+    pub preamble_timezone: Option<String>,
+    /// preamble 变量 `{{business_hours}}` 的取值
+    pub business_hours: Option<String>,
+}
+
+impl Default for LlmSection {
+    fn default() -> Self {
+        Self {
+            api_key: String::new(),
+            base_url: String::new(),
+            model: String::new(),
+            temperature: 0.7,
+            top_k: None,
+            preamble_file: "data/preamble.md".to_string(),
+            preamble_timezone: None,
+            business_hours: None,
+        }
+    }
+}
+
+/// 嵌入模型（EMBEDDING_*），api_key 和 base_url 不设置时使用 llm 的配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbeddingSection {
+    pub api_key: Option<String>,
+    pub base_url: Option<String>,
+    pub model: String,
+}
+
+impl Default for EmbeddingSection {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            base_url: None,
+            model: "text-embedding-ada-002".to_string(),
+        }
+    }
+}
+
+/// 向量数据库（QDRANT_*）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QdrantSection {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub collection: String,
+    pub vector_size: usize,
+    /// cosine、dot、euclid 或 manhattan
+    pub distance: String,
+}
+
+impl Default for QdrantSection {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:6334".to_string(),
+            api_key: None,
+            collection: "rig_documents".to_string(),
+            vector_size: 1024,
+            distance: "cosine".to_string(),
+        }
+    }
+}
+
+/// 登录 token、默认管理员、密码策略、登录锁定和 preamble 审批
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSection {
+    pub jwt_secret: String,
+    pub access_token_minutes: i64,
+    pub refresh_token_days: i64,
+    /// 默认管理员的初始密码
+    pub default_admin_password: String,
+    /// preamble 修改需要另一位管理员审批后发布
+    pub preamble_require_approval: bool,
+    /// 拥有管理权限的角色必须启用两步验证（ADMIN_MFA_REQUIRED）
+    pub admin_mfa_required: bool,
+    pub password: PasswordPolicy,
+    pub lockout: LockoutPolicy,
+}
+
+impl AuthSection {
+    /// 默认管理员密码为空或仍是示例值，只在创建默认管理员时拒绝，其他时候仅警告
+    pub fn has_example_admin_password(&self) -> bool {
+        self.default_admin_password.is_empty()
+            || self.default_admin_password == DEFAULT_ADMIN_PASSWORD
+    }
+}
+
+impl Default for AuthSection {
+    fn default() -> Self {
+        Self {
+            jwt_secret: DEFAULT_JWT_SECRET.to_string(),
+            access_token_minutes: 15,
+            refresh_token_days: 30,
+            default_admin_password: DEFAULT_ADMIN_PASSWORD.to_string(),
+            preamble_require_approval: false,
+            admin_mfa_required: true,
+            password: PasswordPolicy::default(),
+            lockout: LockoutPolicy::default(),
+        }
+    }
+}
+
+/// OIDC 单点登录（OIDC_*），未配置 issuer 或 client_id 时不启用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OidcSection {
+    pub issuer: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: String,
+    pub redirect_url: String,
+    pub scopes: String,
+    pub username_claim: String,
+    pub role_claim: String,
+    /// 角色 claim 中包含其中任一值时映射为管理员
+    pub admin_values: Vec<String>,
+    /// 角色 claim 取值到本地角色的映射，如 `"rag-editor" = "editor"`（OIDC_ROLE_VALUES）
+    pub role_values: BTreeMap<String, UserRole>,
+    /// 同步为用户分组的 claim，不设置时不同步
+    pub groups_claim: Option<String>,
+    /// 接受的 id_token 签名算法（OIDC_ID_TOKEN_ALGS）
+    pub id_token_algorithms: Vec<String>,
+}
+
+impl Default for OidcSection {
+    fn default() -> Self {
+        Self {
+            issuer: None,
+            client_id: None,
+            client_secret: String::new(),
+            redirect_url: "http://localhost:3000/api/auth/oidc/callback".to_string(),
+            scopes: "openid profile email".to_string(),
+            username_claim: "preferred_username".to_string(),
+            role_claim: "roles".to_string(),
+            admin_values: vec!["admin".to_string()],
+            role_values: BTreeMap::new(),
+            groups_claim: None,
+            id_token_algorithms: vec!["RS256".to_string()],
+        }
+    }
+}
+
+impl OidcSection {
+    /// 同时配置了 issuer 和 client_id 时启用
+    pub fn enabled(&self) -> bool {
+        self.issuer.is_some() && self.client_id.is_some()
+    }
+}
+
+/// 聊天请求可覆盖的生成参数范围（CHAT_*）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatSection {
+    /// 允许请求指定的模型，为空时不允许覆盖模型
+    pub allowed_models: Vec<String>,
+    pub min_temperature: f64,
+    pub max_temperature: f64,
+    pub max_tokens: u64,
+    pub max_top_k: usize,
+    /// 每个 IP 每小时最多新建的匿名聊天会话数
+    pub max_sessions_per_ip: u32,
+    /// 聊天接口每隔多少秒恢复一次请求额度，可在运行时设置中覆盖
+    pub replenish_seconds: u64,
+    /// 聊天接口的突发请求数，可在运行时设置中覆盖
+    pub burst: u32,
+    /// 升级到签名会话的时间（RFC 3339），此前以原始 user_id 保存的对话可以认领一次；
+    /// 不设置时不接受原始 user_id
+    pub legacy_user_ids_before: Option<DateTime<Utc>>,
+}
+
+impl Default for ChatSection {
+    fn default() -> Self {
+        Self {
+            allowed_models: Vec::new(),
+            min_temperature: 0.0,
+            max_temperature: 1.0,
+            max_tokens: 4096,
+            max_top_k: 10,
+            max_sessions_per_ip: 20,
+            replenish_seconds: 3,
+            burst: 10,
+            legacy_user_ids_before: None,
+        }
+    }
+}
+
+/// 数据库、文件目录和备份
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageSection {
+    pub user_db_path: String,
+    pub conversation_db_path: String,
+    pub knowledge_base_db_path: String,
+    pub documents_dir: String,
+    pub knowledge_base_dir: String,
+    pub workspace_dir: String,
+    pub backup_dir: String,
+    pub backup_keep_versions: usize,
+    /// 不设置时不按时间清理备份
+    pub backup_max_age_days: Option<i64>,
+    /// 为 0 时关闭定时清理
+    pub backup_cleanup_interval_hours: u64,
+    /// 审计日志保留天数，为 0 时永久保留
+    pub audit_retention_days: u64,
+}
+
+impl Default for StorageSection {
+    fn default() -> Self {
+        Self {
+            user_db_path: "sqlite:data/users.db?mode=rwc".to_string(),
+            conversation_db_path: "sqlite:data/conversations.db?mode=rwc".to_string(),
+            knowledge_base_db_path: "sqlite:data/knowledge_bases.db?mode=rwc".to_string(),
+            documents_dir: "data/documents".to_string(),
+            knowledge_base_dir: "data/knowledge_bases".to_string(),
+            workspace_dir: "data/workspaces".to_string(),
+            backup_dir: "data/backups".to_string(),
+            backup_keep_versions: 5,
+            backup_max_age_days: None,
+            backup_cleanup_interval_hours: 24,
+            audit_retention_days: 365,
+        }
+    }
+}
+
+/// 读取环境变量覆盖配置项，记录无法解析的取值
+#[derive(Default)]
+struct EnvOverrides {
+    errors: Vec<String>,
+}
+
+impl EnvOverrides {
+    fn string(&mut self, key: &str, target: &mut String) {
+        if let Ok(value) = env::var(key) {
+            *target = value;
+        }
+    }
+
+    /// 空字符串表示不设置
+    fn optional_string(&mut self, key: &str, target: &mut Option<String>) {
+        if let Ok(value) = env::var(key) {
+            *target = Some(value).filter(|v| !v.is_empty());
+        }
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &str, target: &mut T) {
+        if let Ok(value) = env::var(key) {
+            match value.trim().parse() {
+                Ok(parsed) => *target = parsed,
+                Err(_) => self
+                    .errors
+                    .push(format!("{}: invalid value {:?}", key, value)),
+            }
+        }
+    }
+
+    /// 逗号分隔的列表，忽略空项
+    fn list(&mut self, key: &str, target: &mut Vec<String>) {
+        if let Ok(value) = env::var(key) {
+            *target = value
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+    }
+
+    /// 逗号分隔的 `键=值` 列表，忽略空项
+    fn map<T: FromStr>(&mut self, key: &str, target: &mut BTreeMap<String, T>) {
+        if let Ok(value) = env::var(key) {
+            let mut parsed = BTreeMap::new();
+            for item in value.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+                match item
+                    .split_once('=')
+                    .and_then(|(k, v)| Some((k.trim(), v.trim().parse().ok()?)))
+                {
+                    Some((k, v)) if !k.is_empty() => {
+                        parsed.insert(k.to_string(), v);
+                    }
+                    _ => {
+                        self.errors
+                            .push(format!("{}: invalid entry {:?}", key, item));
+                        return;
+                    }
+                }
+            }
+            *target = parsed;
+        }
+    }
+
+    /// 空字符串表示不设置
+    fn parse_optional<T: FromStr>(&mut self, key: &str, target: &mut Option<T>) {
+        if let Ok(value) = env::var(key) {
+            if value.trim().is_empty() {
+                *target = None;
+                return;
+            }
+            match value.trim().parse() {
+                Ok(parsed) => *target = Some(parsed),
+                Err(_) => self
+                    .errors
+                    .push(format!("{}: invalid value {:?}", key, value)),
+            }
+        }
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+impl Config {
+    /// 读取配置文件和环境变量并校验，返回全部错误
+    pub fn load() -> Result<Self, Vec<String>> {
+        let (config, errors) = Self::check();
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// 读取并校验配置，出错时同样返回读取到的配置，供 `config check` 输出
+    pub fn check() -> (Self, Vec<String>) {
+        let (config, mut errors) = Self::read();
+        if let Err(invalid) = config.validate() {
+            errors.extend(invalid);
+        }
+        (config, errors)
+    }
+
+    /// 配置文件路径（CONFIG_FILE）
+    pub fn file_path() -> String {
+        env::var("CONFIG_FILE").unwrap_or_else(|_| DEFAULT_CONFIG_FILE.to_string())
+    }
+
+    /// 读取配置文件并应用环境变量，返回配置和读取中遇到的错误
+    fn read() -> (Self, Vec<String>) {
+        let path = Self::file_path();
+        let mut errors = Vec::new();
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+                errors.push(format!("{}: {}", path, e));
+                Self::default()
+            }),
+            // 默认配置文件可以不存在，显式指定的必须存在
+            Err(_) if env::var("CONFIG_FILE").is_err() => Self::default(),
+            Err(e) => {
+                errors.push(format!("{}: {}", path, e));
+                Self::default()
+            }
+        };
+        errors.extend(config.apply_env());
+        (config, errors)
+    }
+
+    /// 用环境变量覆盖配置项，返回无法解析的环境变量
+    fn apply_env(&mut self) -> Vec<String> {
+        let mut env = EnvOverrides::default();
+
+        env.string("SERVER_HOST", &mut self.server.host);
+        env.list("CORS_ORIGINS", &mut self.server.cors_origins);
+
+        env.string("OPENAI_API_KEY", &mut self.llm.api_key);
+        env.string("OPENAI_BASE_URL", &mut self.llm.base_url);
+        env.string("OPENAI_MODEL", &mut self.llm.model);
+        env.parse("TEMPERATURE", &mut self.llm.temperature);
+        env.parse_optional("RETRIEVAL_TOP_K", &mut self.llm.top_k);
+        env.string("PREAMBLE_FILE", &mut self.llm.preamble_file);
+        env.optional_string("PREAMBLE_TIMEZONE", &mut self.llm.preamble_timezone);
+        env.optional_string("BUSINESS_HOURS", &mut self.llm.business_hours);
+
+        env.optional_string("EMBEDDING_API_KEY", &mut self.embedding.api_key);
+        env.optional_string("EMBEDDING_BASE_URL", &mut self.embedding.base_url);
+        env.string("EMBEDDING_MODEL", &mut self.embedding.model);
+
+        env.string("QDRANT_URL", &mut self.qdrant.url);
+        env.optional_string("QDRANT_API_KEY", &mut self.qdrant.api_key);
+        env.string("QDRANT_COLLECTION", &mut self.qdrant.collection);
+        env.parse("QDRANT_VECTOR_SIZE", &mut self.qdrant.vector_size);
+        env.string("QDRANT_DISTANCE", &mut self.qdrant.distance);
+
+        env.string("JWT_SECRET", &mut self.auth.jwt_secret);
+        env.parse("ACCESS_TOKEN_MINUTES", &mut self.auth.access_token_minutes);
+        env.parse("REFRESH_TOKEN_DAYS", &mut self.auth.refresh_token_days);
+        env.string(
+            "DEFAULT_ADMIN_PASSWORD",
+            &mut self.auth.default_admin_password,
+        );
+        env.parse(
+            "PREAMBLE_REQUIRE_APPROVAL",
+            &mut self.auth.preamble_require_approval,
+        );
+        env.parse("ADMIN_MFA_REQUIRED", &mut self.auth.admin_mfa_required);
+        env.parse("PASSWORD_MIN_LENGTH", &mut self.auth.password.min_length);
+        env.parse(
+            "PASSWORD_REQUIRE_LETTER",
+            &mut self.auth.password.require_letter,
+        );
+        env.parse(
+            "PASSWORD_REQUIRE_DIGIT",
+            &mut self.auth.password.require_digit,
+        );
+        env.parse(
+            "PASSWORD_REQUIRE_SYMBOL",
+            &mut self.auth.password.require_symbol,
+        );
+        env.parse(
+            "PASSWORD_REQUIRE_MIXED_CASE",
+            &mut self.auth.password.require_mixed_case,
+        );
+        env.parse("LOGIN_MAX_ATTEMPTS", &mut self.auth.lockout.max_attempts);
+        env.parse(
+            "LOGIN_LOCKOUT_SECONDS",
+            &mut self.auth.lockout.lockout_seconds,
+        );
+        env.parse(
+            "LOGIN_LOCKOUT_MAX_SECONDS",
+            &mut self.auth.lockout.max_lockout_seconds,
+        );
+        env.parse(
+            "LOGIN_ACCOUNT_MAX_ATTEMPTS",
+            &mut self.auth.lockout.account_max_attempts,
+        );
+
+        env.optional_string("OIDC_ISSUER", &mut self.oidc.issuer);
+        env.optional_string("OIDC_CLIENT_ID", &mut self.oidc.client_id);
+        env.string("OIDC_CLIENT_SECRET", &mut self.oidc.client_secret);
+        env.string("OIDC_REDIRECT_URL", &mut self.oidc.redirect_url);
+        env.string("OIDC_SCOPES", &mut self.oidc.scopes);
+        env.string("OIDC_USERNAME_CLAIM", &mut self.oidc.username_claim);
+        env.string("OIDC_ROLE_CLAIM", &mut self.oidc.role_claim);
+        env.list("OIDC_ADMIN_VALUES", &mut self.oidc.admin_values);
+        env.map("OIDC_ROLE_VALUES", &mut self.oidc.role_values);
+        env.optional_string("OIDC_GROUPS_CLAIM", &mut self.oidc.groups_claim);
+        env.list("OIDC_ID_TOKEN_ALGS", &mut self.oidc.id_token_algorithms);
+
+        env.list("CHAT_ALLOWED_MODELS", &mut self.chat.allowed_models);
+        env.parse("CHAT_MIN_TEMPERATURE", &mut self.chat.min_temperature);
+        env.parse("CHAT_MAX_TEMPERATURE", &mut self.chat.max_temperature);
+        env.parse("CHAT_MAX_TOKENS", &mut self.chat.max_tokens);
+        env.parse("CHAT_MAX_TOP_K", &mut self.chat.max_top_k);
+        env.parse(
+            "CHAT_MAX_SESSIONS_PER_IP",
+            &mut self.chat.max_sessions_per_ip,
+        );
+        env.parse("CHAT_REPLENISH_SECONDS", &mut self.chat.replenish_seconds);
+        env.parse("CHAT_BURST", &mut self.chat.burst);
+        env.parse_optional(
+            "CHAT_LEGACY_USER_IDS_BEFORE",
+            &mut self.chat.legacy_user_ids_before,
+        );
+
+        env.string("USER_DB_PATH", &mut self.storage.user_db_path);
+        env.string(
+            "CONVERSATION_DB_PATH",
+            &mut self.storage.conversation_db_path,
+        );
+        env.string(
+            "KNOWLEDGE_BASE_DB_PATH",
+            &mut self.storage.knowledge_base_db_path,
+        );
+        env.string("DOCUMENTS_DIR", &mut self.storage.documents_dir);
+        env.string("KNOWLEDGE_BASE_DIR", &mut self.storage.knowledge_base_dir);
+        env.string("WORKSPACE_DIR", &mut self.storage.workspace_dir);
+        env.string("BACKUP_DIR", &mut self.storage.backup_dir);
+        env.parse(
+            "BACKUP_KEEP_VERSIONS",
+            &mut self.storage.backup_keep_versions,
+        );
+        env.parse_optional("BACKUP_MAX_AGE_DAYS", &mut self.storage.backup_max_age_days);
+        env.parse(
+            "BACKUP_CLEANUP_INTERVAL_HOURS",
+            &mut self.storage.backup_cleanup_interval_hours,
+        );
+        env.parse(
+            "AUDIT_RETENTION_DAYS",
+            &mut self.storage.audit_retention_days,
+        );
+
+        env.string("LOG_LEVEL", &mut self.log.level);
+        env.parse("LOG_TO_FILE", &mut self.log.to_file);
+        env.parse("LOG_TO_STDOUT", &mut self.log.to_stdout);
+        env.string("LOG_FILE_PATH", &mut self.log.file_path);
+        env.string("LOG_FILE_NAME", &mut self.log.file_name);
+
+        env.errors
+    }
+
+    /// 校验全部配置项，一次返回所有错误
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut check = |valid: bool, message: &str| {
+            if !valid {
+                errors.push(message.to_string());
+            }
+        };
+
+        let port = self
+            .server
+            .host
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse::<u16>().ok());
+        check(
+            port.is_some(),
+            "server.host must be host:port (SERVER_HOST)",
+        );
+        check(
+            !self.server.cors_origins.is_empty()
+                && self
+                    .server
+                    .cors_origins
+                    .iter()
+                    .all(|origin| origin == "*" || (is_http_url(origin) && !origin.ends_with('/'))),
+            "server.cors_origins must list \"*\" or origins such as https://example.com (CORS_ORIGINS)",
+        );
+
+        check(
+            !self.llm.api_key.is_empty(),
+            "llm.api_key is required (OPENAI_API_KEY)",
+        );
+        check(
+            is_http_url(&self.llm.base_url),
+            "llm.base_url must be an http(s) URL (OPENAI_BASE_URL)",
+        );
+        check(
+            !self.llm.model.trim().is_empty(),
+            "llm.model is required (OPENAI_MODEL)",
+        );
+        check(
+            (0.0..=2.0).contains(&self.llm.temperature),
+            "llm.temperature must be within 0-2 (TEMPERATURE)",
+        );
+        check(
+            self.llm.top_k.is_none_or(|k| k > 0),
+            "llm.top_k must be at least 1 (RETRIEVAL_TOP_K)",
+        );
+        check(
+            !self.llm.preamble_file.is_empty(),
+            "llm.preamble_file is required (PREAMBLE_FILE)",
+        );
+        check(
+            self.llm
+                .preamble_timezone
+                .as_deref()
+                .is_none_or(|tz| tz.trim().parse::<FixedOffset>().is_ok()),
+            "llm.preamble_timezone must be a UTC offset such as +08:00 (PREAMBLE_TIMEZONE)",
+        );
+
+        check(
+            self.embedding.base_url.as_deref().is_none_or(is_http_url),
+            "embedding.base_url must be an http(s) URL (EMBEDDING_BASE_URL)",
+        );
+        check(
+            !self.embedding.model.trim().is_empty(),
+            "embedding.model is required (EMBEDDING_MODEL)",
+        );
+
+        check(
+            is_http_url(&self.qdrant.url),
+            "qdrant.url must be an http(s) URL (QDRANT_URL)",
+        );
+        check(
+            !self.qdrant.collection.trim().is_empty(),
+            "qdrant.collection is required (QDRANT_COLLECTION)",
+        );
+        check(
+            self.qdrant.vector_size > 0,
+            "qdrant.vector_size must be positive (QDRANT_VECTOR_SIZE)",
+        );
+        check(
+            QdrantConfig::parse_distance(&self.qdrant.distance).is_some(),
+            "qdrant.distance must be one of cosine, dot, euclid, manhattan (QDRANT_DISTANCE)",
+        );
+
+        check(
+            !self.auth.jwt_secret.is_empty(),
+            "auth.jwt_secret is required (JWT_SECRET)",
+        );
+        check(
+            self.auth.jwt_secret != DEFAULT_JWT_SECRET,
+            "auth.jwt_secret must be changed from the example value (JWT_SECRET)",
+        );
+        check(
+            self.auth.access_token_minutes > 0,
+            "auth.access_token_minutes must be positive (ACCESS_TOKEN_MINUTES)",
+        );
+        check(
+            self.auth.refresh_token_days > 0,
+            "auth.refresh_token_days must be positive (REFRESH_TOKEN_DAYS)",
+        );
+        check(
+            self.auth.password.min_length > 0,
+            "auth.password.min_length must be at least 1 (PASSWORD_MIN_LENGTH)",
+        );
+        check(
+            self.auth.lockout.max_attempts > 0,
+            "auth.lockout.max_attempts must be at least 1 (LOGIN_MAX_ATTEMPTS)",
+        );
+        check(
+            self.auth.lockout.lockout_seconds >= 0
+                && self.auth.lockout.lockout_seconds <= self.auth.lockout.max_lockout_seconds,
+            "auth.lockout must satisfy 0 <= lockout_seconds <= max_lockout_seconds (LOGIN_LOCKOUT_SECONDS, LOGIN_LOCKOUT_MAX_SECONDS)",
+        );
+        check(
+            self.auth.lockout.account_max_attempts == 0
+                || self.auth.lockout.account_max_attempts >= self.auth.lockout.max_attempts,
+            "auth.lockout.account_max_attempts must be 0 or at least max_attempts (LOGIN_ACCOUNT_MAX_ATTEMPTS)",
+        );
+
+        check(
+            self.oidc.issuer.is_some() == self.oidc.client_id.is_some(),
+            "oidc.issuer and oidc.client_id must be set together (OIDC_ISSUER, OIDC_CLIENT_ID)",
+        );
+        if self.oidc.enabled() {
+            check(
+                self.oidc.issuer.as_deref().is_some_and(is_http_url),
+                "oidc.issuer must be an http(s) URL (OIDC_ISSUER)",
+            );
+            check(
+                is_http_url(&self.oidc.redirect_url),
+                "oidc.redirect_url must be an http(s) URL (OIDC_REDIRECT_URL)",
+            );
+            check(
+                !self.oidc.username_claim.trim().is_empty(),
+                "oidc.username_claim is required (OIDC_USERNAME_CLAIM)",
+            );
+            check(
+                !self.oidc.id_token_algorithms.is_empty()
+                    && self
+                        .oidc
+                        .id_token_algorithms
+                        .iter()
+                        .all(|alg| alg.trim().parse::<Algorithm>().is_ok()),
+                "oidc.id_token_algorithms must list supported algorithms such as RS256 (OIDC_ID_TOKEN_ALGS)",
+            );
+        }
+
+        check(
+            0.0 <= self.chat.min_temperature
+                && self.chat.min_temperature <= self.chat.max_temperature
+                && self.chat.max_temperature <= 2.0,
+            "chat.min_temperature and chat.max_temperature must satisfy 0 <= min <= max <= 2 (CHAT_MIN_TEMPERATURE, CHAT_MAX_TEMPERATURE)",
+        );
+        check(
+            self.chat.max_tokens > 0,
+            "chat.max_tokens must be positive (CHAT_MAX_TOKENS)",
+        );
+        check(
+            self.chat.max_top_k > 0,
+            "chat.max_top_k must be positive (CHAT_MAX_TOP_K)",
+        );
+        check(
+            self.chat.max_sessions_per_ip > 0,
+            "chat.max_sessions_per_ip must be positive (CHAT_MAX_SESSIONS_PER_IP)",
+        );
+        check(
+            (1..=3600).contains(&self.chat.replenish_seconds),
+            "chat.replenish_seconds must be within 1-3600 (CHAT_REPLENISH_SECONDS)",
+        );
+        check(
+            (1..=1000).contains(&self.chat.burst),
+            "chat.burst must be within 1-1000 (CHAT_BURST)",
+        );
+
+        for (value, message) in [
+            (
+                &self.storage.user_db_path,
+                "storage.user_db_path is required (USER_DB_PATH)",
+            ),
+            (
+                &self.storage.conversation_db_path,
+                "storage.conversation_db_path is required (CONVERSATION_DB_PATH)",
+            ),
+            (
+                &self.storage.knowledge_base_db_path,
+                "storage.knowledge_base_db_path is required (KNOWLEDGE_BASE_DB_PATH)",
+            ),
+            (
+                &self.storage.documents_dir,
+                "storage.documents_dir is required (DOCUMENTS_DIR)",
+            ),
+            (
+                &self.storage.knowledge_base_dir,
+                "storage.knowledge_base_dir is required (KNOWLEDGE_BASE_DIR)",
+            ),
+            (
+                &self.storage.workspace_dir,
+                "storage.workspace_dir is required (WORKSPACE_DIR)",
+            ),
+            (
+                &self.storage.backup_dir,
+                "storage.backup_dir is required (BACKUP_DIR)",
+            ),
+        ] {
+            check(!value.trim().is_empty(), message);
+        }
+        check(
+            self.storage.backup_keep_versions > 0,
+            "storage.backup_keep_versions must be at least 1 (BACKUP_KEEP_VERSIONS)",
+        );
+        check(
+            self.storage.backup_max_age_days.is_none_or(|days| days > 0),
+            "storage.backup_max_age_days must be positive (BACKUP_MAX_AGE_DAYS)",
+        );
+        check(
+            self.storage.audit_retention_days <= 36_500,
+            "storage.audit_retention_days must be at most 36500 (AUDIT_RETENTION_DAYS)",
+        );
+
+        check(
+            !self.log.level.trim().is_empty(),
+            "log.level is required (LOG_LEVEL)",
+        );
+        check(
+            !self.log.to_file || !self.log.file_name.is_empty(),
+            "log.file_name is required when log.to_file is enabled (LOG_FILE_NAME)",
+        );
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// 隐藏密钥后的配置，用于 `config check` 输出
+    pub fn redacted(&self) -> Self {
+        let redact = |value: &str| {
+            if value.is_empty() {
+                String::new()
+            } else {
+                "***".to_string()
+            }
+        };
+        let mut config = self.clone();
+        config.llm.api_key = redact(&config.llm.api_key);
+        config.embedding.api_key = config.embedding.api_key.as_deref().map(redact);
+        config.qdrant.api_key = config.qdrant.api_key.as_deref().map(redact);
+        config.auth.jwt_secret = redact(&config.auth.jwt_secret);
+        config.auth.default_admin_password = redact(&config.auth.default_admin_password);
+        config.oidc.client_secret = redact(&config.oidc.client_secret);
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_file() {
+        let config: Config = toml::from_str(
+            r#"
+            [llm]
+            api_key = "sk-test"
+            base_url = "https://api.openai.com/v1"
+            model = "gpt-4o-mini"
+            top_k = 5
+
+            [auth]
+            jwt_secret = "test-secret"
+            default_admin_password = "Test-admin-1"
+
+            [auth.lockout]
+            max_attempts = 3
+
+            [oidc]
+            issuer = "https://idp.example.com"
+            client_id = "rig-rag"
+            client_secret = "oidc-secret"
+
+            [oidc.role_values]
+            "rag-editor" = "editor"
+
+            [log]
+            level = "info"
+            to_file = false
+            to_stdout = true
+            file_path = "logs"
+            file_name = "app.log"
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.llm.top_k, Some(5));
+        assert_eq!(config.storage.backup_keep_versions, 5);
+        assert_eq!(config.auth.lockout.max_attempts, 3);
+        assert_eq!(config.auth.password.min_length, 8);
+        assert_eq!(
+            config.oidc.role_values.get("rag-editor"),
+            Some(&UserRole::Editor)
+        );
+
+        let redacted = toml::to_string(&config.redacted()).unwrap();
+        assert!(!redacted.contains("sk-test"));
+        assert!(!redacted.contains("test-secret"));
+        assert!(!redacted.contains("oidc-secret"));
+
+        // 未知配置项视为错误，避免拼写错误被忽略
+        assert!(toml::from_str::<Config>("[llm]\nmodle = \"gpt-4o\"").is_err());
+    }
+
+    #[test]
+    fn test_validate_reports_all_errors() {
+        let mut config = Config::default();
+        config.qdrant.distance = "cosin".to_string();
+        config.storage.backup_keep_versions = 0;
+        config.chat.min_temperature = 1.5;
+        config.oidc.issuer = Some("https://idp.example.com".to_string());
+        let errors = config.validate().unwrap_err();
+        for key in [
+            "auth.jwt_secret",
+            "oidc.issuer",
+            "llm.api_key",
+            "llm.base_url",
+            "llm.model",
+            "qdrant.distance",
+            "chat.min_temperature",
+            "storage.backup_keep_versions",
+        ] {
+            assert!(
+                errors.iter().any(|e| e.starts_with(key)),
+                "missing error for {}",
+                key
+            );
+        }
+        // 示例管理员密码只在创建默认管理员时拒绝，不阻止已有部署启动
+        assert!(config.auth.has_example_admin_password());
+        assert!(
+            !errors
+                .iter()
+                .any(|e| e.starts_with("auth.default_admin_password"))
+        );
+    }
+}
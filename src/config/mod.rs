@@ -0,0 +1,96 @@
+mod config_file;
+
+use qdrant_client::qdrant::Distance;
+
+pub use config_file::*;
+
+/// Qdrant 配置
+#[derive(Debug, Clone)]
+pub struct QdrantConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub collection_name: String,
+    pub vector_size: usize,
+    pub distance: Distance,
+}
+
+impl QdrantConfig {
+    /// 从全局配置（配置文件和环境变量）创建
+    pub fn current() -> Self {
+        Self::from_config(&get_config().qdrant)
+    }
+
+    pub fn from_config(config: &QdrantSection) -> Self {
+        Self {
+            url: config.url.clone(),
+            api_key: config.api_key.clone(),
+            collection_name: config.collection.clone(),
+            vector_size: config.vector_size,
+            distance: Self::parse_distance(&config.distance).unwrap_or(Distance::Cosine),
+        }
+    }
+
+    pub(crate) fn parse_distance(value: &str) -> Option<Distance> {
+        match value.trim().to_lowercase().as_str() {
+            "cosine" => Some(Distance::Cosine),
+            "dot" | "dotproduct" | "dot_product" => Some(Distance::Dot),
+            "euclid" | "euclidean" => Some(Distance::Euclid),
+            "manhattan" | "l1" => Some(Distance::Manhattan),
+            _ => None,
+        }
+    }
+}
+
+/// 应用配置
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub qdrant: QdrantConfig,
+    pub preamble_file: String,
+    pub temperature: f64,
+    /// 每次检索的最大文档片段数，未设置时检索集合中的全部文档
+    pub top_k: Option<usize>,
+    pub documents_dir: String,
+    /// 知识库 preamble 等文件的存放目录
+    pub knowledge_base_dir: String,
+    /// 非默认工作区的 preamble 等文件的存放目录
+    pub workspace_dir: String,
+    pub openai_api_key: String,
+    pub openai_base_url: String,
+    pub openai_model: String,
+    pub embedding_api_key: String,
+    pub embedding_url: String,
+    pub embedding_model: String,
+}
+
+impl AppConfig {
+    /// 从全局配置（配置文件和环境变量）创建
+    pub fn current() -> Self {
+        Self::from_config(get_config())
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            qdrant: QdrantConfig::from_config(&config.qdrant),
+            preamble_file: config.llm.preamble_file.clone(),
+            temperature: config.llm.temperature,
+            top_k: config.llm.top_k,
+            documents_dir: config.storage.documents_dir.clone(),
+            knowledge_base_dir: config.storage.knowledge_base_dir.clone(),
+            workspace_dir: config.storage.workspace_dir.clone(),
+            openai_api_key: config.llm.api_key.clone(),
+            openai_base_url: config.llm.base_url.clone(),
+            openai_model: config.llm.model.clone(),
+            embedding_api_key: config
+                .embedding
+                .api_key
+                .clone()
+                .unwrap_or_else(|| config.llm.api_key.clone()),
+            embedding_url: config
+                .embedding
+                .base_url
+                .clone()
+                .unwrap_or_else(|| config.llm.base_url.clone()),
+            embedding_model: config.embedding.model.clone(),
+        }
+    }
+}
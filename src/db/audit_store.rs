@@ -0,0 +1,379 @@
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+use tracing::warn;
+
+use crate::config::get_config;
+
+/// 全局 AuditStore 实例，供各管理接口写入审计日志
+static AUDIT_STORE: OnceLock<Arc<AuditStore>> = OnceLock::new();
+
+/// 初始化全局 AuditStore
+pub fn init_audit_store(store: Arc<AuditStore>) -> Result<()> {
+    AUDIT_STORE
+        .set(store)
+        .map_err(|_| anyhow::anyhow!("AuditStore already initialized"))
+}
+
+/// 获取全局 AuditStore 实例
+pub fn get_audit_store() -> Option<&'static Arc<AuditStore>> {
+    AUDIT_STORE.get()
+}
+
+/// 审计操作类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// 创建或上传文档
+    DocumentCreated,
+    DocumentUpdated,
+    DocumentDeleted,
+    /// 保存新版本或回滚到历史版本
+    PreambleUpdated,
+    /// 指定生效的历史版本
+    PreamblePinned,
+    /// 需要审批时提交 preamble 草稿
+    PreambleDraftProposed,
+    PreambleDraftRejected,
+    /// 创建、停止或删除 A/B 实验
+    ExperimentCreated,
+    ExperimentStopped,
+    ExperimentDeleted,
+    /// 修改或重置运行时设置
+    SettingsUpdated,
+    UserCreated,
+    /// 修改角色、状态、分组或重置密码
+    UserUpdated,
+    /// 移出工作区或删除用户
+    UserDeleted,
+    UserSessionsRevoked,
+    /// 管理员重置成员的两步验证
+    UserMfaReset,
+    ConversationsCleanedUp,
+    /// 把用户加入或移出工作区，记录在该工作区
+    WorkspaceMemberAdded,
+    WorkspaceMemberRemoved,
+    ApiKeyCreated,
+    ApiKeyDeleted,
+    /// 创建、修改或删除知识库
+    KnowledgeBaseCreated,
+    KnowledgeBaseUpdated,
+    KnowledgeBaseDeleted,
+    /// 一致性修复时从备份重新导入文档
+    BackupRestored,
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AuditAction::DocumentCreated => "document_created",
+            AuditAction::DocumentUpdated => "document_updated",
+            AuditAction::DocumentDeleted => "document_deleted",
+            AuditAction::PreambleUpdated => "preamble_updated",
+            AuditAction::PreamblePinned => "preamble_pinned",
+            AuditAction::PreambleDraftProposed => "preamble_draft_proposed",
+            AuditAction::PreambleDraftRejected => "preamble_draft_rejected",
+            AuditAction::ExperimentCreated => "experiment_created",
+            AuditAction::ExperimentStopped => "experiment_stopped",
+            AuditAction::ExperimentDeleted => "experiment_deleted",
+            AuditAction::SettingsUpdated => "settings_updated",
+            AuditAction::UserCreated => "user_created",
+            AuditAction::UserUpdated => "user_updated",
+            AuditAction::UserDeleted => "user_deleted",
+            AuditAction::UserSessionsRevoked => "user_sessions_revoked",
+            AuditAction::UserMfaReset => "user_mfa_reset",
+            AuditAction::ConversationsCleanedUp => "conversations_cleaned_up",
+            AuditAction::WorkspaceMemberAdded => "workspace_member_added",
+            AuditAction::WorkspaceMemberRemoved => "workspace_member_removed",
+            AuditAction::ApiKeyCreated => "api_key_created",
+            AuditAction::ApiKeyDeleted => "api_key_deleted",
+            AuditAction::KnowledgeBaseCreated => "knowledge_base_created",
+            AuditAction::KnowledgeBaseUpdated => "knowledge_base_updated",
+            AuditAction::KnowledgeBaseDeleted => "knowledge_base_deleted",
+            AuditAction::BackupRestored => "backup_restored",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for AuditAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .map_err(|_| anyhow::anyhow!("Unknown audit action: {}", s))
+    }
+}
+
+/// 待写入的审计记录
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub workspace: String,
+    /// 操作者用户名，API key 为 `apikey:{name}`
+    pub actor: String,
+    pub actor_id: Option<i64>,
+    pub action: AuditAction,
+    /// 操作对象 id（文档 base_id、用户 id 等）
+    pub target: Option<String>,
+    /// 操作前的摘要
+    pub before: Option<serde_json::Value>,
+    /// 操作后的摘要
+    pub after: Option<serde_json::Value>,
+    pub ip: Option<String>,
+}
+
+/// 审计记录
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub workspace: String,
+    pub actor: String,
+    pub actor_id: Option<i64>,
+    pub action: AuditAction,
+    pub target: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for AuditEntry {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let created_at_ts: i64 = row.try_get("created_at")?;
+        let created_at = DateTime::from_timestamp(created_at_ts, 0).ok_or_else(|| {
+            sqlx::Error::Decode(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid timestamp created_at",
+            )))
+        })?;
+        let action: String = row.try_get("action")?;
+        let summary = |column: &str| -> sqlx::Result<Option<serde_json::Value>> {
+            let text: Option<String> = row.try_get(column)?;
+            text.map(|t| serde_json::from_str(&t))
+                .transpose()
+                .map_err(|e| sqlx::Error::Decode(e.into()))
+        };
+
+        Ok(AuditEntry {
+            id: row.try_get("id")?,
+            workspace: row.try_get("workspace")?,
+            actor: row.try_get("actor")?,
+            actor_id: row.try_get("actor_id")?,
+            action: action
+                .parse()
+                .map_err(|e: anyhow::Error| sqlx::Error::Decode(e.into()))?,
+            target: row.try_get("target")?,
+            before: summary("before")?,
+            after: summary("after")?,
+            ip: row.try_get("ip")?,
+            created_at,
+        })
+    }
+}
+
+/// 审计日志查询条件，均为可选
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    pub target: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// 只返回 id 小于该值的记录，导出时用于分批读取
+    pub before_id: Option<i64>,
+}
+
+/// 审计日志存储，与用户表位于同一个数据库
+pub struct AuditStore {
+    pool: SqlitePool,
+}
+
+impl AuditStore {
+    /// 使用配置中的 user_db_path 创建审计日志存储
+    pub async fn from_config() -> Result<Self> {
+        Self::new(&get_config().storage.user_db_path).await
+    }
+
+    /// 创建新的审计日志存储实例
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url)
+            .await
+            .context("Failed to connect to audit database")?;
+
+        let store = Self { pool };
+        store.init_database().await?;
+        Ok(store)
+    }
+
+    async fn init_database(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                workspace TEXT NOT NULL,
+                actor TEXT NOT NULL,
+                actor_id INTEGER,
+                action TEXT NOT NULL,
+                target TEXT,
+                before TEXT,
+                after TEXT,
+                ip TEXT,
+                created_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_audit_log_workspace ON audit_log(workspace, created_at);
+            CREATE INDEX IF NOT EXISTS idx_audit_log_target ON audit_log(target);
+            CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize audit_log table")?;
+        Ok(())
+    }
+
+    /// 写入审计记录，写入失败只记日志，不影响原操作
+    pub async fn record(&self, entry: NewAuditEntry) {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO audit_log (workspace, actor, actor_id, action, target, before, after, ip, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&entry.workspace)
+        .bind(&entry.actor)
+        .bind(entry.actor_id)
+        .bind(entry.action.to_string())
+        .bind(&entry.target)
+        .bind(entry.before.as_ref().map(|v| v.to_string()))
+        .bind(entry.after.as_ref().map(|v| v.to_string()))
+        .bind(&entry.ip)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await;
+
+        if let Err(err) = result {
+            warn!("Failed to record audit entry {}: {:?}", entry.action, err);
+        }
+    }
+
+    /// 查询工作区的审计记录，按时间倒序
+    pub async fn list(
+        &self,
+        workspace: &str,
+        filter: &AuditFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<AuditEntry>> {
+        sqlx::query_as::<_, AuditEntry>(
+            r#"
+            SELECT id, workspace, actor, actor_id, action, target, before, after, ip, created_at
+            FROM audit_log
+            WHERE workspace = ?
+              AND (? IS NULL OR actor = ?)
+              AND (? IS NULL OR action = ?)
+              AND (? IS NULL OR target = ?)
+              AND (? IS NULL OR created_at >= ?)
+              AND (? IS NULL OR created_at <= ?)
+              AND (? IS NULL OR id < ?)
+            ORDER BY id DESC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(workspace)
+        .bind(&filter.actor)
+        .bind(&filter.actor)
+        .bind(filter.action.map(|a| a.to_string()))
+        .bind(filter.action.map(|a| a.to_string()))
+        .bind(&filter.target)
+        .bind(&filter.target)
+        .bind(filter.since.map(|t| t.timestamp()))
+        .bind(filter.since.map(|t| t.timestamp()))
+        .bind(filter.until.map(|t| t.timestamp()))
+        .bind(filter.until.map(|t| t.timestamp()))
+        .bind(filter.before_id)
+        .bind(filter.before_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list audit entries")
+    }
+
+    /// 删除早于 cutoff 的审计记录，返回删除条数
+    pub async fn delete_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM audit_log WHERE created_at < ?")
+            .bind(cutoff.timestamp())
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete old audit entries")?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_audit_filter() {
+        let db = std::env::temp_dir().join(format!("audit-{}.db", nanoid::nanoid!(8)));
+        let store = AuditStore::new(&format!("sqlite:{}?mode=rwc", db.display()))
+            .await
+            .unwrap();
+        let entry = |workspace: &str, action, target: &str| NewAuditEntry {
+            workspace: workspace.to_string(),
+            actor: "admin".to_string(),
+            actor_id: Some(1),
+            action,
+            target: Some(target.to_string()),
+            before: None,
+            after: Some(serde_json::json!({ "filename": "a.md" })),
+            ip: Some("127.0.0.1".to_string()),
+        };
+        store
+            .record(entry("default", AuditAction::DocumentCreated, "doc1"))
+            .await;
+        store
+            .record(entry("default", AuditAction::DocumentDeleted, "doc1"))
+            .await;
+        store
+            .record(entry("other", AuditAction::DocumentCreated, "doc2"))
+            .await;
+
+        let all = store
+            .list("default", &AuditFilter::default(), 50, 0)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].action, AuditAction::DocumentDeleted);
+        assert_eq!(
+            all[1].after,
+            Some(serde_json::json!({ "filename": "a.md" }))
+        );
+
+        let filter = AuditFilter {
+            action: Some(AuditAction::DocumentCreated),
+            ..Default::default()
+        };
+        let created = store.list("default", &filter, 50, 0).await.unwrap();
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].target.as_deref(), Some("doc1"));
+
+        let filter = AuditFilter {
+            before_id: Some(all[0].id),
+            ..Default::default()
+        };
+        let older = store.list("default", &filter, 50, 0).await.unwrap();
+        assert_eq!(older.len(), 1);
+        assert_eq!(older[0].id, all[1].id);
+
+        let deleted = store
+            .delete_before(Utc::now() + chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        assert_eq!(deleted, 3);
+    }
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::get_config;
+
+/// 密码强度规则（配置文件的 `[auth.password]`，PASSWORD_*）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_letter: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub require_mixed_case: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            require_letter: true,
+            require_digit: true,
+            require_symbol: false,
+            require_mixed_case: false,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// 全局配置中的密码规则
+    pub fn current() -> Self {
+        get_config().auth.password.clone()
+    }
+
+    /// 校验密码，不满足时返回第一条未通过的规则
+    pub fn validate(&self, username: &str, password: &str) -> Result<(), String> {
+        if password.chars().count() < self.min_length {
+            return Err(format!(
+                "Password must be at least {} characters",
+                self.min_length
+            ));
+        }
+        if self.require_letter && !password.chars().any(|c| c.is_alphabetic()) {
+            return Err("Password must contain a letter".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            return Err("Password must contain a digit".to_string());
+        }
+        if self.require_symbol && password.chars().all(|c| c.is_alphanumeric()) {
+            return Err("Password must contain a symbol".to_string());
+        }
+        if self.require_mixed_case
+            && !(password.chars().any(|c| c.is_uppercase())
+                && password.chars().any(|c| c.is_lowercase()))
+        {
+            return Err("Password must contain upper and lower case letters".to_string());
+        }
+        if password.eq_ignore_ascii_case(username) {
+            return Err("Password must not be the same as the username".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 管理员是否必须启用两步验证（`ADMIN_MFA_REQUIRED`，默认开启）
+///
+/// 开启时未绑定 TOTP 的管理员（以及其他拥有管理权限的角色）登录后只能访问认证接口，
+/// 完成绑定后才能使用管理功能
+pub fn admin_mfa_required() -> bool {
+    get_config().auth.admin_mfa_required
+}
+
+/// 登录失败锁定规则（配置文件的 `[auth.lockout]`，LOGIN_*）
+///
+/// 同一用户名在同一 IP 上连续失败达到 `max_attempts` 次后锁定 `lockout_seconds`，之后每多失败一次锁定时间翻倍，
+/// 最长 `max_lockout_seconds`；同一用户名在所有 IP 上累计失败达到 `account_max_attempts` 次后
+/// 按同样的规则锁定所有 IP 的登录，防止分散到多个 IP 猜测密码；超过一天没有失败时重新计数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockoutPolicy {
+    pub max_attempts: u32,
+    pub lockout_seconds: i64,
+    pub max_lockout_seconds: i64,
+    /// 为 0 时不限制所有 IP 的累计失败次数
+    pub account_max_attempts: u32,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            lockout_seconds: 60,
+            max_lockout_seconds: 3600,
+            account_max_attempts: 20,
+        }
+    }
+}
+
+impl LockoutPolicy {
+    /// 全局配置中的锁定规则
+    pub fn current() -> Self {
+        get_config().auth.lockout.clone()
+    }
+
+    /// 连续失败 `failures` 次后的锁定截止时间，未达到上限时为 None
+    pub fn locked_until(&self, failures: u32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.lock_after(self.max_attempts, failures, now)
+    }
+
+    /// 用户名在所有 IP 上累计失败 `failures` 次后的锁定截止时间
+    pub fn account_locked_until(&self, failures: u32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.account_max_attempts == 0 {
+            return None;
+        }
+        self.lock_after(self.account_max_attempts, failures, now)
+    }
+
+    fn lock_after(
+        &self,
+        max_attempts: u32,
+        failures: u32,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        if failures < max_attempts || self.lockout_seconds <= 0 {
+            return None;
+        }
+        let doublings = (failures - max_attempts).min(20);
+        let seconds = self
+            .lockout_seconds
+            .saturating_mul(1 << doublings)
+            .min(self.max_lockout_seconds.max(self.lockout_seconds));
+        Some(now + Duration::seconds(seconds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_policy() {
+        let policy = PasswordPolicy::default();
+        assert!(policy.validate("alice", "short1").is_err());
+        assert!(policy.validate("alice", "longenough").is_err());
+        assert!(policy.validate("alice1234", "Alice1234").is_err());
+        assert!(policy.validate("alice", "correct horse 1").is_ok());
+    }
+
+    #[test]
+    fn test_progressive_lockout() {
+        let policy = LockoutPolicy::default();
+        let now = Utc::now();
+        assert_eq!(policy.locked_until(4, now), None);
+        assert_eq!(
+            policy.locked_until(5, now),
+            Some(now + Duration::seconds(60))
+        );
+        assert_eq!(
+            policy.locked_until(6, now),
+            Some(now + Duration::seconds(120))
+        );
+        assert_eq!(
+            policy.locked_until(50, now),
+            Some(now + Duration::seconds(3600))
+        );
+        assert_eq!(policy.account_locked_until(19, now), None);
+        assert_eq!(
+            policy.account_locked_until(20, now),
+            Some(now + Duration::seconds(60))
+        );
+    }
+}
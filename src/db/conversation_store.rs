@@ -7,6 +7,12 @@ use std::str::FromStr;
 use std::time::Duration;
 use tracing::{debug, info};
 
+use super::DEFAULT_WORKSPACE;
+use crate::config::get_config;
+
+/// 访客活跃时间的记录间隔（分钟），间隔内的聊天不再写库
+const VISITOR_TOUCH_MINUTES: i64 = 5;
+
 /// 对话会话状态
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, sqlx::Type)]
 #[sqlx(rename_all = "lowercase")]
@@ -52,6 +58,8 @@ impl std::fmt::Display for MessageRole {
 pub struct Conversation {
     pub id: String,
     pub user_id: String,
+    /// 所属工作区
+    pub workspace_id: String,
     pub status: ConversationStatus,
     pub title: Option<String>,
     pub metadata: Option<serde_json::Value>,
@@ -104,6 +112,7 @@ impl sqlx::FromRow<'_, SqliteRow> for Conversation {
         Ok(Conversation {
             id: row.try_get("id")?,
             user_id: row.try_get("user_id")?,
+            workspace_id: row.try_get("workspace_id")?,
             status: row.try_get("status")?,
             title: row.try_get("title")?,
             metadata: row
@@ -141,14 +150,64 @@ impl sqlx::FromRow<'_, SqliteRow> for ConversationMessage {
     }
 }
 
+/// 匿名访客记录，对应一个服务端签发的聊天会话
+///
+/// `generation` 随会话 token 轮换递增，只有最新一代的 token 有效；
+/// 访客登录后合并到正式账号，`merged_into` 记录账号的对话 user_id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Visitor {
+    pub id: String,
+    pub workspace_id: String,
+    pub generation: i64,
+    pub merged_into: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for Visitor {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let created_at_ts: i64 = row.try_get("created_at")?;
+        let last_seen_at_ts: i64 = row.try_get("last_seen_at")?;
+
+        let created_at = DateTime::from_timestamp(created_at_ts, 0).ok_or_else(|| {
+            sqlx::Error::Decode(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid timestamp created_at",
+            )))
+        })?;
+
+        let last_seen_at = DateTime::from_timestamp(last_seen_at_ts, 0).ok_or_else(|| {
+            sqlx::Error::Decode(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid timestamp last_seen_at",
+            )))
+        })?;
+
+        Ok(Visitor {
+            id: row.try_get("id")?,
+            workspace_id: row.try_get("workspace_id")?,
+            generation: row.try_get("generation")?,
+            merged_into: row.try_get("merged_into")?,
+            created_at,
+            last_seen_at,
+        })
+    }
+}
+
 /// 创建对话请求
 #[derive(Debug, Clone, Deserialize)]
 pub struct CreateConversationRequest {
     pub user_id: String,
+    #[serde(default = "default_workspace")]
+    pub workspace_id: String,
     pub title: Option<String>,
     pub metadata: Option<serde_json::Value>,
 }
 
+fn default_workspace() -> String {
+    DEFAULT_WORKSPACE.to_string()
+}
+
 /// 创建消息请求
 #[derive(Debug, Clone, Deserialize)]
 pub struct CreateMessageRequest {
@@ -172,10 +231,9 @@ pub struct ConversationStore {
 }
 
 impl ConversationStore {
-    pub async fn from_env() -> Result<Self> {
-        let conversation_db_path = std::env::var("CONVERSATION_DB_PATH")
-            .unwrap_or_else(|_| "sqlite:data/conversations.db?mode=rwc".to_string());
-        Self::new(&conversation_db_path).await
+    /// 使用配置中的 conversation_db_path 创建对话存储
+    pub async fn from_config() -> Result<Self> {
+        Self::new(&get_config().storage.conversation_db_path).await
     }
 
     /// 创建新的对话存储实例
@@ -222,6 +280,7 @@ impl ConversationStore {
                 CREATE TABLE IF NOT EXISTS conversations (
                     id TEXT PRIMARY KEY,
                     user_id TEXT NOT NULL,
+                    workspace_id TEXT NOT NULL DEFAULT 'default',
                     status TEXT NOT NULL CHECK(status IN ('active', 'closed', 'escalated')),
                     title TEXT,
                     metadata TEXT, -- JSON string
@@ -262,6 +321,21 @@ impl ConversationStore {
             info!("Conversation database tables created successfully");
         }
 
+        // 旧表补充 workspace_id 列，已有对话归入默认工作区
+        let has_workspace: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM pragma_table_info('conversations') WHERE name = 'workspace_id'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if has_workspace == 0 {
+            sqlx::query(
+                "ALTER TABLE conversations ADD COLUMN workspace_id TEXT NOT NULL DEFAULT 'default'",
+            )
+            .execute(&self.pool)
+            .await
+            .context("Failed to add workspace_id column")?;
+        }
+
         // 始终确保关键复合索引存在（即使是旧库也可补齐）
         sqlx::query(
             r#"
@@ -269,21 +343,326 @@ impl ConversationStore {
                 ON conversation_messages(conversation_id, created_at);
             CREATE INDEX IF NOT EXISTS idx_conversations_user_updated_at
                 ON conversations(user_id, updated_at DESC, created_at DESC);
+            CREATE INDEX IF NOT EXISTS idx_conversations_workspace_updated_at
+                ON conversations(workspace_id, updated_at DESC);
+            CREATE INDEX IF NOT EXISTS idx_messages_experiment
+                ON conversation_messages(json_extract(metadata, '$.experiment.id'));
             "#,
         )
         .execute(&self.pool)
         .await
         .context("Failed to ensure composite indexes")?;
 
+        // 匿名访客表（旧库升级时同样补建）
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS visitors (
+                id TEXT PRIMARY KEY,
+                workspace_id TEXT NOT NULL,
+                generation INTEGER NOT NULL DEFAULT 0,
+                merged_into TEXT,
+                created_at INTEGER NOT NULL,
+                last_seen_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_visitors_last_seen_at ON visitors(last_seen_at);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create visitors table")?;
+
+        // 不能再作为升级前的原始 user_id 认领的 id（已认领，或访客记录已清理）
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS retired_chat_ids (
+                id TEXT PRIMARY KEY,
+                reason TEXT NOT NULL CHECK(reason IN ('claimed', 'expired')),
+                retired_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to create retired_chat_ids table")?;
+
+        Ok(())
+    }
+
+    /// 创建匿名访客记录
+    pub async fn create_visitor(&self, workspace_id: &str) -> Result<Visitor> {
+        let visitor = Visitor {
+            id: nanoid::nanoid!(),
+            workspace_id: workspace_id.to_string(),
+            generation: 0,
+            merged_into: None,
+            created_at: Utc::now(),
+            last_seen_at: Utc::now(),
+        };
+        self.insert_visitor(&visitor).await?;
+
+        debug!(
+            "Created visitor: {} (workspace: {})",
+            visitor.id, visitor.workspace_id
+        );
+        Ok(visitor)
+    }
+
+    /// 为升级前签发、没有访客记录的会话补建记录
+    pub async fn adopt_visitor(&self, id: &str, workspace_id: &str) -> Result<Visitor> {
+        let visitor = Visitor {
+            id: id.to_string(),
+            workspace_id: workspace_id.to_string(),
+            generation: 0,
+            merged_into: None,
+            created_at: Utc::now(),
+            last_seen_at: Utc::now(),
+        };
+        self.insert_visitor(&visitor).await?;
+        self.get_visitor(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Visitor not found"))
+    }
+
+    /// 升级前以客户端生成的 user_id 直接保存的对话所在的工作区（取最近更新的一条）
+    ///
+    /// 只有全部对话都创建于 `before` 之前、没有访客记录且从未被认领或清理的 id 才符合条件
+    pub async fn legacy_conversation_workspace(
+        &self,
+        user_id: &str,
+        before: DateTime<Utc>,
+    ) -> Result<Option<String>> {
+        let workspace = sqlx::query_scalar(
+            r#"
+            SELECT workspace_id FROM conversations
+            WHERE user_id = ?
+              AND NOT EXISTS (SELECT 1 FROM conversations WHERE user_id = ? AND created_at >= ?)
+              AND NOT EXISTS (SELECT 1 FROM visitors WHERE id = ?)
+              AND NOT EXISTS (SELECT 1 FROM retired_chat_ids WHERE id = ?)
+            ORDER BY updated_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .bind(before.timestamp())
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to find legacy conversations")?;
+        Ok(workspace)
+    }
+
+    /// 认领升级前的原始 user_id 并建立访客记录，每个 id 只能认领一次
+    ///
+    /// 已被认领或清理过时返回 None
+    pub async fn claim_legacy_user(&self, id: &str, workspace_id: &str) -> Result<Option<Visitor>> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+        let claimed = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO retired_chat_ids (id, reason, retired_at)
+            VALUES (?, 'claimed', ?)
+            "#,
+        )
+        .bind(id)
+        .bind(now.timestamp())
+        .execute(&mut *tx)
+        .await
+        .context("Failed to claim legacy user id")?
+        .rows_affected();
+        if claimed == 0 {
+            return Ok(None);
+        }
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO visitors (id, workspace_id, generation, merged_into, created_at, last_seen_at)
+            VALUES (?, ?, 0, NULL, ?, ?)
+            "#,
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .bind(now.timestamp())
+        .bind(now.timestamp())
+        .execute(&mut *tx)
+        .await
+        .context("Failed to create visitor for legacy user id")?
+        .rows_affected();
+        if inserted == 0 {
+            return Ok(None);
+        }
+        tx.commit().await?;
+
+        Ok(Some(Visitor {
+            id: id.to_string(),
+            workspace_id: workspace_id.to_string(),
+            generation: 0,
+            merged_into: None,
+            created_at: now,
+            last_seen_at: now,
+        }))
+    }
+
+    async fn insert_visitor(&self, visitor: &Visitor) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO visitors (id, workspace_id, generation, merged_into, created_at, last_seen_at)
+            VALUES (?, ?, ?, NULL, ?, ?)
+            "#,
+        )
+        .bind(&visitor.id)
+        .bind(&visitor.workspace_id)
+        .bind(visitor.generation)
+        .bind(visitor.created_at.timestamp())
+        .bind(visitor.last_seen_at.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to insert visitor")?;
+        Ok(())
+    }
+
+    /// 获取访客记录
+    pub async fn get_visitor(&self, id: &str) -> Result<Option<Visitor>> {
+        let visitor = sqlx::query_as::<_, Visitor>(
+            r#"
+            SELECT id, workspace_id, generation, merged_into, created_at, last_seen_at
+            FROM visitors
+            WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query visitor")?;
+
+        Ok(visitor)
+    }
+
+    /// 记录访客最近活跃时间，距上次记录不足 `VISITOR_TOUCH_MINUTES` 分钟时不写库
+    pub async fn touch_visitor(&self, visitor: &Visitor) -> Result<()> {
+        let now = Utc::now();
+        if now - visitor.last_seen_at < chrono::Duration::minutes(VISITOR_TOUCH_MINUTES) {
+            return Ok(());
+        }
+        sqlx::query("UPDATE visitors SET last_seen_at = ? WHERE id = ?")
+            .bind(now.timestamp())
+            .bind(&visitor.id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update visitor")?;
         Ok(())
     }
 
+    /// 删除超过 `max_idle_days` 天未活跃的访客记录，返回删除数
+    ///
+    /// 会话 token 在签发或轮换时都会更新活跃时间，闲置超过 token 有效期的访客不可能再持有有效 token；
+    /// 访客的对话保留，按对话的清理规则处理；其 id 记入 `retired_chat_ids`，不能再被认领
+    pub async fn delete_inactive_visitors(&self, max_idle_days: i64) -> Result<u64> {
+        let cutoff = Utc::now() - chrono::Duration::days(max_idle_days);
+        let mut tx = self.pool.begin().await?;
+        // 对话仍然保留，留下记录防止其 id 被当作升级前的原始 user_id 再次认领
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO retired_chat_ids (id, reason, retired_at)
+            SELECT id, 'expired', ? FROM visitors WHERE last_seen_at < ?
+            "#,
+        )
+        .bind(Utc::now().timestamp())
+        .bind(cutoff.timestamp())
+        .execute(&mut *tx)
+        .await
+        .context("Failed to retire inactive visitors")?;
+        let deleted = sqlx::query("DELETE FROM visitors WHERE last_seen_at < ?")
+            .bind(cutoff.timestamp())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete inactive visitors")?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    /// 轮换访客会话：只有持有当前一代 token 时才递增，返回新的 generation
+    ///
+    /// 旧 token 随之失效；已合并的访客不能轮换
+    pub async fn rotate_visitor(&self, id: &str, generation: i64) -> Result<Option<i64>> {
+        let rotated = sqlx::query(
+            r#"
+            UPDATE visitors
+            SET generation = generation + 1, last_seen_at = ?
+            WHERE id = ? AND generation = ? AND merged_into IS NULL
+            "#,
+        )
+        .bind(Utc::now().timestamp())
+        .bind(id)
+        .bind(generation)
+        .execute(&self.pool)
+        .await
+        .context("Failed to rotate visitor")?
+        .rows_affected();
+
+        Ok((rotated > 0).then_some(generation + 1))
+    }
+
+    /// 把访客的对话合并到登录用户名下，返回转移的对话数
+    ///
+    /// 合并后访客的会话 token 不再有效
+    pub async fn merge_visitor(&self, id: &str, user_id: &str) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let merged = sqlx::query(
+            r#"
+            UPDATE visitors
+            SET merged_into = ?, generation = generation + 1, last_seen_at = ?
+            WHERE id = ? AND merged_into IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(Utc::now().timestamp())
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to mark visitor as merged")?
+        .rows_affected();
+
+        if merged == 0 {
+            tx.rollback().await.ok();
+            return Err(anyhow::anyhow!("Visitor not found or already merged"));
+        }
+
+        let moved = sqlx::query("UPDATE conversations SET user_id = ? WHERE user_id = ?")
+            .bind(user_id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to move visitor conversations")?
+            .rows_affected();
+
+        tx.commit()
+            .await
+            .context("Failed to commit visitor merge")?;
+
+        info!(
+            "Merged visitor {} into {} ({} conversations)",
+            id, user_id, moved
+        );
+        Ok(moved)
+    }
+
     /// 创建新对话
     pub async fn create_conversation(
         &self,
         req: CreateConversationRequest,
     ) -> Result<Conversation> {
-        let id = nanoid::nanoid!();
+        self.insert_conversation(nanoid::nanoid!(), req).await
+    }
+
+    async fn insert_conversation(
+        &self,
+        id: String,
+        req: CreateConversationRequest,
+    ) -> Result<Conversation> {
         let now = Utc::now();
         let timestamp = now.timestamp();
 
@@ -296,12 +675,13 @@ impl ConversationStore {
 
         sqlx::query(
             r#"
-            INSERT INTO conversations (id, user_id, status, title, metadata, created_at, updated_at)
-            VALUES (?, ?, 'active', ?, ?, ?, ?)
+            INSERT INTO conversations (id, user_id, workspace_id, status, title, metadata, created_at, updated_at)
+            VALUES (?, ?, ?, 'active', ?, ?, ?, ?)
             "#,
         )
         .bind(&id)
         .bind(&req.user_id)
+        .bind(&req.workspace_id)
         .bind(&req.title)
         .bind(&metadata_json)
         .bind(timestamp)
@@ -315,6 +695,7 @@ impl ConversationStore {
         Ok(Conversation {
             id,
             user_id: req.user_id,
+            workspace_id: req.workspace_id,
             status: ConversationStatus::Active,
             title: req.title,
             metadata: req.metadata,
@@ -323,34 +704,50 @@ impl ConversationStore {
         })
     }
 
-    /// 获取或创建活跃对话
-    pub async fn get_or_create_active_conversation(&self, user_id: &str) -> Result<Conversation> {
-        // 首先查找活跃的对话
-        let conversation = sqlx::query_as::<_, Conversation>(
+    /// 用户在工作区中最近的活跃对话
+    pub async fn get_active_conversation(
+        &self,
+        user_id: &str,
+        workspace_id: &str,
+    ) -> Result<Option<Conversation>> {
+        sqlx::query_as::<_, Conversation>(
             r#"
-            SELECT id, user_id, status, title, metadata, created_at, updated_at
+            SELECT id, user_id, workspace_id, status, title, metadata, created_at, updated_at
             FROM conversations
-            WHERE user_id = ? AND status = 'active'
+            WHERE user_id = ? AND workspace_id = ? AND status = 'active'
             ORDER BY created_at DESC
             LIMIT 1
             "#,
         )
         .bind(user_id)
+        .bind(workspace_id)
         .fetch_optional(&self.pool)
         .await
-        .context("Failed to query active conversation")?;
+        .context("Failed to query active conversation")
+    }
 
-        if let Some(conversation) = conversation {
+    /// 获取或创建用户在工作区中的活跃对话
+    ///
+    /// 新建时使用 `new_id`（如实验分组时预留的对话 id），未提供时生成新的 id
+    pub async fn get_or_create_active_conversation(
+        &self,
+        user_id: &str,
+        workspace_id: &str,
+        new_id: Option<&str>,
+    ) -> Result<Conversation> {
+        if let Some(conversation) = self.get_active_conversation(user_id, workspace_id).await? {
             return Ok(conversation);
         }
 
         // 如果没有活跃对话，创建一个新的
         let req = CreateConversationRequest {
             user_id: user_id.to_string(),
+            workspace_id: workspace_id.to_string(),
             title: None,
             metadata: None,
         };
-        self.create_conversation(req).await
+        let id = new_id.map_or_else(|| nanoid::nanoid!(), str::to_string);
+        self.insert_conversation(id, req).await
     }
 
     /// 添加消息到对话
@@ -413,6 +810,37 @@ impl ConversationStore {
         })
     }
 
+    /// 记录用户对助手回复的评分（1-5），写入消息元数据的 `feedback` 字段
+    ///
+    /// 消息不存在、不属于该对话或不是助手回复时返回 false
+    pub async fn set_message_feedback(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+        rating: i64,
+        comment: Option<&str>,
+    ) -> Result<bool> {
+        let feedback = serde_json::json!({
+            "rating": rating,
+            "comment": comment,
+            "created_at": Utc::now().to_rfc3339(),
+        });
+        let result = sqlx::query(
+            r#"
+            UPDATE conversation_messages
+            SET metadata = json_set(COALESCE(metadata, '{}'), '$.feedback', json(?))
+            WHERE id = ? AND conversation_id = ? AND role = 'assistant'
+            "#,
+        )
+        .bind(feedback.to_string())
+        .bind(message_id)
+        .bind(conversation_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to save message feedback")?;
+        Ok(result.rows_affected() > 0)
+    }
+
     /// 获取对话消息历史
     pub async fn get_conversation_messages(
         &self,
@@ -442,10 +870,11 @@ impl ConversationStore {
         Ok(messages)
     }
 
-    /// 获取用户的对话列表
+    /// 获取用户在工作区中的对话列表
     pub async fn get_user_conversations(
         &self,
         user_id: &str,
+        workspace_id: &str,
         limit: Option<i64>,
         offset: Option<i64>,
     ) -> Result<Vec<Conversation>> {
@@ -454,14 +883,15 @@ impl ConversationStore {
 
         let conversations = sqlx::query_as::<_, Conversation>(
             r#"
-            SELECT id, user_id, status, title, metadata, created_at, updated_at
+            SELECT id, user_id, workspace_id, status, title, metadata, created_at, updated_at
             FROM conversations
-            WHERE user_id = ?
+            WHERE user_id = ? AND workspace_id = ?
             ORDER BY updated_at DESC, created_at DESC
             LIMIT ? OFFSET ?
             "#,
         )
         .bind(user_id)
+        .bind(workspace_id)
         .bind(limit)
         .bind(offset)
         .fetch_all(&self.pool)
@@ -561,7 +991,7 @@ impl ConversationStore {
     ) -> Result<Option<Conversation>> {
         let conversation = sqlx::query_as::<_, Conversation>(
             r#"
-            SELECT id, user_id, status, title, metadata, created_at, updated_at
+            SELECT id, user_id, workspace_id, status, title, metadata, created_at, updated_at
             FROM conversations
             WHERE id = ?
             "#,
@@ -574,8 +1004,12 @@ impl ConversationStore {
         Ok(conversation)
     }
 
-    /// 获取用户交互统计
-    pub async fn get_user_interaction_stats(&self, user_id: &str) -> Result<UserInteractionStats> {
+    /// 获取用户在工作区中的交互统计
+    pub async fn get_user_interaction_stats(
+        &self,
+        user_id: &str,
+        workspace_id: &str,
+    ) -> Result<UserInteractionStats> {
         let stats = sqlx::query_as::<_, (i64, i64, Option<i64>)>(
             r#"
             SELECT 
@@ -584,10 +1018,11 @@ impl ConversationStore {
                 MAX(c.updated_at) as last_interaction
             FROM conversations c
             LEFT JOIN conversation_messages m ON c.id = m.conversation_id
-            WHERE c.user_id = ?
+            WHERE c.user_id = ? AND c.workspace_id = ?
             "#,
         )
         .bind(user_id)
+        .bind(workspace_id)
         .fetch_one(&self.pool)
         .await
         .context("Failed to query user interaction stats")?;
@@ -653,6 +1088,40 @@ impl ConversationStore {
         Ok(())
     }
 
+    /// 删除工作区的全部对话、消息和匿名访客，返回删除的对话数
+    pub async fn delete_workspace(&self, workspace_id: &str) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM conversation_messages
+            WHERE conversation_id IN (SELECT id FROM conversations WHERE workspace_id = ?)
+            "#,
+        )
+        .bind(workspace_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete workspace messages")?;
+
+        let deleted = sqlx::query("DELETE FROM conversations WHERE workspace_id = ?")
+            .bind(workspace_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete workspace conversations")?
+            .rows_affected();
+
+        sqlx::query("DELETE FROM visitors WHERE workspace_id = ?")
+            .bind(workspace_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete workspace visitors")?;
+
+        tx.commit()
+            .await
+            .context("Failed to commit delete transaction")?;
+        Ok(deleted)
+    }
+
     /// 清理旧数据（可选功能）
     pub async fn cleanup_old_data(&self, days_to_keep: i64) -> Result<u64> {
         let cutoff_timestamp = Utc::now().timestamp() - (days_to_keep * 24 * 60 * 60);
@@ -757,8 +1226,8 @@ impl ConversationStore {
         Ok(true)
     }
 
-    /// 获取对话统计信息
-    pub async fn get_conversation_stats(&self) -> Result<ConversationStats> {
+    /// 获取工作区的对话统计信息
+    pub async fn get_conversation_stats(&self, workspace_id: &str) -> Result<ConversationStats> {
         let stats = sqlx::query_as::<_, (i64, i64, i64, i64)>(
             r#"
             SELECT 
@@ -767,8 +1236,10 @@ impl ConversationStore {
                 COUNT(CASE WHEN status = 'closed' THEN 1 END) as closed_conversations,
                 COUNT(CASE WHEN status = 'escalated' THEN 1 END) as escalated_conversations
             FROM conversations
+            WHERE workspace_id = ?
             "#,
         )
+        .bind(workspace_id)
         .fetch_one(&self.pool)
         .await
         .context("Failed to query conversation stats")?;
@@ -776,9 +1247,12 @@ impl ConversationStore {
         let message_stats = sqlx::query_as::<_, (i64,)>(
             r#"
             SELECT COUNT(*) as total_messages
-            FROM conversation_messages
+            FROM conversation_messages m
+            JOIN conversations c ON c.id = m.conversation_id
+            WHERE c.workspace_id = ?
             "#,
         )
+        .bind(workspace_id)
         .fetch_one(&self.pool)
         .await
         .context("Failed to query message stats")?;
@@ -787,9 +1261,10 @@ impl ConversationStore {
             r#"
             SELECT COUNT(*) as today_conversations
             FROM conversations
-            WHERE created_at >= ?
+            WHERE workspace_id = ? AND created_at >= ?
             "#,
         )
+        .bind(workspace_id)
         .bind(Utc::now().timestamp() - 24 * 60 * 60) // 24小时前
         .fetch_one(&self.pool)
         .await
@@ -805,9 +1280,92 @@ impl ConversationStore {
         })
     }
 
-    /// 获取所有对话（管理员功能）
+    /// 按变体统计实验效果，变体来自消息元数据中的 `experiment`
+    ///
+    /// 对话长度按对话的全部消息计算，评分只统计该变体生成的回复；
+    /// 通过 `idx_messages_experiment` 只读取该实验的消息
+    pub async fn experiment_report(
+        &self,
+        workspace_id: &str,
+        experiment_id: i64,
+    ) -> Result<Vec<ExperimentVariantStats>> {
+        let conversations = sqlx::query_as::<_, (String, i64, i64, f64)>(
+            r#"
+            WITH tagged AS (
+                SELECT DISTINCT m.conversation_id,
+                    json_extract(m.metadata, '$.experiment.variant') AS variant
+                FROM conversation_messages m
+                JOIN conversations c ON c.id = m.conversation_id
+                WHERE c.workspace_id = ? AND json_extract(m.metadata, '$.experiment.id') = ?
+            )
+            , lengths AS (
+                SELECT m.conversation_id, COUNT(*) AS messages
+                FROM conversation_messages m
+                WHERE m.conversation_id IN (SELECT conversation_id FROM tagged)
+                GROUP BY m.conversation_id
+            )
+            SELECT
+                t.variant,
+                COUNT(*) AS conversations,
+                COUNT(CASE WHEN c.status = 'escalated' THEN 1 END) AS escalated,
+                AVG(l.messages) AS avg_messages
+            FROM tagged t
+            JOIN conversations c ON c.id = t.conversation_id
+            JOIN lengths l ON l.conversation_id = t.conversation_id
+            GROUP BY t.variant
+            ORDER BY t.variant
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(experiment_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to query experiment conversations")?;
+
+        let ratings = sqlx::query_as::<_, (String, i64, Option<f64>)>(
+            r#"
+            SELECT
+                json_extract(m.metadata, '$.experiment.variant') AS variant,
+                COUNT(json_extract(m.metadata, '$.feedback.rating')) AS ratings,
+                AVG(json_extract(m.metadata, '$.feedback.rating')) AS avg_rating
+            FROM conversation_messages m
+            JOIN conversations c ON c.id = m.conversation_id
+            WHERE c.workspace_id = ? AND json_extract(m.metadata, '$.experiment.id') = ?
+                AND m.role = 'assistant'
+            GROUP BY variant
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(experiment_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to query experiment ratings")?;
+
+        Ok(conversations
+            .into_iter()
+            .map(|(variant, conversations, escalated, avg_messages)| {
+                let (ratings, avg_rating) = ratings
+                    .iter()
+                    .find(|(v, _, _)| *v == variant)
+                    .map(|(_, count, avg)| (*count, *avg))
+                    .unwrap_or((0, None));
+                ExperimentVariantStats {
+                    variant,
+                    conversations,
+                    escalated_conversations: escalated,
+                    escalation_rate: escalated as f64 / conversations.max(1) as f64,
+                    avg_messages,
+                    ratings,
+                    avg_rating,
+                }
+            })
+            .collect())
+    }
+
+    /// 获取工作区的所有对话（管理员功能）
     pub async fn get_all_conversations(
         &self,
+        workspace_id: &str,
         limit: Option<i64>,
         offset: Option<i64>,
         search: Option<&str>,
@@ -818,13 +1376,14 @@ impl ConversationStore {
         let conversations = if let Some(search_term) = search {
             sqlx::query_as::<_, Conversation>(
                 r#"
-                SELECT id, user_id, status, title, metadata, created_at, updated_at
+                SELECT id, user_id, workspace_id, status, title, metadata, created_at, updated_at
                 FROM conversations
-                WHERE user_id LIKE ? OR id LIKE ?
+                WHERE workspace_id = ? AND (user_id LIKE ? OR id LIKE ?)
                 ORDER BY updated_at DESC, created_at DESC
                 LIMIT ? OFFSET ?
                 "#,
             )
+            .bind(workspace_id)
             .bind(format!("%{}%", search_term))
             .bind(format!("%{}%", search_term))
             .bind(limit)
@@ -835,12 +1394,14 @@ impl ConversationStore {
         } else {
             sqlx::query_as::<_, Conversation>(
                 r#"
-                SELECT id, user_id, status, title, metadata, created_at, updated_at
+                SELECT id, user_id, workspace_id, status, title, metadata, created_at, updated_at
                 FROM conversations
+                WHERE workspace_id = ?
                 ORDER BY updated_at DESC, created_at DESC
                 LIMIT ? OFFSET ?
                 "#,
             )
+            .bind(workspace_id)
             .bind(limit)
             .bind(offset)
             .fetch_all(&self.pool)
@@ -862,3 +1423,66 @@ pub struct ConversationStats {
     pub total_messages: i64,
     pub today_conversations: i64,
 }
+
+/// 实验中一个变体的统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentVariantStats {
+    pub variant: String,
+    pub conversations: i64,
+    pub escalated_conversations: i64,
+    /// 转人工的对话占比
+    pub escalation_rate: f64,
+    /// 平均每个对话的消息数
+    pub avg_messages: f64,
+    /// 收到评分的回复数
+    pub ratings: i64,
+    pub avg_rating: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_expired_visitor_cannot_be_reclaimed() {
+        let db = std::env::temp_dir().join(format!("conversations-{}.db", nanoid::nanoid!(8)));
+        let store = ConversationStore::new(&format!("sqlite:{}?mode=rwc", db.display()))
+            .await
+            .unwrap();
+        let visitor = store.create_visitor(DEFAULT_WORKSPACE).await.unwrap();
+        store
+            .create_conversation(CreateConversationRequest {
+                user_id: visitor.id.clone(),
+                workspace_id: DEFAULT_WORKSPACE.to_string(),
+                title: None,
+                metadata: None,
+            })
+            .await
+            .unwrap();
+        sqlx::query("UPDATE visitors SET last_seen_at = 0 WHERE id = ?")
+            .bind(&visitor.id)
+            .execute(&store.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(store.delete_inactive_visitors(8).await.unwrap(), 1);
+        assert!(store.get_visitor(&visitor.id).await.unwrap().is_none());
+
+        // 访客记录已删除，但对话仍在，不能当作原始 user_id 认领
+        let before = Utc::now() + chrono::Duration::days(1);
+        assert!(
+            store
+                .legacy_conversation_workspace(&visitor.id, before)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            store
+                .claim_legacy_user(&visitor.id, DEFAULT_WORKSPACE)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,441 @@
+use std::{
+    collections::HashSet,
+    ops::RangeInclusive,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+
+use crate::config::get_config;
+
+/// 全局 ExperimentStore 实例，聊天接口通过它查找工作区正在运行的实验
+static EXPERIMENT_STORE: OnceLock<Arc<ExperimentStore>> = OnceLock::new();
+
+/// 初始化全局 ExperimentStore
+pub fn init_experiment_store(store: Arc<ExperimentStore>) -> Result<()> {
+    EXPERIMENT_STORE
+        .set(store)
+        .map_err(|_| anyhow::anyhow!("ExperimentStore already initialized"))
+}
+
+/// 获取全局 ExperimentStore 实例
+pub fn get_experiment_store() -> Option<&'static Arc<ExperimentStore>> {
+    EXPERIMENT_STORE.get()
+}
+
+/// 变体中检索条数的允许范围
+const TOP_K_RANGE: RangeInclusive<usize> = 1..=50;
+
+/// 分组依据：同一用户或同一对话始终分到同一个变体
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExperimentUnit {
+    #[default]
+    User,
+    Conversation,
+}
+
+impl ExperimentUnit {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ExperimentUnit::User => "user",
+            ExperimentUnit::Conversation => "conversation",
+        }
+    }
+}
+
+impl std::str::FromStr for ExperimentUnit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "user" => Ok(ExperimentUnit::User),
+            "conversation" => Ok(ExperimentUnit::Conversation),
+            _ => Err(anyhow::anyhow!("Unknown experiment unit: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExperimentStatus {
+    Running,
+    Stopped,
+}
+
+impl std::str::FromStr for ExperimentStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "running" => Ok(ExperimentStatus::Running),
+            "stopped" => Ok(ExperimentStatus::Stopped),
+            _ => Err(anyhow::anyhow!("Unknown experiment status: {}", s)),
+        }
+    }
+}
+
+/// 实验的一个变体，未设置的字段沿用工作区当前的配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExperimentVariant {
+    /// 变体 id，记录在消息元数据中（如 `control`、`b`）
+    pub id: String,
+    /// 分流权重，默认 1
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// 使用工作区 preamble 的某个历史版本
+    pub preamble_version: Option<i64>,
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub top_k: Option<usize>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// A/B 实验，作用于工作区默认知识库的聊天
+#[derive(Debug, Clone, Serialize)]
+pub struct Experiment {
+    pub id: i64,
+    pub workspace: String,
+    pub name: String,
+    pub unit: ExperimentUnit,
+    pub status: ExperimentStatus,
+    pub variants: Vec<ExperimentVariant>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+impl Experiment {
+    /// 按实验 id 和分组键（user_id 或对话 id）稳定地选择变体
+    pub fn assign(&self, key: &str) -> Option<&ExperimentVariant> {
+        let total: u64 = self.variants.iter().map(|v| u64::from(v.weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let digest = Sha256::digest(format!("{}:{}", self.id, key).as_bytes());
+        let mut bucket = u64::from_be_bytes(digest[..8].try_into().ok()?) % total;
+        self.variants.iter().find(|variant| {
+            let weight = u64::from(variant.weight);
+            if bucket < weight {
+                return true;
+            }
+            bucket -= weight;
+            false
+        })
+    }
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for Experiment {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let timestamp = |ts: i64| {
+            DateTime::from_timestamp(ts, 0).ok_or_else(|| {
+                sqlx::Error::Decode(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Invalid timestamp",
+                )))
+            })
+        };
+        let unit: String = row.try_get("unit")?;
+        let status: String = row.try_get("status")?;
+        let variants: String = row.try_get("variants")?;
+        let stopped_at: Option<i64> = row.try_get("stopped_at")?;
+
+        Ok(Experiment {
+            id: row.try_get("id")?,
+            workspace: row.try_get("workspace")?,
+            name: row.try_get("name")?,
+            unit: unit
+                .parse()
+                .map_err(|e: anyhow::Error| sqlx::Error::Decode(e.into()))?,
+            status: status
+                .parse()
+                .map_err(|e: anyhow::Error| sqlx::Error::Decode(e.into()))?,
+            variants: serde_json::from_str(&variants).map_err(|e| sqlx::Error::Decode(e.into()))?,
+            created_by: row.try_get("created_by")?,
+            created_at: timestamp(row.try_get("created_at")?)?,
+            stopped_at: stopped_at.map(timestamp).transpose()?,
+        })
+    }
+}
+
+/// 校验变体：至少两个、id 不重复、权重为正，温度和 top_k 在允许范围内
+pub fn validate_variants(variants: &[ExperimentVariant]) -> Result<(), String> {
+    if variants.len() < 2 {
+        return Err("An experiment needs at least two variants".to_string());
+    }
+    let mut ids = HashSet::new();
+    for variant in variants {
+        let id = variant.id.trim();
+        if id.is_empty() || !ids.insert(id) {
+            return Err(format!(
+                "Variant ids must be unique and non-empty: {:?}",
+                id
+            ));
+        }
+        if variant.weight == 0 {
+            return Err(format!("Variant {} must have a positive weight", id));
+        }
+        if variant
+            .temperature
+            .is_some_and(|t| !(0.0..=2.0).contains(&t))
+        {
+            return Err(format!("Variant {} temperature must be within 0-2", id));
+        }
+        if variant.top_k.is_some_and(|k| !TOP_K_RANGE.contains(&k)) {
+            return Err(format!(
+                "Variant {} top_k must be within {}-{}",
+                id,
+                TOP_K_RANGE.start(),
+                TOP_K_RANGE.end()
+            ));
+        }
+        if variant.model.as_ref().is_some_and(|m| m.trim().is_empty()) {
+            return Err(format!("Variant {} model must not be empty", id));
+        }
+    }
+    Ok(())
+}
+
+/// 实验存储，与用户表位于同一个数据库
+///
+/// 每个工作区同时最多运行一个实验
+pub struct ExperimentStore {
+    pool: SqlitePool,
+}
+
+impl ExperimentStore {
+    /// 使用配置中的 user_db_path 创建实验存储
+    pub async fn from_config() -> Result<Self> {
+        Self::new(&get_config().storage.user_db_path).await
+    }
+
+    /// 创建新的实验存储实例
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url)
+            .await
+            .context("Failed to connect to experiment database")?;
+
+        let store = Self { pool };
+        store.init_database().await?;
+        Ok(store)
+    }
+
+    async fn init_database(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS experiments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                workspace TEXT NOT NULL,
+                name TEXT NOT NULL,
+                unit TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'running',
+                variants TEXT NOT NULL, -- JSON array
+                created_by TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                stopped_at INTEGER
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_experiments_running
+                ON experiments(workspace) WHERE status = 'running';
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize experiments table")?;
+        Ok(())
+    }
+
+    /// 创建并立即开始实验，工作区已有运行中的实验时失败
+    pub async fn create(
+        &self,
+        workspace: &str,
+        name: &str,
+        unit: ExperimentUnit,
+        variants: Vec<ExperimentVariant>,
+        created_by: &str,
+    ) -> Result<Experiment> {
+        let created_at = Utc::now();
+        let id = sqlx::query(
+            r#"
+            INSERT INTO experiments (workspace, name, unit, status, variants, created_by, created_at)
+            VALUES (?, ?, ?, 'running', ?, ?, ?)
+            "#,
+        )
+        .bind(workspace)
+        .bind(name)
+        .bind(unit.as_str())
+        .bind(serde_json::to_string(&variants)?)
+        .bind(created_by)
+        .bind(created_at.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to create experiment")?
+        .last_insert_rowid();
+
+        Ok(Experiment {
+            id,
+            workspace: workspace.to_string(),
+            name: name.to_string(),
+            unit,
+            status: ExperimentStatus::Running,
+            variants,
+            created_by: created_by.to_string(),
+            created_at,
+            stopped_at: None,
+        })
+    }
+
+    /// 工作区的全部实验，按创建时间倒序
+    pub async fn list(&self, workspace: &str) -> Result<Vec<Experiment>> {
+        sqlx::query_as::<_, Experiment>(
+            r#"
+            SELECT id, workspace, name, unit, status, variants, created_by, created_at, stopped_at
+            FROM experiments
+            WHERE workspace = ?
+            ORDER BY id DESC
+            "#,
+        )
+        .bind(workspace)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list experiments")
+    }
+
+    pub async fn get(&self, workspace: &str, id: i64) -> Result<Option<Experiment>> {
+        sqlx::query_as::<_, Experiment>(
+            r#"
+            SELECT id, workspace, name, unit, status, variants, created_by, created_at, stopped_at
+            FROM experiments
+            WHERE workspace = ? AND id = ?
+            "#,
+        )
+        .bind(workspace)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get experiment")
+    }
+
+    /// 工作区正在运行的实验
+    pub async fn running(&self, workspace: &str) -> Result<Option<Experiment>> {
+        sqlx::query_as::<_, Experiment>(
+            r#"
+            SELECT id, workspace, name, unit, status, variants, created_by, created_at, stopped_at
+            FROM experiments
+            WHERE workspace = ? AND status = 'running'
+            "#,
+        )
+        .bind(workspace)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get running experiment")
+    }
+
+    /// 停止实验，实验不存在或已停止时返回 false
+    pub async fn stop(&self, workspace: &str, id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE experiments
+            SET status = 'stopped', stopped_at = ?
+            WHERE workspace = ? AND id = ? AND status = 'running'
+            "#,
+        )
+        .bind(Utc::now().timestamp())
+        .bind(workspace)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to stop experiment")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 删除实验，消息元数据中的变体记录保留
+    pub async fn delete(&self, workspace: &str, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM experiments WHERE workspace = ? AND id = ?")
+            .bind(workspace)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete experiment")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 删除工作区的全部实验
+    pub async fn delete_workspace(&self, workspace: &str) -> Result<()> {
+        sqlx::query("DELETE FROM experiments WHERE workspace = ?")
+            .bind(workspace)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(id: &str, weight: u32) -> ExperimentVariant {
+        ExperimentVariant {
+            id: id.to_string(),
+            weight,
+            preamble_version: None,
+            model: None,
+            temperature: None,
+            top_k: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_experiments() {
+        let db = std::env::temp_dir().join(format!("experiment-{}.db", nanoid::nanoid!(8)));
+        let store = ExperimentStore::new(&format!("sqlite:{}?mode=rwc", db.display()))
+            .await
+            .unwrap();
+
+        let variants = vec![variant("control", 1), variant("b", 3)];
+        assert!(validate_variants(&variants).is_ok());
+        assert!(validate_variants(&[variant("a", 1), variant("a", 1)]).is_err());
+        assert!(validate_variants(&[variant("a", 1)]).is_err());
+
+        let experiment = store
+            .create("default", "tone", ExperimentUnit::User, variants, "admin")
+            .await
+            .unwrap();
+        // 同一工作区只能有一个运行中的实验
+        assert!(
+            store
+                .create(
+                    "default",
+                    "other",
+                    ExperimentUnit::User,
+                    vec![variant("a", 1), variant("b", 1)],
+                    "admin"
+                )
+                .await
+                .is_err()
+        );
+
+        let running = store.running("default").await.unwrap().unwrap();
+        assert_eq!(running.id, experiment.id);
+        assert_eq!(running.variants[1].weight, 3);
+        let first = running.assign("user:1").unwrap().id.clone();
+        assert_eq!(running.assign("user:1").unwrap().id, first);
+        let b = (0..200)
+            .filter(|i| running.assign(&format!("user:{}", i)).unwrap().id == "b")
+            .count();
+        assert!((100..200).contains(&b));
+
+        assert!(store.stop("default", experiment.id).await.unwrap());
+        assert!(!store.stop("default", experiment.id).await.unwrap());
+        assert!(store.running("default").await.unwrap().is_none());
+        let stopped = store.get("default", experiment.id).await.unwrap().unwrap();
+        assert_eq!(stopped.status, ExperimentStatus::Stopped);
+        assert!(stopped.stopped_at.is_some());
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+use tracing::info;
+
+use crate::config::get_config;
+
+/// 知识库配置（preamble 单独保存在文件中）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeBase {
+    pub id: String,
+    /// 所属工作区
+    pub workspace_id: String,
+    pub name: String,
+    pub description: String,
+    pub collection_name: String,
+    pub chunk_size: i64,
+    /// 未设置时使用全局 TEMPERATURE
+    pub temperature: Option<f64>,
+    /// 未设置时使用全局 OPENAI_MODEL
+    pub openai_model: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for KnowledgeBase {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let timestamp = |column: &str| -> sqlx::Result<DateTime<Utc>> {
+            let ts: i64 = row.try_get(column)?;
+            DateTime::from_timestamp(ts, 0).ok_or_else(|| {
+                sqlx::Error::Decode(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Invalid timestamp {}", column),
+                )))
+            })
+        };
+
+        Ok(KnowledgeBase {
+            id: row.try_get("id")?,
+            workspace_id: row.try_get("workspace_id")?,
+            name: row.try_get("name")?,
+            description: row.try_get("description")?,
+            collection_name: row.try_get("collection_name")?,
+            chunk_size: row.try_get("chunk_size")?,
+            temperature: row.try_get("temperature")?,
+            openai_model: row.try_get("openai_model")?,
+            created_at: timestamp("created_at")?,
+            updated_at: timestamp("updated_at")?,
+        })
+    }
+}
+
+/// 创建知识库请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateKnowledgeBaseRequest {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub collection_name: Option<String>,
+    pub chunk_size: Option<i64>,
+    pub temperature: Option<f64>,
+    pub openai_model: Option<String>,
+    pub preamble: Option<String>,
+}
+
+/// 更新知识库请求（集合名创建后不可修改）
+///
+/// `temperature` 和 `openai_model` 传 null 时取消覆盖，恢复使用全局配置
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpdateKnowledgeBaseRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub chunk_size: Option<i64>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub temperature: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub openai_model: Option<Option<String>>,
+    pub preamble: Option<String>,
+}
+
+/// 区分未提供的字段（None）和显式传入的 null（Some(None)）
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// 知识库存储
+pub struct KnowledgeBaseStore {
+    pool: SqlitePool,
+}
+
+impl KnowledgeBaseStore {
+    /// 使用配置中的 knowledge_base_db_path 创建知识库存储
+    pub async fn from_config() -> Result<Self> {
+        Self::new(&get_config().storage.knowledge_base_db_path).await
+    }
+
+    /// 创建新的知识库存储实例
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url)
+            .await
+            .context("Failed to connect to knowledge base database")?;
+
+        let store = Self { pool };
+        store.init_database().await?;
+        Ok(store)
+    }
+
+    /// 初始化数据库表
+    async fn init_database(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS knowledge_bases (
+                id TEXT PRIMARY KEY,
+                workspace_id TEXT NOT NULL DEFAULT 'default',
+                name TEXT NOT NULL,
+                description TEXT NOT NULL DEFAULT '',
+                collection_name TEXT NOT NULL UNIQUE,
+                chunk_size INTEGER NOT NULL,
+                temperature REAL,
+                openai_model TEXT,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize knowledge_bases table")?;
+
+        // 旧表补充 workspace_id 列
+        let has_workspace: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM pragma_table_info('knowledge_bases') WHERE name = 'workspace_id'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if has_workspace == 0 {
+            sqlx::query(
+                "ALTER TABLE knowledge_bases ADD COLUMN workspace_id TEXT NOT NULL DEFAULT 'default'",
+            )
+            .execute(&self.pool)
+            .await
+            .context("Failed to add workspace_id column")?;
+        }
+
+        info!("Knowledge base database initialized");
+        Ok(())
+    }
+
+    /// 列出工作区的知识库
+    pub async fn list(&self, workspace_id: &str) -> Result<Vec<KnowledgeBase>> {
+        sqlx::query_as::<_, KnowledgeBase>(
+            "SELECT * FROM knowledge_bases WHERE workspace_id = ? ORDER BY created_at",
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list knowledge bases")
+    }
+
+    /// 列出所有工作区的知识库
+    pub async fn list_all(&self) -> Result<Vec<KnowledgeBase>> {
+        sqlx::query_as::<_, KnowledgeBase>("SELECT * FROM knowledge_bases ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to list knowledge bases")
+    }
+
+    /// 根据 id 获取知识库
+    pub async fn get(&self, id: &str) -> Result<Option<KnowledgeBase>> {
+        sqlx::query_as::<_, KnowledgeBase>("SELECT * FROM knowledge_bases WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to get knowledge base")
+    }
+
+    /// 创建知识库（字段需已校验并补全默认值）
+    pub async fn create(&self, knowledge_base: &KnowledgeBase) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO knowledge_bases
+                (id, workspace_id, name, description, collection_name, chunk_size, temperature, openai_model, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&knowledge_base.id)
+        .bind(&knowledge_base.workspace_id)
+        .bind(&knowledge_base.name)
+        .bind(&knowledge_base.description)
+        .bind(&knowledge_base.collection_name)
+        .bind(knowledge_base.chunk_size)
+        .bind(knowledge_base.temperature)
+        .bind(&knowledge_base.openai_model)
+        .bind(knowledge_base.created_at.timestamp())
+        .bind(knowledge_base.updated_at.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to create knowledge base")?;
+
+        info!("Created knowledge base: {}", knowledge_base.id);
+        Ok(())
+    }
+
+    /// 保存知识库的可修改字段
+    pub async fn update(&self, knowledge_base: &KnowledgeBase) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE knowledge_bases
+            SET name = ?, description = ?, chunk_size = ?, temperature = ?, openai_model = ?, updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(&knowledge_base.name)
+        .bind(&knowledge_base.description)
+        .bind(knowledge_base.chunk_size)
+        .bind(knowledge_base.temperature)
+        .bind(&knowledge_base.openai_model)
+        .bind(knowledge_base.updated_at.timestamp())
+        .bind(&knowledge_base.id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to update knowledge base")?;
+
+        Ok(())
+    }
+
+    /// 删除知识库
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM knowledge_bases WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete knowledge base")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
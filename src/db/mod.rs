@@ -1,10 +1,26 @@
+mod audit_store;
+mod auth_policy;
 mod conversation_store;
+mod experiment_store;
+mod knowledge_base_store;
+mod permission;
+mod preamble_store;
 pub mod qdrant_store;
+mod settings_store;
 mod user_store;
+mod workspace_store;
 
+pub use audit_store::*;
+pub use auth_policy::*;
 pub use conversation_store::*;
+pub use experiment_store::*;
+pub use knowledge_base_store::*;
+pub use permission::*;
+pub use preamble_store::*;
 pub use qdrant_store::*;
+pub use settings_store::*;
 pub use user_store::*;
+pub use workspace_store::*;
 
 // alias for DocumentStore
 pub type DocumentStore = qdrant_store::DocumentStore<rig::providers::openai::EmbeddingModel>;
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use super::UserRole;
+
+/// 接口权限，由用户角色决定
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Permission {
+    /// 查看、检索文档
+    #[serde(rename = "document.read")]
+    DocumentRead,
+    /// 创建、修改、删除自己的文档
+    #[serde(rename = "document.write")]
+    DocumentWrite,
+    /// 查看和修改所有文档，不受所有者和可见范围限制
+    #[serde(rename = "document.manage")]
+    DocumentManage,
+    #[serde(rename = "preamble.write")]
+    PreambleWrite,
+    /// 知识库管理和检索调试
+    #[serde(rename = "knowledge_base.manage")]
+    KnowledgeBaseManage,
+    /// 查看工作区内所有用户的对话
+    #[serde(rename = "conversation.read_all")]
+    ConversationReadAll,
+    /// 清理历史对话
+    #[serde(rename = "conversation.manage")]
+    ConversationManage,
+    /// 用户、角色和两步验证管理
+    #[serde(rename = "user.manage")]
+    UserManage,
+    #[serde(rename = "api_key.manage")]
+    ApiKeyManage,
+    #[serde(rename = "workspace.manage")]
+    WorkspaceManage,
+    /// 备份和一致性检查
+    #[serde(rename = "system.manage")]
+    SystemManage,
+    /// 查看和导出审计日志
+    #[serde(rename = "audit.read")]
+    AuditRead,
+    /// 管理 A/B 实验并查看实验报告
+    #[serde(rename = "experiment.manage")]
+    ExperimentManage,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 13] = [
+        Permission::DocumentRead,
+        Permission::DocumentWrite,
+        Permission::DocumentManage,
+        Permission::PreambleWrite,
+        Permission::KnowledgeBaseManage,
+        Permission::ConversationReadAll,
+        Permission::ConversationManage,
+        Permission::UserManage,
+        Permission::ApiKeyManage,
+        Permission::WorkspaceManage,
+        Permission::SystemManage,
+        Permission::AuditRead,
+        Permission::ExperimentManage,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::DocumentRead => "document.read",
+            Permission::DocumentWrite => "document.write",
+            Permission::DocumentManage => "document.manage",
+            Permission::PreambleWrite => "preamble.write",
+            Permission::KnowledgeBaseManage => "knowledge_base.manage",
+            Permission::ConversationReadAll => "conversation.read_all",
+            Permission::ConversationManage => "conversation.manage",
+            Permission::UserManage => "user.manage",
+            Permission::ApiKeyManage => "api_key.manage",
+            Permission::WorkspaceManage => "workspace.manage",
+            Permission::SystemManage => "system.manage",
+            Permission::AuditRead => "audit.read",
+            Permission::ExperimentManage => "experiment.manage",
+        }
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl UserRole {
+    /// 角色拥有的权限
+    pub fn permissions(&self) -> &'static [Permission] {
+        use Permission::*;
+        match self {
+            UserRole::Admin => &Permission::ALL,
+            UserRole::KnowledgeAdmin => &[
+                DocumentRead,
+                DocumentWrite,
+                DocumentManage,
+                PreambleWrite,
+                KnowledgeBaseManage,
+                ExperimentManage,
+            ],
+            UserRole::SupportAgent => &[DocumentRead, ConversationReadAll],
+            UserRole::Editor | UserRole::User => &[DocumentRead, DocumentWrite],
+            UserRole::Viewer => &[DocumentRead],
+        }
+    }
+
+    pub fn has(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+
+    /// 拥有文档读写以外的管理权限，要求管理员启用两步验证时同样适用
+    pub fn is_privileged(&self) -> bool {
+        self.permissions()
+            .iter()
+            .any(|p| !matches!(p, Permission::DocumentRead | Permission::DocumentWrite))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_permissions() {
+        assert!(UserRole::Admin.has(Permission::SystemManage));
+        assert!(UserRole::User.has(Permission::DocumentWrite));
+        assert!(!UserRole::Viewer.has(Permission::DocumentWrite));
+        assert!(UserRole::KnowledgeAdmin.has(Permission::PreambleWrite));
+        assert!(UserRole::KnowledgeAdmin.has(Permission::ExperimentManage));
+        assert!(!UserRole::KnowledgeAdmin.has(Permission::UserManage));
+        assert!(UserRole::SupportAgent.has(Permission::ConversationReadAll));
+        assert!(!UserRole::SupportAgent.has(Permission::DocumentWrite));
+        assert!(UserRole::SupportAgent.is_privileged());
+        assert!(!UserRole::Editor.is_privileged());
+
+        for permission in Permission::ALL {
+            let json = serde_json::to_string(&permission).unwrap();
+            assert_eq!(json, format!("\"{}\"", permission));
+        }
+    }
+}
@@ -0,0 +1,683 @@
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, Sqlite, SqlitePool, Transaction, sqlite::SqliteRow};
+
+use crate::config::get_config;
+
+/// 全局 PreambleStore 实例
+static PREAMBLE_STORE: OnceLock<Arc<PreambleStore>> = OnceLock::new();
+
+/// 初始化全局 PreambleStore
+pub fn init_preamble_store(store: Arc<PreambleStore>) -> Result<()> {
+    PREAMBLE_STORE
+        .set(store)
+        .map_err(|_| anyhow::anyhow!("PreambleStore already initialized"))
+}
+
+/// 获取全局 PreambleStore 实例
+pub fn get_preamble_store() -> Option<&'static Arc<PreambleStore>> {
+    PREAMBLE_STORE.get()
+}
+
+/// 版本存储中 preamble 的 key：工作区的默认知识库为工作区 id，其他知识库为 `工作区/知识库 id`
+pub fn preamble_key(workspace: &str, knowledge_base: Option<&str>) -> String {
+    match knowledge_base {
+        Some(id) => format!("{}/{}", workspace, id),
+        None => workspace.to_string(),
+    }
+}
+
+/// preamble 的一个历史版本，版本号在每个 key 内从 1 递增
+#[derive(Debug, Clone, Serialize)]
+pub struct PreambleVersion {
+    pub version: i64,
+    pub content: String,
+    pub author: String,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for PreambleVersion {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let created_at_ts: i64 = row.try_get("created_at")?;
+        let created_at = DateTime::from_timestamp(created_at_ts, 0).ok_or_else(|| {
+            sqlx::Error::Decode(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid timestamp created_at",
+            )))
+        })?;
+
+        Ok(PreambleVersion {
+            version: row.try_get("version")?,
+            content: row.try_get("content")?,
+            author: row.try_get("author")?,
+            comment: row.try_get("comment")?,
+            created_at,
+        })
+    }
+}
+
+/// 草稿状态
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreambleDraftStatus {
+    Pending,
+    Published,
+    Rejected,
+}
+
+impl PreambleDraftStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PreambleDraftStatus::Pending => "pending",
+            PreambleDraftStatus::Published => "published",
+            PreambleDraftStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl std::str::FromStr for PreambleDraftStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pending" => Ok(PreambleDraftStatus::Pending),
+            "published" => Ok(PreambleDraftStatus::Published),
+            "rejected" => Ok(PreambleDraftStatus::Rejected),
+            _ => Err(anyhow::anyhow!("Unknown preamble draft status: {}", s)),
+        }
+    }
+}
+
+/// 待审批的 preamble 草稿，由一位管理员提交、另一位管理员发布
+#[derive(Debug, Clone, Serialize)]
+pub struct PreambleDraft {
+    pub id: i64,
+    pub content: String,
+    pub author: String,
+    pub author_id: i64,
+    pub comment: Option<String>,
+    /// 提交时的生效版本，发布时生效版本已变化则需重新提交
+    pub base_version: Option<i64>,
+    pub status: PreambleDraftStatus,
+    /// 发布或拒绝草稿的用户
+    pub reviewer: Option<String>,
+    /// 发布后生成的版本号
+    pub version: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for PreambleDraft {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let timestamp = |ts: i64| {
+            DateTime::from_timestamp(ts, 0).ok_or_else(|| {
+                sqlx::Error::Decode(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Invalid timestamp",
+                )))
+            })
+        };
+        let status: String = row.try_get("status")?;
+        let reviewed_at: Option<i64> = row.try_get("reviewed_at")?;
+
+        Ok(PreambleDraft {
+            id: row.try_get("id")?,
+            content: row.try_get("content")?,
+            author: row.try_get("author")?,
+            author_id: row.try_get("author_id")?,
+            comment: row.try_get("comment")?,
+            base_version: row.try_get("base_version")?,
+            status: status
+                .parse()
+                .map_err(|e: anyhow::Error| sqlx::Error::Decode(e.into()))?,
+            reviewer: row.try_get("reviewer")?,
+            version: row.try_get("version")?,
+            created_at: timestamp(row.try_get("created_at")?)?,
+            reviewed_at: reviewed_at.map(timestamp).transpose()?,
+        })
+    }
+}
+
+/// 发布草稿的结果
+#[derive(Debug)]
+pub enum PublishDraftOutcome {
+    Published(PreambleVersion),
+    /// 草稿不存在或已处理
+    NotPending,
+    /// 提交后生效版本已变化，草稿基于旧版本，需重新提交审批
+    Outdated {
+        live_version: Option<i64>,
+    },
+}
+
+/// preamble 版本存储，与用户表位于同一个数据库
+///
+/// 每个工作区记录一个生效版本，保存新版本时自动生效
+pub struct PreambleStore {
+    pool: SqlitePool,
+}
+
+impl PreambleStore {
+    /// 使用配置中的 user_db_path 创建 preamble 版本存储
+    pub async fn from_config() -> Result<Self> {
+        Self::new(&get_config().storage.user_db_path).await
+    }
+
+    /// 创建新的 preamble 版本存储实例
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url)
+            .await
+            .context("Failed to connect to preamble database")?;
+
+        let store = Self { pool };
+        store.init_database().await?;
+        Ok(store)
+    }
+
+    async fn init_database(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS preamble_versions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                workspace TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                author TEXT NOT NULL,
+                comment TEXT,
+                created_at INTEGER NOT NULL,
+                UNIQUE(workspace, version)
+            );
+            CREATE TABLE IF NOT EXISTS preamble_live (
+                workspace TEXT PRIMARY KEY,
+                version INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS preamble_drafts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                workspace TEXT NOT NULL,
+                content TEXT NOT NULL,
+                author TEXT NOT NULL,
+                author_id INTEGER NOT NULL,
+                comment TEXT,
+                base_version INTEGER,
+                status TEXT NOT NULL DEFAULT 'pending',
+                reviewer TEXT,
+                version INTEGER,
+                created_at INTEGER NOT NULL,
+                reviewed_at INTEGER
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize preamble tables")?;
+
+        // 旧表补充 base_version 列，升级前提交的草稿没有记录，发布前需重新提交
+        let has_base_version: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM pragma_table_info('preamble_drafts') WHERE name = 'base_version'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if has_base_version == 0 {
+            sqlx::query("ALTER TABLE preamble_drafts ADD COLUMN base_version INTEGER")
+                .execute(&self.pool)
+                .await
+                .context("Failed to add base_version column")?;
+        }
+        Ok(())
+    }
+
+    /// 保存新版本并设为生效版本
+    pub async fn create_version(
+        &self,
+        workspace: &str,
+        content: &str,
+        author: &str,
+        comment: Option<&str>,
+    ) -> Result<PreambleVersion> {
+        let mut tx = self.pool.begin().await?;
+        let version = Self::insert_version(&mut tx, workspace, content, author, comment).await?;
+        tx.commit().await?;
+        Ok(version)
+    }
+
+    async fn insert_version(
+        tx: &mut Transaction<'_, Sqlite>,
+        workspace: &str,
+        content: &str,
+        author: &str,
+        comment: Option<&str>,
+    ) -> Result<PreambleVersion> {
+        let version: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(version), 0) + 1 FROM preamble_versions WHERE workspace = ?",
+        )
+        .bind(workspace)
+        .fetch_one(&mut **tx)
+        .await?;
+        let created_at = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO preamble_versions (workspace, version, content, author, comment, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(workspace)
+        .bind(version)
+        .bind(content)
+        .bind(author)
+        .bind(comment)
+        .bind(created_at.timestamp())
+        .execute(&mut **tx)
+        .await
+        .context("Failed to save preamble version")?;
+        sqlx::query(
+            "INSERT INTO preamble_live (workspace, version) VALUES (?, ?) ON CONFLICT(workspace) DO UPDATE SET version = excluded.version",
+        )
+        .bind(workspace)
+        .bind(version)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(PreambleVersion {
+            version,
+            content: content.to_string(),
+            author: author.to_string(),
+            comment: comment.map(str::to_string),
+            created_at,
+        })
+    }
+
+    /// 工作区的全部版本，按版本号倒序
+    pub async fn list_versions(&self, workspace: &str) -> Result<Vec<PreambleVersion>> {
+        sqlx::query_as::<_, PreambleVersion>(
+            r#"
+            SELECT version, content, author, comment, created_at
+            FROM preamble_versions
+            WHERE workspace = ?
+            ORDER BY version DESC
+            "#,
+        )
+        .bind(workspace)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list preamble versions")
+    }
+
+    pub async fn get_version(
+        &self,
+        workspace: &str,
+        version: i64,
+    ) -> Result<Option<PreambleVersion>> {
+        sqlx::query_as::<_, PreambleVersion>(
+            r#"
+            SELECT version, content, author, comment, created_at
+            FROM preamble_versions
+            WHERE workspace = ? AND version = ?
+            "#,
+        )
+        .bind(workspace)
+        .bind(version)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get preamble version")
+    }
+
+    /// 版本是否由审批通过的草稿发布
+    pub async fn is_published_draft(&self, workspace: &str, version: i64) -> Result<bool> {
+        sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM preamble_drafts WHERE workspace = ? AND version = ? AND status = 'published')",
+        )
+        .bind(workspace)
+        .bind(version)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to check preamble draft")
+    }
+
+    /// 当前生效的版本号，尚未保存过版本时为 None
+    pub async fn live_version(&self, workspace: &str) -> Result<Option<i64>> {
+        sqlx::query_scalar("SELECT version FROM preamble_live WHERE workspace = ?")
+            .bind(workspace)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to get live preamble version")
+    }
+
+    /// 指定生效版本，版本不存在时返回 None
+    pub async fn pin_version(
+        &self,
+        workspace: &str,
+        version: i64,
+    ) -> Result<Option<PreambleVersion>> {
+        let Some(preamble) = self.get_version(workspace, version).await? else {
+            return Ok(None);
+        };
+        sqlx::query(
+            "INSERT INTO preamble_live (workspace, version) VALUES (?, ?) ON CONFLICT(workspace) DO UPDATE SET version = excluded.version",
+        )
+        .bind(workspace)
+        .bind(version)
+        .execute(&self.pool)
+        .await
+        .context("Failed to pin preamble version")?;
+        Ok(Some(preamble))
+    }
+
+    /// 提交待审批的草稿，`base_version` 为提交时的生效版本
+    pub async fn create_draft(
+        &self,
+        workspace: &str,
+        content: &str,
+        author: &str,
+        author_id: i64,
+        comment: Option<&str>,
+        base_version: i64,
+    ) -> Result<PreambleDraft> {
+        let created_at = Utc::now();
+        let id = sqlx::query(
+            r#"
+            INSERT INTO preamble_drafts (workspace, content, author, author_id, comment, base_version, status, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, 'pending', ?)
+            "#,
+        )
+        .bind(workspace)
+        .bind(content)
+        .bind(author)
+        .bind(author_id)
+        .bind(comment)
+        .bind(base_version)
+        .bind(created_at.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to save preamble draft")?
+        .last_insert_rowid();
+
+        Ok(PreambleDraft {
+            id,
+            content: content.to_string(),
+            author: author.to_string(),
+            author_id,
+            comment: comment.map(str::to_string),
+            base_version: Some(base_version),
+            status: PreambleDraftStatus::Pending,
+            reviewer: None,
+            version: None,
+            created_at,
+            reviewed_at: None,
+        })
+    }
+
+    /// 工作区的草稿，按提交时间倒序，可按状态筛选
+    pub async fn list_drafts(
+        &self,
+        workspace: &str,
+        status: Option<PreambleDraftStatus>,
+    ) -> Result<Vec<PreambleDraft>> {
+        let status = status.map(|s| s.as_str());
+        sqlx::query_as::<_, PreambleDraft>(
+            r#"
+            SELECT id, content, author, author_id, comment, base_version, status, reviewer, version, created_at, reviewed_at
+            FROM preamble_drafts
+            WHERE workspace = ? AND (? IS NULL OR status = ?)
+            ORDER BY id DESC
+            "#,
+        )
+        .bind(workspace)
+        .bind(status)
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list preamble drafts")
+    }
+
+    pub async fn get_draft(&self, workspace: &str, id: i64) -> Result<Option<PreambleDraft>> {
+        sqlx::query_as::<_, PreambleDraft>(
+            r#"
+            SELECT id, content, author, author_id, comment, base_version, status, reviewer, version, created_at, reviewed_at
+            FROM preamble_drafts
+            WHERE workspace = ? AND id = ?
+            "#,
+        )
+        .bind(workspace)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get preamble draft")
+    }
+
+    /// 发布待审批的草稿：保存为新版本并生效
+    ///
+    /// 生效版本与草稿提交时不同（期间有其他版本生效或回滚）时不发布，避免覆盖未经审阅的改动
+    pub async fn publish_draft(
+        &self,
+        workspace: &str,
+        id: i64,
+        reviewer: &str,
+    ) -> Result<PublishDraftOutcome> {
+        let mut tx = self.pool.begin().await?;
+        let draft = sqlx::query_as::<_, PreambleDraft>(
+            r#"
+            SELECT id, content, author, author_id, comment, base_version, status, reviewer, version, created_at, reviewed_at
+            FROM preamble_drafts
+            WHERE workspace = ? AND id = ? AND status = 'pending'
+            "#,
+        )
+        .bind(workspace)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(draft) = draft else {
+            return Ok(PublishDraftOutcome::NotPending);
+        };
+        let live_version: Option<i64> =
+            sqlx::query_scalar("SELECT version FROM preamble_live WHERE workspace = ?")
+                .bind(workspace)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if draft.base_version.is_none() || draft.base_version != live_version {
+            return Ok(PublishDraftOutcome::Outdated { live_version });
+        }
+
+        let version = Self::insert_version(
+            &mut tx,
+            workspace,
+            &draft.content,
+            &draft.author,
+            draft.comment.as_deref(),
+        )
+        .await?;
+        sqlx::query(
+            r#"
+            UPDATE preamble_drafts
+            SET status = 'published', reviewer = ?, version = ?, reviewed_at = ?
+            WHERE id = ? AND status = 'pending'
+            "#,
+        )
+        .bind(reviewer)
+        .bind(version.version)
+        .bind(Utc::now().timestamp())
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to publish preamble draft")?;
+        tx.commit().await?;
+        Ok(PublishDraftOutcome::Published(version))
+    }
+
+    /// 拒绝待审批的草稿，草稿不存在或已处理时返回 false
+    pub async fn reject_draft(&self, workspace: &str, id: i64, reviewer: &str) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE preamble_drafts
+            SET status = 'rejected', reviewer = ?, reviewed_at = ?
+            WHERE workspace = ? AND id = ? AND status = 'pending'
+            "#,
+        )
+        .bind(reviewer)
+        .bind(Utc::now().timestamp())
+        .bind(workspace)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to reject preamble draft")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 删除一个 preamble 的全部版本和草稿
+    pub async fn delete_preamble(&self, key: &str) -> Result<()> {
+        for table in ["preamble_versions", "preamble_live", "preamble_drafts"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE workspace = ?", table))
+                .bind(key)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// 删除工作区及其知识库的全部版本和草稿
+    pub async fn delete_workspace(&self, workspace: &str) -> Result<()> {
+        let prefix = preamble_key(workspace, Some(""));
+        for table in ["preamble_versions", "preamble_live", "preamble_drafts"] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE workspace = ? OR substr(workspace, 1, ?) = ?",
+                table
+            ))
+            .bind(workspace)
+            .bind(prefix.len() as i64)
+            .bind(&prefix)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_preamble_versions() {
+        let db = std::env::temp_dir().join(format!("preamble-{}.db", nanoid::nanoid!(8)));
+        let store = PreambleStore::new(&format!("sqlite:{}?mode=rwc", db.display()))
+            .await
+            .unwrap();
+
+        let v1 = store
+            .create_version("default", "first", "admin", None)
+            .await
+            .unwrap();
+        let v2 = store
+            .create_version("default", "second", "admin", Some("tweak"))
+            .await
+            .unwrap();
+        store
+            .create_version("other", "other", "admin", None)
+            .await
+            .unwrap();
+        assert_eq!((v1.version, v2.version), (1, 2));
+        assert_eq!(store.live_version("default").await.unwrap(), Some(2));
+
+        let pinned = store.pin_version("default", 1).await.unwrap().unwrap();
+        assert_eq!(pinned.content, "first");
+        assert_eq!(store.live_version("default").await.unwrap(), Some(1));
+        assert!(store.pin_version("default", 9).await.unwrap().is_none());
+
+        let versions = store.list_versions("default").await.unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].comment.as_deref(), Some("tweak"));
+
+        // 知识库的 preamble 单独编号，随工作区一起删除
+        let faq = preamble_key("default", Some("faq"));
+        let kb = store
+            .create_version(&faq, "faq", "admin", None)
+            .await
+            .unwrap();
+        assert_eq!(kb.version, 1);
+        store.delete_workspace("default").await.unwrap();
+        assert!(store.live_version(&faq).await.unwrap().is_none());
+        assert!(store.list_versions("default").await.unwrap().is_empty());
+        assert_eq!(store.live_version("other").await.unwrap(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_preamble_drafts() {
+        let db = std::env::temp_dir().join(format!("preamble-{}.db", nanoid::nanoid!(8)));
+        let store = PreambleStore::new(&format!("sqlite:{}?mode=rwc", db.display()))
+            .await
+            .unwrap();
+
+        store
+            .create_version("default", "first", "system", None)
+            .await
+            .unwrap();
+        let draft = store
+            .create_draft("default", "draft", "alice", 1, Some("new tone"), 1)
+            .await
+            .unwrap();
+        let rejected = store
+            .create_draft("default", "other", "alice", 1, None, 1)
+            .await
+            .unwrap();
+        let outdated = store
+            .create_draft("default", "stale", "carol", 3, None, 1)
+            .await
+            .unwrap();
+        assert!(
+            store
+                .reject_draft("default", rejected.id, "bob")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !store
+                .reject_draft("default", rejected.id, "bob")
+                .await
+                .unwrap()
+        );
+
+        let PublishDraftOutcome::Published(version) = store
+            .publish_draft("default", draft.id, "bob")
+            .await
+            .unwrap()
+        else {
+            panic!("expected draft to be published");
+        };
+        assert_eq!((version.version, version.author.as_str()), (2, "alice"));
+        assert_eq!(store.live_version("default").await.unwrap(), Some(2));
+        assert!(matches!(
+            store
+                .publish_draft("default", draft.id, "bob")
+                .await
+                .unwrap(),
+            PublishDraftOutcome::NotPending
+        ));
+
+        let published = store.get_draft("default", draft.id).await.unwrap().unwrap();
+        assert_eq!(published.status, PreambleDraftStatus::Published);
+        assert_eq!(published.reviewer.as_deref(), Some("bob"));
+        assert_eq!(published.version, Some(2));
+
+        // 基于 v1 提交的草稿在 v2 生效后不能发布，仍保持待审批
+        assert!(matches!(
+            store
+                .publish_draft("default", outdated.id, "bob")
+                .await
+                .unwrap(),
+            PublishDraftOutcome::Outdated {
+                live_version: Some(2)
+            }
+        ));
+        assert_eq!(store.live_version("default").await.unwrap(), Some(2));
+        let pending = store
+            .list_drafts("default", Some(PreambleDraftStatus::Pending))
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, outdated.id);
+    }
+}
@@ -6,8 +6,10 @@ use qdrant_client::{
     Payload, Qdrant,
     qdrant::{
         Condition, CountPointsBuilder, CreateCollectionBuilder, CreateFieldIndexCollectionBuilder,
-        DeletePointsBuilder, Direction, FieldType, Filter as QdrantClientFilter, OrderByBuilder,
-        Query, QueryPointsBuilder, ScrollPointsBuilder, VectorParamsBuilder, points_selector,
+        DatetimeRange, DeletePointsBuilder, Direction, FieldType, Filter as QdrantClientFilter,
+        OrderByBuilder, PointId, PointsIdsList, Query, QueryPointsBuilder, ScrollPointsBuilder,
+        SetPayloadPointsBuilder, TextIndexParamsBuilder, Timestamp, TokenizerType,
+        VectorParamsBuilder, point_id::PointIdOptions, points_selector,
     },
 };
 use rig::{
@@ -22,7 +24,10 @@ use rig_qdrant::QdrantVectorStore;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info, warn};
 
-use crate::config::QdrantConfig;
+use crate::{
+    config::QdrantConfig,
+    db::{Permission, UserRole, normalize_groups},
+};
 
 /// 文档结构
 #[derive(Debug, Clone, Serialize, Deserialize, Embed, PartialEq)]
@@ -35,6 +40,292 @@ pub struct Document {
     pub source: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub metadata: DocumentMetadata,
+    #[serde(default)]
+    pub access: DocumentAccess,
+}
+
+/// 文档级元数据，所有分块共享同一份
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DocumentMetadata {
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub product: Option<String>,
+    pub language: Option<String>,
+    /// 生效时间，之前不参与检索
+    pub valid_from: Option<DateTime<Utc>>,
+    /// 失效时间，之后不参与检索
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+impl DocumentMetadata {
+    /// 去除首尾空白、空值和重复标签，并校验有效期
+    pub fn normalized(self) -> Result<Self> {
+        if let (Some(from), Some(until)) = (self.valid_from, self.valid_until)
+            && from > until
+        {
+            anyhow::bail!("valid_from must not be later than valid_until");
+        }
+
+        let clean = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let mut tags: Vec<String> = Vec::with_capacity(self.tags.len());
+        for tag in self.tags {
+            let tag = tag.trim().to_string();
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        Ok(Self {
+            tags,
+            category: clean(self.category),
+            product: clean(self.product),
+            language: clean(self.language).map(|l| l.to_lowercase()),
+            valid_from: self.valid_from,
+            valid_until: self.valid_until,
+        })
+    }
+
+    /// 给定时间是否在有效期内（未设置的边界视为不限制）
+    pub fn is_valid_at(&self, at: DateTime<Utc>) -> bool {
+        self.valid_from.is_none_or(|from| from <= at)
+            && self.valid_until.is_none_or(|until| at <= until)
+    }
+}
+
+/// 文档可见范围
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// 所有人可见，包括未登录的聊天用户
+    #[default]
+    Public,
+    /// 拥有指定角色的登录用户可见
+    Role,
+    /// 属于任一指定分组的登录用户可见
+    Groups,
+    /// 仅所有者和管理员可见
+    Private,
+}
+
+/// 文档访问控制，所有分块共享同一份
+///
+/// 旧数据没有该字段时按公开文档处理，且只有管理员可以修改
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DocumentAccess {
+    pub owner_id: Option<i64>,
+    pub owner: Option<String>,
+    pub visibility: Visibility,
+    /// visibility 为 role 时要求的角色
+    pub role: Option<UserRole>,
+    /// visibility 为 groups 时允许的分组
+    pub groups: Vec<String>,
+}
+
+impl DocumentAccess {
+    /// 清理分组，并校验 visibility 所需的字段
+    pub fn normalized(self) -> Result<Self> {
+        let groups = normalize_groups(self.groups);
+        let (role, groups) = match self.visibility {
+            Visibility::Role => (Some(self.role.unwrap_or(UserRole::User)), Vec::new()),
+            Visibility::Groups if groups.is_empty() => {
+                anyhow::bail!("groups visibility requires at least one group")
+            }
+            Visibility::Groups => (None, groups),
+            Visibility::Public | Visibility::Private => (None, Vec::new()),
+        };
+
+        Ok(Self {
+            role,
+            groups,
+            ..self
+        })
+    }
+
+    /// 设置所有者
+    pub fn owned_by(mut self, owner_id: i64, owner: &str) -> Self {
+        self.owner_id = Some(owner_id);
+        self.owner = Some(owner.to_string());
+        self
+    }
+
+    fn is_owner(&self, viewer: &DocumentViewer) -> bool {
+        matches!(
+            (viewer, self.owner_id),
+            (DocumentViewer::User { user_id, .. }, Some(owner_id)) if *user_id == owner_id
+        )
+    }
+
+    /// 是否可以查看（以及被检索到）
+    pub fn can_view(&self, viewer: &DocumentViewer) -> bool {
+        if self.visibility == Visibility::Public || viewer.can_manage_all() || self.is_owner(viewer)
+        {
+            return true;
+        }
+
+        match (viewer, self.visibility) {
+            (DocumentViewer::User { role, .. }, Visibility::Role) => self
+                .role
+                .as_ref()
+                .is_some_and(|required| role.satisfies(required)),
+            (DocumentViewer::User { groups, .. }, Visibility::Groups) => {
+                self.groups.iter().any(|group| groups.contains(group))
+            }
+            _ => false,
+        }
+    }
+
+    /// 是否可以修改或删除：拥有 document.manage 权限的用户或所有者
+    pub fn can_edit(&self, viewer: &DocumentViewer) -> bool {
+        viewer.can_manage_all() || self.is_owner(viewer)
+    }
+}
+
+/// 访问文档的身份
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum DocumentViewer {
+    /// 未登录的聊天用户，只能看到公开文档
+    #[default]
+    Anonymous,
+    User {
+        user_id: i64,
+        role: UserRole,
+        groups: Vec<String>,
+    },
+}
+
+impl DocumentViewer {
+    /// 是否拥有 document.manage 权限，不受所有者和可见范围限制
+    pub fn can_manage_all(&self) -> bool {
+        matches!(
+            self,
+            DocumentViewer::User { role, .. } if role.has(Permission::DocumentManage)
+        )
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        matches!(self, DocumentViewer::Anonymous)
+    }
+
+    /// 转换为 Qdrant 过滤条件，拥有 document.manage 权限时不限制
+    fn condition(&self) -> Option<Condition> {
+        let visibility = |v: &str| Condition::matches("access.visibility", v.to_string());
+        // 旧数据没有 access 字段，按公开处理
+        let mut should = vec![
+            visibility("public"),
+            Condition::is_empty("access.visibility"),
+        ];
+
+        match self {
+            DocumentViewer::Anonymous => {}
+            DocumentViewer::User { .. } if self.can_manage_all() => return None,
+            DocumentViewer::User {
+                user_id,
+                role,
+                groups,
+            } => {
+                should.push(Condition::matches("access.owner_id", *user_id));
+                let roles: Vec<String> = UserRole::ALL
+                    .into_iter()
+                    .filter(|required| role.satisfies(required))
+                    .map(|required| required.to_string())
+                    .collect();
+                should.push(
+                    QdrantClientFilter::must([
+                        visibility("role"),
+                        Condition::matches("access.role", roles),
+                    ])
+                    .into(),
+                );
+                if !groups.is_empty() {
+                    should.push(
+                        QdrantClientFilter::must([
+                            visibility("groups"),
+                            Condition::matches("access.groups", groups.clone()),
+                        ])
+                        .into(),
+                    );
+                }
+            }
+        }
+
+        Some(QdrantClientFilter::should(should).into())
+    }
+}
+
+/// 按元数据限制检索范围，各字段之间为 AND，标签之间为 OR
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MetadataFilter {
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub product: Option<String>,
+    pub language: Option<String>,
+}
+
+impl MetadataFilter {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.category.is_none()
+            && self.product.is_none()
+            && self.language.is_none()
+    }
+
+    /// 转换为 Qdrant 过滤条件
+    fn conditions(&self) -> Vec<Condition> {
+        let eq = |field: &str, value: &str| {
+            Condition::matches(format!("metadata.{}", field), value.to_string())
+        };
+
+        let mut conditions = Vec::new();
+        if !self.tags.is_empty() {
+            conditions.push(Condition::matches("metadata.tags", self.tags.clone()));
+        }
+        if let Some(category) = &self.category {
+            conditions.push(eq("category", category));
+        }
+        if let Some(product) = &self.product {
+            conditions.push(eq("product", product));
+        }
+        if let Some(language) = &self.language {
+            conditions.push(eq("language", &language.to_lowercase()));
+        }
+        conditions
+    }
+}
+
+/// 给定时间在有效期内的条件，未设置的边界（包括没有 metadata 的旧数据）视为不限制
+fn validity_conditions(at: DateTime<Utc>) -> Vec<Condition> {
+    let bound = |field: &str, range: DatetimeRange| -> Condition {
+        QdrantClientFilter::should([
+            Condition::is_empty(field),
+            Condition::datetime_range(field, range),
+        ])
+        .into()
+    };
+    vec![
+        bound(
+            "metadata.valid_from",
+            DatetimeRange {
+                lte: Some(to_timestamp(at)),
+                ..Default::default()
+            },
+        ),
+        bound(
+            "metadata.valid_until",
+            DatetimeRange {
+                gte: Some(to_timestamp(at)),
+                ..Default::default()
+            },
+        ),
+    ]
 }
 
 impl Document {
@@ -54,24 +345,166 @@ impl Document {
             source,
             created_at: timestamp,
             updated_at: timestamp,
+            metadata: DocumentMetadata::default(),
+            access: DocumentAccess::default(),
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: DocumentMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_access(mut self, access: DocumentAccess) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// 原始文件名（去掉分块文档 source 中的 "(Part i/N)" 后缀）
+    pub fn filename(&self) -> &str {
+        match self.source.rsplit_once(" (Part ") {
+            Some((filename, _)) if self.total_chunks().is_some() => filename,
+            _ => &self.source,
+        }
+    }
+
+    /// 分块总数（从 source 的 "(Part i/N)" 后缀解析）
+    pub fn total_chunks(&self) -> Option<u32> {
+        let (_, part) = self.source.rsplit_once(" (Part ")?;
+        let (_, total) = part.strip_suffix(')')?.split_once('/')?;
+        total.trim().parse().ok()
+    }
+}
+
+/// 文档检索过滤条件
+#[derive(Debug, Clone, Default)]
+pub struct DocumentFilter {
+    /// source 包含的文本
+    pub source: Option<String>,
+    pub updated_from: Option<DateTime<Utc>>,
+    pub updated_to: Option<DateTime<Utc>>,
+    /// 只返回该身份可见的文档
+    pub viewer: DocumentViewer,
+}
+
+impl DocumentFilter {
+    fn conditions(&self) -> Vec<Condition> {
+        let mut conditions: Vec<Condition> = self.viewer.condition().into_iter().collect();
+        if let Some(source) = self.source.as_deref().filter(|s| !s.trim().is_empty()) {
+            // 未建全文索引时 Qdrant 按子串匹配
+            conditions.push(Condition::matches_text("source", source.trim()));
+        }
+        if self.updated_from.is_some() || self.updated_to.is_some() {
+            conditions.push(Condition::datetime_range(
+                "updated_at",
+                DatetimeRange {
+                    gte: self.updated_from.map(to_timestamp),
+                    lte: self.updated_to.map(to_timestamp),
+                    ..Default::default()
+                },
+            ));
         }
+        conditions
     }
 }
 
+/// 供 agent 检索使用的向量索引
+///
+/// 直接查询 Qdrant，元数据、有效期和访问控制都作为查询过滤条件，
+/// 不会因为先取 top_n 再过滤而少返回结果
 #[derive(Clone)]
 pub struct SerializableQdrantVectorStore<M: EmbeddingModel> {
-    inner: Arc<QdrantVectorStore<M>>,
+    client: Arc<Qdrant>,
+    model: Arc<M>,
+    collection_name: String,
+    filter: MetadataFilter,
+    viewer: DocumentViewer,
 }
 
 impl<M: EmbeddingModel> SerializableQdrantVectorStore<M> {
-    pub fn new(inner: QdrantVectorStore<M>) -> Self {
+    pub fn new(client: Qdrant, model: M, collection_name: &str) -> Self {
         Self {
-            inner: Arc::new(inner),
+            client: Arc::new(client),
+            model: Arc::new(model),
+            collection_name: collection_name.to_string(),
+            filter: MetadataFilter::default(),
+            viewer: DocumentViewer::default(),
         }
     }
 
-    pub fn inner(&self) -> Arc<QdrantVectorStore<M>> {
-        Arc::clone(&self.inner)
+    /// 为所有检索附加元数据过滤
+    pub fn with_filter(mut self, filter: MetadataFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 只检索该身份可见的文档，默认仅公开文档
+    pub fn with_viewer(mut self, viewer: DocumentViewer) -> Self {
+        self.viewer = viewer;
+        self
+    }
+
+    /// 本次检索的全部过滤条件
+    fn conditions(&self, at: DateTime<Utc>) -> Vec<Condition> {
+        let mut conditions = self.filter.conditions();
+        conditions.extend(validity_conditions(at));
+        conditions.extend(self.viewer.condition());
+        conditions
+    }
+}
+
+impl<M> SerializableQdrantVectorStore<M>
+where
+    M: EmbeddingModel + Send + Sync + 'static,
+{
+    /// 按过滤条件检索最相近的点，返回 (分数, 点 id, payload)
+    fn query(
+        &self,
+        req: VectorSearchRequest<RigFilter<serde_json::Value>>,
+    ) -> impl std::future::Future<
+        Output = Result<Vec<(f64, String, serde_json::Value)>, VectorStoreError>,
+    > + Send
+    + use<M> {
+        let client = Arc::clone(&self.client);
+        let model = Arc::clone(&self.model);
+        let collection_name = self.collection_name.clone();
+        let conditions = self.conditions(Utc::now());
+        async move {
+            // 过滤条件由 with_filter/with_viewer 指定，rig 的过滤器无法表达"字段不存在"
+            if req.filter().is_some() {
+                return Err(VectorStoreError::DatastoreError(
+                    "Request filters are not supported, use with_filter instead".into(),
+                ));
+            }
+
+            let embedding = model.embed_text(req.query()).await?;
+            let vector: Vec<f32> = embedding.vec.iter().map(|v| *v as f32).collect();
+            let mut builder = QueryPointsBuilder::new(&collection_name)
+                .query(Query::new_nearest(vector))
+                .limit(req.samples())
+                .with_payload(true)
+                .with_vectors(false);
+            if let Some(threshold) = req.threshold() {
+                builder = builder.score_threshold(threshold as f32);
+            }
+            if !conditions.is_empty() {
+                builder = builder.filter(QdrantClientFilter::must(conditions));
+            }
+
+            let response = client
+                .query(builder.build())
+                .await
+                .map_err(|e| VectorStoreError::DatastoreError(Box::new(e)))?;
+            Ok(response
+                .result
+                .into_iter()
+                .filter_map(|point| {
+                    let id = point.id.as_ref().map(point_id_to_string)?;
+                    let payload: serde_json::Value = Payload::from(point.payload).into();
+                    Some((point.score as f64, id, payload))
+                })
+                .collect())
+        }
     }
 }
 
@@ -86,11 +519,13 @@ where
         req: VectorSearchRequest<Self::Filter>,
     ) -> impl std::future::Future<Output = Result<Vec<(f64, String, T)>, VectorStoreError>> + Send
     {
-        let inner = self.inner();
+        let query = self.query(req);
         async move {
-            type StoreFilter<M> = <QdrantVectorStore<M> as VectorStoreIndex>::Filter;
-            let mapped = req.map_filter(|filter| filter.interpret::<StoreFilter<M>>());
-            inner.top_n::<T>(mapped).await
+            query
+                .await?
+                .into_iter()
+                .map(|(score, id, payload)| Ok((score, id, serde_json::from_value(payload)?)))
+                .collect()
         }
     }
 
@@ -99,11 +534,13 @@ where
         req: VectorSearchRequest<Self::Filter>,
     ) -> impl std::future::Future<Output = Result<Vec<(f64, String)>, VectorStoreError>> + Send
     {
-        let inner = self.inner();
+        let query = self.query(req);
         async move {
-            type StoreFilter<M> = <QdrantVectorStore<M> as VectorStoreIndex>::Filter;
-            let mapped = req.map_filter(|filter| filter.interpret::<StoreFilter<M>>());
-            inner.top_n_ids(mapped).await
+            Ok(query
+                .await?
+                .into_iter()
+                .map(|(score, id, _)| (score, id))
+                .collect())
         }
     }
 }
@@ -140,7 +577,8 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
             .await
             .context("Failed to check Qdrant collection existence")?
         {
-            return Ok(());
+            // 已有集合也补齐新增字段的索引
+            return self.ensure_payload_indexes(client).await;
         }
 
         let size = vector_size.max(self.config.vector_size) as u64;
@@ -169,6 +607,16 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
             ("id", FieldType::Keyword),
             ("base_id", FieldType::Keyword),
             ("updated_at", FieldType::Datetime),
+            ("metadata.tags", FieldType::Keyword),
+            ("metadata.category", FieldType::Keyword),
+            ("metadata.product", FieldType::Keyword),
+            ("metadata.language", FieldType::Keyword),
+            ("metadata.valid_from", FieldType::Datetime),
+            ("metadata.valid_until", FieldType::Datetime),
+            ("access.visibility", FieldType::Keyword),
+            ("access.owner_id", FieldType::Integer),
+            ("access.role", FieldType::Keyword),
+            ("access.groups", FieldType::Keyword),
         ] {
             if let Err(err) = client
                 .create_field_index(
@@ -185,6 +633,28 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
                 warn!("Failed to create index on field '{}': {}", field, err);
             }
         }
+
+        // 关键词检索使用的全文索引，忽略大小写，多语言分词以支持中文
+        if let Err(err) = client
+            .create_field_index(
+                CreateFieldIndexCollectionBuilder::new(
+                    &self.config.collection_name,
+                    "content",
+                    FieldType::Text,
+                )
+                .field_index_params(
+                    TextIndexParamsBuilder::new(TokenizerType::Multilingual).lowercase(true),
+                )
+                .wait(true),
+            )
+            .await
+            && !is_already_exists(&err)
+        {
+            warn!(
+                "Failed to create full-text index on field 'content': {}",
+                err
+            );
+        }
         Ok(())
     }
 
@@ -239,7 +709,7 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
     pub async fn create_vector_index(
         &self,
         embedding_model: M,
-    ) -> Result<(SerializableQdrantVectorStore<M>, usize)>
+    ) -> Result<SerializableQdrantVectorStore<M>>
     where
         M: Clone + Send + Sync + 'static,
     {
@@ -247,36 +717,158 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
         self.ensure_collection(&client, embedding_model.ndims())
             .await?;
 
-        let vector_store = self.build_vector_store(client.clone(), embedding_model);
-        let wrapped = SerializableQdrantVectorStore::new(vector_store);
-        let total = self.collection_count(&client).await?;
-
-        Ok((wrapped, total))
+        Ok(SerializableQdrantVectorStore::new(
+            client,
+            embedding_model,
+            &self.config.collection_name,
+        ))
     }
 
+    /// 与 agent 的 dynamic_context 相同的检索，返回 (分数, 点 id, payload)
     pub async fn search(
         &self,
         vector_index: &SerializableQdrantVectorStore<M>,
         query: &str,
         limit: usize,
-    ) -> Result<Vec<(f64, Document)>> {
+    ) -> Result<Vec<(f64, String, serde_json::Value)>> {
         let req = VectorSearchRequest::builder()
             .query(query)
             .samples(limit as u64)
             .build()
             .context("Failed to build vector search request")?;
 
-        let results: Vec<(f64, String, Document)> =
-            <SerializableQdrantVectorStore<M> as VectorStoreIndex>::top_n(vector_index, req)
-                .await
-                .context("Vector search on Qdrant failed")?;
+        <SerializableQdrantVectorStore<M> as VectorStoreIndex>::top_n(vector_index, req)
+            .await
+            .context("Vector search on Qdrant failed")
+    }
+
+    /// 语义检索，可附加过滤条件
+    pub async fn semantic_search(
+        &self,
+        embedding_model: &M,
+        query: &str,
+        limit: usize,
+        filter: &DocumentFilter,
+    ) -> Result<Vec<(f64, Document)>> {
+        let client = self.client()?;
+        if !self.collection_exists(&client).await? {
+            return Ok(Vec::new());
+        }
+
+        let embedding = embedding_model
+            .embed_text(query)
+            .await
+            .context("Failed to embed search query")?;
+        let vector: Vec<f32> = embedding.vec.iter().map(|v| *v as f32).collect();
 
-        Ok(results
+        let mut builder = QueryPointsBuilder::new(&self.config.collection_name)
+            .query(Query::new_nearest(vector))
+            .limit(limit as u64)
+            .with_payload(true)
+            .with_vectors(false);
+        let conditions = filter.conditions();
+        if !conditions.is_empty() {
+            builder = builder.filter(QdrantClientFilter::must(conditions));
+        }
+
+        let response = client
+            .query(builder.build())
+            .await
+            .context("Vector search on Qdrant failed")?;
+
+        Ok(response
+            .result
             .into_iter()
-            .map(|(score, _, doc)| (score, doc))
+            .filter_map(|point| match Self::deserialize_document(point.payload) {
+                Ok(doc) => Some((point.score as f64, doc)),
+                Err(err) => {
+                    warn!("Failed to deserialize document payload: {}", err);
+                    None
+                }
+            })
             .collect())
     }
 
+    /// 关键词检索：在 content 包含任一关键词的文档中按 `score` 返回得分最高的 limit 个
+    ///
+    /// 全文索引忽略大小写，得分不大于 0 的文档不返回
+    pub async fn keyword_search<F>(
+        &self,
+        terms: &[String],
+        limit: usize,
+        filter: &DocumentFilter,
+        score: F,
+    ) -> Result<Vec<(f64, Document)>>
+    where
+        F: Fn(&Document) -> f64,
+    {
+        const SCROLL_BATCH_SIZE: u32 = 256;
+
+        if terms.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let client = self.client()?;
+        if !self.collection_exists(&client).await? {
+            return Ok(Vec::new());
+        }
+
+        let mut conditions = filter.conditions();
+        conditions.push(
+            QdrantClientFilter::should(
+                terms
+                    .iter()
+                    .map(|term| Condition::matches_text("content", term.clone())),
+            )
+            .into(),
+        );
+        let filter = QdrantClientFilter::must(conditions);
+
+        // scroll 不按相关度排序，需要翻完全部匹配的点，只保留得分最高的 limit 个
+        let mut scored: Vec<(f64, Document)> = Vec::new();
+        let mut offset: Option<PointId> = None;
+        loop {
+            let mut builder = ScrollPointsBuilder::new(&self.config.collection_name)
+                .filter(filter.clone())
+                .with_payload(true)
+                .with_vectors(false)
+                .limit(SCROLL_BATCH_SIZE);
+            if let Some(offset) = offset.take() {
+                builder = builder.offset(offset);
+            }
+
+            let response = client
+                .scroll(builder.build())
+                .await
+                .context("Keyword search on Qdrant failed")?;
+
+            for point in response.result {
+                match Self::deserialize_document(point.payload) {
+                    Ok(doc) => {
+                        let score = score(&doc);
+                        if score > 0.0 {
+                            scored.push((score, doc));
+                        }
+                    }
+                    Err(err) => warn!("Failed to deserialize document payload: {}", err),
+                }
+            }
+            if scored.len() > limit * 2 {
+                scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+                scored.truncate(limit);
+            }
+
+            match response.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+        }
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
     pub async fn count_documents_async(&self) -> Result<usize> {
         let client = self.client()?;
         if !self.collection_exists(&client).await? {
@@ -328,6 +920,74 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
         Ok(())
     }
 
+    /// 用新文档替换 identifier 下的全部点：先生成全部向量并写入，成功后再删除原有的点
+    ///
+    /// 生成向量或写入失败时原有数据保持不变
+    pub async fn replace_document(
+        &self,
+        identifier: &str,
+        documents: Vec<Document>,
+        embedding_model: M,
+    ) -> Result<()>
+    where
+        M: Clone + Send + Sync + 'static,
+    {
+        let client = self.client()?;
+        self.ensure_collection(&client, embedding_model.ndims())
+            .await?;
+
+        let mut embeddings = Vec::with_capacity(documents.len());
+        for chunk in documents.chunks(10) {
+            embeddings.extend(
+                EmbeddingsBuilder::new(embedding_model.clone())
+                    .documents(chunk.to_vec())
+                    .context("Failed to create embeddings builder")?
+                    .build()
+                    .await
+                    .context("Failed to create embeddings")?,
+            );
+        }
+
+        let stale = self
+            .point_ids(&client, self.build_filter_for_identifier(identifier))
+            .await?;
+        self.build_vector_store(client, embedding_model)
+            .insert_documents(embeddings)
+            .await
+            .context("Failed to insert documents into Qdrant")?;
+        self.delete_points(stale).await
+    }
+
+    /// 符合条件的全部点 ID
+    async fn point_ids(&self, client: &Qdrant, filter: QdrantClientFilter) -> Result<Vec<PointId>> {
+        const SCROLL_BATCH_SIZE: u32 = 256;
+
+        let mut ids = Vec::new();
+        let mut offset: Option<PointId> = None;
+        loop {
+            let mut builder = ScrollPointsBuilder::new(&self.config.collection_name)
+                .filter(filter.clone())
+                .with_payload(false)
+                .with_vectors(false)
+                .limit(SCROLL_BATCH_SIZE);
+            if let Some(offset) = offset.take() {
+                builder = builder.offset(offset);
+            }
+
+            let response = client
+                .scroll(builder.build())
+                .await
+                .context("Failed to scroll point ids from Qdrant")?;
+            ids.extend(response.result.into_iter().filter_map(|point| point.id));
+
+            match response.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+        }
+        Ok(ids)
+    }
+
     pub async fn get_document(&self, id: &str) -> Result<Option<Document>> {
         let client = self.client()?;
         if !self.collection_exists(&client).await? {
@@ -354,17 +1014,37 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
         Ok(None)
     }
 
+    /// 分页列出给定身份可见的文档
     pub async fn list_documents_paginated(
         &self,
         limit: usize,
         offset: usize,
+        viewer: &DocumentViewer,
     ) -> Result<(Vec<Document>, usize)> {
         let client = self.client()?;
         if !self.collection_exists(&client).await? {
             return Ok((Vec::new(), 0));
         }
 
-        let total = self.collection_count(&client).await?;
+        let condition = viewer.condition();
+        let total = match &condition {
+            Some(condition) => {
+                let response = client
+                    .count(
+                        CountPointsBuilder::new(&self.config.collection_name)
+                            .filter(QdrantClientFilter::must([condition.clone()]))
+                            .exact(true)
+                            .build(),
+                    )
+                    .await
+                    .context("Failed to count documents in Qdrant")?;
+                response
+                    .result
+                    .map(|r| r.count as usize)
+                    .unwrap_or_default()
+            }
+            None => self.collection_count(&client).await?,
+        };
         if total == 0 {
             return Ok((Vec::new(), 0));
         }
@@ -375,16 +1055,18 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
             .direction(Direction::Desc as i32)
             .build();
 
+        let mut builder = QueryPointsBuilder::new(&self.config.collection_name)
+            .query(Query::new_order_by(order_by))
+            .offset(offset as u64)
+            .limit(safe_limit as u64)
+            .with_payload(true)
+            .with_vectors(false);
+        if let Some(condition) = condition {
+            builder = builder.filter(QdrantClientFilter::must([condition]));
+        }
+
         let response = client
-            .query(
-                QueryPointsBuilder::new(&self.config.collection_name)
-                    .query(Query::new_order_by(order_by))
-                    .offset(offset as u64)
-                    .limit(safe_limit as u64)
-                    .with_payload(true)
-                    .with_vectors(false)
-                    .build(),
-            )
+            .query(builder.build())
             .await
             .context("Failed to query documents from Qdrant")?;
 
@@ -403,6 +1085,45 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
         Ok((documents, total))
     }
 
+    /// 把文档级元数据写入同一文档的全部分块，无需重新生成向量
+    pub async fn set_document_metadata(
+        &self,
+        base_id: &str,
+        metadata: &DocumentMetadata,
+    ) -> Result<()> {
+        self.set_document_payload(base_id, serde_json::json!({ "metadata": metadata }))
+            .await
+    }
+
+    /// 把文档的可见范围写入同一文档的全部分块
+    pub async fn set_document_access(&self, base_id: &str, access: &DocumentAccess) -> Result<()> {
+        self.set_document_payload(base_id, serde_json::json!({ "access": access }))
+            .await
+    }
+
+    /// 按 base_id 更新全部分块的 payload 字段
+    async fn set_document_payload(&self, base_id: &str, payload: serde_json::Value) -> Result<()> {
+        let client = self.client()?;
+        if !self.collection_exists(&client).await? {
+            return Ok(());
+        }
+
+        let payload = Payload::try_from(payload).context("Invalid document payload")?;
+        client
+            .set_payload(
+                SetPayloadPointsBuilder::new(&self.config.collection_name, payload)
+                    .points_selector(QdrantClientFilter::must([Condition::matches(
+                        "base_id",
+                        base_id.to_string(),
+                    )]))
+                    .wait(true),
+            )
+            .await
+            .context("Failed to update document payload in Qdrant")?;
+
+        Ok(())
+    }
+
     pub async fn delete_document(&self, identifier: &str) -> Result<()> {
         let client = self.client()?;
         if !self.collection_exists(&client).await? {
@@ -425,6 +1146,73 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
         Ok(())
     }
 
+    /// 遍历集合中的全部点（不含向量），用于一致性检查
+    ///
+    /// 无法反序列化的 payload 以 Err 返回，而不是被跳过
+    pub async fn scroll_all_documents(&self) -> Result<Vec<(PointId, Result<Document>)>> {
+        const SCROLL_BATCH_SIZE: u32 = 256;
+
+        let client = self.client()?;
+        if !self.collection_exists(&client).await? {
+            return Ok(Vec::new());
+        }
+
+        let mut points = Vec::new();
+        let mut offset: Option<PointId> = None;
+
+        loop {
+            let mut builder = ScrollPointsBuilder::new(&self.config.collection_name)
+                .with_payload(true)
+                .with_vectors(false)
+                .limit(SCROLL_BATCH_SIZE);
+            if let Some(offset) = offset.take() {
+                builder = builder.offset(offset);
+            }
+
+            let response = client
+                .scroll(builder.build())
+                .await
+                .context("Failed to scroll documents from Qdrant")?;
+
+            for point in response.result {
+                if let Some(point_id) = point.id {
+                    points.push((point_id, Self::deserialize_document(point.payload)));
+                }
+            }
+
+            match response.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(points)
+    }
+
+    /// 按 Qdrant 点 ID 删除
+    pub async fn delete_points(&self, point_ids: Vec<PointId>) -> Result<()> {
+        if point_ids.is_empty() {
+            return Ok(());
+        }
+
+        let client = self.client()?;
+        if !self.collection_exists(&client).await? {
+            return Ok(());
+        }
+
+        client
+            .delete_points(
+                DeletePointsBuilder::new(&self.config.collection_name)
+                    .points(PointsIdsList { ids: point_ids })
+                    .wait(true)
+                    .build(),
+            )
+            .await
+            .context("Failed to delete points from Qdrant")?;
+
+        Ok(())
+    }
+
     pub async fn reset_table(&self) -> Result<()> {
         let client = self.client()?;
         if client
@@ -443,6 +1231,140 @@ impl<M: EmbeddingModel + Send + Sync + 'static> DocumentStore<M> {
     }
 }
 
+/// 将 Qdrant 点 ID 格式化为字符串
+pub fn point_id_to_string(point_id: &PointId) -> String {
+    match &point_id.point_id_options {
+        Some(PointIdOptions::Num(num)) => num.to_string(),
+        Some(PointIdOptions::Uuid(uuid)) => uuid.clone(),
+        None => String::new(),
+    }
+}
+
+fn to_timestamp(datetime: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: datetime.timestamp(),
+        nanos: datetime.timestamp_subsec_nanos() as i32,
+    }
+}
+
 fn is_already_exists(err: &qdrant_client::QdrantError) -> bool {
     err.to_string().contains("already exists")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_metadata_normalized() {
+        let metadata = DocumentMetadata {
+            tags: vec![" faq ".to_string(), "faq".to_string(), "".to_string()],
+            category: Some("  ".to_string()),
+            language: Some("ZH".to_string()),
+            ..Default::default()
+        }
+        .normalized()
+        .unwrap();
+        assert_eq!(metadata.tags, vec!["faq"]);
+        assert_eq!(metadata.category, None);
+        assert_eq!(metadata.language.as_deref(), Some("zh"));
+
+        let invalid = DocumentMetadata {
+            valid_from: Some(Utc.with_ymd_and_hms(2025, 2, 1, 0, 0, 0).unwrap()),
+            valid_until: Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+            ..Default::default()
+        };
+        assert!(invalid.normalized().is_err());
+
+        // 检索时按同样的规则构造 Qdrant 的有效期条件
+        let now = Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap();
+        assert!(DocumentMetadata::default().is_valid_at(now));
+        let expired = DocumentMetadata {
+            valid_until: Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+            ..Default::default()
+        };
+        assert!(!expired.is_valid_at(now));
+        assert!(expired.is_valid_at(Utc.with_ymd_and_hms(2024, 12, 31, 0, 0, 0).unwrap()));
+        assert_eq!(validity_conditions(now).len(), 2);
+    }
+
+    #[test]
+    fn test_document_access() {
+        let owner = DocumentViewer::User {
+            user_id: 1002,
+            role: UserRole::User,
+            groups: vec![],
+        };
+        let support = DocumentViewer::User {
+            user_id: 1003,
+            role: UserRole::User,
+            groups: vec!["support".to_string()],
+        };
+        let admin = DocumentViewer::User {
+            user_id: 1001,
+            role: UserRole::Admin,
+            groups: vec![],
+        };
+        let anonymous = DocumentViewer::Anonymous;
+
+        let internal = DocumentAccess {
+            visibility: Visibility::Role,
+            ..Default::default()
+        }
+        .normalized()
+        .unwrap()
+        .owned_by(1002, "alice");
+        assert_eq!(internal.role, Some(UserRole::User));
+        assert!(internal.can_view(&support));
+        assert!(!internal.can_view(&anonymous));
+
+        let grouped = DocumentAccess {
+            visibility: Visibility::Groups,
+            groups: vec![" support ".to_string()],
+            ..Default::default()
+        }
+        .normalized()
+        .unwrap()
+        .owned_by(1002, "alice");
+        assert!(grouped.can_view(&owner));
+        assert!(grouped.can_view(&support));
+        assert!(grouped.can_view(&admin));
+        assert!(!grouped.can_view(&anonymous));
+
+        let viewer = DocumentViewer::User {
+            user_id: 1004,
+            role: UserRole::Viewer,
+            groups: vec![],
+        };
+        let knowledge_admin = DocumentViewer::User {
+            user_id: 1005,
+            role: UserRole::KnowledgeAdmin,
+            groups: vec![],
+        };
+        assert!(internal.can_view(&viewer));
+        assert!(!internal.can_edit(&viewer));
+        assert!(!grouped.can_view(&viewer));
+        assert!(grouped.can_view(&knowledge_admin));
+        assert!(grouped.can_edit(&knowledge_admin));
+        assert!(grouped.can_edit(&owner));
+        assert!(!grouped.can_edit(&support));
+
+        let no_groups = DocumentAccess {
+            visibility: Visibility::Groups,
+            ..Default::default()
+        };
+        assert!(no_groups.normalized().is_err());
+
+        // 旧数据没有 access 字段时视为公开，但只有管理员可以修改
+        let legacy = DocumentAccess::default();
+        assert!(legacy.can_view(&anonymous));
+        assert!(!legacy.can_edit(&owner));
+        assert!(legacy.can_edit(&admin));
+
+        // 检索时访问控制作为 Qdrant 过滤条件，拥有 document.manage 权限时不限制
+        assert!(anonymous.condition().is_some());
+        assert!(owner.condition().is_some());
+        assert!(knowledge_admin.condition().is_none());
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Row, SqlitePool,
+    sqlite::{SqliteConnectOptions, SqliteRow},
+};
+
+use crate::config::{AppConfig, Config};
+
+/// 全局 SettingsStore 实例，限流和 CORS 中间件在每次请求时读取当前设置
+static SETTINGS_STORE: OnceLock<Arc<SettingsStore>> = OnceLock::new();
+
+/// 初始化全局 SettingsStore
+pub fn init_settings_store(store: Arc<SettingsStore>) -> Result<()> {
+    SETTINGS_STORE
+        .set(store)
+        .map_err(|_| anyhow::anyhow!("SettingsStore already initialized"))
+}
+
+/// 获取全局 SettingsStore 实例
+pub fn get_settings_store() -> Option<&'static Arc<SettingsStore>> {
+    SETTINGS_STORE.get()
+}
+
+/// 可在运行时修改的设置，保存后立即生效，无需重启
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuntimeSettings {
+    pub openai_model: String,
+    pub temperature: f64,
+    /// 每次检索的最大文档片段数，为空时检索集合中的全部文档
+    pub top_k: Option<usize>,
+    /// 聊天接口每隔多少秒恢复一次请求额度
+    pub chat_replenish_seconds: u64,
+    /// 聊天接口的突发请求数
+    pub chat_burst: u32,
+    /// 允许跨域访问的来源，`*` 表示全部
+    pub cors_origins: Vec<String>,
+}
+
+impl RuntimeSettings {
+    /// 以启动时的统一配置（配置文件和环境变量）作为默认设置
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            openai_model: config.llm.model.clone(),
+            temperature: config.llm.temperature,
+            top_k: config.llm.top_k,
+            chat_replenish_seconds: config.chat.replenish_seconds,
+            chat_burst: config.chat.burst,
+            cors_origins: config.server.cors_origins.clone(),
+        }
+    }
+
+    /// 把模型参数写入应用配置
+    pub fn apply_to(&self, config: &mut AppConfig) {
+        config.openai_model = self.openai_model.clone();
+        config.temperature = self.temperature;
+        config.top_k = self.top_k;
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.openai_model.trim().is_empty() {
+            return Err("openai_model must not be empty".to_string());
+        }
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return Err("temperature must be within 0-2".to_string());
+        }
+        if self.top_k.is_some_and(|k| !(1..=50).contains(&k)) {
+            return Err("top_k must be within 1-50".to_string());
+        }
+        if !(1..=3600).contains(&self.chat_replenish_seconds) {
+            return Err("chat_replenish_seconds must be within 1-3600".to_string());
+        }
+        if !(1..=1000).contains(&self.chat_burst) {
+            return Err("chat_burst must be within 1-1000".to_string());
+        }
+        if self.cors_origins.is_empty() {
+            return Err("cors_origins must not be empty".to_string());
+        }
+        for origin in &self.cors_origins {
+            let valid = origin == "*"
+                || ((origin.starts_with("http://") || origin.starts_with("https://"))
+                    && !origin.ends_with('/')
+                    && !origin.contains(char::is_whitespace));
+            if !valid {
+                return Err(format!(
+                    "Invalid CORS origin {:?}, expected \"*\" or e.g. https://example.com",
+                    origin
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 请求来源是否在允许列表中
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.cors_origins.iter().any(|o| o == "*" || o == origin)
+    }
+}
+
+/// 当前生效的设置，未保存过时 updated_by 为空
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredSettings {
+    #[serde(flatten)]
+    pub settings: RuntimeSettings,
+    pub updated_by: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// 运行时设置存储，与用户表位于同一个数据库
+///
+/// 保存的设置覆盖配置中的对应项，删除后恢复为配置的值
+pub struct SettingsStore {
+    pool: SqlitePool,
+    defaults: RuntimeSettings,
+    current: RwLock<StoredSettings>,
+}
+
+impl SettingsStore {
+    /// 创建设置存储并加载已保存的设置
+    pub async fn new(database_url: &str, defaults: RuntimeSettings) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url)
+            .await
+            .context("Failed to connect to settings database")?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS settings (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                settings TEXT NOT NULL, -- JSON
+                updated_by TEXT NOT NULL,
+                updated_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .context("Failed to initialize settings table")?;
+
+        let current = match Self::load(&pool).await? {
+            Some(stored) => stored,
+            None => StoredSettings {
+                settings: defaults.clone(),
+                updated_by: None,
+                updated_at: None,
+            },
+        };
+
+        Ok(Self {
+            pool,
+            defaults,
+            current: RwLock::new(current),
+        })
+    }
+
+    /// 以只读方式读取已保存的设置，不创建数据库和表，供 `config check` 输出
+    pub async fn saved(database_url: &str) -> Result<Option<StoredSettings>> {
+        let options = SqliteConnectOptions::from_str(database_url)
+            .context("Invalid settings database URL")?
+            .read_only(true)
+            .create_if_missing(false);
+        let pool = SqlitePool::connect_with(options)
+            .await
+            .context("Failed to connect to settings database")?;
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'settings')",
+        )
+        .fetch_one(&pool)
+        .await
+        .context("Failed to check settings table")?;
+        if !exists {
+            return Ok(None);
+        }
+        Self::load(&pool).await
+    }
+
+    async fn load(pool: &SqlitePool) -> Result<Option<StoredSettings>> {
+        let row = sqlx::query("SELECT settings, updated_by, updated_at FROM settings WHERE id = 1")
+            .fetch_optional(pool)
+            .await
+            .context("Failed to load settings")?;
+        row.as_ref().map(Self::stored_from_row).transpose()
+    }
+
+    fn stored_from_row(row: &SqliteRow) -> Result<StoredSettings> {
+        let settings: String = row.try_get("settings")?;
+        Ok(StoredSettings {
+            settings: serde_json::from_str(&settings).context("Failed to parse saved settings")?,
+            updated_by: Some(row.try_get("updated_by")?),
+            updated_at: DateTime::from_timestamp(row.try_get("updated_at")?, 0),
+        })
+    }
+
+    /// 当前生效的设置
+    pub fn current(&self) -> StoredSettings {
+        self.current.read().clone()
+    }
+
+    pub fn settings(&self) -> RuntimeSettings {
+        self.current.read().settings.clone()
+    }
+
+    /// 保存并立即生效，调用方负责先校验
+    pub async fn save(
+        &self,
+        settings: RuntimeSettings,
+        updated_by: &str,
+    ) -> Result<StoredSettings> {
+        let updated_at = Utc::now();
+        sqlx::query(
+            r#"
+            INSERT INTO settings (id, settings, updated_by, updated_at)
+            VALUES (1, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                settings = excluded.settings,
+                updated_by = excluded.updated_by,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(serde_json::to_string(&settings)?)
+        .bind(updated_by)
+        .bind(updated_at.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to save settings")?;
+
+        let stored = StoredSettings {
+            settings,
+            updated_by: Some(updated_by.to_string()),
+            updated_at: Some(updated_at),
+        };
+        *self.current.write() = stored.clone();
+        Ok(stored)
+    }
+
+    /// 恢复为之前的设置（包括修改人和时间），用于应用失败时回滚
+    pub async fn restore(&self, previous: StoredSettings) -> Result<()> {
+        match (&previous.updated_by, previous.updated_at) {
+            (Some(updated_by), Some(updated_at)) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO settings (id, settings, updated_by, updated_at)
+                    VALUES (1, ?, ?, ?)
+                    ON CONFLICT(id) DO UPDATE SET
+                        settings = excluded.settings,
+                        updated_by = excluded.updated_by,
+                        updated_at = excluded.updated_at
+                    "#,
+                )
+                .bind(serde_json::to_string(&previous.settings)?)
+                .bind(updated_by)
+                .bind(updated_at.timestamp())
+                .execute(&self.pool)
+                .await
+                .context("Failed to restore settings")?;
+            }
+            _ => {
+                sqlx::query("DELETE FROM settings")
+                    .execute(&self.pool)
+                    .await
+                    .context("Failed to restore settings")?;
+            }
+        }
+        *self.current.write() = previous;
+        Ok(())
+    }
+
+    /// 删除已保存的设置，恢复为环境变量的值
+    pub async fn reset(&self) -> Result<StoredSettings> {
+        sqlx::query("DELETE FROM settings")
+            .execute(&self.pool)
+            .await
+            .context("Failed to reset settings")?;
+
+        let stored = StoredSettings {
+            settings: self.defaults.clone(),
+            updated_by: None,
+            updated_at: None,
+        };
+        *self.current.write() = stored.clone();
+        Ok(stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> RuntimeSettings {
+        RuntimeSettings {
+            openai_model: "gpt-4o-mini".to_string(),
+            temperature: 0.7,
+            top_k: None,
+            chat_replenish_seconds: 3,
+            chat_burst: 10,
+            cors_origins: vec!["*".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_settings() {
+        let db = std::env::temp_dir().join(format!("settings-{}.db", nanoid::nanoid!(8)));
+        let url = format!("sqlite:{}?mode=rwc", db.display());
+        let store = SettingsStore::new(&url, defaults()).await.unwrap();
+        assert!(store.current().updated_by.is_none());
+
+        let settings = RuntimeSettings {
+            temperature: 0.2,
+            top_k: Some(5),
+            cors_origins: vec!["https://example.com".to_string()],
+            ..defaults()
+        };
+        assert!(settings.validate().is_ok());
+        assert!(settings.allows_origin("https://example.com"));
+        assert!(!settings.allows_origin("https://evil.com"));
+        store.save(settings.clone(), "admin").await.unwrap();
+
+        // 重新打开时加载已保存的设置
+        let store = SettingsStore::new(&url, defaults()).await.unwrap();
+        let saved = SettingsStore::saved(&url).await.unwrap().unwrap();
+        assert_eq!(saved.settings, settings);
+        assert_eq!(store.settings(), settings);
+        assert_eq!(store.current().updated_by.as_deref(), Some("admin"));
+
+        // 回滚到之前的设置时保留修改人
+        let previous = store.current();
+        store.reset().await.unwrap();
+        store.restore(previous).await.unwrap();
+        let store = SettingsStore::new(&url, defaults()).await.unwrap();
+        assert_eq!(store.settings(), settings);
+        assert_eq!(store.current().updated_by.as_deref(), Some("admin"));
+
+        assert_eq!(store.reset().await.unwrap().settings, defaults());
+
+        let invalid = |settings: RuntimeSettings| settings.validate().is_err();
+        assert!(invalid(RuntimeSettings {
+            temperature: 3.0,
+            ..defaults()
+        }));
+        assert!(invalid(RuntimeSettings {
+            cors_origins: vec!["https://example.com/".to_string()],
+            ..defaults()
+        }));
+        assert!(invalid(RuntimeSettings {
+            chat_burst: 0,
+            ..defaults()
+        }));
+
+        // 默认设置取自统一配置
+        let mut config = Config::default();
+        config.chat.burst = 30;
+        config.server.cors_origins = vec!["https://example.com".to_string()];
+        let settings = RuntimeSettings::from_config(&config);
+        assert_eq!(settings.chat_burst, 30);
+        assert_eq!(settings.chat_replenish_seconds, 3);
+        assert!(!settings.allows_origin("https://evil.com"));
+    }
+}
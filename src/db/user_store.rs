@@ -1,24 +1,87 @@
+use std::sync::{Arc, OnceLock};
+
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+use super::LockoutPolicy;
+use crate::{
+    config::get_config,
+    utils::{generate_totp_secret, verify_totp},
+};
+
+/// 全局 UserStore 实例，供认证中间件校验用户状态
+static USER_STORE: OnceLock<Arc<UserStore>> = OnceLock::new();
+
+/// 初始化全局 UserStore
+pub fn init_user_store(store: Arc<UserStore>) -> Result<()> {
+    USER_STORE
+        .set(store)
+        .map_err(|_| anyhow::anyhow!("UserStore already initialized"))
+}
+
+/// 获取全局 UserStore 实例
+pub fn get_user_store() -> Option<&'static Arc<UserStore>> {
+    USER_STORE.get()
+}
 
-/// 用户角色
+/// 用户角色，对应的权限见 [`UserRole::permissions`]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, sqlx::Type)]
-#[sqlx(rename_all = "lowercase")]
-#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum UserRole {
+    /// 超级管理员，拥有全部权限
     Admin,
+    /// 升级前的普通用户，权限与 editor 相同
     User,
+    Viewer,
+    Editor,
+    KnowledgeAdmin,
+    SupportAgent,
+}
+
+impl UserRole {
+    pub const ALL: [UserRole; 6] = [
+        UserRole::Admin,
+        UserRole::User,
+        UserRole::Viewer,
+        UserRole::Editor,
+        UserRole::KnowledgeAdmin,
+        UserRole::SupportAgent,
+    ];
+
+    /// 是否满足文档可见范围要求的角色：管理员满足所有角色，
+    /// 要求 user 或 viewer 时任何登录用户都满足，其他角色需一致
+    pub fn satisfies(&self, required: &UserRole) -> bool {
+        self == required
+            || *self == UserRole::Admin
+            || matches!(required, UserRole::User | UserRole::Viewer)
+    }
 }
 
 impl std::fmt::Display for UserRole {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            UserRole::Admin => write!(f, "admin"),
-            UserRole::User => write!(f, "user"),
-        }
+        let name = match self {
+            UserRole::Admin => "admin",
+            UserRole::User => "user",
+            UserRole::Viewer => "viewer",
+            UserRole::Editor => "editor",
+            UserRole::KnowledgeAdmin => "knowledge_admin",
+            UserRole::SupportAgent => "support_agent",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for UserRole {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .map_err(|_| anyhow::anyhow!("Unknown role: {}", s))
     }
 }
 
@@ -27,10 +90,19 @@ impl std::fmt::Display for UserRole {
 pub struct User {
     pub id: i64,
     pub username: String,
+    /// 显示名称，为空时显示用户名
+    pub display_name: Option<String>,
     #[serde(skip_serializing)]
     pub password_hash: String,
     pub role: UserRole,
     pub status: i32,
+    /// 用户所属分组，用于文档访问控制
+    pub groups: Vec<String>,
+    /// token 代数，递增后此前签发的 access token 全部失效
+    #[serde(skip_serializing)]
+    pub token_generation: i64,
+    /// 下次登录后必须先修改密码（默认管理员、管理员重置的密码）
+    pub must_change_password: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -55,33 +127,314 @@ impl sqlx::FromRow<'_, SqliteRow> for User {
             )))
         })?;
 
+        let groups: String = row.try_get("user_groups")?;
+        let groups = serde_json::from_str(&groups).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+
         Ok(User {
             id: row.try_get("id")?,
             username: row.try_get("username")?,
+            display_name: row.try_get("display_name")?,
             password_hash: row.try_get("password_hash")?,
             role: row.try_get("role")?,
             status: row.try_get("status")?,
+            groups,
+            token_generation: row.try_get("token_generation")?,
+            must_change_password: row.try_get::<i64, _>("must_change_password")? != 0,
             created_at,
             updated_at,
         })
     }
 }
 
+/// 刷新 token 记录，数据库中只保存 token 的哈希
+///
+/// 同一次登录轮换出的 token 属于同一个 family，已轮换的 token 被再次使用时整个 family 作废
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub id: String,
+    pub user_id: i64,
+    pub workspace_id: String,
+    pub family_id: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked: bool,
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for RefreshToken {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let expires_at_ts: i64 = row.try_get("expires_at")?;
+        let expires_at = DateTime::from_timestamp(expires_at_ts, 0).ok_or_else(|| {
+            sqlx::Error::Decode(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid timestamp expires_at",
+            )))
+        })?;
+        let revoked_at: Option<i64> = row.try_get("revoked_at")?;
+
+        Ok(RefreshToken {
+            id: row.try_get("id")?,
+            user_id: row.try_get("user_id")?,
+            workspace_id: row.try_get("workspace_id")?,
+            family_id: row.try_get("family_id")?,
+            expires_at,
+            revoked: revoked_at.is_some(),
+        })
+    }
+}
+
+/// 刷新 token 和 API key 只保存 SHA-256 哈希
+fn hash_secret(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// API key 前缀，用于和用户 JWT 区分
+pub const API_KEY_PREFIX: &str = "rk_";
+
+/// API key 的权限范围
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiScope {
+    #[serde(rename = "chat")]
+    Chat,
+    #[serde(rename = "documents:read")]
+    DocumentsRead,
+    #[serde(rename = "documents:write")]
+    DocumentsWrite,
+    /// 管理接口，同时包含其他所有 scope
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiScope::Chat => write!(f, "chat"),
+            ApiScope::DocumentsRead => write!(f, "documents:read"),
+            ApiScope::DocumentsWrite => write!(f, "documents:write"),
+            ApiScope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+/// 供后端服务使用的 API key，以创建者的身份在所属工作区内访问
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    /// key 的开头几位，便于识别
+    pub key_prefix: String,
+    /// 创建者
+    pub user_id: i64,
+    pub workspace_id: String,
+    pub scopes: Vec<ApiScope>,
+    /// 每分钟请求数上限，None 表示不限制
+    pub rate_limit_per_minute: Option<u32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKey {
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&ApiScope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for ApiKey {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let timestamp = |column: &str| -> sqlx::Result<Option<DateTime<Utc>>> {
+            let ts: Option<i64> = row.try_get(column)?;
+            ts.map(|ts| {
+                DateTime::from_timestamp(ts, 0).ok_or_else(|| {
+                    sqlx::Error::Decode(Box::new(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid timestamp {}", column),
+                    )))
+                })
+            })
+            .transpose()
+        };
+
+        let scopes: String = row.try_get("scopes")?;
+        let scopes = serde_json::from_str(&scopes).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        let rate_limit: Option<i64> = row.try_get("rate_limit_per_minute")?;
+
+        Ok(ApiKey {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            key_prefix: row.try_get("key_prefix")?,
+            user_id: row.try_get("user_id")?,
+            workspace_id: row.try_get("workspace_id")?,
+            scopes,
+            rate_limit_per_minute: rate_limit.map(|limit| limit as u32),
+            expires_at: timestamp("expires_at")?,
+            last_used_at: timestamp("last_used_at")?,
+            created_at: timestamp("created_at")?.unwrap_or_default(),
+        })
+    }
+}
+
+/// 创建 API key 请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    /// 有效天数，不填表示永不过期
+    pub expires_in_days: Option<i64>,
+    pub rate_limit_per_minute: Option<u32>,
+}
+
+/// 认证事件类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthEventKind {
+    LoginSucceeded,
+    LoginFailed,
+    /// 锁定期间的登录尝试
+    LoginBlocked,
+    AccountLocked,
+    PasswordChanged,
+    /// 管理员重置密码
+    PasswordReset,
+    /// 绑定两步验证
+    MfaEnabled,
+    /// 关闭或被管理员重置两步验证
+    MfaDisabled,
+    /// 两步验证码错误
+    MfaFailed,
+    /// 使用恢复码通过两步验证
+    RecoveryCodeUsed,
+}
+
+impl std::fmt::Display for AuthEventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AuthEventKind::LoginSucceeded => "login_succeeded",
+            AuthEventKind::LoginFailed => "login_failed",
+            AuthEventKind::LoginBlocked => "login_blocked",
+            AuthEventKind::AccountLocked => "account_locked",
+            AuthEventKind::PasswordChanged => "password_changed",
+            AuthEventKind::PasswordReset => "password_reset",
+            AuthEventKind::MfaEnabled => "mfa_enabled",
+            AuthEventKind::MfaDisabled => "mfa_disabled",
+            AuthEventKind::MfaFailed => "mfa_failed",
+            AuthEventKind::RecoveryCodeUsed => "recovery_code_used",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for AuthEventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .map_err(|_| anyhow::anyhow!("Unknown auth event: {}", s))
+    }
+}
+
+/// 待写入的认证事件
+#[derive(Debug, Clone)]
+pub struct NewAuthEvent<'a> {
+    pub kind: AuthEventKind,
+    pub username: &'a str,
+    /// 用户不存在时为 None
+    pub user_id: Option<i64>,
+    pub ip: Option<String>,
+    pub detail: Option<String>,
+}
+
+/// 认证事件记录
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthEvent {
+    pub id: i64,
+    pub event: AuthEventKind,
+    pub username: String,
+    pub user_id: Option<i64>,
+    pub ip: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for AuthEvent {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let created_at_ts: i64 = row.try_get("created_at")?;
+        let created_at = DateTime::from_timestamp(created_at_ts, 0).ok_or_else(|| {
+            sqlx::Error::Decode(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid timestamp created_at",
+            )))
+        })?;
+        let event: String = row.try_get("event")?;
+
+        Ok(AuthEvent {
+            id: row.try_get("id")?,
+            event: event
+                .parse()
+                .map_err(|e: anyhow::Error| sqlx::Error::Decode(e.into()))?,
+            username: row.try_get("username")?,
+            user_id: row.try_get("user_id")?,
+            ip: row.try_get("ip")?,
+            detail: row.try_get("detail")?,
+            created_at,
+        })
+    }
+}
+
+/// 每次生成的恢复码数量
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// 恢复码字符集（去掉易混淆的字符）
+const RECOVERY_CODE_ALPHABET: [char; 31] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'j', 'k', 'm', 'n', 'p', 'q', 'r', 's', 't', 'u', 'v',
+    'w', 'x', 'y', 'z', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+/// 两步验证状态
+#[derive(Debug, Clone, Serialize)]
+pub struct TotpStatus {
+    pub enabled: bool,
+    /// 剩余可用的恢复码数量
+    pub recovery_codes_remaining: i64,
+}
+
+/// 通过的第二因素类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SecondFactor {
+    Totp,
+    RecoveryCode,
+}
+
 /// 创建用户请求
 #[derive(Debug, Clone, Deserialize)]
 pub struct CreateUserRequest {
     pub username: String,
+    /// 显示名称，聊天时作为 `{{user_name}}` 填入提示词
+    #[serde(default)]
+    pub display_name: Option<String>,
     pub password: String,
     pub role: Option<UserRole>,
     pub status: Option<i32>,
+    pub groups: Option<Vec<String>>,
+    /// 首次登录后是否必须修改密码
+    #[serde(default)]
+    pub must_change_password: Option<bool>,
 }
 
 /// 更新用户请求
 #[derive(Debug, Clone, Deserialize)]
 pub struct UpdateUserRequest {
+    /// 显示名称，空字符串表示清除
+    #[serde(default)]
+    pub display_name: Option<String>,
     pub password: Option<String>,
     pub status: Option<i32>, // 0: disabled, 1: enabled
     pub role: Option<UserRole>,
+    pub groups: Option<Vec<String>>,
+    /// 下次登录后是否必须修改密码
+    #[serde(default)]
+    pub must_change_password: Option<bool>,
 }
 
 /// 用户存储
@@ -120,9 +473,13 @@ impl UserStore {
             CREATE TABLE IF NOT EXISTS users (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 username TEXT NOT NULL UNIQUE,
+                display_name TEXT,
                 password_hash TEXT NOT NULL,
-                role TEXT NOT NULL CHECK(role IN ('admin', 'user')),
+                role TEXT NOT NULL CHECK(role IN ('admin', 'user', 'viewer', 'editor', 'knowledge_admin', 'support_agent')),
                 status INTEGER NOT NULL CHECK(status IN (0, 1)),
+                user_groups TEXT NOT NULL DEFAULT '[]',
+                token_generation INTEGER NOT NULL DEFAULT 0,
+                must_change_password INTEGER NOT NULL DEFAULT 0,
                 created_at INTEGER NOT NULL,
                 updated_at INTEGER NOT NULL
             );
@@ -139,27 +496,321 @@ impl UserStore {
                 .context("Failed to set sequence start value")?;
         }
 
-        // 检查是否有admin用户，如果没有则创建默认admin
+        // 旧表补充 display_name 列
+        let has_display_name: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM pragma_table_info('users') WHERE name = 'display_name'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if has_display_name == 0 {
+            sqlx::query("ALTER TABLE users ADD COLUMN display_name TEXT")
+                .execute(&self.pool)
+                .await
+                .context("Failed to add display_name column")?;
+        }
+
+        // 旧表补充 user_groups 列
+        let has_groups: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM pragma_table_info('users') WHERE name = 'user_groups'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if has_groups == 0 {
+            sqlx::query("ALTER TABLE users ADD COLUMN user_groups TEXT NOT NULL DEFAULT '[]'")
+                .execute(&self.pool)
+                .await
+                .context("Failed to add user_groups column")?;
+        }
+
+        // 旧表补充 token_generation 列
+        let has_generation: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM pragma_table_info('users') WHERE name = 'token_generation'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if has_generation == 0 {
+            sqlx::query("ALTER TABLE users ADD COLUMN token_generation INTEGER NOT NULL DEFAULT 0")
+                .execute(&self.pool)
+                .await
+                .context("Failed to add token_generation column")?;
+        }
+
+        // 旧表补充 must_change_password 列，仍在使用默认密码的管理员需要修改密码
+        let has_must_change: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM pragma_table_info('users') WHERE name = 'must_change_password'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if has_must_change == 0 {
+            sqlx::query(
+                "ALTER TABLE users ADD COLUMN must_change_password INTEGER NOT NULL DEFAULT 0",
+            )
+            .execute(&self.pool)
+            .await
+            .context("Failed to add must_change_password column")?;
+
+            // 密码哈希异常时视为已修改，不影响启动
+            let admin = self.get_user_by_username("admin").await?.filter(|admin| {
+                bcrypt::verify(default_admin_password(), &admin.password_hash).unwrap_or(false)
+            });
+            if let Some(admin) = admin {
+                sqlx::query("UPDATE users SET must_change_password = 1 WHERE id = ?")
+                    .bind(admin.id)
+                    .execute(&self.pool)
+                    .await?;
+                warn!("Default admin password is still in use, a password change will be required");
+            }
+        }
+
+        self.migrate_role_constraint().await?;
+
+        // 刷新 token 表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS refresh_tokens (
+                id TEXT PRIMARY KEY,
+                token_hash TEXT NOT NULL UNIQUE,
+                user_id INTEGER NOT NULL,
+                workspace_id TEXT NOT NULL,
+                family_id TEXT NOT NULL,
+                expires_at INTEGER NOT NULL,
+                revoked_at INTEGER,
+                created_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_refresh_tokens_user_id ON refresh_tokens(user_id);
+            CREATE INDEX IF NOT EXISTS idx_refresh_tokens_family_id ON refresh_tokens(family_id);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize refresh_tokens table")?;
+
+        // API key 表
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_keys (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                key_prefix TEXT NOT NULL,
+                key_hash TEXT NOT NULL UNIQUE,
+                user_id INTEGER NOT NULL,
+                workspace_id TEXT NOT NULL,
+                scopes TEXT NOT NULL,
+                rate_limit_per_minute INTEGER,
+                expires_at INTEGER,
+                last_used_at INTEGER,
+                created_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_api_keys_workspace_id ON api_keys(workspace_id);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize api_keys table")?;
+
+        // 外部身份（OIDC 等）与本地用户的绑定
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_identities (
+                issuer TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                user_id INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (issuer, subject)
+            );
+            CREATE INDEX IF NOT EXISTS idx_user_identities_user_id ON user_identities(user_id);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize user_identities table")?;
+
+        // 按用户名和来源 IP 记录连续登录失败次数（包括不存在的用户名），
+        // 其他 IP 的失败不会锁定该用户；旧版按用户名计数的表直接删除
+        sqlx::query(
+            r#"
+            DROP TABLE IF EXISTS login_attempts;
+            CREATE TABLE IF NOT EXISTS login_failures (
+                username TEXT NOT NULL,
+                ip TEXT NOT NULL,
+                failures INTEGER NOT NULL,
+                locked_until INTEGER,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (username, ip)
+            );
+            CREATE INDEX IF NOT EXISTS idx_login_failures_updated_at ON login_failures(updated_at);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize login_failures table")?;
+
+        // 按用户名记录所有 IP 上的累计登录失败次数，分散到多个 IP 的猜测同样会触发锁定
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS account_login_failures (
+                username TEXT PRIMARY KEY,
+                failures INTEGER NOT NULL,
+                locked_until INTEGER,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_account_login_failures_updated_at ON account_login_failures(updated_at);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize account_login_failures table")?;
+
+        // 按用户记录连续的第二因素验证失败次数，与密码登录的失败次数分开计算
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS mfa_attempts (
+                user_id INTEGER PRIMARY KEY,
+                failures INTEGER NOT NULL,
+                locked_until INTEGER,
+                updated_at INTEGER NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize mfa_attempts table")?;
+
+        // TOTP 两步验证，确认前 enabled 为 0；恢复码只保存哈希
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_totp (
+                user_id INTEGER PRIMARY KEY,
+                secret TEXT NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 0,
+                last_used_step INTEGER NOT NULL DEFAULT 0,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS recovery_codes (
+                user_id INTEGER NOT NULL,
+                code_hash TEXT NOT NULL,
+                used_at INTEGER,
+                PRIMARY KEY (user_id, code_hash)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize two-factor tables")?;
+
+        // 认证事件
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS auth_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                event TEXT NOT NULL,
+                username TEXT NOT NULL,
+                user_id INTEGER,
+                ip TEXT,
+                detail TEXT,
+                created_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_auth_events_user_id ON auth_events(user_id);
+            CREATE INDEX IF NOT EXISTS idx_auth_events_created_at ON auth_events(created_at);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize auth_events table")?;
+
+        Ok(())
+    }
+
+    /// 没有管理员时创建默认管理员，启动时在初始化数据库后调用
+    ///
+    /// 只有真正需要创建时才要求修改示例密码，已有管理员的部署升级后仍可启动
+    pub async fn ensure_default_admin(&self) -> Result<()> {
         let admin_count: i64 =
             sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE role = 'admin'")
                 .fetch_one(&self.pool)
                 .await?;
 
         if admin_count == 0 {
+            if get_config().auth.has_example_admin_password() {
+                anyhow::bail!(
+                    "auth.default_admin_password must be set and changed from the example value before the default admin is created (DEFAULT_ADMIN_PASSWORD)"
+                );
+            }
             info!("No admin user found, creating default admin");
-            let default_password =
-                std::env::var("DEFAULT_ADMIN_PASSWORD").unwrap_or_else(|_| "aaa111".to_string());
-
             self.create_user(CreateUserRequest {
                 username: "admin".to_string(),
-                password: default_password,
+                display_name: None,
+                password: default_admin_password(),
                 role: Some(UserRole::Admin),
                 status: Some(1),
+                groups: None,
+                must_change_password: Some(true),
             })
             .await?;
 
-            info!("Default admin user created (username: admin)");
+            info!(
+                "Default admin user created (username: admin), password change required on first login"
+            );
+        } else if get_config().auth.has_example_admin_password() {
+            warn!(
+                "auth.default_admin_password is empty or the example value (DEFAULT_ADMIN_PASSWORD); it is only used to create the default admin, set it before creating a new deployment"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// 旧表的 role 只允许 admin/user，重建表以支持新角色
+    ///
+    /// SQLite 不能修改 CHECK 约束，只能复制到新表后改名；保留自增序列，已删除用户的 id 不会被复用
+    async fn migrate_role_constraint(&self) -> Result<()> {
+        let table_sql: String =
+            sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE type='table' AND name='users'")
+                .fetch_one(&self.pool)
+                .await?;
+        if table_sql.contains("'viewer'") {
+            return Ok(());
+        }
+
+        info!("Migrating users table to support fine-grained roles");
+        let seq: Option<i64> =
+            sqlx::query_scalar("SELECT seq FROM sqlite_sequence WHERE name = 'users'")
+                .fetch_optional(&self.pool)
+                .await?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            CREATE TABLE users_new (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                username TEXT NOT NULL UNIQUE,
+                display_name TEXT,
+                password_hash TEXT NOT NULL,
+                role TEXT NOT NULL CHECK(role IN ('admin', 'user', 'viewer', 'editor', 'knowledge_admin', 'support_agent')),
+                status INTEGER NOT NULL CHECK(status IN (0, 1)),
+                user_groups TEXT NOT NULL DEFAULT '[]',
+                token_generation INTEGER NOT NULL DEFAULT 0,
+                must_change_password INTEGER NOT NULL DEFAULT 0,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            INSERT INTO users_new (id, username, display_name, password_hash, role, status, user_groups, token_generation, must_change_password, created_at, updated_at)
+                SELECT id, username, display_name, password_hash, role, status, user_groups, token_generation, must_change_password, created_at, updated_at FROM users;
+            DROP TABLE users;
+            ALTER TABLE users_new RENAME TO users;
+            CREATE INDEX IF NOT EXISTS idx_users_username ON users(username);
+            "#,
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to migrate users table")?;
+        if let Some(seq) = seq {
+            sqlx::query("UPDATE sqlite_sequence SET seq = MAX(seq, ?) WHERE name = 'users'")
+                .bind(seq)
+                .execute(&mut *tx)
+                .await?;
         }
+        tx.commit().await?;
 
         Ok(())
     }
@@ -171,19 +822,25 @@ impl UserStore {
 
         let role = req.role.unwrap_or(UserRole::User);
         let status = req.status.unwrap_or(1);
+        let groups = normalize_groups(req.groups.unwrap_or_default());
+        let must_change_password = req.must_change_password.unwrap_or(false);
+        let display_name = req.display_name.as_deref().and_then(normalize_display_name);
         let now = Utc::now();
         let timestamp = now.timestamp();
 
         let id = sqlx::query(
             r#"
-            INSERT INTO users (username, password_hash, role, status, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?)
+            INSERT INTO users (username, display_name, password_hash, role, status, user_groups, must_change_password, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&req.username)
+        .bind(&display_name)
         .bind(&password_hash)
         .bind(role.to_string())
         .bind(status)
+        .bind(serde_json::to_string(&groups)?)
+        .bind(must_change_password)
         .bind(timestamp)
         .bind(timestamp)
         .execute(&self.pool)
@@ -196,9 +853,13 @@ impl UserStore {
         Ok(User {
             id,
             username: req.username,
+            display_name,
             password_hash,
             role,
             status,
+            groups,
+            token_generation: 0,
+            must_change_password,
             created_at: now,
             updated_at: now,
         })
@@ -208,7 +869,7 @@ impl UserStore {
     pub async fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
         let user = sqlx::query_as::<_, User>(
             r#"
-            SELECT id, username, password_hash, role, status, created_at, updated_at
+            SELECT id, username, display_name, password_hash, role, status, user_groups, token_generation, must_change_password, created_at, updated_at
             FROM users
             WHERE username = ?
             "#,
@@ -225,7 +886,7 @@ impl UserStore {
     pub async fn get_user_by_id(&self, id: i64) -> Result<Option<User>> {
         let user = sqlx::query_as::<_, User>(
             r#"
-            SELECT id, username, password_hash, role, status, created_at, updated_at
+            SELECT id, username, display_name, password_hash, role, status, user_groups, token_generation, must_change_password, created_at, updated_at
             FROM users
             WHERE id = ?
             "#,
@@ -238,6 +899,45 @@ impl UserStore {
         Ok(user)
     }
 
+    /// 根据外部身份（issuer + subject）查找绑定的用户
+    pub async fn get_user_by_identity(&self, issuer: &str, subject: &str) -> Result<Option<User>> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            SELECT u.id, u.username, u.display_name, u.password_hash, u.role, u.status, u.user_groups, u.token_generation, u.must_change_password, u.created_at, u.updated_at
+            FROM users u
+            JOIN user_identities i ON i.user_id = u.id
+            WHERE i.issuer = ? AND i.subject = ?
+            "#,
+        )
+        .bind(issuer)
+        .bind(subject)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query user by identity")?;
+
+        Ok(user)
+    }
+
+    /// 将外部身份绑定到用户
+    pub async fn link_identity(&self, issuer: &str, subject: &str, user_id: i64) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_identities (issuer, subject, user_id, created_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(issuer)
+        .bind(subject)
+        .bind(user_id)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to link user identity")?;
+
+        debug!(
+            "Linked identity {} from {} to user {}",
+            subject, issuer, user_id
+        );
+        Ok(())
+    }
+
     /// 验证用户密码
     pub async fn verify_password(&self, username: &str, password: &str) -> Result<Option<User>> {
         let user = self.get_user_by_username(username).await?;
@@ -255,7 +955,7 @@ impl UserStore {
     pub async fn list_users(&self) -> Result<Vec<User>> {
         let users = sqlx::query_as::<_, User>(
             r#"
-            SELECT id, username, password_hash, role, status, created_at, updated_at
+            SELECT id, username, display_name, password_hash, role, status, user_groups, token_generation, must_change_password, created_at, updated_at
             FROM users
             ORDER BY created_at DESC
             "#,
@@ -268,12 +968,39 @@ impl UserStore {
     }
 
     /// 更新用户
+    ///
+    /// 状态、角色、分组或密码发生变化，或要求修改密码时使已签发的 token 全部失效，
+    /// 禁用、降权和文档访问范围的收紧立即生效
     pub async fn update_user(&self, id: i64, req: UpdateUserRequest) -> Result<User> {
         let mut set_clauses = Vec::new();
 
+        let current = self
+            .get_user_by_id(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+        let groups = req.groups.map(normalize_groups);
+        let groups_changed = groups.as_ref().is_some_and(|groups| {
+            let mut new_groups = groups.clone();
+            let mut old_groups = current.groups.clone();
+            new_groups.sort();
+            old_groups.sort();
+            new_groups != old_groups
+        });
+        let revoke_tokens = req.status.is_some_and(|status| status != current.status)
+            || req.role.as_ref().is_some_and(|role| *role != current.role)
+            || groups_changed
+            || req.password.is_some()
+            || (req.must_change_password == Some(true) && !current.must_change_password);
+
         let now = Utc::now();
         let timestamp = now.timestamp();
 
+        // 处理显示名称更新，空字符串清除
+        let display_name = req.display_name.as_deref().map(normalize_display_name);
+        if display_name.is_some() {
+            set_clauses.push("display_name = ?");
+        }
+
         // 处理密码更新
         let password_hash = if let Some(password) = req.password {
             let hash =
@@ -295,14 +1022,30 @@ impl UserStore {
             set_clauses.push("status = ?");
         }
 
-        // 如果没有任何字段需要更新，直接返回当前用户
-        if set_clauses.is_empty() {
-            return self
+        // 处理分组更新
+        let groups_json = groups
+            .map(|groups| serde_json::to_string(&groups))
+            .transpose()?;
+        if groups_json.is_some() {
+            set_clauses.push("user_groups = ?");
+        }
+
+        if req.must_change_password.is_some() {
+            set_clauses.push("must_change_password = ?");
+        }
+
+        // 如果没有任何字段需要更新，直接返回当前用户
+        if set_clauses.is_empty() {
+            return self
                 .get_user_by_id(id)
                 .await?
                 .ok_or_else(|| anyhow::anyhow!("User not found"));
         }
 
+        if revoke_tokens {
+            set_clauses.push("token_generation = token_generation + 1");
+        }
+
         // 总是更新 updated_at
         set_clauses.push("updated_at = ?");
 
@@ -312,6 +1055,9 @@ impl UserStore {
         // 绑定参数
         let mut query = sqlx::query(&sql);
 
+        if let Some(ref display_name) = display_name {
+            query = query.bind(display_name);
+        }
         if let Some(ref hash) = password_hash {
             query = query.bind(hash);
         }
@@ -321,6 +1067,12 @@ impl UserStore {
         if let Some(status) = req.status {
             query = query.bind(status);
         }
+        if let Some(ref groups) = groups_json {
+            query = query.bind(groups);
+        }
+        if let Some(must_change_password) = req.must_change_password {
+            query = query.bind(must_change_password);
+        }
         query = query.bind(timestamp).bind(id);
 
         // 执行更新
@@ -333,6 +1085,14 @@ impl UserStore {
             return Err(anyhow::anyhow!("User not found"));
         }
 
+        if revoke_tokens {
+            self.revoke_refresh_tokens(id).await?;
+            info!(
+                "Revoked all sessions of user {} after status/role/password change",
+                id
+            );
+        }
+
         // 查询并返回更新后的用户
         let user = self
             .get_user_by_id(id)
@@ -370,8 +1130,859 @@ impl UserStore {
             .execute(&self.pool)
             .await
             .context("Failed to delete user")?;
+        self.revoke_refresh_tokens(id).await?;
+        sqlx::query("DELETE FROM user_identities WHERE user_id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete user identities")?;
+        self.disable_totp(id).await?;
 
         info!("Deleted user: {} (id: {})", user.username, id);
         Ok(())
     }
+
+    /// 使用户已签发的 access token 和刷新 token 全部失效，返回新的 token 代数
+    pub async fn revoke_user_sessions(&self, id: i64) -> Result<i64> {
+        let generation: i64 = sqlx::query_scalar(
+            "UPDATE users SET token_generation = token_generation + 1 WHERE id = ? RETURNING token_generation",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to bump token generation")?
+        .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        self.revoke_refresh_tokens(id).await?;
+        info!("Revoked all sessions of user {}", id);
+        Ok(generation)
+    }
+
+    /// 为用户签发新的刷新 token，返回 token 原文（只在此时可见）
+    ///
+    /// `family_id` 为 None 时开始新的 family（即一次新的登录）
+    pub async fn create_refresh_token(
+        &self,
+        user_id: i64,
+        workspace_id: &str,
+        family_id: Option<&str>,
+        ttl: Duration,
+    ) -> Result<String> {
+        let token = nanoid::nanoid!(48);
+        let id = nanoid::nanoid!();
+        let family_id = family_id.map(str::to_string).unwrap_or_else(|| id.clone());
+        let now = Utc::now();
+
+        // 顺便清理该用户已过期的 token
+        sqlx::query("DELETE FROM refresh_tokens WHERE user_id = ? AND expires_at < ?")
+            .bind(user_id)
+            .bind(now.timestamp())
+            .execute(&self.pool)
+            .await
+            .context("Failed to clean up expired refresh tokens")?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO refresh_tokens (id, token_hash, user_id, workspace_id, family_id, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(hash_secret(&token))
+        .bind(user_id)
+        .bind(workspace_id)
+        .bind(&family_id)
+        .bind((now + ttl).timestamp())
+        .bind(now.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to insert refresh token")?;
+
+        Ok(token)
+    }
+
+    /// 轮换刷新 token：作废旧 token 并在同一 family 中签发新 token
+    ///
+    /// token 不存在、已过期或已作废时返回 None；已作废的 token 被再次使用说明可能泄露，
+    /// 此时整个 family 一并作废
+    pub async fn rotate_refresh_token(
+        &self,
+        token: &str,
+        ttl: Duration,
+    ) -> Result<Option<(RefreshToken, String)>> {
+        let Some(record) = self.find_refresh_token(token).await? else {
+            return Ok(None);
+        };
+
+        if record.revoked {
+            warn!(
+                "Refresh token reuse detected for user {}, revoking token family {}",
+                record.user_id, record.family_id
+            );
+            self.revoke_refresh_token_family(&record.family_id).await?;
+            return Ok(None);
+        }
+        if record.expires_at < Utc::now() {
+            return Ok(None);
+        }
+
+        // 条件更新保证并发请求中只有一个能完成轮换
+        let revoked = sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+        )
+        .bind(Utc::now().timestamp())
+        .bind(&record.id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to revoke refresh token")?
+        .rows_affected();
+        if revoked == 0 {
+            return Ok(None);
+        }
+
+        let new_token = self
+            .create_refresh_token(
+                record.user_id,
+                &record.workspace_id,
+                Some(&record.family_id),
+                ttl,
+            )
+            .await?;
+        Ok(Some((record, new_token)))
+    }
+
+    /// 作废刷新 token 所在的整个 family（退出登录），返回 token 所属用户
+    pub async fn revoke_refresh_token(&self, token: &str) -> Result<Option<i64>> {
+        let Some(record) = self.find_refresh_token(token).await? else {
+            return Ok(None);
+        };
+        self.revoke_refresh_token_family(&record.family_id).await?;
+        Ok(Some(record.user_id))
+    }
+
+    async fn find_refresh_token(&self, token: &str) -> Result<Option<RefreshToken>> {
+        let record = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            SELECT id, user_id, workspace_id, family_id, expires_at, revoked_at
+            FROM refresh_tokens
+            WHERE token_hash = ?
+            "#,
+        )
+        .bind(hash_secret(token))
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query refresh token")?;
+
+        Ok(record)
+    }
+
+    async fn revoke_refresh_token_family(&self, family_id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = ? WHERE family_id = ? AND revoked_at IS NULL",
+        )
+        .bind(Utc::now().timestamp())
+        .bind(family_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to revoke refresh token family")?;
+        Ok(())
+    }
+
+    async fn revoke_refresh_tokens(&self, user_id: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL",
+        )
+        .bind(Utc::now().timestamp())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to revoke refresh tokens")?;
+        Ok(())
+    }
+
+    /// 创建 API key，返回记录和 key 原文（只在此时可见）
+    pub async fn create_api_key(
+        &self,
+        user_id: i64,
+        workspace_id: &str,
+        req: CreateApiKeyRequest,
+    ) -> Result<(ApiKey, String)> {
+        let key = format!("{}{}", API_KEY_PREFIX, nanoid::nanoid!(40));
+        let now = Utc::now();
+        let api_key = ApiKey {
+            id: nanoid::nanoid!(),
+            name: req.name,
+            key_prefix: key.chars().take(API_KEY_PREFIX.len() + 6).collect(),
+            user_id,
+            workspace_id: workspace_id.to_string(),
+            scopes: req.scopes,
+            rate_limit_per_minute: req.rate_limit_per_minute,
+            expires_at: req.expires_in_days.map(|days| now + Duration::days(days)),
+            last_used_at: None,
+            created_at: now,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO api_keys (id, name, key_prefix, key_hash, user_id, workspace_id, scopes,
+                                  rate_limit_per_minute, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&api_key.id)
+        .bind(&api_key.name)
+        .bind(&api_key.key_prefix)
+        .bind(hash_secret(&key))
+        .bind(user_id)
+        .bind(workspace_id)
+        .bind(serde_json::to_string(&api_key.scopes)?)
+        .bind(api_key.rate_limit_per_minute.map(i64::from))
+        .bind(api_key.expires_at.map(|t| t.timestamp()))
+        .bind(now.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to insert api key")?;
+
+        info!(
+            "Created API key {} ({}) in workspace {}",
+            api_key.name, api_key.id, workspace_id
+        );
+        Ok((api_key, key))
+    }
+
+    /// 列出工作区的 API key
+    pub async fn list_api_keys(&self, workspace_id: &str) -> Result<Vec<ApiKey>> {
+        let keys = sqlx::query_as::<_, ApiKey>(
+            r#"
+            SELECT id, name, key_prefix, user_id, workspace_id, scopes, rate_limit_per_minute,
+                   expires_at, last_used_at, created_at
+            FROM api_keys WHERE workspace_id = ? ORDER BY created_at DESC
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list api keys")?;
+
+        Ok(keys)
+    }
+
+    /// 删除工作区中的 API key，不存在时返回 false
+    pub async fn delete_api_key(&self, workspace_id: &str, id: &str) -> Result<bool> {
+        let deleted = sqlx::query("DELETE FROM api_keys WHERE id = ? AND workspace_id = ?")
+            .bind(id)
+            .bind(workspace_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete api key")?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    /// 删除用户在所有工作区创建的 API key，返回删除的数量
+    pub async fn revoke_user_api_keys(&self, user_id: i64) -> Result<u64> {
+        let deleted = sqlx::query("DELETE FROM api_keys WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to revoke api keys")?
+            .rows_affected();
+
+        if deleted > 0 {
+            info!("Revoked {} API keys of user {}", deleted, user_id);
+        }
+        Ok(deleted)
+    }
+
+    /// 校验 API key 原文，返回未过期的 key 并记录使用时间
+    pub async fn verify_api_key(&self, key: &str) -> Result<Option<ApiKey>> {
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"
+            SELECT id, name, key_prefix, user_id, workspace_id, scopes, rate_limit_per_minute,
+                   expires_at, last_used_at, created_at
+            FROM api_keys WHERE key_hash = ?
+            "#,
+        )
+        .bind(hash_secret(key))
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to query api key")?;
+
+        let Some(api_key) = api_key else {
+            return Ok(None);
+        };
+        let now = Utc::now();
+        if api_key
+            .expires_at
+            .is_some_and(|expires_at| expires_at < now)
+        {
+            return Ok(None);
+        }
+
+        // 使用时间精确到分钟即可，避免每个请求都写库
+        if api_key
+            .last_used_at
+            .is_none_or(|last_used_at| now - last_used_at >= Duration::minutes(1))
+        {
+            sqlx::query("UPDATE api_keys SET last_used_at = ? WHERE id = ?")
+                .bind(now.timestamp())
+                .bind(&api_key.id)
+                .execute(&self.pool)
+                .await
+                .context("Failed to update api key usage")?;
+        }
+
+        Ok(Some(api_key))
+    }
+
+    /// 用户自行修改密码：清除强制修改标记，并使已有 token 全部失效
+    pub async fn change_password(&self, id: i64, new_password: &str) -> Result<User> {
+        let hash =
+            bcrypt::hash(new_password, bcrypt::DEFAULT_COST).context("Failed to hash password")?;
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET password_hash = ?, must_change_password = 0,
+                token_generation = token_generation + 1, updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(hash)
+        .bind(Utc::now().timestamp())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to change password")?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("User not found"));
+        }
+        self.revoke_refresh_tokens(id).await?;
+
+        self.get_user_by_id(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))
+    }
+
+    /// 开始绑定 TOTP，生成新的密钥，用验证码确认后才生效
+    pub async fn start_totp_enrollment(&self, user_id: i64) -> Result<String> {
+        let secret = generate_totp_secret();
+        sqlx::query(
+            r#"
+            INSERT INTO user_totp (user_id, secret, enabled, last_used_step, created_at)
+            VALUES (?, ?, 0, 0, ?)
+            ON CONFLICT(user_id) DO UPDATE SET
+                secret = excluded.secret, last_used_step = 0, created_at = excluded.created_at
+            WHERE enabled = 0
+            "#,
+        )
+        .bind(user_id)
+        .bind(&secret)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to start TOTP enrollment")?;
+        Ok(secret)
+    }
+
+    /// 用户的两步验证状态
+    pub async fn totp_status(&self, user_id: i64) -> Result<TotpStatus> {
+        let enabled: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM user_totp WHERE user_id = ? AND enabled = 1",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to query TOTP status")?;
+        let recovery_codes_remaining: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM recovery_codes WHERE user_id = ? AND used_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to count recovery codes")?;
+
+        Ok(TotpStatus {
+            enabled,
+            recovery_codes_remaining,
+        })
+    }
+
+    /// 用验证码确认 TOTP 绑定，成功后返回新生成的恢复码（只在此时可见）
+    ///
+    /// 绑定后此前签发的 token 全部失效
+    pub async fn confirm_totp(&self, user_id: i64, code: &str) -> Result<Option<Vec<String>>> {
+        let secret: Option<String> =
+            sqlx::query_scalar("SELECT secret FROM user_totp WHERE user_id = ? AND enabled = 0")
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await
+                .context("Failed to query TOTP enrollment")?;
+        let Some(step) =
+            secret.and_then(|secret| verify_totp(&secret, code, Utc::now().timestamp()))
+        else {
+            return Ok(None);
+        };
+
+        sqlx::query("UPDATE user_totp SET enabled = 1, last_used_step = ? WHERE user_id = ?")
+            .bind(step)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to enable TOTP")?;
+        let codes = self.regenerate_recovery_codes(user_id).await?;
+        self.revoke_user_sessions(user_id).await?;
+
+        info!("Enabled two-factor authentication for user {}", user_id);
+        Ok(Some(codes))
+    }
+
+    /// 校验第二因素：6 位数字按 TOTP 校验（同一时间步只能用一次），否则按恢复码校验（用后作废）
+    pub async fn verify_second_factor(
+        &self,
+        user_id: i64,
+        code: &str,
+    ) -> Result<Option<SecondFactor>> {
+        let code = code.trim();
+        if code.len() == 6 && code.bytes().all(|b| b.is_ascii_digit()) {
+            let secret: Option<String> = sqlx::query_scalar(
+                "SELECT secret FROM user_totp WHERE user_id = ? AND enabled = 1",
+            )
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to query TOTP secret")?;
+            let Some(step) =
+                secret.and_then(|secret| verify_totp(&secret, code, Utc::now().timestamp()))
+            else {
+                return Ok(None);
+            };
+
+            let updated = sqlx::query(
+                "UPDATE user_totp SET last_used_step = ? WHERE user_id = ? AND last_used_step < ?",
+            )
+            .bind(step)
+            .bind(user_id)
+            .bind(step)
+            .execute(&self.pool)
+            .await
+            .context("Failed to record TOTP usage")?
+            .rows_affected();
+            return Ok((updated == 1).then_some(SecondFactor::Totp));
+        }
+
+        let used = sqlx::query(
+            "UPDATE recovery_codes SET used_at = ? WHERE user_id = ? AND code_hash = ? AND used_at IS NULL",
+        )
+        .bind(Utc::now().timestamp())
+        .bind(user_id)
+        .bind(hash_secret(&code.to_lowercase()))
+        .execute(&self.pool)
+        .await
+        .context("Failed to use recovery code")?
+        .rows_affected();
+        Ok((used == 1).then_some(SecondFactor::RecoveryCode))
+    }
+
+    /// 重新生成恢复码，旧的恢复码全部作废
+    pub async fn regenerate_recovery_codes(&self, user_id: i64) -> Result<Vec<String>> {
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                format!(
+                    "{}-{}",
+                    nanoid::nanoid!(5, &RECOVERY_CODE_ALPHABET),
+                    nanoid::nanoid!(5, &RECOVERY_CODE_ALPHABET)
+                )
+            })
+            .collect();
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete recovery codes")?;
+        for code in &codes {
+            sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) VALUES (?, ?)")
+                .bind(user_id)
+                .bind(hash_secret(code))
+                .execute(&mut *tx)
+                .await
+                .context("Failed to insert recovery code")?;
+        }
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// 关闭两步验证并删除恢复码，返回此前是否已启用
+    pub async fn disable_totp(&self, user_id: i64) -> Result<bool> {
+        let enabled = self.totp_status(user_id).await?.enabled;
+        sqlx::query("DELETE FROM user_totp WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete TOTP secret")?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete recovery codes")?;
+        self.clear_mfa_failures(user_id).await?;
+        Ok(enabled)
+    }
+
+    /// 用户名在该 IP 上的锁定截止时间（包括所有 IP 累计失败触发的锁定），未锁定时为 None
+    pub async fn login_locked_until(
+        &self,
+        username: &str,
+        ip: &str,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let locked_until: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT MAX(locked_until) FROM (
+                SELECT locked_until FROM login_failures WHERE username = ? AND ip = ?
+                UNION ALL
+                SELECT locked_until FROM account_login_failures WHERE username = ?
+            )
+            "#,
+        )
+        .bind(username)
+        .bind(ip)
+        .bind(username)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to query login attempts")?;
+
+        let now = Utc::now();
+        Ok(locked_until
+            .and_then(|ts| DateTime::from_timestamp(ts, 0))
+            .filter(|until| *until > now))
+    }
+
+    /// 记录用户名在该 IP 上的一次登录失败，同时计入该用户名在所有 IP 上的累计次数，
+    /// 返回因此触发的锁定截止时间
+    ///
+    /// 同时删除一天内没有再失败的记录（不会再计入锁定），避免不存在的用户名累积
+    pub async fn record_login_failure(
+        &self,
+        username: &str,
+        ip: &str,
+        policy: &LockoutPolicy,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let now = Utc::now();
+        let stale_before = (now - Duration::days(1)).timestamp();
+        sqlx::query(
+            r#"
+            DELETE FROM login_failures
+            WHERE updated_at < ? AND (locked_until IS NULL OR locked_until < ?)
+            "#,
+        )
+        .bind(stale_before)
+        .bind(now.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to prune login failures")?;
+        sqlx::query(
+            r#"
+            DELETE FROM account_login_failures
+            WHERE updated_at < ? AND (locked_until IS NULL OR locked_until < ?)
+            "#,
+        )
+        .bind(stale_before)
+        .bind(now.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to prune account login failures")?;
+        let failures: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO login_failures (username, ip, failures, locked_until, updated_at)
+            VALUES (?, ?, 1, NULL, ?)
+            ON CONFLICT(username, ip) DO UPDATE SET
+                failures = failures + 1,
+                updated_at = excluded.updated_at
+            RETURNING failures
+            "#,
+        )
+        .bind(username)
+        .bind(ip)
+        .bind(now.timestamp())
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to record login failure")?;
+
+        let locked_until = policy.locked_until(failures as u32, now);
+        if let Some(until) = locked_until {
+            sqlx::query("UPDATE login_failures SET locked_until = ? WHERE username = ? AND ip = ?")
+                .bind(until.timestamp())
+                .bind(username)
+                .bind(ip)
+                .execute(&self.pool)
+                .await
+                .context("Failed to lock account")?;
+            warn!(
+                "Login for {} from {} locked until {} after {} failed attempts",
+                username, ip, until, failures
+            );
+        }
+
+        let account_failures: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO account_login_failures (username, failures, locked_until, updated_at)
+            VALUES (?, 1, NULL, ?)
+            ON CONFLICT(username) DO UPDATE SET
+                failures = failures + 1,
+                updated_at = excluded.updated_at
+            RETURNING failures
+            "#,
+        )
+        .bind(username)
+        .bind(now.timestamp())
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to record account login failure")?;
+
+        let account_locked_until = policy.account_locked_until(account_failures as u32, now);
+        if let Some(until) = account_locked_until {
+            sqlx::query("UPDATE account_login_failures SET locked_until = ? WHERE username = ?")
+                .bind(until.timestamp())
+                .bind(username)
+                .execute(&self.pool)
+                .await
+                .context("Failed to lock account")?;
+            warn!(
+                "Login for {} locked on all addresses until {} after {} failed attempts",
+                username, until, account_failures
+            );
+        }
+        Ok(locked_until.max(account_locked_until))
+    }
+
+    /// 登录成功或修改密码后清除该用户名在所有 IP 上的失败计数
+    pub async fn clear_login_failures(&self, username: &str) -> Result<()> {
+        sqlx::query("DELETE FROM login_failures WHERE username = ?")
+            .bind(username)
+            .execute(&self.pool)
+            .await
+            .context("Failed to clear login attempts")?;
+        sqlx::query("DELETE FROM account_login_failures WHERE username = ?")
+            .bind(username)
+            .execute(&self.pool)
+            .await
+            .context("Failed to clear account login attempts")?;
+        Ok(())
+    }
+
+    /// 用户第二因素验证的锁定截止时间，未锁定时为 None
+    pub async fn mfa_locked_until(&self, user_id: i64) -> Result<Option<DateTime<Utc>>> {
+        let locked_until: Option<i64> =
+            sqlx::query_scalar("SELECT locked_until FROM mfa_attempts WHERE user_id = ?")
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await
+                .context("Failed to query mfa attempts")?
+                .flatten();
+
+        let now = Utc::now();
+        Ok(locked_until
+            .and_then(|ts| DateTime::from_timestamp(ts, 0))
+            .filter(|until| *until > now))
+    }
+
+    /// 记录一次第二因素验证失败，达到上限时只锁定验证码校验，不影响密码登录的计数
+    pub async fn record_mfa_failure(
+        &self,
+        user_id: i64,
+        policy: &LockoutPolicy,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let now = Utc::now();
+        let stale_before = (now - Duration::days(1)).timestamp();
+        let failures: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO mfa_attempts (user_id, failures, locked_until, updated_at)
+            VALUES (?, 1, NULL, ?)
+            ON CONFLICT(user_id) DO UPDATE SET
+                failures = CASE WHEN updated_at < ? THEN 1 ELSE failures + 1 END,
+                updated_at = excluded.updated_at
+            RETURNING failures
+            "#,
+        )
+        .bind(user_id)
+        .bind(now.timestamp())
+        .bind(stale_before)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to record mfa failure")?;
+
+        let locked_until = policy.locked_until(failures as u32, now);
+        if let Some(until) = locked_until {
+            sqlx::query("UPDATE mfa_attempts SET locked_until = ? WHERE user_id = ?")
+                .bind(until.timestamp())
+                .bind(user_id)
+                .execute(&self.pool)
+                .await
+                .context("Failed to lock two-factor verification")?;
+            warn!(
+                "Two-factor verification for user {} locked until {} after {} failed attempts",
+                user_id, until, failures
+            );
+        }
+        Ok(locked_until)
+    }
+
+    pub async fn clear_mfa_failures(&self, user_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM mfa_attempts WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to clear mfa attempts")?;
+        Ok(())
+    }
+
+    /// 记录认证事件，写入失败只记日志，不影响登录流程
+    pub async fn record_auth_event(&self, event: NewAuthEvent<'_>) {
+        let result = sqlx::query(
+            "INSERT INTO auth_events (event, username, user_id, ip, detail, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(event.kind.to_string())
+        .bind(event.username)
+        .bind(event.user_id)
+        .bind(event.ip)
+        .bind(event.detail)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await;
+
+        if let Err(err) = result {
+            warn!("Failed to record auth event {}: {:?}", event.kind, err);
+        }
+    }
+
+    /// 查询用户的认证事件，按时间倒序
+    pub async fn list_auth_events(&self, user_id: i64, limit: i64) -> Result<Vec<AuthEvent>> {
+        sqlx::query_as::<_, AuthEvent>(
+            r#"
+            SELECT id, event, username, user_id, ip, detail, created_at
+            FROM auth_events
+            WHERE user_id = ?
+            ORDER BY id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list auth events")
+    }
+}
+
+/// 默认管理员的初始密码
+fn default_admin_password() -> String {
+    get_config().auth.default_admin_password.clone()
+}
+
+/// 显示名称的最大长度（字符数）
+pub const MAX_DISPLAY_NAME_CHARS: usize = 64;
+
+/// 校验显示名称，名称会填入提示词，不允许换行等控制字符
+pub fn validate_display_name(name: &str) -> Result<(), String> {
+    if name.trim().chars().count() > MAX_DISPLAY_NAME_CHARS {
+        return Err(format!(
+            "display_name must be at most {} characters",
+            MAX_DISPLAY_NAME_CHARS
+        ));
+    }
+    if name.chars().any(char::is_control) {
+        return Err("display_name must not contain control characters".to_string());
+    }
+    Ok(())
+}
+
+/// 去除首尾空白，空值或不合法的名称视为未设置
+pub fn normalize_display_name(name: &str) -> Option<String> {
+    let name = name.trim();
+    (!name.is_empty() && validate_display_name(name).is_ok()).then(|| name.to_string())
+}
+
+/// 去除空白、空值和重复的分组名
+pub fn normalize_groups(groups: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(groups.len());
+    for group in groups {
+        let group = group.trim().to_string();
+        if !group.is_empty() && !normalized.contains(&group) {
+            normalized.push(group);
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_login_failures_from_many_ips_lock_account() {
+        let db = std::env::temp_dir().join(format!("users-{}.db", nanoid::nanoid!(8)));
+        let store = UserStore::new(&format!("sqlite:{}?mode=rwc", db.display()))
+            .await
+            .unwrap();
+        let policy = LockoutPolicy {
+            max_attempts: 3,
+            account_max_attempts: 6,
+            ..LockoutPolicy::default()
+        };
+
+        // 每个 IP 只失败一次，单个 IP 的计数达不到上限
+        for i in 1..6 {
+            let ip = format!("10.0.0.{}", i);
+            assert!(
+                store
+                    .record_login_failure("alice", &ip, &policy)
+                    .await
+                    .unwrap()
+                    .is_none()
+            );
+            assert!(
+                store
+                    .login_locked_until("alice", &ip)
+                    .await
+                    .unwrap()
+                    .is_none()
+            );
+        }
+        // 累计达到上限后所有 IP 都被锁定，其他用户名不受影响
+        assert!(
+            store
+                .record_login_failure("alice", "10.0.0.6", &policy)
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            store
+                .login_locked_until("alice", "192.168.1.1")
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            store
+                .login_locked_until("bob", "10.0.0.1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        store.clear_login_failures("alice").await.unwrap();
+        assert!(
+            store
+                .login_locked_until("alice", "192.168.1.1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let _ = std::fs::remove_file(db);
+    }
 }
@@ -0,0 +1,300 @@
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool, sqlite::SqliteRow};
+use tracing::info;
+
+use super::User;
+use crate::config::get_config;
+
+/// 默认工作区 id，升级前的用户、文档和对话都属于它
+pub const DEFAULT_WORKSPACE: &str = "default";
+
+/// 全局 WorkspaceStore 实例，认证中间件通过它确认 API key 的创建者仍属于所属工作区
+static WORKSPACE_STORE: OnceLock<Arc<WorkspaceStore>> = OnceLock::new();
+
+/// 初始化全局 WorkspaceStore
+pub fn init_workspace_store(store: Arc<WorkspaceStore>) -> Result<()> {
+    WORKSPACE_STORE
+        .set(store)
+        .map_err(|_| anyhow::anyhow!("WorkspaceStore already initialized"))
+}
+
+/// 获取全局 WorkspaceStore 实例
+pub fn get_workspace_store() -> Option<&'static Arc<WorkspaceStore>> {
+    WORKSPACE_STORE.get()
+}
+
+/// 工作区（组织/部门），文档、对话和 preamble 按工作区隔离
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for Workspace {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let timestamp = |column: &str| -> sqlx::Result<DateTime<Utc>> {
+            let ts: i64 = row.try_get(column)?;
+            DateTime::from_timestamp(ts, 0).ok_or_else(|| {
+                sqlx::Error::Decode(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Invalid timestamp {}", column),
+                )))
+            })
+        };
+
+        Ok(Workspace {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            created_at: timestamp("created_at")?,
+            updated_at: timestamp("updated_at")?,
+        })
+    }
+}
+
+/// 创建工作区请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateWorkspaceRequest {
+    pub id: String,
+    pub name: String,
+}
+
+/// 工作区存储，与用户表位于同一个数据库
+pub struct WorkspaceStore {
+    pool: SqlitePool,
+}
+
+impl WorkspaceStore {
+    /// 使用配置中的 user_db_path 创建工作区存储
+    pub async fn from_config() -> Result<Self> {
+        Self::new(&get_config().storage.user_db_path).await
+    }
+
+    /// 创建新的工作区存储实例（用户表需已初始化）
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url)
+            .await
+            .context("Failed to connect to workspace database")?;
+
+        let store = Self { pool };
+        store.init_database().await?;
+        Ok(store)
+    }
+
+    /// 初始化数据库表，并把不属于任何工作区的用户加入默认工作区
+    async fn init_database(&self) -> Result<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS workspaces (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS workspace_members (
+                workspace_id TEXT NOT NULL,
+                user_id INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (workspace_id, user_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_workspace_members_user_id ON workspace_members(user_id);
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to initialize workspace tables")?;
+
+        let now = Utc::now().timestamp();
+        sqlx::query(
+            "INSERT OR IGNORE INTO workspaces (id, name, created_at, updated_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(DEFAULT_WORKSPACE)
+        .bind("Default")
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .context("Failed to create default workspace")?;
+
+        let migrated = sqlx::query(
+            r#"
+            INSERT INTO workspace_members (workspace_id, user_id, created_at)
+            SELECT ?, id, ? FROM users
+            WHERE id NOT IN (SELECT user_id FROM workspace_members)
+            "#,
+        )
+        .bind(DEFAULT_WORKSPACE)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .context("Failed to migrate users into default workspace")?
+        .rows_affected();
+        if migrated > 0 {
+            info!(
+                "Added {} existing user(s) to the default workspace",
+                migrated
+            );
+        }
+
+        Ok(())
+    }
+
+    /// 列出所有工作区
+    pub async fn list(&self) -> Result<Vec<Workspace>> {
+        sqlx::query_as::<_, Workspace>("SELECT * FROM workspaces ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to list workspaces")
+    }
+
+    /// 列出用户所属的工作区
+    pub async fn list_for_user(&self, user_id: i64) -> Result<Vec<Workspace>> {
+        sqlx::query_as::<_, Workspace>(
+            r#"
+            SELECT w.* FROM workspaces w
+            JOIN workspace_members m ON m.workspace_id = w.id
+            WHERE m.user_id = ?
+            ORDER BY w.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list user workspaces")
+    }
+
+    /// 根据 id 获取工作区
+    pub async fn get(&self, id: &str) -> Result<Option<Workspace>> {
+        sqlx::query_as::<_, Workspace>("SELECT * FROM workspaces WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to get workspace")
+    }
+
+    /// 创建工作区（id 需已校验）
+    pub async fn create(&self, req: CreateWorkspaceRequest) -> Result<Workspace> {
+        let now = Utc::now();
+        sqlx::query(
+            "INSERT INTO workspaces (id, name, created_at, updated_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&req.id)
+        .bind(&req.name)
+        .bind(now.timestamp())
+        .bind(now.timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to create workspace")?;
+
+        info!("Created workspace: {}", req.id);
+        Ok(Workspace {
+            id: req.id,
+            name: req.name,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// 修改工作区名称
+    pub async fn rename(&self, id: &str, name: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE workspaces SET name = ?, updated_at = ? WHERE id = ?")
+            .bind(name)
+            .bind(Utc::now().timestamp())
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to rename workspace")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 删除工作区及其成员关系（默认工作区不能删除）
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        if id == DEFAULT_WORKSPACE {
+            anyhow::bail!("Cannot delete the default workspace");
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM workspace_members WHERE workspace_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete workspace members")?;
+        let deleted = sqlx::query("DELETE FROM workspaces WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to delete workspace")?
+            .rows_affected();
+        tx.commit()
+            .await
+            .context("Failed to commit workspace deletion")?;
+
+        Ok(deleted > 0)
+    }
+
+    /// 用户是否属于工作区
+    pub async fn is_member(&self, workspace_id: &str, user_id: i64) -> Result<bool> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM workspace_members WHERE workspace_id = ? AND user_id = ?",
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to check workspace membership")?;
+        Ok(count > 0)
+    }
+
+    /// 列出工作区成员
+    pub async fn list_members(&self, workspace_id: &str) -> Result<Vec<User>> {
+        sqlx::query_as::<_, User>(
+            r#"
+            SELECT u.id, u.username, u.display_name, u.password_hash, u.role, u.status, u.user_groups,
+                   u.token_generation, u.must_change_password, u.created_at, u.updated_at
+            FROM users u
+            JOIN workspace_members m ON m.user_id = u.id
+            WHERE m.workspace_id = ?
+            ORDER BY u.created_at DESC
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list workspace members")
+    }
+
+    /// 添加成员（已是成员时忽略）
+    pub async fn add_member(&self, workspace_id: &str, user_id: i64) -> Result<()> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO workspace_members (workspace_id, user_id, created_at) VALUES (?, ?, ?)",
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .context("Failed to add workspace member")?;
+        Ok(())
+    }
+
+    /// 移除成员，返回用户剩余的工作区数量
+    pub async fn remove_member(&self, workspace_id: &str, user_id: i64) -> Result<i64> {
+        sqlx::query("DELETE FROM workspace_members WHERE workspace_id = ? AND user_id = ?")
+            .bind(workspace_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to remove workspace member")?;
+
+        sqlx::query_scalar("SELECT COUNT(*) FROM workspace_members WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to count user workspaces")
+    }
+}
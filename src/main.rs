@@ -1,10 +1,15 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use rig_rag::{
-    agent::RigAgent,
-    config::AppConfig,
-    db::{ConversationStore, DocumentStore, UserStore},
-    utils::logger::init_logger,
+    agent::{KnowledgeBaseRegistry, RigAgent, RigAgentBuilder, init_knowledge_bases},
+    config::{AppConfig, Config, get_config, init_config},
+    db::{
+        AuditStore, ConversationStore, DocumentStore, ExperimentStore, KnowledgeBaseStore,
+        PreambleStore, RuntimeSettings, SettingsStore, UserStore, WorkspaceStore, init_audit_store,
+        init_experiment_store, init_preamble_store, init_settings_store, init_user_store,
+        init_workspace_store,
+    },
+    utils::{BackupRetention, logger::init_logger},
     web,
 };
 use tracing::info;
@@ -13,29 +18,97 @@ use tracing::info;
 async fn main() {
     dotenv::dotenv().ok();
 
-    init_logger().expect("Failed to initialize logger");
+    // 配置检查命令: rig-rag config check
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("config") {
+        run_config_check(&args[1..]).await;
+        return;
+    }
+
+    // 读取配置文件和环境变量，配置有误时列出全部错误后退出
+    let app_config = match Config::load() {
+        Ok(config) => config,
+        Err(errors) => {
+            eprintln!("Invalid configuration ({}):", Config::file_path());
+            for error in errors {
+                eprintln!("  - {}", error);
+            }
+            std::process::exit(1);
+        }
+    };
+    init_config(app_config.clone()).expect("Failed to initialize config");
+
+    init_logger(&app_config.log).expect("Failed to initialize logger");
     info!("Starting Agent");
 
     // 初始化文件备份
-    let backup_dir = std::env::var("BACKUP_DIR").unwrap_or_else(|_| "data/backups".to_string());
-    if let Err(e) = rig_rag::utils::init_file_backup(&backup_dir).await {
+    let backup_dir = app_config.storage.backup_dir.clone();
+    let retention = BackupRetention::current();
+    if let Err(e) = rig_rag::utils::init_file_backup(&backup_dir, retention.clone()).await {
         tracing::warn!("⚠️ Failed to initialize file backup: {}", e);
     } else {
-        info!("📁 Initialized file backup at: {}", backup_dir);
+        info!(
+            "📁 Initialized file backup at: {} (keep {} versions, max age {:?} days)",
+            backup_dir, retention.keep_versions, retention.max_age_days
+        );
+    }
+
+    // 一致性检查命令: rig-rag doctor [--repair] [--orphan-backups=restore|delete|keep]
+    if args.first().map(String::as_str) == Some("doctor") {
+        run_doctor(&args[1..]).await;
+        return;
     }
 
     // 初始化用户数据库
-    let user_db_path = std::env::var("USER_DB_PATH")
-        .unwrap_or_else(|_| "sqlite:data/users.db?mode=rwc".to_string());
+    let user_db_path = app_config.storage.user_db_path.clone();
     info!("Initializing user database at: {}", user_db_path);
     let user_store = Arc::new(
         UserStore::new(&user_db_path)
             .await
             .expect("Failed to initialize user store"),
     );
+    user_store
+        .ensure_default_admin()
+        .await
+        .expect("Failed to create default admin");
+    // 认证中间件通过全局实例校验用户状态和 token 代数
+    init_user_store(user_store.clone()).expect("Failed to initialize global user store");
+    // 工作区表与用户表同库，需在用户表初始化之后创建
+    let workspace_store = Arc::new(
+        WorkspaceStore::new(&user_db_path)
+            .await
+            .expect("Failed to initialize workspace store"),
+    );
+    init_workspace_store(workspace_store.clone())
+        .expect("Failed to initialize global workspace store");
+    // 审计日志同样写入用户数据库，各管理接口通过全局实例记录
+    let audit_store = AuditStore::new(&user_db_path)
+        .await
+        .expect("Failed to initialize audit store");
+    init_audit_store(Arc::new(audit_store)).expect("Failed to initialize global audit store");
+    // preamble 版本历史
+    let preamble_store = PreambleStore::new(&user_db_path)
+        .await
+        .expect("Failed to initialize preamble store");
+    init_preamble_store(Arc::new(preamble_store))
+        .expect("Failed to initialize global preamble store");
+    // A/B 实验
+    let experiment_store = ExperimentStore::new(&user_db_path)
+        .await
+        .expect("Failed to initialize experiment store");
+    init_experiment_store(Arc::new(experiment_store))
+        .expect("Failed to initialize global experiment store");
 
     // 加载应用配置
-    let config = AppConfig::from_env();
+    let mut config = AppConfig::current();
+    // 运行时设置：已保存的值覆盖配置中的模型、温度、检索条数、限流和 CORS
+    let settings_store =
+        SettingsStore::new(&user_db_path, RuntimeSettings::from_config(get_config()))
+            .await
+            .expect("Failed to initialize settings store");
+    settings_store.settings().apply_to(&mut config);
+    init_settings_store(Arc::new(settings_store))
+        .expect("Failed to initialize global settings store");
 
     let agent = RigAgent::new_from_config(&config).await.unwrap();
 
@@ -44,15 +117,31 @@ async fn main() {
 
     let agent = Arc::new(agent);
 
-    let app = web::create_router(agent, document_store, user_store).await;
+    // 初始化知识库注册表（默认知识库即上面的 agent 和集合）
+    let knowledge_base_store = KnowledgeBaseStore::from_config()
+        .await
+        .expect("Failed to initialize knowledge base store");
+    init_knowledge_bases(KnowledgeBaseRegistry::new(
+        config.clone(),
+        knowledge_base_store,
+        workspace_store.clone(),
+        agent.clone(),
+        document_store.clone(),
+        web::DEFAULT_CHUNK_SIZE,
+    ))
+    .expect("Failed to initialize knowledge base registry");
 
-    let addr = std::env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0:3000".to_string());
+    let app = web::create_router(agent, document_store, user_store, workspace_store).await;
+
+    let addr = app_config.server.host.clone();
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     info!(
         "Starting server on http://{}",
         listener.local_addr().unwrap()
     );
     close_old_conversations().await;
+    cleanup_backups_periodically(app_config.storage.backup_cleanup_interval_hours);
+    cleanup_audit_log_periodically(app_config.storage.audit_retention_days);
     axum::serve(
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
@@ -62,11 +151,12 @@ async fn main() {
 }
 
 async fn close_old_conversations() {
-    let conversation_store = ConversationStore::from_env()
+    let conversation_store = ConversationStore::from_config()
         .await
         .expect("Failed to initialize conversation store");
 
     tokio::spawn(async move {
+        let mut next_visitor_cleanup = std::time::Instant::now();
         loop {
             let closed_count = conversation_store
                 .close_old_conversations()
@@ -75,7 +165,159 @@ async fn close_old_conversations() {
             if closed_count > 0 {
                 // info!("Closed {} conversations (older than 1 day)", closed_count);
             }
+            // 会话 token 过期后访客记录不再有用，每小时清理一次
+            if std::time::Instant::now() >= next_visitor_cleanup {
+                next_visitor_cleanup += std::time::Duration::from_secs(3600);
+                match conversation_store
+                    .delete_inactive_visitors(web::CHAT_SESSION_DAYS + 1)
+                    .await
+                {
+                    Ok(0) => {}
+                    Ok(deleted) => info!("Deleted {} inactive visitors", deleted),
+                    Err(e) => tracing::error!("Failed to delete inactive visitors: {}", e),
+                }
+            }
             tokio::time::sleep(std::time::Duration::from_secs(60)).await;
         }
     });
 }
+
+fn cleanup_backups_periodically(interval_hours: u64) {
+    if interval_hours == 0 {
+        info!("Scheduled backup cleanup disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        loop {
+            if let Some(backup) = rig_rag::utils::get_file_backup() {
+                match backup.apply_retention(backup.retention()).await {
+                    Ok(deleted) if deleted > 0 => {
+                        info!("🧹 Scheduled backup cleanup removed {} file(s)", deleted);
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("⚠️ Scheduled backup cleanup failed: {}", e),
+                }
+            }
+            // 知识库和工作区的备份位于全局备份目录的子目录中，使用同样的保留策略
+            if let Some(registry) = rig_rag::agent::get_knowledge_bases() {
+                match registry.apply_backup_retention().await {
+                    Ok(deleted) if deleted > 0 => {
+                        info!(
+                            "🧹 Scheduled backup cleanup removed {} knowledge base file(s)",
+                            deleted
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::warn!("⚠️ Scheduled knowledge base backup cleanup failed: {}", e)
+                    }
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(interval_hours * 60 * 60)).await;
+        }
+    });
+}
+
+/// 每天删除超过保留天数的审计日志
+fn cleanup_audit_log_periodically(retention_days: u64) {
+    if retention_days == 0 {
+        info!("Audit log retention disabled, keeping all entries");
+        return;
+    }
+
+    tokio::spawn(async move {
+        loop {
+            if let Some(store) = rig_rag::db::get_audit_store() {
+                let cutoff = chrono::Utc::now() - chrono::Duration::days(retention_days as i64);
+                match store.delete_before(cutoff).await {
+                    Ok(0) => {}
+                    Ok(deleted) => info!("🧹 Deleted {} expired audit log entries", deleted),
+                    Err(e) => tracing::warn!("⚠️ Audit log cleanup failed: {}", e),
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(24 * 60 * 60)).await;
+        }
+    });
+}
+
+/// 打印生效的配置（隐藏密钥）和已保存的运行时设置并校验，有错误时以状态码 1 退出
+async fn run_config_check(args: &[String]) {
+    if args.first().map(String::as_str) != Some("check") {
+        eprintln!("Usage: rig-rag config check");
+        std::process::exit(2);
+    }
+
+    let (config, errors) = Config::check();
+    println!("# {}", Config::file_path());
+    println!("{}", toml::to_string_pretty(&config.redacted()).unwrap());
+    print_runtime_settings(&config.storage.user_db_path).await;
+    if errors.is_empty() {
+        eprintln!("Configuration OK");
+    } else {
+        eprintln!("Invalid configuration:");
+        for error in errors {
+            eprintln!("  - {}", error);
+        }
+        std::process::exit(1);
+    }
+}
+
+/// 已保存的运行时设置覆盖上面的模型、温度和检索条数，以注释形式输出
+async fn print_runtime_settings(user_db_path: &str) {
+    match SettingsStore::saved(user_db_path).await {
+        Ok(Some(stored)) => {
+            println!(
+                "# Runtime settings saved by {} at {} (override llm.model, llm.temperature, llm.top_k):",
+                stored.updated_by.as_deref().unwrap_or_default(),
+                stored
+                    .updated_at
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default()
+            );
+            for line in toml::to_string_pretty(&stored.settings)
+                .unwrap_or_default()
+                .lines()
+            {
+                println!("# {}", line);
+            }
+        }
+        Ok(None) => println!("# No saved runtime settings"),
+        Err(e) => eprintln!("Unable to read saved runtime settings: {:#}", e),
+    }
+}
+
+async fn run_doctor(args: &[String]) {
+    let config = AppConfig::current();
+    let document_store = DocumentStore::with_config(&config.qdrant);
+
+    let repair = args.iter().any(|a| a == "--repair");
+    let orphan_backups = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--orphan-backups="))
+        .map(|v| {
+            serde_json::from_value(serde_json::Value::String(v.to_string()))
+                .expect("--orphan-backups must be one of: restore, delete, keep")
+        })
+        .unwrap_or_default();
+
+    let report = if repair {
+        let embedding_model = RigAgentBuilder::from_config(config).init_embedding_client();
+        web::repair_consistency(
+            &document_store,
+            rig_rag::utils::get_file_backup(),
+            web::DEFAULT_CHUNK_SIZE,
+            &embedding_model,
+            &web::RepairOptions { orphan_backups },
+        )
+        .await
+    } else {
+        web::check_consistency(&document_store, rig_rag::utils::get_file_backup()).await
+    }
+    .expect("Consistency check failed");
+
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    if !report.is_healthy() && !repair {
+        std::process::exit(1);
+    }
+}
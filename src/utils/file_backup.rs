@@ -4,16 +4,19 @@ use std::{
 };
 
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
 use tokio::fs;
 use tracing::{error, info, warn};
 
+use crate::config::get_config;
+
 /// 全局 FileBackup 实例
 static FILE_BACKUP: OnceLock<FileBackup> = OnceLock::new();
 
 /// 初始化全局 FileBackup
-pub async fn init_file_backup(backup_dir: &str) -> anyhow::Result<()> {
-    let backup = FileBackup::new(backup_dir);
+pub async fn init_file_backup(backup_dir: &str, retention: BackupRetention) -> anyhow::Result<()> {
+    let backup = FileBackup::new(backup_dir).with_retention(retention);
     backup.init().await?;
     FILE_BACKUP
         .set(backup)
@@ -26,6 +29,48 @@ pub fn get_file_backup() -> Option<&'static FileBackup> {
     FILE_BACKUP.get()
 }
 
+/// 备份保留策略
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupRetention {
+    /// 每个文档保留的最新版本数量
+    pub keep_versions: usize,
+    /// 备份最长保留天数，None 表示不按时间清理
+    /// 每个文档的最新备份始终保留
+    pub max_age_days: Option<i64>,
+}
+
+impl Default for BackupRetention {
+    fn default() -> Self {
+        Self {
+            keep_versions: 5,
+            max_age_days: None,
+        }
+    }
+}
+
+impl BackupRetention {
+    /// 从全局配置创建保留策略
+    pub fn current() -> Self {
+        let storage = &get_config().storage;
+        Self {
+            keep_versions: storage.backup_keep_versions.max(1),
+            max_age_days: storage.backup_max_age_days.filter(|days| *days > 0),
+        }
+    }
+}
+
+/// 备份文件信息
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupInfo {
+    pub doc_id: String,
+    /// 备份文件名
+    pub filename: String,
+    /// 原始文件名
+    pub original_filename: String,
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
 /// 文件备份管理器
 /// 负责保存、删除和恢复文档的原始文件副本
 #[derive(Debug, Clone)]
@@ -33,6 +78,8 @@ pub struct FileBackup {
     backup_dir: PathBuf,
     /// 单个文件最大大小（字节），默认 10MB
     max_file_size: u64,
+    /// 保留策略
+    retention: BackupRetention,
 }
 
 impl FileBackup {
@@ -43,6 +90,7 @@ impl FileBackup {
         Self {
             backup_dir: backup_dir.as_ref().to_path_buf(),
             max_file_size: Self::DEFAULT_MAX_FILE_SIZE,
+            retention: BackupRetention::default(),
         }
     }
 
@@ -51,9 +99,26 @@ impl FileBackup {
         Self {
             backup_dir: backup_dir.as_ref().to_path_buf(),
             max_file_size,
+            retention: BackupRetention::default(),
         }
     }
 
+    /// 设置保留策略
+    pub fn with_retention(mut self, retention: BackupRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// 获取保留策略
+    pub fn retention(&self) -> &BackupRetention {
+        &self.retention
+    }
+
+    /// 获取备份目录
+    pub fn backup_dir(&self) -> &Path {
+        &self.backup_dir
+    }
+
     /// 初始化备份目录
     pub async fn init(&self) -> Result<()> {
         if !self.backup_dir.exists() {
@@ -240,44 +305,24 @@ impl FileBackup {
     /// # Returns
     /// 返回 (原始文件名, 内容) 元组
     pub async fn read_backup(&self, doc_id: &str) -> Result<Option<(String, String)>> {
-        let backup_paths = self.get_backup_paths(doc_id).await?;
-
-        if backup_paths.is_empty() {
+        // 取最新的备份（list_backups 按时间倒序，且只匹配完整的 doc_id）
+        let Some(latest_backup) = self.list_backups(doc_id).await?.into_iter().next() else {
             return Ok(None);
-        }
-
-        // 取最新的备份（按文件名排序，因为包含时间戳）
-        let latest_backup = backup_paths
-            .iter()
-            .max_by_key(|p| p.file_name())
-            .context("Failed to find latest backup")?;
+        };
 
-        let content = fs::read_to_string(latest_backup)
+        let path = self.backup_dir.join(&latest_backup.filename);
+        let content = fs::read_to_string(&path)
             .await
-            .context(format!("Failed to read backup: {:?}", latest_backup))?;
-
-        // 从文件名中提取原始文件名
-        // 格式: {doc_id}_{timestamp}_{original_filename}
-        let filename = latest_backup
-            .file_name()
-            .and_then(|n| n.to_str())
-            .and_then(|s| {
-                // 跳过 doc_id 和 timestamp 部分
-                let parts: Vec<&str> = s.splitn(3, '_').collect();
-                parts.get(2).map(|s| s.to_string())
-            })
-            .unwrap_or_else(|| "unknown.txt".to_string());
+            .context(format!("Failed to read backup: {:?}", path))?;
 
-        Ok(Some((filename, content)))
+        Ok(Some((latest_backup.original_filename, content)))
     }
 
     /// 列出所有备份文件
     ///
     /// # Returns
-    /// 返回 (doc_id, 文件名, 大小, 修改时间) 列表
-    pub async fn list_all_backups(
-        &self,
-    ) -> Result<Vec<(String, String, u64, chrono::DateTime<Utc>)>> {
+    /// 返回按创建时间倒序排列的备份列表
+    pub async fn list_all_backups(&self) -> Result<Vec<BackupInfo>> {
         let mut backups = Vec::new();
 
         if !self.backup_dir.exists() {
@@ -296,76 +341,91 @@ impl FileBackup {
                     entry.metadata().await,
                 )
             {
-                // 提取 doc_id (文件名第一部分)
-                let doc_id = filename.split('_').next().unwrap_or("unknown").to_string();
-
-                let size = metadata.len();
-                let modified = metadata
-                    .modified()
-                    .ok()
-                    .and_then(|t| {
-                        t.duration_since(std::time::UNIX_EPOCH)
-                            .ok()
-                            .and_then(|d| chrono::DateTime::from_timestamp(d.as_secs() as i64, 0))
-                    })
-                    .unwrap_or_else(Utc::now);
-
-                backups.push((doc_id, filename.to_string(), size, modified));
+                let Some((doc_id, created_at, original_filename)) = parse_backup_filename(filename)
+                else {
+                    warn!("Skipping unrecognized backup file: {:?}", path);
+                    continue;
+                };
+
+                backups.push(BackupInfo {
+                    doc_id: doc_id.to_string(),
+                    filename: filename.to_string(),
+                    original_filename: original_filename.to_string(),
+                    size: metadata.len(),
+                    created_at,
+                });
             }
         }
 
-        // 按修改时间倒序排列
-        backups.sort_by(|a, b| b.3.cmp(&a.3));
+        // 按创建时间倒序排列
+        backups.sort_by(|a, b| {
+            b.created_at
+                .cmp(&a.created_at)
+                .then_with(|| b.filename.cmp(&a.filename))
+        });
 
         Ok(backups)
     }
 
+    /// 列出指定文档的所有备份版本（最新的在前）
+    pub async fn list_backups(&self, doc_id: &str) -> Result<Vec<BackupInfo>> {
+        let backups = self.list_all_backups().await?;
+        Ok(backups.into_iter().filter(|b| b.doc_id == doc_id).collect())
+    }
+
     /// 清理旧备份
     /// 每个文档只保留最新的 N 个备份
     ///
     /// # Arguments
     /// * `keep_count` - 每个文档保留的备份数量
     pub async fn cleanup_old_backups(&self, keep_count: usize) -> Result<usize> {
-        use std::collections::HashMap;
-
-        if !self.backup_dir.exists() {
-            return Ok(0);
-        }
-
-        // 按 doc_id 分组所有备份
-        let mut doc_backups: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        self.apply_retention(&BackupRetention {
+            keep_versions: keep_count,
+            max_age_days: None,
+        })
+        .await
+    }
 
-        let mut entries = fs::read_dir(&self.backup_dir)
-            .await
-            .context("Failed to read backup directory")?;
+    /// 按保留策略清理备份
+    ///
+    /// 每个文档最多保留 `keep_versions` 个版本，超过 `max_age_days` 的旧版本也会被删除，
+    /// 但每个文档的最新备份始终保留。
+    ///
+    /// # Returns
+    /// 返回删除的文件数量
+    pub async fn apply_retention(&self, retention: &BackupRetention) -> Result<usize> {
+        use std::collections::HashMap;
 
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.is_file()
-                && let Some(filename) = path.file_name().and_then(|n| n.to_str())
-            {
-                let doc_id = filename.split('_').next().unwrap_or("unknown").to_string();
-                doc_backups.entry(doc_id).or_default().push(path);
-            }
+        let keep_versions = retention.keep_versions.max(1);
+        let cutoff = retention
+            .max_age_days
+            .map(|days| Utc::now() - chrono::Duration::days(days));
+
+        // 按 doc_id 分组所有备份（已按时间倒序）
+        let mut doc_backups: HashMap<String, Vec<BackupInfo>> = HashMap::new();
+        for backup in self.list_all_backups().await? {
+            doc_backups
+                .entry(backup.doc_id.clone())
+                .or_default()
+                .push(backup);
         }
 
         let mut deleted_count = 0;
 
-        // 对每个 doc_id 的备份进行清理
-        for (doc_id, mut paths) in doc_backups {
-            if paths.len() <= keep_count {
-                continue;
-            }
+        for (doc_id, backups) in doc_backups {
+            let mut doc_deleted = 0;
 
-            // 按文件名排序（文件名包含时间戳）
-            paths.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
+            for (idx, backup) in backups.iter().enumerate() {
+                let expired = idx > 0 && cutoff.is_some_and(|c| backup.created_at < c);
+                if idx < keep_versions && !expired {
+                    continue;
+                }
 
-            // 删除超出保留数量的备份
-            for path in paths.iter().skip(keep_count) {
-                match fs::remove_file(path).await {
+                let path = self.backup_dir.join(&backup.filename);
+                match fs::remove_file(&path).await {
                     Ok(_) => {
                         info!("🧹 Cleaned up old backup: {:?}", path);
-                        deleted_count += 1;
+                        doc_deleted += 1;
                     }
                     Err(e) => {
                         error!("Failed to delete old backup {:?}: {}", path, e);
@@ -373,12 +433,13 @@ impl FileBackup {
                 }
             }
 
-            if deleted_count > 0 {
+            if doc_deleted > 0 {
                 info!(
                     "🧹 Cleaned {} old backups for doc_id: {}",
-                    deleted_count, doc_id
+                    doc_deleted, doc_id
                 );
             }
+            deleted_count += doc_deleted;
         }
 
         Ok(deleted_count)
@@ -413,26 +474,97 @@ impl FileBackup {
         }
     }
 
-    /// 获取备份目录总大小
+    /// 获取备份总大小
+    ///
+    /// 只统计能识别的备份文件，与 `list_all_backups` 的文件数保持一致
     pub async fn get_total_size(&self) -> Result<u64> {
-        let mut total_size = 0u64;
+        Ok(self.list_all_backups().await?.iter().map(|b| b.size).sum())
+    }
+}
 
-        if !self.backup_dir.exists() {
-            return Ok(0);
+/// 解析备份文件名
+///
+/// 格式: {doc_id}_{%Y%m%d_%H%M%S}_{original_filename}
+/// doc_id 可能包含下划线，因此从时间戳位置定位分隔
+fn parse_backup_filename(filename: &str) -> Option<(&str, DateTime<Utc>, &str)> {
+    const TIMESTAMP_LEN: usize = "20240101_000000".len();
+
+    for (idx, _) in filename.match_indices('_') {
+        let rest = &filename[idx + 1..];
+        if rest.len() <= TIMESTAMP_LEN || rest.as_bytes()[TIMESTAMP_LEN] != b'_' {
+            continue;
         }
 
-        let mut entries = fs::read_dir(&self.backup_dir)
-            .await
-            .context("Failed to read backup directory")?;
+        let Ok(timestamp) = NaiveDateTime::parse_from_str(&rest[..TIMESTAMP_LEN], "%Y%m%d_%H%M%S")
+        else {
+            continue;
+        };
 
-        while let Some(entry) = entries.next_entry().await? {
-            if let Ok(metadata) = entry.metadata().await
-                && metadata.is_file()
-            {
-                total_size += metadata.len();
-            }
+        let doc_id = &filename[..idx];
+        if doc_id.is_empty() {
+            return None;
+        }
+        return Some((doc_id, timestamp.and_utc(), &rest[TIMESTAMP_LEN + 1..]));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_backup_filename() {
+        let (doc_id, created_at, original) =
+            parse_backup_filename("V1StGXR8_Z5jdHi6B-myT_20240315_101530_report_pdf").unwrap();
+        assert_eq!(doc_id, "V1StGXR8_Z5jdHi6B-myT");
+        assert_eq!(
+            created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "2024-03-15 10:15:30"
+        );
+        assert_eq!(original, "report_pdf");
+
+        assert!(parse_backup_filename("no_timestamp_here").is_none());
+        assert!(parse_backup_filename("_20240315_101530_x").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_apply_retention_keeps_latest_versions() {
+        let dir = std::env::temp_dir().join(format!("rig_rag_backup_{}", nanoid::nanoid!(8)));
+        let backup = FileBackup::new(&dir);
+        backup.init().await.unwrap();
+
+        for ts in ["20240101_000000", "20240102_000000", "20240103_000000"] {
+            fs::write(dir.join(format!("doc-a_{}_a_md", ts)), "a")
+                .await
+                .unwrap();
         }
+        fs::write(dir.join("doc-b_20200101_000000_b_md"), "b")
+            .await
+            .unwrap();
+
+        let deleted = backup
+            .apply_retention(&BackupRetention {
+                keep_versions: 2,
+                max_age_days: Some(30),
+            })
+            .await
+            .unwrap();
+        assert_eq!(deleted, 2);
+
+        let remaining: Vec<String> = backup
+            .list_all_backups()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|b| b.filename)
+            .collect();
+        assert_eq!(
+            remaining,
+            vec!["doc-a_20240103_000000_a_md", "doc-b_20200101_000000_b_md"]
+        );
 
-        Ok(total_size)
+        fs::remove_dir_all(&dir).await.ok();
     }
 }
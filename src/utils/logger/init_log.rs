@@ -9,9 +9,7 @@ use tracing_subscriber::{
 use super::{LogConfig, formatter::CustomFormatter};
 
 /// 初始化日志
-pub fn init_logger() -> Result<(), Box<dyn std::error::Error>> {
-    let config = LogConfig::from_file()?;
-
+pub fn init_logger(config: &LogConfig) -> Result<(), Box<dyn std::error::Error>> {
     let local_time = OffsetTime::new(
         ::time::UtcOffset::from_hms(8, 0, 0).unwrap(),
         ::time::format_description::parse(
@@ -20,7 +18,7 @@ pub fn init_logger() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap(),
     );
 
-    let env_filter = EnvFilter::new(config.level);
+    let env_filter = EnvFilter::new(&config.level);
     let to_file = config.to_file;
     let to_stdout = config.to_stdout;
     // let to_opentelemetry = config.to_opentelemetry;
@@ -1,8 +1,8 @@
-use std::error::Error;
+use serde::{Deserialize, Serialize};
 
-use serde::Deserialize;
-
-#[derive(Debug, Deserialize)]
+/// 日志配置，对应配置文件中的 `[log]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct LogConfig {
     pub level: String,
     pub to_file: bool,
@@ -12,16 +12,14 @@ pub struct LogConfig {
     pub file_name: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Config {
-    pub log: LogConfig,
-}
-
-impl LogConfig {
-    pub fn from_file() -> Result<Self, Box<dyn Error>> {
-        let path = "data/config.toml";
-        let content = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
-        Ok(config.log)
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info,rig_rag=debug,rig=warn,lance=warn".to_string(),
+            to_file: false,
+            to_stdout: true,
+            file_path: "logs".to_string(),
+            file_name: "app.log".to_string(),
+        }
     }
 }
@@ -1,9 +1,11 @@
 pub mod document_parser;
 pub mod file_backup;
 pub mod logger;
+pub mod totp;
 
 pub use document_parser::*;
 pub use file_backup::*;
+pub use totp::*;
 
 pub fn get_env(key: &str) -> Option<String> {
     std::env::var(key).ok()
@@ -0,0 +1,109 @@
+//! 基于时间的一次性密码（TOTP，RFC 6238），与常见的身份验证器 App 兼容：
+//! HMAC-SHA1、6 位数字、30 秒步长
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+/// RFC 4648 base32 字母表（身份验证器 App 使用的密钥编码）
+const BASE32_ALPHABET: [char; 32] = [
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S',
+    'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '2', '3', '4', '5', '6', '7',
+];
+
+/// 时间步长（秒）
+pub const TOTP_STEP_SECONDS: i64 = 30;
+
+/// 验证码位数
+const TOTP_DIGITS: u32 = 6;
+
+/// 生成 160 位随机密钥（32 个 base32 字符）
+pub fn generate_totp_secret() -> String {
+    nanoid::nanoid!(32, &BASE32_ALPHABET)
+}
+
+/// 身份验证器 App 扫码/手动添加用的 otpauth URI
+pub fn totp_provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let label: String =
+        url::form_urlencoded::byte_serialize(format!("{}:{}", issuer, account).as_bytes())
+            .collect();
+    let issuer: String = url::form_urlencoded::byte_serialize(issuer.as_bytes()).collect();
+    format!(
+        "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        label, secret, issuer, TOTP_DIGITS, TOTP_STEP_SECONDS
+    )
+}
+
+/// 计算指定时间步的验证码
+pub fn totp_code(secret: &str, step: i64) -> Option<String> {
+    let key = base32_decode(secret)?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&key).ok()?;
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    Some(format!(
+        "{:0width$}",
+        binary % 10u32.pow(TOTP_DIGITS),
+        width = TOTP_DIGITS as usize
+    ))
+}
+
+/// 校验验证码，允许前后各一个时间步的时钟偏差，返回匹配的时间步
+///
+/// 调用方应拒绝不大于上次使用时间步的结果，防止同一验证码被重放
+pub fn verify_totp(secret: &str, code: &str, timestamp: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = timestamp / TOTP_STEP_SECONDS;
+    (current - 1..=current + 1).find(|step| totp_code(secret, *step).as_deref() == Some(code))
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut bits: u64 = 0;
+    let mut bit_count = 0;
+    let mut output = Vec::with_capacity(input.len() * 5 / 8);
+
+    for c in input.chars().filter(|c| *c != '=' && !c.is_whitespace()) {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a == c.to_ascii_uppercase())? as u64;
+        bits = (bits << 5) | value;
+        bit_count += 5;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            output.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        // RFC 6238 附录 B 的 SHA1 密钥 "12345678901234567890"
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(totp_code(secret, 59 / 30).unwrap(), "287082");
+        assert_eq!(totp_code(secret, 1111111109 / 30).unwrap(), "081804");
+        assert_eq!(totp_code(secret, 1234567890 / 30).unwrap(), "005924");
+
+        assert_eq!(
+            verify_totp(secret, "081804", 1111111109 + 30),
+            Some(1111111109 / 30)
+        );
+        assert_eq!(verify_totp(secret, "081804", 1111111109 + 90), None);
+        assert_eq!(verify_totp(secret, "08180", 1111111109), None);
+    }
+}
@@ -0,0 +1,99 @@
+use std::net::SocketAddr;
+
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Extension, Path, State},
+    http::StatusCode,
+    middleware,
+    routing::{delete, get},
+};
+use serde::Serialize;
+use tracing::info;
+
+use super::auth_routes::{AppError, Claims, UserAppState, require_permission};
+use crate::{
+    db::{ApiKey, AuditAction, CreateApiKeyRequest, Permission},
+    web::record_audit,
+};
+
+/// 新建 API key 的响应，`key` 只在创建时返回一次
+#[derive(Debug, Serialize)]
+pub struct CreateApiKeyResponse {
+    pub key: String,
+    pub api_key: ApiKey,
+}
+
+/// 创建 API key 管理路由（当前工作区的管理员）
+pub fn create_api_key_router(state: UserAppState) -> Router {
+    Router::new()
+        .route(
+            "/api/admin/api-keys",
+            get(list_api_keys).post(create_api_key),
+        )
+        .route("/api/admin/api-keys/{id}", delete(delete_api_key))
+        .route_layer(middleware::from_fn_with_state(
+            Permission::ApiKeyManage,
+            require_permission,
+        ))
+        .with_state(state)
+}
+
+async fn list_api_keys(
+    Extension(claims): Extension<Claims>,
+    State((user_store, _)): State<UserAppState>,
+) -> Result<Json<Vec<ApiKey>>, AppError> {
+    Ok(Json(user_store.list_api_keys(&claims.workspace).await?))
+}
+
+async fn create_api_key(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((user_store, _)): State<UserAppState>,
+    Json(mut req): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreateApiKeyResponse>), AppError> {
+    req.name = req.name.trim().to_string();
+    if req.name.is_empty() || req.scopes.is_empty() {
+        return Err(AppError::BadRequest(
+            "API key name and scopes are required".to_string(),
+        ));
+    }
+    if req.expires_in_days.is_some_and(|days| days <= 0) || req.rate_limit_per_minute == Some(0) {
+        return Err(AppError::BadRequest(
+            "Invalid expiry or rate limit".to_string(),
+        ));
+    }
+    req.scopes.sort();
+    req.scopes.dedup();
+
+    let (api_key, key) = user_store
+        .create_api_key(claims.user_id, &claims.workspace, req)
+        .await?;
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::ApiKeyCreated,
+        &api_key.id,
+        None,
+        serde_json::to_value(&api_key).ok(),
+    )
+    .await;
+    info!("🔑 {} created API key {}", claims.sub, api_key.id);
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateApiKeyResponse { key, api_key }),
+    ))
+}
+
+async fn delete_api_key(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    State((user_store, _)): State<UserAppState>,
+) -> Result<StatusCode, AppError> {
+    if !user_store.delete_api_key(&claims.workspace, &id).await? {
+        return Err(AppError::NotFound("API key not found".to_string()));
+    }
+    record_audit(&claims, addr, AuditAction::ApiKeyDeleted, &id, None, None).await;
+    info!("🗑️  {} deleted API key {}", claims.sub, id);
+    Ok(StatusCode::NO_CONTENT)
+}
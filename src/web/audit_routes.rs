@@ -0,0 +1,232 @@
+use std::net::SocketAddr;
+
+use axum::{
+    Json, Router,
+    extract::{Extension, Query},
+    http::{HeaderValue, header},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Deserialize;
+
+use super::auth_routes::{AppError, Claims, require_permission};
+use crate::{
+    db::{AuditAction, AuditEntry, AuditFilter, NewAuditEntry, Permission, get_audit_store},
+    web::parse_date_param,
+};
+
+/// 单次导出的最大条数，超出时通过响应头告知调用方用 `before_id` 继续导出
+const AUDIT_EXPORT_LIMIT: i64 = 10_000;
+
+/// 导出被截断时为 `true`
+const AUDIT_TRUNCATED_HEADER: &str = "x-audit-truncated";
+/// 导出被截断时，继续导出应传入的 `before_id`
+const AUDIT_NEXT_BEFORE_ID_HEADER: &str = "x-audit-next-before-id";
+
+/// 审计日志查询参数，`since`/`until` 为 RFC 3339 时间或 `YYYY-MM-DD`，
+/// `before_id` 只返回 id 更小的记录
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditLogQuery {
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    pub target: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub before_id: Option<i64>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl AuditLogQuery {
+    fn filter(&self) -> Result<AuditFilter, AppError> {
+        let date = |value: Option<&String>, end_of_day| {
+            parse_date_param(value.map(String::as_str), end_of_day)
+                .map_err(|_| AppError::BadRequest("Invalid date".to_string()))
+        };
+        Ok(AuditFilter {
+            actor: self.actor.clone().filter(|v| !v.is_empty()),
+            action: self.action,
+            target: self.target.clone().filter(|v| !v.is_empty()),
+            since: date(self.since.as_ref(), false)?,
+            until: date(self.until.as_ref(), true)?,
+            before_id: self.before_id,
+        })
+    }
+}
+
+/// 创建审计日志路由（audit.read），只能查看当前工作区的记录
+pub fn create_audit_router() -> Router {
+    Router::new()
+        .route("/api/admin/audit-log", get(list_audit_log_handler))
+        .route("/api/admin/audit-log/export", get(export_audit_log_handler))
+        .route_layer(middleware::from_fn_with_state(
+            Permission::AuditRead,
+            require_permission,
+        ))
+}
+
+/// 写入一条由当前登录身份发起的审计记录，审计日志未初始化时忽略
+pub(crate) async fn record_audit(
+    claims: &Claims,
+    addr: SocketAddr,
+    action: AuditAction,
+    target: impl Into<String>,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+) {
+    record_audit_in(
+        &claims.workspace,
+        claims,
+        addr,
+        action,
+        target,
+        before,
+        after,
+    )
+    .await;
+}
+
+/// 同 [`record_audit`]，但记录在指定的工作区，用于管理其他工作区的操作
+pub(crate) async fn record_audit_in(
+    workspace: &str,
+    claims: &Claims,
+    addr: SocketAddr,
+    action: AuditAction,
+    target: impl Into<String>,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+) {
+    let Some(store) = get_audit_store() else {
+        return;
+    };
+    store
+        .record(NewAuditEntry {
+            workspace: workspace.to_string(),
+            actor: claims.sub.clone(),
+            actor_id: Some(claims.user_id),
+            action,
+            target: Some(target.into()),
+            before,
+            after,
+            ip: Some(addr.ip().to_string()),
+        })
+        .await;
+}
+
+async fn list_audit_log_handler(
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<Vec<AuditEntry>>, AppError> {
+    let store = get_audit_store()
+        .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Audit store not initialized")))?;
+    let filter = query.filter()?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let offset = query.offset.unwrap_or(0).max(0);
+    Ok(Json(
+        store
+            .list(&claims.workspace, &filter, limit, offset)
+            .await?,
+    ))
+}
+
+/// 按相同的筛选条件导出 CSV
+///
+/// 超过 [`AUDIT_EXPORT_LIMIT`] 条时只导出最新的部分，并在响应头中返回
+/// `X-Audit-Truncated: true` 和继续导出用的 `X-Audit-Next-Before-Id`
+async fn export_audit_log_handler(
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Response, AppError> {
+    let store = get_audit_store()
+        .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Audit store not initialized")))?;
+    let filter = query.filter()?;
+    let mut entries = store
+        .list(&claims.workspace, &filter, AUDIT_EXPORT_LIMIT + 1, 0)
+        .await?;
+    let truncated = entries.len() as i64 > AUDIT_EXPORT_LIMIT;
+    entries.truncate(AUDIT_EXPORT_LIMIT as usize);
+
+    let filename = format!(
+        "audit-{}-{}.csv",
+        claims.workspace,
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    );
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        audit_csv(&entries),
+    )
+        .into_response();
+    if truncated && let Some(last) = entries.last() {
+        let headers = response.headers_mut();
+        headers.insert(AUDIT_TRUNCATED_HEADER, HeaderValue::from_static("true"));
+        headers.insert(AUDIT_NEXT_BEFORE_ID_HEADER, HeaderValue::from(last.id));
+    }
+    Ok(response)
+}
+
+/// 审计记录转为 CSV，摘要字段保留 JSON 原文
+fn audit_csv(entries: &[AuditEntry]) -> String {
+    let mut csv = String::from("id,created_at,actor,actor_id,action,target,before,after,ip\n");
+    for entry in entries {
+        let summary = |value: &Option<serde_json::Value>| {
+            value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+        };
+        let fields = [
+            entry.id.to_string(),
+            entry.created_at.to_rfc3339(),
+            entry.actor.clone(),
+            entry.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+            entry.action.to_string(),
+            entry.target.clone().unwrap_or_default(),
+            summary(&entry.before),
+            summary(&entry.after),
+            entry.ip.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_csv() {
+        let entry = AuditEntry {
+            id: 1,
+            workspace: "default".to_string(),
+            actor: "admin".to_string(),
+            actor_id: Some(1),
+            action: AuditAction::DocumentUpdated,
+            target: Some("doc1".to_string()),
+            before: Some(serde_json::json!({ "filename": "a.md" })),
+            after: None,
+            ip: Some("127.0.0.1".to_string()),
+            created_at: chrono::DateTime::from_timestamp(0, 0).unwrap(),
+        };
+        let csv = audit_csv(&[entry]);
+        let row = csv.lines().nth(1).unwrap();
+        assert_eq!(
+            row,
+            r#"1,1970-01-01T00:00:00+00:00,admin,1,document_updated,doc1,"{""filename"":""a.md""}",,127.0.0.1"#
+        );
+    }
+}
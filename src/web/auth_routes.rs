@@ -1,10 +1,10 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
     Json, Router,
     body::Body,
-    extract::{Request, State},
-    http::{StatusCode, header},
+    extract::{ConnectInfo, Request, State},
+    http::{Method, StatusCode, header},
     middleware::Next,
     response::{IntoResponse, Response},
     routing::post,
@@ -12,17 +12,128 @@ use axum::{
 use chrono::{Duration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 
-use crate::db::{UserRole, UserStore};
+use crate::{
+    config::get_config,
+    db::{
+        API_KEY_PREFIX, ApiKey, ApiScope, AuthEventKind, DEFAULT_WORKSPACE, DocumentViewer,
+        LockoutPolicy, NewAuthEvent, PasswordPolicy, Permission, SecondFactor, User, UserRole,
+        UserStore, WorkspaceStore, admin_mfa_required, get_user_store, get_workspace_store,
+    },
+    web::check_api_key_rate,
+};
 
 /// JWT Claims
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // username
+    /// 显示名称（旧 token 和未设置时没有该字段）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     pub user_id: i64,
     pub role: UserRole,
+    /// 用户分组（旧 token 中没有该字段）
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// 当前工作区（旧 token 中没有该字段，视为默认工作区）
+    #[serde(default = "default_workspace")]
+    pub workspace: String,
+    /// 签发时用户的 token 代数，与当前代数不一致的 token 视为已吊销
+    #[serde(default)]
+    pub generation: i64,
+    /// 必须先修改密码，此时只能访问认证接口
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub must_change_password: bool,
+    /// 管理员必须先绑定两步验证，此时只能访问认证接口
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mfa_enrollment_required: bool,
     pub exp: i64, // expiration time
+    /// 通过 API key 认证时的 key 信息，用户 token 为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<ApiKeyGrant>,
+}
+
+/// API key 认证得到的授权
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyGrant {
+    pub id: String,
+    pub scopes: Vec<ApiScope>,
+}
+
+fn default_workspace() -> String {
+    DEFAULT_WORKSPACE.to_string()
+}
+
+/// 匿名聊天会话 token 的请求头
+pub const CHAT_SESSION_HEADER: &str = "x-chat-session";
+
+/// 匿名聊天会话 token 有效期（天），活跃访客会在过期前轮换得到新 token
+pub const CHAT_SESSION_DAYS: i64 = 7;
+
+/// 匿名聊天会话 token 签发超过该时长（小时）后，下次聊天时轮换
+pub const CHAT_SESSION_ROTATE_HOURS: i64 = 24;
+
+/// 匿名聊天会话 Claims，由服务端签发，客户端无法伪造会话 id
+///
+/// 与用户 token 字段不同，二者不能互相冒用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSessionClaims {
+    /// 会话 id，作为匿名对话记录的 user_id
+    pub sid: String,
+    /// 会话所属工作区
+    pub workspace: String,
+    /// 访客记录的 generation，轮换后旧 token 失效（旧 token 中没有该字段）
+    #[serde(default)]
+    pub generation: i64,
+    /// 签发时间（旧 token 中没有该字段，视为需要轮换）
+    #[serde(default)]
+    pub iat: i64,
+    pub exp: i64,
+}
+
+impl ChatSessionClaims {
+    /// 是否已到轮换时间
+    pub fn needs_rotation(&self) -> bool {
+        Utc::now().timestamp() - self.iat >= CHAT_SESSION_ROTATE_HOURS * 3600
+    }
+}
+
+/// 两步验证票据有效期（分钟）
+const MFA_TICKET_MINUTES: i64 = 5;
+
+/// 已启用两步验证但登录请求未带验证码时的错误信息，登录页据此显示验证码输入框
+pub const MFA_CODE_REQUIRED: &str = "Two-factor code required";
+
+/// 单点登录通过后、提交第二因素前的票据，只能用于 `/api/auth/login/mfa`
+///
+/// 字段与用户 token 不同，不能当作 access token 使用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaTicketClaims {
+    pub mfa_user_id: i64,
+    pub workspace: String,
+    pub exp: i64,
+}
+
+/// 用户认证路由的 State
+pub type UserAppState = (Arc<UserStore>, Arc<WorkspaceStore>);
+
+impl Claims {
+    /// API key 是否拥有该 scope，用户 token 不受 scope 限制
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.api_key.as_ref().is_none_or(|grant| {
+            grant.scopes.contains(&ApiScope::Admin) || grant.scopes.contains(&scope)
+        })
+    }
+
+    /// 文档访问控制使用的身份
+    pub fn viewer(&self) -> DocumentViewer {
+        DocumentViewer::User {
+            user_id: self.user_id,
+            role: self.role.clone(),
+            groups: self.groups.clone(),
+        }
+    }
 }
 
 /// 登录请求
@@ -30,45 +141,109 @@ pub struct Claims {
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    /// 登录后进入的工作区，不指定时优先默认工作区
+    pub workspace: Option<String>,
+    /// 已启用两步验证时必填：验证器 App 中的 6 位验证码或一次性恢复码
+    pub otp: Option<String>,
+}
+
+/// 提交第二因素完成单点登录的请求
+#[derive(Debug, Deserialize)]
+pub struct MfaLoginRequest {
+    pub ticket: String,
+    pub otp: String,
+}
+
+/// 切换工作区请求
+#[derive(Debug, Deserialize)]
+pub struct SwitchWorkspaceRequest {
+    pub workspace: String,
+    /// 原工作区的刷新 token，切换后作废
+    pub refresh_token: Option<String>,
+}
+
+/// 刷新/退出登录请求
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
 }
 
 /// 登录响应
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
+    /// 短期 access token
     pub token: String,
+    /// access token 有效期（秒）
+    pub expires_in: i64,
+    /// 用于换取新 access token 的刷新 token，每次使用后轮换
+    pub refresh_token: String,
     pub username: String,
     pub role: UserRole,
+    pub workspace: String,
+    /// 为 true 时需先调用 `/api/auth/password` 修改密码
+    pub must_change_password: bool,
+    /// 为 true 时需先通过 `/api/auth/totp/setup` 绑定两步验证
+    pub mfa_enrollment_required: bool,
+}
+
+/// 修改密码请求
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
 }
 
 /// JWT工具
 pub struct JwtUtil {
     secret: String,
+    access_token_minutes: i64,
+    refresh_token_days: i64,
 }
 
 impl JwtUtil {
     pub fn new() -> Self {
-        let secret = std::env::var("JWT_SECRET")
-            .unwrap_or_else(|_| "your-secret-key-change-in-production".to_string());
-        Self { secret }
+        let auth = &get_config().auth;
+        Self {
+            secret: auth.jwt_secret.clone(),
+            access_token_minutes: auth.access_token_minutes,
+            refresh_token_days: auth.refresh_token_days,
+        }
+    }
+
+    /// access token 有效期（秒）
+    pub fn access_token_ttl(&self) -> i64 {
+        self.access_token_minutes * 60
+    }
+
+    /// 刷新 token 有效期
+    pub fn refresh_token_ttl(&self) -> Duration {
+        Duration::days(self.refresh_token_days)
     }
 
     /// 生成JWT token
     pub fn generate_token(
         &self,
-        user_id: i64,
-        username: &str,
-        role: UserRole,
+        user: &User,
+        workspace: &str,
+        mfa_enrollment_required: bool,
     ) -> anyhow::Result<String> {
         let expiration = Utc::now()
-            .checked_add_signed(Duration::days(7))
+            .checked_add_signed(Duration::minutes(self.access_token_minutes))
             .expect("Valid timestamp")
             .timestamp();
 
         let claims = Claims {
-            sub: username.to_string(),
-            user_id,
-            role,
+            sub: user.username.clone(),
+            name: user.display_name.clone(),
+            user_id: user.id,
+            role: user.role.clone(),
+            groups: user.groups.clone(),
+            workspace: workspace.to_string(),
+            generation: user.token_generation,
+            must_change_password: user.must_change_password,
+            mfa_enrollment_required,
             exp: expiration,
+            api_key: None,
         };
 
         let token = encode(
@@ -90,49 +265,544 @@ impl JwtUtil {
 
         Ok(token_data.claims)
     }
+
+    /// 签发匿名聊天会话 token
+    pub fn generate_chat_session(
+        &self,
+        sid: &str,
+        workspace: &str,
+        generation: i64,
+    ) -> anyhow::Result<String> {
+        let now = Utc::now();
+        let expiration = now
+            .checked_add_signed(Duration::days(CHAT_SESSION_DAYS))
+            .expect("Valid timestamp")
+            .timestamp();
+
+        let claims = ChatSessionClaims {
+            sid: sid.to_string(),
+            workspace: workspace.to_string(),
+            generation,
+            iat: now.timestamp(),
+            exp: expiration,
+        };
+
+        Ok(encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.secret.as_bytes()),
+        )?)
+    }
+
+    /// 验证匿名聊天会话 token
+    pub fn verify_chat_session(&self, token: &str) -> anyhow::Result<ChatSessionClaims> {
+        let token_data = decode::<ChatSessionClaims>(
+            token,
+            &DecodingKey::from_secret(self.secret.as_bytes()),
+            &Validation::default(),
+        )?;
+
+        Ok(token_data.claims)
+    }
+
+    /// 签发两步验证票据
+    pub fn generate_mfa_ticket(&self, user_id: i64, workspace: &str) -> anyhow::Result<String> {
+        let expiration = Utc::now()
+            .checked_add_signed(Duration::minutes(MFA_TICKET_MINUTES))
+            .expect("Valid timestamp")
+            .timestamp();
+
+        let claims = MfaTicketClaims {
+            mfa_user_id: user_id,
+            workspace: workspace.to_string(),
+            exp: expiration,
+        };
+
+        Ok(encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.secret.as_bytes()),
+        )?)
+    }
+
+    /// 验证两步验证票据
+    pub fn verify_mfa_ticket(&self, ticket: &str) -> anyhow::Result<MfaTicketClaims> {
+        let token_data = decode::<MfaTicketClaims>(
+            ticket,
+            &DecodingKey::from_secret(self.secret.as_bytes()),
+            &Validation::default(),
+        )?;
+
+        Ok(token_data.claims)
+    }
 }
 
 /// 登录处理器
+///
+/// 同一 IP 上连续失败达到上限后锁定该用户名一段时间，锁定期间不校验密码，
+/// 其他 IP 不受影响；
+/// 已启用两步验证的用户还需提交验证码，验证码错误单独计数，见 [`verify_second_factor`]
 async fn login_handler(
-    State(user_store): State<Arc<UserStore>>,
+    State((user_store, workspace_store)): State<UserAppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>, AppError> {
     debug!("Login attempt for user: {}", req.username);
+    let ip = Some(addr.ip().to_string());
 
-    let user = user_store
+    if let Some(until) = user_store
+        .login_locked_until(&req.username, &addr.ip().to_string())
+        .await?
+    {
+        user_store
+            .record_auth_event(NewAuthEvent {
+                kind: AuthEventKind::LoginBlocked,
+                username: &req.username,
+                user_id: None,
+                ip,
+                detail: None,
+            })
+            .await;
+        let seconds = (until - Utc::now()).num_seconds().max(1);
+        return Err(AppError::TooManyRequests(format!(
+            "Too many failed login attempts, try again in {} seconds",
+            seconds
+        )));
+    }
+
+    let Some(user) = user_store
         .verify_password(&req.username, &req.password)
         .await?
-        .ok_or_else(|| AppError::Unauthorized("Invalid username or password".to_string()))?;
+    else {
+        let locked_until = user_store
+            .record_login_failure(
+                &req.username,
+                &addr.ip().to_string(),
+                &LockoutPolicy::current(),
+            )
+            .await?;
+        let user_id = user_store
+            .get_user_by_username(&req.username)
+            .await?
+            .map(|user| user.id);
+        user_store
+            .record_auth_event(NewAuthEvent {
+                kind: AuthEventKind::LoginFailed,
+                username: &req.username,
+                user_id,
+                ip: ip.clone(),
+                detail: None,
+            })
+            .await;
+        if let Some(until) = locked_until {
+            user_store
+                .record_auth_event(NewAuthEvent {
+                    kind: AuthEventKind::AccountLocked,
+                    username: &req.username,
+                    user_id,
+                    ip,
+                    detail: Some(format!("locked until {}", until.to_rfc3339())),
+                })
+                .await;
+        }
+        return Err(AppError::Unauthorized(
+            "Invalid username or password".to_string(),
+        ));
+    };
+    if user_store.totp_status(user.id).await?.enabled {
+        let Some(otp) = req.otp.as_deref().filter(|otp| !otp.trim().is_empty()) else {
+            return Err(AppError::Unauthorized(MFA_CODE_REQUIRED.to_string()));
+        };
+        verify_second_factor(&user_store, &user, otp, ip.clone()).await?;
+    }
+    user_store.clear_login_failures(&req.username).await?;
+    if user.status != 1 {
+        warn!("Login rejected for disabled user: {}", user.username);
+        return Err(AppError::Forbidden("User is disabled".to_string()));
+    }
 
-    let jwt_util = JwtUtil::new();
-    let token = jwt_util.generate_token(user.id, &user.username, user.role.clone())?;
+    let workspace = select_workspace(&workspace_store, user.id, req.workspace.as_deref()).await?;
+    let (user_id, username) = (user.id, user.username.clone());
+    let response = issue_tokens(&user_store, user, &workspace, None).await?;
+    user_store
+        .record_auth_event(NewAuthEvent {
+            kind: AuthEventKind::LoginSucceeded,
+            username: &username,
+            user_id: Some(user_id),
+            ip,
+            detail: None,
+        })
+        .await;
 
-    debug!("Login successful for user: {}", user.username);
+    debug!(
+        "Login successful for user: {} (workspace: {})",
+        response.username, response.workspace
+    );
 
-    Ok(Json(LoginResponse {
+    Ok(Json(response))
+}
+
+/// 单点登录后提交第二因素，通过后签发 token
+async fn mfa_login_handler(
+    State((user_store, workspace_store)): State<UserAppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(req): Json<MfaLoginRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    let ip = Some(addr.ip().to_string());
+    let ticket = JwtUtil::new()
+        .verify_mfa_ticket(&req.ticket)
+        .map_err(|_| AppError::Unauthorized("Login request expired".to_string()))?;
+    let user = user_store
+        .get_user_by_id(ticket.mfa_user_id)
+        .await?
+        .filter(|user| user.status == 1)
+        .ok_or_else(|| AppError::Unauthorized("User is disabled".to_string()))?;
+
+    verify_second_factor(&user_store, &user, &req.otp, ip.clone()).await?;
+    if !workspace_store
+        .is_member(&ticket.workspace, user.id)
+        .await?
+    {
+        return Err(AppError::Unauthorized(
+            "Not a member of workspace".to_string(),
+        ));
+    }
+
+    user_store
+        .record_auth_event(NewAuthEvent {
+            kind: AuthEventKind::LoginSucceeded,
+            username: &user.username,
+            user_id: Some(user.id),
+            ip,
+            detail: Some("two-factor".to_string()),
+        })
+        .await;
+    let response = issue_tokens(&user_store, user, &ticket.workspace, None).await?;
+    Ok(Json(response))
+}
+
+/// 校验第二因素（TOTP 验证码或恢复码），防止暴力尝试验证码
+///
+/// 错误次数按用户单独计数，达到上限后只锁定验证码校验，不计入密码登录的失败次数，
+/// 不知道密码的人无法借此锁定账号
+pub(crate) async fn verify_second_factor(
+    user_store: &UserStore,
+    user: &User,
+    otp: &str,
+    ip: Option<String>,
+) -> Result<(), AppError> {
+    if user_store.mfa_locked_until(user.id).await?.is_some() {
+        return Err(AppError::TooManyRequests(
+            "Too many failed attempts, try again later".to_string(),
+        ));
+    }
+    let factor = user_store.verify_second_factor(user.id, otp).await?;
+    if factor.is_some() {
+        user_store.clear_mfa_failures(user.id).await?;
+    }
+    match factor {
+        Some(SecondFactor::Totp) => Ok(()),
+        Some(SecondFactor::RecoveryCode) => {
+            let remaining = user_store
+                .totp_status(user.id)
+                .await?
+                .recovery_codes_remaining;
+            user_store
+                .record_auth_event(NewAuthEvent {
+                    kind: AuthEventKind::RecoveryCodeUsed,
+                    username: &user.username,
+                    user_id: Some(user.id),
+                    ip,
+                    detail: Some(format!("{} remaining", remaining)),
+                })
+                .await;
+            Ok(())
+        }
+        None => {
+            let locked_until = user_store
+                .record_mfa_failure(user.id, &LockoutPolicy::current())
+                .await?;
+            user_store
+                .record_auth_event(NewAuthEvent {
+                    kind: AuthEventKind::MfaFailed,
+                    username: &user.username,
+                    user_id: Some(user.id),
+                    ip: ip.clone(),
+                    detail: None,
+                })
+                .await;
+            if let Some(until) = locked_until {
+                user_store
+                    .record_auth_event(NewAuthEvent {
+                        kind: AuthEventKind::AccountLocked,
+                        username: &user.username,
+                        user_id: Some(user.id),
+                        ip,
+                        detail: Some(format!("locked until {}", until.to_rfc3339())),
+                    })
+                    .await;
+            }
+            Err(AppError::Unauthorized(
+                "Invalid verification code".to_string(),
+            ))
+        }
+    }
+}
+
+/// 修改当前用户的密码，成功后其他设备上的登录全部失效，返回新的 token
+///
+/// 当前密码错误同样计入登录失败次数
+async fn change_password_handler(
+    State((user_store, _)): State<UserAppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    axum::extract::Extension(claims): axum::extract::Extension<Claims>,
+    Json(req): Json<ChangePasswordRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    let ip = Some(addr.ip().to_string());
+    if user_store
+        .login_locked_until(&claims.sub, &addr.ip().to_string())
+        .await?
+        .is_some()
+    {
+        return Err(AppError::TooManyRequests(
+            "Too many failed attempts, try again later".to_string(),
+        ));
+    }
+    if user_store
+        .verify_password(&claims.sub, &req.current_password)
+        .await?
+        .is_none()
+    {
+        user_store
+            .record_login_failure(
+                &claims.sub,
+                &addr.ip().to_string(),
+                &LockoutPolicy::current(),
+            )
+            .await?;
+        user_store
+            .record_auth_event(NewAuthEvent {
+                kind: AuthEventKind::LoginFailed,
+                username: &claims.sub,
+                user_id: Some(claims.user_id),
+                ip,
+                detail: Some("password change".to_string()),
+            })
+            .await;
+        return Err(AppError::Unauthorized(
+            "Current password is incorrect".to_string(),
+        ));
+    }
+
+    if req.new_password == req.current_password {
+        return Err(AppError::BadRequest(
+            "New password must differ from the current password".to_string(),
+        ));
+    }
+    PasswordPolicy::current()
+        .validate(&claims.sub, &req.new_password)
+        .map_err(AppError::BadRequest)?;
+
+    let user = user_store
+        .change_password(claims.user_id, &req.new_password)
+        .await?;
+    user_store.clear_login_failures(&claims.sub).await?;
+    user_store
+        .record_auth_event(NewAuthEvent {
+            kind: AuthEventKind::PasswordChanged,
+            username: &claims.sub,
+            user_id: Some(claims.user_id),
+            ip,
+            detail: None,
+        })
+        .await;
+    info!("User {} changed password", claims.sub);
+
+    let response = issue_tokens(&user_store, user, &claims.workspace, None).await?;
+    Ok(Json(response))
+}
+
+/// 选择登录后进入的工作区：指定时必须是成员，不指定时优先默认工作区
+pub(crate) async fn select_workspace(
+    workspace_store: &WorkspaceStore,
+    user_id: i64,
+    requested: Option<&str>,
+) -> Result<String, AppError> {
+    let workspaces = workspace_store.list_for_user(user_id).await?;
+    let workspace = match requested {
+        Some(id) => workspaces.iter().find(|w| w.id == id),
+        None => workspaces
+            .iter()
+            .find(|w| w.id == DEFAULT_WORKSPACE)
+            .or(workspaces.first()),
+    }
+    .ok_or_else(|| AppError::Forbidden("No accessible workspace".to_string()))?;
+
+    Ok(workspace.id.clone())
+}
+
+/// 签发 access token 和刷新 token
+///
+/// `refresh_token` 为轮换得到的新刷新 token，为 None 时开始新的登录会话；
+/// 要求管理员启用两步验证而拥有管理权限的用户尚未绑定时，签发的 token 只能访问认证接口
+pub(crate) async fn issue_tokens(
+    user_store: &UserStore,
+    user: User,
+    workspace: &str,
+    refresh_token: Option<String>,
+) -> Result<LoginResponse, AppError> {
+    let mfa_enrollment_required = user.role.is_privileged()
+        && admin_mfa_required()
+        && !user_store.totp_status(user.id).await?.enabled;
+    let jwt_util = JwtUtil::new();
+    let token = jwt_util.generate_token(&user, workspace, mfa_enrollment_required)?;
+    let refresh_token = match refresh_token {
+        Some(refresh_token) => refresh_token,
+        None => {
+            user_store
+                .create_refresh_token(user.id, workspace, None, jwt_util.refresh_token_ttl())
+                .await?
+        }
+    };
+
+    Ok(LoginResponse {
         token,
+        expires_in: jwt_util.access_token_ttl(),
+        refresh_token,
+        must_change_password: user.must_change_password,
+        mfa_enrollment_required,
         username: user.username,
         role: user.role,
-    }))
+        workspace: workspace.to_string(),
+    })
+}
+
+/// 用刷新 token 换取新的 access token，刷新 token 同时轮换
+async fn refresh_handler(
+    State((user_store, workspace_store)): State<UserAppState>,
+    Json(req): Json<RefreshTokenRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    let (record, refresh_token) = user_store
+        .rotate_refresh_token(&req.refresh_token, JwtUtil::new().refresh_token_ttl())
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+
+    let user = user_store
+        .get_user_by_id(record.user_id)
+        .await?
+        .filter(|user| user.status == 1)
+        .ok_or_else(|| AppError::Unauthorized("User is disabled".to_string()))?;
+    if !workspace_store
+        .is_member(&record.workspace_id, user.id)
+        .await?
+    {
+        return Err(AppError::Unauthorized(
+            "Not a member of workspace".to_string(),
+        ));
+    }
+
+    let response =
+        issue_tokens(&user_store, user, &record.workspace_id, Some(refresh_token)).await?;
+    Ok(Json(response))
+}
+
+/// 退出登录：作废该刷新 token 所在的会话，已签发的 access token 在短期内自然过期
+async fn logout_handler(
+    State((user_store, _)): State<UserAppState>,
+    Json(req): Json<RefreshTokenRequest>,
+) -> Result<StatusCode, AppError> {
+    if let Some(user_id) = user_store.revoke_refresh_token(&req.refresh_token).await? {
+        debug!("User {} logged out", user_id);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 退出所有设备：吊销当前用户全部 access token、刷新 token 和 API key
+async fn revoke_all_handler(
+    State((user_store, _)): State<UserAppState>,
+    axum::extract::Extension(claims): axum::extract::Extension<Claims>,
+) -> Result<StatusCode, AppError> {
+    user_store.revoke_user_sessions(claims.user_id).await?;
+    user_store.revoke_user_api_keys(claims.user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 切换工作区：校验成员关系后签发新 token
+async fn switch_workspace_handler(
+    State((user_store, workspace_store)): State<UserAppState>,
+    axum::extract::Extension(claims): axum::extract::Extension<Claims>,
+    Json(req): Json<SwitchWorkspaceRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    if !workspace_store
+        .is_member(&req.workspace, claims.user_id)
+        .await?
+    {
+        warn!(
+            "User {} is not a member of workspace {}",
+            claims.sub, req.workspace
+        );
+        return Err(AppError::Forbidden("Not a member of workspace".to_string()));
+    }
+
+    let user = user_store
+        .get_user_by_id(claims.user_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))?;
+    if let Some(refresh_token) = req.refresh_token.as_deref() {
+        user_store.revoke_refresh_token(refresh_token).await?;
+    }
+
+    let response = issue_tokens(&user_store, user, &req.workspace, None).await?;
+    Ok(Json(response))
+}
+
+/// 验证 token 的响应：token 内容及当前角色拥有的权限
+#[derive(Debug, Serialize)]
+pub struct VerifyResponse {
+    #[serde(flatten)]
+    pub claims: Claims,
+    pub permissions: Vec<Permission>,
 }
 
 /// 验证当前token（这个handler需要通过中间件提取Claims）
 async fn verify_handler(
     axum::extract::Extension(claims): axum::extract::Extension<Claims>,
-) -> Json<Claims> {
-    Json(claims)
+) -> Json<VerifyResponse> {
+    let permissions = claims.role.permissions().to_vec();
+    Json(VerifyResponse {
+        claims,
+        permissions,
+    })
 }
 
 /// 创建认证路由
-pub fn create_auth_router(user_store: Arc<UserStore>) -> Router {
+pub fn create_auth_router(state: UserAppState) -> Router {
     Router::new()
         .route("/api/auth/login", post(login_handler))
+        .route("/api/auth/login/mfa", post(mfa_login_handler))
+        .route("/api/auth/refresh", post(refresh_handler))
+        .route("/api/auth/logout", post(logout_handler))
+        .route(
+            "/api/auth/password",
+            post(change_password_handler)
+                .route_layer(axum::middleware::from_fn(require_user_auth_middleware)),
+        )
+        .route(
+            "/api/auth/revoke-all",
+            post(revoke_all_handler)
+                .route_layer(axum::middleware::from_fn(require_user_auth_middleware)),
+        )
         .route(
             "/api/auth/verify",
             post(verify_handler)
                 .route_layer(axum::middleware::from_fn(require_user_auth_middleware)),
         )
-        .with_state(user_store)
+        .route(
+            "/api/auth/workspace",
+            post(switch_workspace_handler)
+                .route_layer(axum::middleware::from_fn(require_user_auth_middleware)),
+        )
+        .with_state(state)
 }
 
 /// 需要用户登录的中间件
@@ -141,11 +811,7 @@ pub async fn require_user_auth_middleware(
     next: Next,
 ) -> Result<Response, AppError> {
     // 提取和验证token
-    let token = extract_token(&req)?;
-    let jwt_util = JwtUtil::new();
-    let claims = jwt_util
-        .verify_token(&token)
-        .map_err(|_| AppError::Unauthorized("Invalid token".to_string()))?;
+    let claims = authenticate(req.headers(), req.method(), req.uri().path()).await?;
 
     // 将Claims插入到request extensions（供handler使用）
     req.extensions_mut().insert(claims);
@@ -153,25 +819,27 @@ pub async fn require_user_auth_middleware(
     Ok(next.run(req).await)
 }
 
-/// JWT认证 + Admin角色检查
-pub async fn require_admin_auth_middleware(
+/// JWT认证 + 权限检查
+///
+/// 通过 `middleware::from_fn_with_state(Permission::..., require_permission)` 挂载
+pub async fn require_permission(
+    State(permission): State<Permission>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, AppError> {
     // 1. 提取和验证token
-    let token = extract_token(&req)?;
-    let jwt_util = JwtUtil::new();
-    let claims = jwt_util
-        .verify_token(&token)
-        .map_err(|_| AppError::Unauthorized("Invalid token".to_string()))?;
+    let claims = authenticate(req.headers(), req.method(), req.uri().path()).await?;
 
-    // 2. 检查Admin角色
-    if claims.role != UserRole::Admin {
+    // 2. 检查角色是否拥有该权限
+    if !claims.role.has(permission) {
         warn!(
-            "Access denied for user: {} (role: {:?})",
-            claims.sub, claims.role
+            "Access denied for user: {} (role: {}, requires {})",
+            claims.sub, claims.role, permission
         );
-        return Err(AppError::Forbidden("Admin role required".to_string()));
+        return Err(AppError::Forbidden(format!(
+            "Permission {} required",
+            permission
+        )));
     }
 
     // 3. 将Claims插入到request extensions（供handler使用）
@@ -180,28 +848,233 @@ pub async fn require_admin_auth_middleware(
     Ok(next.run(req).await)
 }
 
-/// 从请求中提取token
-fn extract_token(req: &Request) -> Result<String, AppError> {
-    let auth_header = req
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
+/// 校验请求凭证（用户 token 或 API key），API key 还需拥有接口要求的 scope
+///
+/// 只借用请求的各部分：`Request` 本身不是 `Sync`，不能跨 await 持有其引用
+async fn authenticate(
+    headers: &header::HeaderMap,
+    method: &Method,
+    path: &str,
+) -> Result<Claims, AppError> {
+    let claims = verified_claims(headers)
+        .await?
         .ok_or_else(|| AppError::Unauthorized("Missing authorization header".to_string()))?;
 
-    if let Some(token) = auth_header.strip_prefix("Bearer ") {
-        Ok(token.to_string())
+    if !path.starts_with("/api/auth/") {
+        ensure_unrestricted(&claims)?;
+    }
+
+    if let Some(grant) = &claims.api_key {
+        let scope = required_scope(method, path)
+            .ok_or_else(|| AppError::Forbidden("Not available to API keys".to_string()))?;
+        if !claims.allows(scope) {
+            warn!("API key {} lacks scope {}", grant.id, scope);
+            return Err(AppError::Forbidden(format!(
+                "API key lacks scope {}",
+                scope
+            )));
+        }
+    }
+    Ok(claims)
+}
+
+/// API key 访问接口所需的 scope；签发 token 的认证接口不对 API key 开放
+fn required_scope(method: &Method, path: &str) -> Option<ApiScope> {
+    if path.starts_with("/api/auth/") {
+        None
+    } else if path.starts_with("/api/documents") {
+        Some(if method == Method::GET {
+            ApiScope::DocumentsRead
+        } else {
+            ApiScope::DocumentsWrite
+        })
+    } else {
+        Some(ApiScope::Admin)
+    }
+}
+
+/// 拒绝已禁用用户的 token，以及 token 代数已被提升（吊销）的 token
+async fn ensure_active_user(claims: &Claims) -> Result<(), AppError> {
+    let Some(user_store) = get_user_store() else {
+        return Ok(());
+    };
+    let user = user_store
+        .get_user_by_id(claims.user_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))?;
+
+    if user.status != 1 {
+        warn!("Rejected token of disabled user: {}", user.username);
+        return Err(AppError::Unauthorized("User is disabled".to_string()));
+    }
+    if user.token_generation != claims.generation {
+        debug!("Rejected revoked token of user: {}", user.username);
+        return Err(AppError::Unauthorized("Token has been revoked".to_string()));
+    }
+    Ok(())
+}
+
+/// 仅允许当前工作区为默认工作区的请求（需在认证中间件之后）
+///
+/// 用于备份、一致性检查等只针对默认工作区存储的运维接口
+pub async fn require_default_workspace_middleware(
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    match req.extensions().get::<Claims>() {
+        Some(claims) if claims.workspace == DEFAULT_WORKSPACE => Ok(next.run(req).await),
+        Some(_) => Err(AppError::Forbidden(
+            "Only available in the default workspace".to_string(),
+        )),
+        None => Err(AppError::Unauthorized("Missing credentials".to_string())),
+    }
+}
+
+/// 需要先修改密码或启用两步验证的 token 只能访问 `/api/auth/*`
+fn ensure_unrestricted(claims: &Claims) -> Result<(), AppError> {
+    if claims.must_change_password {
+        return Err(AppError::Forbidden("Password change required".to_string()));
+    }
+    if claims.mfa_enrollment_required {
+        return Err(AppError::Forbidden(
+            "Two-factor enrollment required".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 可选认证：没有 Authorization 头时返回 None，token 或 API key 无效时返回错误
+///
+/// 用于 `/api/auth/*` 以外的接口（如聊天），需要先修改密码或启用两步验证的 token 同样被拒绝
+pub async fn optional_claims(headers: &header::HeaderMap) -> Result<Option<Claims>, AppError> {
+    let claims = verified_claims(headers).await?;
+    if let Some(claims) = &claims {
+        ensure_unrestricted(claims)?;
+    }
+    Ok(claims)
+}
+
+/// 校验 Authorization 头中的凭证，不检查 token 是否受限
+///
+/// 用户 token 会校验用户状态和 token 代数，API key 会校验创建者状态和每分钟请求上限
+async fn verified_claims(headers: &header::HeaderMap) -> Result<Option<Claims>, AppError> {
+    let Some(token) = bearer_token(headers)? else {
+        return Ok(None);
+    };
+
+    if token.starts_with(API_KEY_PREFIX) {
+        return api_key_claims(token).await.map(Some);
+    }
+
+    let claims = JwtUtil::new()
+        .verify_token(token)
+        .map_err(|_| AppError::Unauthorized("Invalid token".to_string()))?;
+    ensure_active_user(&claims).await?;
+    Ok(Some(claims))
+}
+
+/// 从请求头中提取 Bearer token
+pub fn bearer_token(headers: &header::HeaderMap) -> Result<Option<&str>, AppError> {
+    let Some(auth_header) = headers.get(header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+    auth_header
+        .to_str()
+        .ok()
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(Some)
+        .ok_or_else(|| AppError::Unauthorized("Invalid authorization header format".to_string()))
+}
+
+/// API key 以创建者的身份访问所属工作区，权限不超过创建者的角色
+async fn api_key_claims(key: &str) -> Result<Claims, AppError> {
+    let user_store = get_user_store()
+        .ok_or_else(|| AppError::Unauthorized("API keys are not available".to_string()))?;
+    let api_key = user_store
+        .verify_api_key(key)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid API key".to_string()))?;
+    let user = user_store
+        .get_user_by_id(api_key.user_id)
+        .await?
+        .filter(|user| user.status == 1)
+        .ok_or_else(|| AppError::Unauthorized("API key owner is disabled".to_string()))?;
+    // 要求管理员启用两步验证时，尚未绑定的创建者不能通过 admin key 绕过
+    if api_key.allows(ApiScope::Admin)
+        && api_key_role(user.role.clone(), &api_key).is_privileged()
+        && admin_mfa_required()
+        && !user_store.totp_status(user.id).await?.enabled
+    {
+        return Err(AppError::Forbidden(
+            "Two-factor enrollment required for admin API keys".to_string(),
+        ));
+    }
+    // 创建者被移出工作区后 key 随之失效
+    let workspace_store = get_workspace_store()
+        .ok_or_else(|| AppError::Unauthorized("API keys are not available".to_string()))?;
+    if !workspace_store
+        .is_member(&api_key.workspace_id, user.id)
+        .await?
+    {
+        return Err(AppError::Unauthorized(
+            "API key owner is no longer a member of the workspace".to_string(),
+        ));
+    }
+
+    if let Some(limit) = api_key.rate_limit_per_minute
+        && !check_api_key_rate(&api_key.id, limit)
+    {
+        return Err(AppError::TooManyRequests(
+            "API key rate limit exceeded".to_string(),
+        ));
+    }
+
+    Ok(claims_for_api_key(api_key, user))
+}
+
+/// API key 的角色以创建者的角色为上限，按 key 的 scope 收窄，不会超出创建者本身的权限
+fn api_key_role(owner: UserRole, api_key: &ApiKey) -> UserRole {
+    if api_key.allows(ApiScope::Admin) {
+        owner
+    } else if api_key.allows(ApiScope::DocumentsWrite) && owner.has(Permission::DocumentWrite) {
+        UserRole::User
     } else {
-        Err(AppError::Unauthorized(
-            "Invalid authorization header format".to_string(),
-        ))
+        UserRole::Viewer
+    }
+}
+
+fn claims_for_api_key(api_key: ApiKey, user: User) -> Claims {
+    let role = api_key_role(user.role, &api_key);
+    Claims {
+        sub: format!("apikey:{}", api_key.name),
+        name: None,
+        user_id: user.id,
+        role,
+        groups: user.groups,
+        workspace: api_key.workspace_id,
+        generation: user.token_generation,
+        must_change_password: false,
+        mfa_enrollment_required: false,
+        exp: api_key
+            .expires_at
+            .map(|t| t.timestamp())
+            .unwrap_or(i64::MAX),
+        api_key: Some(ApiKeyGrant {
+            id: api_key.id,
+            scopes: api_key.scopes,
+        }),
     }
 }
 
 /// 应用错误类型
 #[derive(Debug)]
 pub enum AppError {
+    BadRequest(String),
     Unauthorized(String),
     Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    TooManyRequests(String),
     Internal(anyhow::Error),
 }
 
@@ -214,8 +1087,12 @@ impl From<anyhow::Error> for AppError {
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, message) = match self {
+            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
             AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg),
             AppError::Internal(err) => {
                 warn!("Internal error: {:?}", err);
                 (
@@ -236,3 +1113,121 @@ impl IntoResponse for AppError {
             .unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_session_and_user_tokens_are_not_interchangeable() {
+        let jwt = JwtUtil::new();
+        let user = User {
+            id: 7,
+            username: "alice".to_string(),
+            display_name: None,
+            password_hash: String::new(),
+            role: UserRole::User,
+            status: 1,
+            groups: vec![],
+            token_generation: 3,
+            must_change_password: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        let session = jwt.generate_chat_session("visitor", "hr", 0).unwrap();
+        let claims = jwt.verify_chat_session(&session).unwrap();
+        assert_eq!(claims.sid, "visitor");
+        assert_eq!(claims.workspace, "hr");
+        assert!(!claims.needs_rotation());
+        assert!(jwt.verify_token(&session).is_err());
+
+        let token = jwt.generate_token(&user, DEFAULT_WORKSPACE, false).unwrap();
+        assert!(jwt.verify_chat_session(&token).is_err());
+        let claims = jwt.verify_token(&token).unwrap();
+        assert_eq!(claims.user_id, 7);
+        assert_eq!(claims.generation, 3);
+        assert!(!claims.mfa_enrollment_required);
+    }
+
+    #[test]
+    fn test_mfa_ticket_is_not_an_access_token() {
+        let jwt = JwtUtil::new();
+        let ticket = jwt.generate_mfa_ticket(7, "hr").unwrap();
+        assert!(jwt.verify_token(&ticket).is_err());
+        assert!(jwt.verify_chat_session(&ticket).is_err());
+
+        let claims = jwt.verify_mfa_ticket(&ticket).unwrap();
+        assert_eq!(claims.mfa_user_id, 7);
+        assert_eq!(claims.workspace, "hr");
+    }
+
+    #[test]
+    fn test_api_key_scopes() {
+        assert_eq!(
+            required_scope(&Method::GET, "/api/documents/search"),
+            Some(ApiScope::DocumentsRead)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/api/documents/abc"),
+            Some(ApiScope::DocumentsWrite)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/api/admin/knowledge-bases"),
+            Some(ApiScope::Admin)
+        );
+        assert_eq!(required_scope(&Method::POST, "/api/auth/workspace"), None);
+
+        let mut claims = Claims {
+            sub: "apikey:indexer".to_string(),
+            name: None,
+            user_id: 7,
+            role: UserRole::User,
+            groups: vec![],
+            workspace: DEFAULT_WORKSPACE.to_string(),
+            generation: 0,
+            must_change_password: false,
+            mfa_enrollment_required: false,
+            exp: 0,
+            api_key: Some(ApiKeyGrant {
+                id: "key".to_string(),
+                scopes: vec![ApiScope::DocumentsRead],
+            }),
+        };
+        assert!(claims.allows(ApiScope::DocumentsRead));
+        assert!(!claims.allows(ApiScope::DocumentsWrite));
+        assert!(!claims.allows(ApiScope::Chat));
+
+        claims.api_key = None;
+        assert!(claims.allows(ApiScope::Admin));
+    }
+
+    #[test]
+    fn test_api_key_role_never_exceeds_owner() {
+        let key = |scopes: Vec<ApiScope>| ApiKey {
+            id: "key".to_string(),
+            name: "indexer".to_string(),
+            key_prefix: "rk_abcd".to_string(),
+            user_id: 7,
+            workspace_id: DEFAULT_WORKSPACE.to_string(),
+            scopes,
+            rate_limit_per_minute: None,
+            expires_at: None,
+            last_used_at: None,
+            created_at: Utc::now(),
+        };
+        let admin = key(vec![ApiScope::Admin]);
+        let writer = key(vec![ApiScope::DocumentsWrite]);
+        let reader = key(vec![ApiScope::Chat, ApiScope::DocumentsRead]);
+
+        assert_eq!(api_key_role(UserRole::Admin, &admin), UserRole::Admin);
+        assert_eq!(api_key_role(UserRole::Viewer, &admin), UserRole::Viewer);
+        assert_eq!(api_key_role(UserRole::Admin, &writer), UserRole::User);
+        assert_eq!(api_key_role(UserRole::Viewer, &writer), UserRole::Viewer);
+        assert_eq!(
+            api_key_role(UserRole::SupportAgent, &writer),
+            UserRole::Viewer
+        );
+        assert_eq!(api_key_role(UserRole::Editor, &reader), UserRole::Viewer);
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    Router,
+    extract::{Json, Path, Query},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::utils::{BackupInfo, BackupRetention, FileBackup, get_file_backup};
+use crate::web::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct BackupListQuery {
+    pub doc_id: Option<String>,
+}
+
+/// 单个文档的备份版本
+#[derive(Debug, Serialize)]
+pub struct DocumentBackups {
+    pub doc_id: String,
+    pub total_size: u64,
+    pub versions: Vec<BackupInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupListResponse {
+    pub documents: Vec<DocumentBackups>,
+    pub total_files: usize,
+    pub total_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupStatsResponse {
+    pub total_documents: usize,
+    pub total_files: usize,
+    pub total_size: u64,
+    pub retention: BackupRetention,
+}
+
+/// 清理请求，未指定的字段使用配置的保留策略
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CleanupBackupsRequest {
+    pub keep_versions: Option<usize>,
+    pub max_age_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CleanupBackupsResponse {
+    pub deleted_count: usize,
+    pub total_size: u64,
+    pub retention: BackupRetention,
+}
+
+/// 创建备份管理路由（仅管理员可访问）
+pub fn create_backup_router() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/backups", get(list_backups))
+        .route("/api/admin/backups/stats", get(get_backup_stats))
+        .route("/api/admin/backups/cleanup", post(cleanup_backups))
+        .route("/api/admin/backups/{doc_id}", get(get_document_backups))
+}
+
+fn file_backup() -> Result<&'static FileBackup, StatusCode> {
+    get_file_backup().ok_or_else(|| {
+        error!("File backup is not initialized");
+        StatusCode::SERVICE_UNAVAILABLE
+    })
+}
+
+async fn list_backups(
+    Query(query): Query<BackupListQuery>,
+) -> Result<ResponseJson<BackupListResponse>, StatusCode> {
+    let backup = file_backup()?;
+    let backups = match query.doc_id.as_deref() {
+        Some(doc_id) => backup.list_backups(doc_id).await,
+        None => backup.list_all_backups().await,
+    }
+    .map_err(|e| {
+        error!("Failed to list backups: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let total_files = backups.len();
+    let total_size = backups.iter().map(|b| b.size).sum();
+
+    // 按文档分组，组内保持最新的在前
+    let mut grouped: BTreeMap<String, Vec<BackupInfo>> = BTreeMap::new();
+    for backup in backups {
+        grouped
+            .entry(backup.doc_id.clone())
+            .or_default()
+            .push(backup);
+    }
+
+    let mut documents: Vec<DocumentBackups> = grouped
+        .into_iter()
+        .map(|(doc_id, versions)| DocumentBackups {
+            doc_id,
+            total_size: versions.iter().map(|b| b.size).sum(),
+            versions,
+        })
+        .collect();
+    // 最近有备份的文档排在前面
+    documents.sort_by(|a, b| b.versions[0].created_at.cmp(&a.versions[0].created_at));
+
+    Ok(ResponseJson(BackupListResponse {
+        documents,
+        total_files,
+        total_size,
+    }))
+}
+
+async fn get_document_backups(
+    Path(doc_id): Path<String>,
+) -> Result<ResponseJson<DocumentBackups>, StatusCode> {
+    let backup = file_backup()?;
+    let versions = backup.list_backups(&doc_id).await.map_err(|e| {
+        error!("Failed to list backups for {}: {}", doc_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if versions.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(ResponseJson(DocumentBackups {
+        doc_id,
+        total_size: versions.iter().map(|b| b.size).sum(),
+        versions,
+    }))
+}
+
+async fn get_backup_stats() -> Result<ResponseJson<BackupStatsResponse>, StatusCode> {
+    let backup = file_backup()?;
+    let backups = backup.list_all_backups().await.map_err(|e| {
+        error!("Failed to list backups: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    // 与 total_files 一样只统计能识别的备份文件
+    let total_size = backups.iter().map(|b| b.size).sum();
+
+    let mut doc_ids: Vec<&str> = backups.iter().map(|b| b.doc_id.as_str()).collect();
+    doc_ids.sort_unstable();
+    doc_ids.dedup();
+
+    Ok(ResponseJson(BackupStatsResponse {
+        total_documents: doc_ids.len(),
+        total_files: backups.len(),
+        total_size,
+        retention: backup.retention().clone(),
+    }))
+}
+
+async fn cleanup_backups(
+    Json(req): Json<CleanupBackupsRequest>,
+) -> Result<ResponseJson<CleanupBackupsResponse>, StatusCode> {
+    let backup = file_backup()?;
+
+    if req.keep_versions == Some(0) || req.max_age_days.is_some_and(|days| days < 1) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let configured = backup.retention();
+    let retention = BackupRetention {
+        keep_versions: req.keep_versions.unwrap_or(configured.keep_versions),
+        max_age_days: req.max_age_days.or(configured.max_age_days),
+    };
+
+    let deleted_count = backup.apply_retention(&retention).await.map_err(|e| {
+        error!("Failed to cleanup backups: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    info!("🧹 Manual backup cleanup removed {} file(s)", deleted_count);
+
+    let total_size = backup.get_total_size().await.map_err(|e| {
+        error!("Failed to get backup size: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(ResponseJson(CleanupBackupsResponse {
+        deleted_count,
+        total_size,
+        retention,
+    }))
+}
@@ -1,8 +1,12 @@
-use std::sync::Arc;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
 
 use axum::{
     Router,
-    extract::{Json, Path, State},
+    extract::{ConnectInfo, Json, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
     response::sse::{Event, Sse},
     routing::{get, post},
 };
@@ -14,12 +18,23 @@ use rig::{
 };
 use serde::{Deserialize, Serialize};
 use tokio_stream::wrappers::ReceiverStream;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::{
-    agent::RigAgent,
-    db::{ConversationStore, CreateMessageRequest, DocumentStore, MessageRole},
-    web::chat_store,
+    agent::{
+        AgentOverrides, DEFAULT_KNOWLEDGE_BASE, GenerationParams, PreambleVariables, RigAgent,
+        get_knowledge_bases, language_tag,
+    },
+    config::get_config,
+    db::{
+        API_KEY_PREFIX, ApiScope, Conversation, ConversationStore, CreateMessageRequest,
+        DEFAULT_WORKSPACE, DocumentStore, DocumentViewer, ExperimentUnit, MessageRole,
+        MetadataFilter, Permission, Visitor, get_experiment_store, get_preamble_store,
+    },
+    web::{
+        AppError, CHAT_SESSION_HEADER, ChatSessionClaims, Claims, ErrorResponse, JwtUtil,
+        bearer_token, chat_store, optional_claims, take_chat_session,
+    },
 };
 
 pub type ChatAppState = (Arc<RigAgent>, Arc<DocumentStore>, Arc<ConversationStore>);
@@ -31,13 +46,110 @@ const MAX_HISTORY_MESSAGES: usize = 10; // 历史记录最大条数
 #[derive(Debug, Deserialize)]
 pub struct ChatRequest {
     message: String,
-    user_id: Option<String>,
+    /// 服务端签发的匿名会话 token，也可通过 `X-Chat-Session` 头传递
+    ///
+    /// 兼容内置聊天组件：它把 `user_id` 事件的内容原样通过 `user_id` 字段回传
+    #[serde(alias = "user_id")]
+    session: Option<String>,
+    /// 按文档元数据限制检索范围
+    #[serde(default)]
+    filters: MetadataFilter,
+    /// 知识库 id，未指定时使用默认知识库
+    knowledge_base: Option<String>,
+    /// 新建匿名会话时使用的工作区，已有会话和登录用户以 token 中的工作区为准
+    workspace: Option<String>,
+    /// 用户语言（如 `zh-CN`），用于 preamble 的 `user_language` 变量
+    language: Option<String>,
+    /// 本次请求的温度、max_tokens、模型、top_k 和回复语言，范围由管理员配置
+    #[serde(flatten)]
+    generation: GenerationParams,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatHistoryQuery {
+    knowledge_base: Option<String>,
+}
+
+/// 合并匿名会话请求，会话 token 也可通过 `X-Chat-Session` 头传递
+#[derive(Debug, Deserialize)]
+pub struct MergeSessionRequest {
+    session: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MergeSessionResponse {
+    user_id: String,
+    merged_conversations: u64,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ChatResponse {
     response: String,
     user_id: String,
+    /// 新签发的匿名会话 token，客户端需保存并在后续请求中带上
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>,
+    /// 保存的回复消息 id，可用于提交评分
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_id: Option<String>,
+}
+
+/// 聊天和对话接口的调用者身份
+#[derive(Debug, Clone)]
+pub struct ChatIdentity {
+    /// 对话记录中的 user_id：登录用户为 `user:{id}`，API key 为 `apikey:{id}`，匿名访客为会话 id
+    pub user_id: String,
+    pub workspace: String,
+    /// 登录用户的 Claims，匿名访客为 None
+    pub claims: Option<Claims>,
+    /// 本次请求新签发的匿名会话 token
+    pub new_session: Option<String>,
+}
+
+impl ChatIdentity {
+    fn user(claims: Claims) -> Self {
+        // API key 的对话与创建者本人的对话分开记录
+        let user_id = match &claims.api_key {
+            Some(grant) => format!("apikey:{}", grant.id),
+            None => format!("user:{}", claims.user_id),
+        };
+        Self {
+            user_id,
+            workspace: claims.workspace.clone(),
+            claims: Some(claims),
+            new_session: None,
+        }
+    }
+
+    fn visitor(session: &ChatSessionClaims) -> Self {
+        Self {
+            user_id: session.sid.clone(),
+            workspace: session.workspace.clone(),
+            claims: None,
+            new_session: None,
+        }
+    }
+
+    /// 文档检索使用的身份
+    pub fn viewer(&self) -> DocumentViewer {
+        self.claims
+            .as_ref()
+            .map(Claims::viewer)
+            .unwrap_or(DocumentViewer::Anonymous)
+    }
+
+    /// 是否拥有 conversation.read_all 权限
+    pub fn can_read_all_conversations(&self) -> bool {
+        self.claims
+            .as_ref()
+            .is_some_and(|claims| claims.role.has(Permission::ConversationReadAll))
+    }
+
+    /// 对话的所有者，或同一工作区内拥有 conversation.read_all 权限的用户
+    pub fn can_access(&self, conversation: &Conversation) -> bool {
+        conversation.user_id == self.user_id
+            || (self.can_read_all_conversations() && conversation.workspace_id == self.workspace)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -50,11 +162,445 @@ pub fn create_chat_router() -> Router<ChatAppState> {
     Router::new()
         .route("/api/chat", post(handle_chat))
         .route("/api/chat/stream", post(handle_stream_chat))
-        .route("/api/history/{user_id}", get(get_chat_history))
+        .route("/api/history", get(get_chat_history))
+        .route("/api/history/{session}", get(get_session_chat_history))
+        .route("/api/chat/session/merge", post(merge_chat_session))
+}
+
+/// 返回非默认知识库的 id
+fn knowledge_base_id(knowledge_base: Option<&str>) -> Option<&str> {
+    knowledge_base
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && *id != DEFAULT_KNOWLEDGE_BASE)
+}
+
+/// 请求的知识库不存在或不属于当前工作区
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown knowledge base: {0}")]
+struct UnknownKnowledgeBase(String);
+
+/// 根据工作区和知识库选择 agent，默认工作区的默认知识库直接使用默认 agent；
+/// 知识库不存在时返回 `UnknownKnowledgeBase`
+async fn resolve_agent(
+    default: &Arc<RigAgent>,
+    workspace: &str,
+    knowledge_base: Option<&str>,
+) -> anyhow::Result<Arc<RigAgent>> {
+    let id = knowledge_base_id(knowledge_base);
+    if workspace == DEFAULT_WORKSPACE && id.is_none() {
+        return Ok(default.clone());
+    }
+
+    let registry =
+        get_knowledge_bases().ok_or_else(|| anyhow::anyhow!("Knowledge bases are not enabled"))?;
+    registry
+        .get(workspace, id)
+        .await?
+        .map(|handle| handle.agent.clone())
+        .ok_or_else(|| {
+            UnknownKnowledgeBase(format!(
+                "{}/{}",
+                workspace,
+                id.unwrap_or(DEFAULT_KNOWLEDGE_BASE)
+            ))
+            .into()
+        })
+}
+
+/// 本次请求的 preamble 变量
+///
+/// 用户名取登录用户的显示名称，未设置时为用户名；默认知识库使用工作区名称
+async fn preamble_variables(
+    agent: &RigAgent,
+    overrides: &AgentOverrides,
+    claims: Option<&Claims>,
+    user_language: String,
+    workspace: &str,
+    knowledge_base: Option<&str>,
+) -> PreambleVariables {
+    let user_name = claims
+        .filter(|claims| claims.api_key.is_none())
+        .map(|claims| claims.name.clone().unwrap_or_else(|| claims.sub.clone()));
+
+    // 工作区和知识库名称需要查询数据库，只在 preamble 用到时查询
+    let registry = get_knowledge_bases();
+    let knowledge_base_id = knowledge_base_id(knowledge_base);
+    let uses_knowledge_base = agent.preamble_uses(overrides, "knowledge_base");
+    let uses_workspace = agent.preamble_uses(overrides, "workspace")
+        || (uses_knowledge_base && knowledge_base_id.is_none());
+    let workspace = match registry {
+        Some(registry) if uses_workspace => match registry.workspaces().get(workspace).await {
+            Ok(Some(workspace)) => Some(workspace.name),
+            _ => Some(workspace.to_string()),
+        },
+        _ if uses_workspace => Some(workspace.to_string()),
+        _ => None,
+    };
+    let knowledge_base = match (registry, knowledge_base_id) {
+        _ if !uses_knowledge_base => None,
+        (Some(registry), Some(id)) => match registry.store().get(id).await {
+            Ok(Some(knowledge_base)) => Some(knowledge_base.name),
+            _ => Some(id.to_string()),
+        },
+        _ => workspace.clone(),
+    };
+
+    PreambleVariables {
+        user_name,
+        user_language: Some(user_language),
+        knowledge_base,
+        workspace,
+    }
+}
+
+/// preamble 的 `user_language`：请求中的 `language`、`Accept-Language`，最后按消息内容判断
+fn user_language(headers: &HeaderMap, language: Option<&str>, message: &str) -> String {
+    language
+        .and_then(language_tag)
+        .or_else(|| accept_language(headers))
+        .unwrap_or_else(|| if is_chinese(message) { "zh" } else { "en" }.to_string())
+}
+
+/// `Accept-Language` 中的首选语言，如 `zh-CN,zh;q=0.9` 取 `zh-CN`，不合法时忽略
+fn accept_language(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .and_then(|lang| language_tag(lang.split(';').next().unwrap_or_default()))
+}
+
+/// 请求中的 `language` 必须是 BCP-47 语言标签（如 `zh-CN`），否则返回 400
+fn validate_language(language: Option<&str>) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    match language {
+        Some(language) if !language.trim().is_empty() && language_tag(language).is_none() => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid language, expected a BCP-47 tag such as zh-CN".to_string(),
+            }),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 按管理员配置的范围校验请求的生成参数，超出范围时返回 400 和错误信息
+fn validate_generation(
+    generation: &GenerationParams,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    generation.validate(&get_config().chat).map_err(|error| {
+        warn!("Rejected chat generation params: {}", error);
+        (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }))
+    })
+}
+
+/// 实验为本次请求分配的变体
+struct ExperimentAssignment {
+    /// 写入消息元数据的 `experiment`：`{"id": 实验 id, "variant": 变体 id}`
+    metadata: serde_json::Value,
+    overrides: AgentOverrides,
+    /// 按对话分组且还没有活跃对话时预留的对话 id，保存消息时用它创建对话
+    new_conversation_id: Option<String>,
+}
+
+/// 工作区正在运行的实验为本次请求分配变体，只作用于默认知识库
+///
+/// 按对话分组时使用当前的活跃对话，没有时预留新对话的 id（不在此处创建对话）；
+/// 查询失败时不参与实验。
+/// 请求自带生成参数时同样不参与，避免这些参数混入实验结果
+async fn experiment_assignment(
+    conversation_store: &ConversationStore,
+    user_id: &str,
+    workspace: &str,
+    knowledge_base: Option<&str>,
+    generation: &GenerationParams,
+) -> Option<ExperimentAssignment> {
+    if knowledge_base_id(knowledge_base).is_some() || !generation.is_empty() {
+        return None;
+    }
+    let experiment = match get_experiment_store()?.running(workspace).await {
+        Ok(experiment) => experiment?,
+        Err(e) => {
+            error!("Failed to load running experiment: {}", e);
+            return None;
+        }
+    };
+    let mut new_conversation_id = None;
+    let key = match experiment.unit {
+        ExperimentUnit::User => user_id.to_string(),
+        ExperimentUnit::Conversation => match conversation_store
+            .get_active_conversation(user_id, workspace)
+            .await
+        {
+            Ok(Some(conversation)) => conversation.id,
+            Ok(None) => new_conversation_id.insert(nanoid::nanoid!()).clone(),
+            Err(e) => {
+                error!("Failed to get conversation for experiment: {}", e);
+                return None;
+            }
+        },
+    };
+    let variant = experiment.assign(&key)?;
+
+    let preamble = match (variant.preamble_version, get_preamble_store()) {
+        (Some(version), Some(store)) => match store.get_version(workspace, version).await {
+            Ok(Some(preamble)) => Some(preamble.content),
+            Ok(None) => {
+                warn!(
+                    "Preamble version {} of experiment {} not found, using live preamble",
+                    version, experiment.id
+                );
+                None
+            }
+            Err(e) => {
+                error!("Failed to load preamble version {}: {}", version, e);
+                None
+            }
+        },
+        _ => None,
+    };
+
+    Some(ExperimentAssignment {
+        metadata: serde_json::json!({ "id": experiment.id, "variant": variant.id }),
+        overrides: AgentOverrides {
+            preamble,
+            model: variant.model.clone(),
+            temperature: variant.temperature,
+            top_k: variant.top_k,
+            ..Default::default()
+        },
+        new_conversation_id,
+    })
+}
+
+/// 登录用户或 API key 的身份
+///
+/// 无效的用户 token 会被忽略（按匿名访客处理）；API key 无效、超出频率限制
+/// 或没有 chat scope 时返回错误
+async fn user_identity(headers: &HeaderMap) -> anyhow::Result<Option<ChatIdentity>> {
+    let uses_api_key =
+        matches!(bearer_token(headers), Ok(Some(token)) if token.starts_with(API_KEY_PREFIX));
+    match optional_claims(headers).await {
+        Ok(Some(claims)) if claims.allows(ApiScope::Chat) => Ok(Some(ChatIdentity::user(claims))),
+        Ok(Some(_)) => anyhow::bail!("API key lacks scope chat"),
+        Ok(None) => Ok(None),
+        Err(AppError::TooManyRequests(msg)) => anyhow::bail!(msg),
+        Err(_) if uses_api_key => anyhow::bail!("Invalid API key"),
+        Err(_) => {
+            warn!("Ignoring invalid authorization token, trying anonymous session");
+            Ok(None)
+        }
+    }
+}
+
+/// 匿名会话 token：优先 `X-Chat-Session` 头，其次请求参数
+fn session_token<'a>(headers: &'a HeaderMap, session: Option<&'a str>) -> Option<&'a str> {
+    headers
+        .get(CHAT_SESSION_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .or(session)
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// 同一 IP 新建匿名会话超过每小时上限
+#[derive(Debug, thiserror::Error)]
+#[error("Too many new chat sessions from this address, try again later")]
+struct SessionLimitExceeded;
+
+/// 校验匿名会话 token：签名有效，且访客记录未合并、generation 与 token 一致
+async fn verify_visitor_session(
+    conversation_store: &ConversationStore,
+    token: &str,
+) -> Option<(ChatSessionClaims, Visitor)> {
+    let claims = match JwtUtil::new().verify_chat_session(token) {
+        Ok(claims) => claims,
+        Err(e) => {
+            warn!("Invalid chat session token: {}", e);
+            return None;
+        }
+    };
+
+    let visitor = match conversation_store.get_visitor(&claims.sid).await {
+        Ok(Some(visitor)) => visitor,
+        // 升级前签发的会话没有访客记录，补建后继续使用
+        Ok(None) if claims.generation == 0 => {
+            match conversation_store
+                .adopt_visitor(&claims.sid, &claims.workspace)
+                .await
+            {
+                Ok(visitor) => visitor,
+                Err(e) => {
+                    error!("Failed to adopt chat session {}: {}", claims.sid, e);
+                    return None;
+                }
+            }
+        }
+        Ok(None) => {
+            warn!("Unknown chat session: {}", claims.sid);
+            return None;
+        }
+        Err(e) => {
+            error!("Failed to load chat session {}: {}", claims.sid, e);
+            return None;
+        }
+    };
+
+    if visitor.merged_into.is_some()
+        || visitor.generation != claims.generation
+        || visitor.workspace_id != claims.workspace
+    {
+        warn!("Rejected stale chat session: {}", claims.sid);
+        return None;
+    }
+    Some((claims, visitor))
+}
+
+/// 解析调用者身份：优先使用 Authorization 中的用户 token，
+/// 其次是匿名会话 token（`X-Chat-Session` 头或 `session` 参数），都无效时返回 None
+pub(crate) async fn request_identity(
+    conversation_store: &ConversationStore,
+    headers: &HeaderMap,
+    session: Option<&str>,
+) -> Option<ChatIdentity> {
+    if let Ok(Some(identity)) = user_identity(headers).await {
+        return Some(identity);
+    }
+
+    let token = session_token(headers, session)?;
+    verify_visitor_session(conversation_store, token)
+        .await
+        .map(|(claims, _)| ChatIdentity::visitor(&claims))
+}
+
+/// 聊天请求的身份：没有有效身份时签发新的匿名会话（只能检索公开文档），
+/// 会话到期前轮换 token；同一 IP 新建会话超过每小时上限时返回 `SessionLimitExceeded`
+async fn chat_identity(
+    conversation_store: &ConversationStore,
+    headers: &HeaderMap,
+    ip: IpAddr,
+    session: Option<&str>,
+    workspace: Option<&str>,
+) -> anyhow::Result<ChatIdentity> {
+    if let Some(identity) = user_identity(headers).await? {
+        return Ok(identity);
+    }
+
+    if let Some(token) = session_token(headers, session)
+        && let Some((claims, visitor)) = verify_visitor_session(conversation_store, token).await
+    {
+        let mut identity = ChatIdentity::visitor(&claims);
+        if !claims.needs_rotation() {
+            conversation_store.touch_visitor(&visitor).await?;
+            return Ok(identity);
+        }
+
+        // 并发请求可能已经完成轮换，此时本次请求仍使用旧 token
+        if let Some(generation) = conversation_store
+            .rotate_visitor(&claims.sid, claims.generation)
+            .await?
+        {
+            identity.new_session = Some(JwtUtil::new().generate_chat_session(
+                &claims.sid,
+                &claims.workspace,
+                generation,
+            )?);
+            info!("Rotated anonymous chat session {}", claims.sid);
+        }
+        return Ok(identity);
+    }
+
+    // 升级前内置聊天组件保存的是客户端生成的 user_id，配置了截止时间时可以认领一次其对话并换发会话 token
+    if let Some(before) = get_config().chat.legacy_user_ids_before
+        && let Some(legacy_id) = session_token(headers, session).filter(|id| is_legacy_user_id(id))
+        && let Some(workspace) = conversation_store
+            .legacy_conversation_workspace(legacy_id, before)
+            .await?
+        && let Some(visitor) = conversation_store
+            .claim_legacy_user(legacy_id, &workspace)
+            .await?
+    {
+        let token =
+            JwtUtil::new().generate_chat_session(&visitor.id, &workspace, visitor.generation)?;
+        info!(
+            "Migrated legacy chat user {} to an anonymous session (workspace: {})",
+            legacy_id, workspace
+        );
+        return Ok(ChatIdentity {
+            user_id: visitor.id,
+            workspace,
+            claims: None,
+            new_session: Some(token),
+        });
+    }
+
+    let workspace = workspace
+        .map(str::trim)
+        .filter(|ws| !ws.is_empty())
+        .unwrap_or(DEFAULT_WORKSPACE);
+    if !take_chat_session(ip, get_config().chat.max_sessions_per_ip) {
+        warn!("Too many new chat sessions from {}", ip);
+        return Err(SessionLimitExceeded.into());
+    }
+    let visitor = conversation_store.create_visitor(workspace).await?;
+    let token = JwtUtil::new().generate_chat_session(&visitor.id, workspace, visitor.generation)?;
+    info!(
+        "Issued anonymous chat session {} (workspace: {})",
+        visitor.id, workspace
+    );
+
+    Ok(ChatIdentity {
+        user_id: visitor.id,
+        workspace: workspace.to_string(),
+        claims: None,
+        new_session: Some(token),
+    })
+}
+
+/// 升级前由服务端用 nanoid 生成、客户端原样回传的 user_id
+fn is_legacy_user_id(id: &str) -> bool {
+    id.len() == 21
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// 把访客的内存历史转移到登录用户名下
+///
+/// 合并只发生在同一工作区内，缓存键中只有 user_id 部分不同
+fn merge_chat_history(sid: &str, user_id: &str) {
+    let suffix = format!(":{}", sid);
+    let keys: Vec<String> = chat_store()
+        .iter()
+        .map(|entry| entry.key().clone())
+        .filter(|key| key == sid || key.ends_with(&suffix))
+        .collect();
+
+    for key in keys {
+        if let Some(history) = chat_store().get(&key) {
+            let target = format!("{}{}", &key[..key.len() - sid.len()], user_id);
+            if chat_store().get(&target).is_none() {
+                chat_store().insert(target, history);
+            }
+            chat_store().invalidate(&key);
+        }
+    }
+}
+
+/// 内存历史的缓存键，不同工作区、不同知识库的对话互不影响
+fn history_key(user_id: &str, workspace: &str, knowledge_base: Option<&str>) -> String {
+    match (workspace, knowledge_base_id(knowledge_base)) {
+        (DEFAULT_WORKSPACE, None) => user_id.to_string(),
+        (DEFAULT_WORKSPACE, Some(id)) => format!("{}:{}", id, user_id),
+        (workspace, id) => format!(
+            "{}/{}:{}",
+            workspace,
+            id.unwrap_or(DEFAULT_KNOWLEDGE_BASE),
+            user_id
+        ),
+    }
 }
 
 // 简单的语言检测逻辑
-#[allow(dead_code)]
 fn is_chinese(text: &str) -> bool {
     let chinese_chars = text
         .chars()
@@ -167,21 +713,37 @@ async fn auto_compress_history(
     }
 }
 
-/// 保存消息到数据库
+/// 保存消息到数据库，返回助手回复的消息 id
 async fn save_messages_to_db(
     conversation_store: &Arc<ConversationStore>,
     user_id: &str,
+    workspace: &str,
     user_message: &str,
     assistant_response: &str,
-) {
+    knowledge_base: Option<&str>,
+    experiment: Option<&ExperimentAssignment>,
+) -> Option<String> {
+    let mut metadata = serde_json::Map::new();
+    if let Some(id) = knowledge_base_id(knowledge_base) {
+        metadata.insert("knowledge_base".to_string(), serde_json::json!(id));
+    }
+    if let Some(experiment) = experiment {
+        metadata.insert("experiment".to_string(), experiment.metadata.clone());
+    }
+    let metadata = (!metadata.is_empty()).then_some(serde_json::Value::Object(metadata));
+
     let conversation = match conversation_store
-        .get_or_create_active_conversation(user_id)
+        .get_or_create_active_conversation(
+            user_id,
+            workspace,
+            experiment.and_then(|e| e.new_conversation_id.as_deref()),
+        )
         .await
     {
         Ok(conv) => conv,
         Err(e) => {
             error!("Failed to get or create conversation for DB storage: {}", e);
-            return;
+            return None;
         }
     };
 
@@ -190,7 +752,7 @@ async fn save_messages_to_db(
         conversation_id: conversation.id.clone(),
         role: MessageRole::User,
         content: user_message.to_string(),
-        metadata: None,
+        metadata: metadata.clone(),
     };
 
     if let Err(e) = conversation_store.add_message(user_message_req).await {
@@ -209,37 +771,136 @@ async fn save_messages_to_db(
         conversation_id: conversation.id.clone(),
         role: MessageRole::Assistant,
         content: assistant_response.to_string(),
-        metadata: None,
+        metadata,
     };
 
-    if let Err(e) = conversation_store.add_message(assistant_message_req).await {
-        error!("Failed to save assistant message to database: {}", e);
+    match conversation_store.add_message(assistant_message_req).await {
+        Ok(message) => Some(message.id),
+        Err(e) => {
+            error!("Failed to save assistant message to database: {}", e);
+            None
+        }
     }
 }
 
+fn error_response(status: StatusCode, e: &anyhow::Error) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
 pub async fn handle_chat(
     State((agent, _, conversation_store)): State<ChatAppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<ChatRequest>,
-) -> Json<ChatResponse> {
-    // 从请求中获取用户 ID 或生成一个新的
-    let user_id = payload.user_id.unwrap_or_else(generate_user_id);
+) -> Result<Json<ChatResponse>, (StatusCode, Json<ErrorResponse>)> {
+    validate_generation(&payload.generation)?;
+    validate_language(payload.language.as_deref())?;
     let message = payload.message.trim();
+    // 身份只来自用户 token 或服务端签发的匿名会话
+    let identity = match chat_identity(
+        &conversation_store,
+        &headers,
+        addr.ip(),
+        payload.session.as_deref(),
+        payload.workspace.as_deref(),
+    )
+    .await
+    {
+        Ok(identity) => identity,
+        Err(e) if e.is::<SessionLimitExceeded>() => {
+            return Err(error_response(StatusCode::TOO_MANY_REQUESTS, &e));
+        }
+        Err(e) => {
+            error!("Failed to issue chat session: {}", e);
+            return Ok(Json(ChatResponse {
+                response: format!("Sorry, I encountered an error: {}", e),
+                user_id: String::new(),
+                session: None,
+                message_id: None,
+            }));
+        }
+    };
+    let viewer = identity.viewer();
+    let knowledge_base = payload.knowledge_base.as_deref();
+    let user_language = user_language(&headers, payload.language.as_deref(), message);
+    let ChatIdentity {
+        user_id,
+        workspace,
+        claims,
+        new_session: session,
+    } = identity;
 
     info!("Received chat request from user {}: {}", user_id, message);
 
+    let agent = match resolve_agent(&agent, &workspace, knowledge_base).await {
+        Ok(agent) => agent,
+        Err(e) if e.is::<UnknownKnowledgeBase>() => {
+            return Err(error_response(StatusCode::NOT_FOUND, &e));
+        }
+        Err(e) => {
+            error!("Failed to resolve knowledge base: {}", e);
+            return Ok(Json(ChatResponse {
+                response: format!("Sorry, I encountered an error: {}", e),
+                user_id,
+                session,
+                message_id: None,
+            }));
+        }
+    };
+    let experiment = experiment_assignment(
+        &conversation_store,
+        &user_id,
+        &workspace,
+        knowledge_base,
+        &payload.generation,
+    )
+    .await;
+    let overrides = payload.generation.apply(
+        experiment
+            .as_ref()
+            .map(|e| e.overrides.clone())
+            .unwrap_or_default(),
+    );
+    let variables = preamble_variables(
+        &agent,
+        &overrides,
+        claims.as_ref(),
+        user_language,
+        &workspace,
+        knowledge_base,
+    )
+    .await;
+
     // 从内存缓存获取或初始化聊天历史
-    let chat_history = if let Some(h) = chat_store().get(&user_id) {
+    let key = history_key(&user_id, &workspace, knowledge_base);
+    let chat_history = if let Some(h) = chat_store().get(&key) {
         h
     } else {
         let h = Arc::new(RwLock::new(Vec::new()));
-        chat_store().insert(user_id.clone(), h.clone());
+        chat_store().insert(key, h.clone());
         h
     };
 
     // 使用 RigAgent 处理聊天请求
     let history_snapshot = { chat_history.read().clone() };
 
-    let response = match agent.chat(message, history_snapshot).await {
+    let mut message_id = None;
+    let response = match agent
+        .chat_with_filter(
+            message,
+            history_snapshot,
+            &payload.filters,
+            &viewer,
+            &variables,
+            &overrides,
+        )
+        .await
+    {
         Ok(response) => {
             // 更新内存缓存（所有消息都保存）
             {
@@ -254,7 +915,16 @@ pub async fn handle_chat(
             }
 
             // 保存消息到数据库
-            save_messages_to_db(&conversation_store, &user_id, message, &response).await;
+            message_id = save_messages_to_db(
+                &conversation_store,
+                &user_id,
+                &workspace,
+                message,
+                &response,
+                knowledge_base,
+                experiment.as_ref(),
+            )
+            .await;
 
             info!("Chat response for user {}: {}", user_id, response);
             response
@@ -265,30 +935,90 @@ pub async fn handle_chat(
         }
     };
 
-    Json(ChatResponse { response, user_id })
+    Ok(Json(ChatResponse {
+        response,
+        user_id,
+        session,
+        message_id,
+    }))
 }
 
 /// 流式聊天处理器
 pub async fn handle_stream_chat(
     State((agent, _, conversation_store)): State<ChatAppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<ChatRequest>,
-) -> Sse<impl futures::Stream<Item = Result<Event, axum::Error>>> {
-    // 从请求中获取用户 ID 或生成一个新的
-    let no_id = payload.user_id.is_none();
-    let user_id = payload.user_id.unwrap_or_else(generate_user_id);
+) -> Result<
+    Sse<impl futures::Stream<Item = Result<Event, axum::Error>>>,
+    (StatusCode, Json<ErrorResponse>),
+> {
+    validate_generation(&payload.generation)?;
+    validate_language(payload.language.as_deref())?;
+    // 创建流式响应
+    let (tx, rx) = tokio::sync::mpsc::channel(128);
+
+    // 身份只来自用户 token 或服务端签发的匿名会话
+    let identity = match chat_identity(
+        &conversation_store,
+        &headers,
+        addr.ip(),
+        payload.session.as_deref(),
+        payload.workspace.as_deref(),
+    )
+    .await
+    {
+        Ok(identity) => identity,
+        Err(e) if e.is::<SessionLimitExceeded>() => {
+            return Err(error_response(StatusCode::TOO_MANY_REQUESTS, &e));
+        }
+        Err(e) => {
+            error!("Failed to issue chat session: {}", e);
+            let _ = tx
+                .send(Ok(Event::default().data(format!("Error: {}", e))))
+                .await;
+            return Ok(Sse::new(ReceiverStream::new(rx))
+                .keep_alive(axum::response::sse::KeepAlive::default()));
+        }
+    };
+    let viewer = identity.viewer();
     let message = payload.message.trim().to_string();
+    let knowledge_base = payload.knowledge_base;
+    let user_language = user_language(&headers, payload.language.as_deref(), &message);
+    let ChatIdentity {
+        user_id,
+        workspace,
+        claims,
+        new_session,
+    } = identity;
 
     info!(
         "Received stream chat request from user {}: {}",
         user_id, message
     );
 
+    let agent = match resolve_agent(&agent, &workspace, knowledge_base.as_deref()).await {
+        Ok(agent) => agent,
+        Err(e) if e.is::<UnknownKnowledgeBase>() => {
+            return Err(error_response(StatusCode::NOT_FOUND, &e));
+        }
+        Err(e) => {
+            error!("Failed to resolve knowledge base: {}", e);
+            let _ = tx
+                .send(Ok(Event::default().data(format!("Error: {}", e))))
+                .await;
+            return Ok(Sse::new(ReceiverStream::new(rx))
+                .keep_alive(axum::response::sse::KeepAlive::default()));
+        }
+    };
+
     // 从内存缓存获取或初始化聊天历史
-    let chat_history = if let Some(h) = chat_store().get(&user_id) {
+    let key = history_key(&user_id, &workspace, knowledge_base.as_deref());
+    let chat_history = if let Some(h) = chat_store().get(&key) {
         h
     } else {
         let h = Arc::new(RwLock::new(Vec::new()));
-        chat_store().insert(user_id.clone(), h.clone());
+        chat_store().insert(key, h.clone());
         h
     };
 
@@ -296,27 +1026,58 @@ pub async fn handle_stream_chat(
     let raw_history = chat_history.read().clone();
     let history_snapshot = filter_meaningless_messages(raw_history);
 
-    // 创建流式响应
-    let (tx, rx) = tokio::sync::mpsc::channel(128);
-
     // 在后台任务中处理流
     let user_id_clone = user_id.clone();
     let message_clone = message.clone();
     let chat_history_clone = chat_history.clone();
     let conversation_store_clone = conversation_store.clone();
     let agent_clone = agent.clone();
+    let filters = payload.filters;
+    let generation = payload.generation;
 
     tokio::spawn(async move {
-        match agent_clone
-            .stream_chat(&message_clone, history_snapshot)
-            .await
-        {
+        let experiment = experiment_assignment(
+            &conversation_store_clone,
+            &user_id_clone,
+            &workspace,
+            knowledge_base.as_deref(),
+            &generation,
+        )
+        .await;
+        let overrides = generation.apply(
+            experiment
+                .as_ref()
+                .map(|e| e.overrides.clone())
+                .unwrap_or_default(),
+        );
+        let variables = preamble_variables(
+            &agent_clone,
+            &overrides,
+            claims.as_ref(),
+            user_language,
+            &workspace,
+            knowledge_base.as_deref(),
+        )
+        .await;
+        let stream = agent_clone
+            .stream_chat_with_filter(
+                &message_clone,
+                history_snapshot,
+                &filters,
+                &viewer,
+                &variables,
+                &overrides,
+            )
+            .await;
+
+        match stream {
             Ok(mut stream) => {
                 let mut full_response = String::with_capacity(2048);
 
-                if no_id {
+                // 事件名沿用 user_id，内置聊天组件会保存并在下次请求时回传
+                if let Some(session) = new_session {
                     let _ = tx
-                        .send(Ok(Event::default().event("user_id").data(user_id)))
+                        .send(Ok(Event::default().event("user_id").data(session)))
                         .await;
                 }
 
@@ -355,8 +1116,11 @@ pub async fn handle_stream_chat(
                 save_messages_to_db(
                     &conversation_store_clone,
                     &user_id_clone,
+                    &workspace,
                     &message_clone,
                     &full_response,
+                    knowledge_base.as_deref(),
+                    experiment.as_ref(),
                 )
                 .await;
             }
@@ -369,15 +1133,84 @@ pub async fn handle_stream_chat(
         }
     });
 
-    Sse::new(ReceiverStream::new(rx)).keep_alive(axum::response::sse::KeepAlive::default())
+    Ok(Sse::new(ReceiverStream::new(rx)).keep_alive(axum::response::sse::KeepAlive::default()))
 }
 
+/// 访客登录后把匿名会话合并到账号，需要同时带上用户 token 和匿名会话 token
+///
+/// 访客的对话和内存历史转移到 `user:{id}` 名下，之后匿名会话 token 失效
+pub async fn merge_chat_session(
+    State((_, _, conversation_store)): State<ChatAppState>,
+    headers: HeaderMap,
+    Json(payload): Json<MergeSessionRequest>,
+) -> Result<Json<MergeSessionResponse>, StatusCode> {
+    let claims = optional_claims(&headers)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?
+        // 只能合并到真实账号
+        .filter(|claims| claims.api_key.is_none())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let token =
+        session_token(&headers, payload.session.as_deref()).ok_or(StatusCode::BAD_REQUEST)?;
+    let (session, _) = verify_visitor_session(&conversation_store, token)
+        .await
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    // 对话按工作区隔离，只能合并到同一工作区的账号
+    if session.workspace != claims.workspace {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let user_id = format!("user:{}", claims.user_id);
+    let merged_conversations = conversation_store
+        .merge_visitor(&session.sid, &user_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to merge chat session {}: {}", session.sid, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    merge_chat_history(&session.sid, &user_id);
+
+    Ok(Json(MergeSessionResponse {
+        user_id,
+        merged_conversations,
+    }))
+}
+
+/// 获取当前身份的聊天历史
 pub async fn get_chat_history(
-    State((_, _, _)): State<ChatAppState>,
-    Path(user_id): Path<String>,
-) -> Json<Vec<ChatHistoryItem>> {
+    State((_, _, conversation_store)): State<ChatAppState>,
+    headers: HeaderMap,
+    Query(query): Query<ChatHistoryQuery>,
+) -> Result<Json<Vec<ChatHistoryItem>>, StatusCode> {
+    chat_history_for(&conversation_store, &headers, None, query).await
+}
+
+/// 兼容内置聊天组件：路径参数为匿名会话 token
+pub async fn get_session_chat_history(
+    State((_, _, conversation_store)): State<ChatAppState>,
+    headers: HeaderMap,
+    Path(session): Path<String>,
+    Query(query): Query<ChatHistoryQuery>,
+) -> Result<Json<Vec<ChatHistoryItem>>, StatusCode> {
+    chat_history_for(&conversation_store, &headers, Some(&session), query).await
+}
+
+async fn chat_history_for(
+    conversation_store: &ConversationStore,
+    headers: &HeaderMap,
+    session: Option<&str>,
+    query: ChatHistoryQuery,
+) -> Result<Json<Vec<ChatHistoryItem>>, StatusCode> {
+    let identity = request_identity(conversation_store, headers, session)
+        .await
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let key = history_key(
+        &identity.user_id,
+        &identity.workspace,
+        query.knowledge_base.as_deref(),
+    );
     // 获取或初始化
-    if let Some(h) = chat_store().get(&user_id) {
+    if let Some(h) = chat_store().get(&key) {
         let history_items = h
             .read()
             .iter()
@@ -399,15 +1232,8 @@ pub async fn get_chat_history(
             })
             .collect();
 
-        Json(history_items)
+        Ok(Json(history_items))
     } else {
-        Json(Vec::new())
+        Ok(Json(Vec::new()))
     }
 }
-
-fn generate_user_id() -> String {
-    // use std::time::{SystemTime, UNIX_EPOCH};
-    // let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-    // format!("user_{}", now)
-    nanoid::nanoid!()
-}
@@ -1,8 +1,10 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
     Router,
-    extract::{Json, Path, Query, State},
+    extract::{ConnectInfo, Extension, Json, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
     response::Json as ResponseJson,
     routing::{get, post},
 };
@@ -12,8 +14,12 @@ use tracing::{error, info};
 use crate::{
     agent::RigAgent,
     db::{
-        Conversation, ConversationMessage, ConversationStats, ConversationStatus,
-        ConversationStore, CreateMessageRequest, DocumentStore, UserInteractionStats,
+        AuditAction, Conversation, ConversationMessage, ConversationStats, ConversationStatus,
+        ConversationStore, CreateMessageRequest, DocumentStore, Permission, UserInteractionStats,
+    },
+    web::{
+        ChatIdentity, Claims, record_audit, request_identity, require_default_workspace_middleware,
+        require_permission,
     },
 };
 
@@ -58,6 +64,14 @@ pub struct UpdateConversationWebRequest {
     pub title: Option<String>,
 }
 
+/// 回复评分请求
+#[derive(Debug, Deserialize)]
+pub struct MessageFeedbackRequest {
+    /// 1-5
+    pub rating: i64,
+    pub comment: Option<String>,
+}
+
 /// 查询参数
 #[derive(Debug, Deserialize)]
 pub struct PaginationQuery {
@@ -73,6 +87,47 @@ pub struct AdminPaginationQuery {
     pub search: Option<String>,
 }
 
+/// 解析调用者身份（用户 token 或匿名会话），都没有时返回 401
+async fn caller(
+    conversation_store: &ConversationStore,
+    headers: &HeaderMap,
+) -> Result<ChatIdentity, StatusCode> {
+    request_identity(conversation_store, headers, None)
+        .await
+        .ok_or(StatusCode::UNAUTHORIZED)
+}
+
+/// 获取调用者可以访问的对话，不存在或无权访问时返回 404
+async fn accessible_conversation(
+    conversation_store: &ConversationStore,
+    identity: &ChatIdentity,
+    conversation_id: &str,
+) -> Result<Conversation, StatusCode> {
+    match conversation_store
+        .get_conversation_by_id(conversation_id)
+        .await
+    {
+        Ok(Some(conversation)) if identity.can_access(&conversation) => Ok(conversation),
+        Ok(_) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to get conversation: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// 路径中的 user_id 必须是调用者自己（`me` 表示自己），拥有 conversation.read_all 权限时可以查看工作区内的其他用户
+fn resolve_user_id(identity: &ChatIdentity, user_id: String) -> Result<String, StatusCode> {
+    if user_id == "me" || user_id == identity.user_id {
+        Ok(identity.user_id.clone())
+    } else if identity.can_read_all_conversations() {
+        Ok(user_id)
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// 对话路由：只能访问自己的对话，管理员可以访问同一工作区的全部对话
 pub fn create_conversation_router() -> Router<AppState> {
     Router::new()
         .route(
@@ -85,46 +140,68 @@ pub fn create_conversation_router() -> Router<AppState> {
             "/api/conversation/{conversation_id}/messages",
             get(get_conversation_messages).post(add_message_to_conversation),
         )
+        .route(
+            "/api/conversation/{conversation_id}/messages/{message_id}/feedback",
+            post(submit_message_feedback),
+        )
         .route(
             "/api/user/{user_id}/conversations",
             get(get_user_conversations),
         )
         .route("/api/user/{user_id}/stats", get(get_user_interaction_stats))
+}
+
+/// 对话管理路由（作用于当前工作区）：查看需要 conversation.read_all，清理需要 conversation.manage
+pub fn create_admin_conversation_router() -> Router<AppState> {
+    let read_routes = Router::new()
         .route("/api/admin/conversations", get(get_all_conversations))
         .route(
             "/api/admin/conversations/stats",
             get(get_conversation_stats),
         )
+        .route_layer(middleware::from_fn_with_state(
+            Permission::ConversationReadAll,
+            require_permission,
+        ));
+
+    // 清理会删除所有工作区的旧记录
+    let cleanup_routes = Router::new()
         .route(
             "/api/admin/conversations/cleanup",
-            post(cleanup_old_conversations),
+            post(cleanup_old_conversations)
+                .route_layer(middleware::from_fn(require_default_workspace_middleware)),
         )
+        .route_layer(middleware::from_fn_with_state(
+            Permission::ConversationManage,
+            require_permission,
+        ));
+
+    read_routes.merge(cleanup_routes)
 }
 
 /// 获取对话详情
 pub async fn get_conversation(
     State((_, _, conversation_store)): State<AppState>,
+    headers: HeaderMap,
     Path(conversation_id): Path<String>,
-) -> ResponseJson<Option<Conversation>> {
-    match conversation_store
-        .get_conversation_by_id(&conversation_id)
+) -> Result<ResponseJson<Conversation>, StatusCode> {
+    let identity = caller(&conversation_store, &headers).await?;
+    accessible_conversation(&conversation_store, &identity, &conversation_id)
         .await
-    {
-        Ok(conversation) => ResponseJson(conversation),
-        Err(e) => {
-            error!("Failed to get conversation: {}", e);
-            ResponseJson(None)
-        }
-    }
+        .map(ResponseJson)
 }
 
 /// 更新对话
 pub async fn update_conversation(
     State((_, _, conversation_store)): State<AppState>,
+    headers: HeaderMap,
     Path(conversation_id): Path<String>,
     Json(payload): Json<UpdateConversationWebRequest>,
-) -> ResponseJson<Option<Conversation>> {
+) -> Result<ResponseJson<Conversation>, StatusCode> {
     use crate::db::UpdateConversationRequest;
+    let identity = caller(&conversation_store, &headers).await?;
+    accessible_conversation(&conversation_store, &identity, &conversation_id).await?;
+
     let req = UpdateConversationRequest {
         status: payload.status,
         title: payload.title,
@@ -135,10 +212,10 @@ pub async fn update_conversation(
         .update_conversation(&conversation_id, req)
         .await
     {
-        Ok(conversation) => ResponseJson(Some(conversation)),
+        Ok(conversation) => Ok(ResponseJson(conversation)),
         Err(e) => {
             error!("Failed to update conversation: {}", e);
-            ResponseJson(None)
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
@@ -146,18 +223,22 @@ pub async fn update_conversation(
 /// 删除对话（硬删除）
 pub async fn delete_conversation(
     State((_, _, conversation_store)): State<AppState>,
+    headers: HeaderMap,
     Path(conversation_id): Path<String>,
-) -> ResponseJson<serde_json::Value> {
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
+    let identity = caller(&conversation_store, &headers).await?;
+    accessible_conversation(&conversation_store, &identity, &conversation_id).await?;
+
     match conversation_store
         .delete_conversation(&conversation_id)
         .await
     {
-        Ok(_) => ResponseJson(
+        Ok(_) => Ok(ResponseJson(
             serde_json::json!({"success": true, "message": "Conversation deleted successfully"}),
-        ),
+        )),
         Err(e) => {
             error!("Failed to delete conversation: {}", e);
-            ResponseJson(serde_json::json!({"success": false, "error": e.to_string()}))
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
@@ -165,17 +246,21 @@ pub async fn delete_conversation(
 /// 获取对话消息
 pub async fn get_conversation_messages(
     State((_, _, conversation_store)): State<AppState>,
+    headers: HeaderMap,
     Path(conversation_id): Path<String>,
     Query(pagination): Query<PaginationQuery>,
-) -> ResponseJson<Vec<ConversationMessage>> {
+) -> Result<ResponseJson<Vec<ConversationMessage>>, StatusCode> {
+    let identity = caller(&conversation_store, &headers).await?;
+    accessible_conversation(&conversation_store, &identity, &conversation_id).await?;
+
     match conversation_store
         .get_conversation_messages(&conversation_id, pagination.limit, pagination.offset)
         .await
     {
-        Ok(messages) => ResponseJson(messages),
+        Ok(messages) => Ok(ResponseJson(messages)),
         Err(e) => {
             error!("Failed to get conversation messages: {}", e);
-            ResponseJson(Vec::new())
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
@@ -183,9 +268,13 @@ pub async fn get_conversation_messages(
 /// 添加消息到对话
 pub async fn add_message_to_conversation(
     State((_, _, conversation_store)): State<AppState>,
+    headers: HeaderMap,
     Path(conversation_id): Path<String>,
     Json(payload): Json<CreateMessageRequest>,
-) -> ResponseJson<Option<ConversationMessage>> {
+) -> Result<ResponseJson<ConversationMessage>, StatusCode> {
+    let identity = caller(&conversation_store, &headers).await?;
+    accessible_conversation(&conversation_store, &identity, &conversation_id).await?;
+
     let req = CreateMessageRequest {
         conversation_id: conversation_id.clone(),
         role: payload.role,
@@ -194,56 +283,105 @@ pub async fn add_message_to_conversation(
     };
 
     match conversation_store.add_message(req).await {
-        Ok(message) => ResponseJson(Some(message)),
+        Ok(message) => Ok(ResponseJson(message)),
         Err(e) => {
             error!("Failed to add message to conversation: {}", e);
-            ResponseJson(None)
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// 对助手回复评分，只有登录的对话所有者可以评分，重复提交覆盖之前的评分
+///
+/// 匿名会话可以随意新建，不接受其评分，避免影响实验报告
+pub async fn submit_message_feedback(
+    State((_, _, conversation_store)): State<AppState>,
+    headers: HeaderMap,
+    Path((conversation_id, message_id)): Path<(String, String)>,
+    Json(payload): Json<MessageFeedbackRequest>,
+) -> Result<StatusCode, StatusCode> {
+    if !(1..=5).contains(&payload.rating) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let identity = caller(&conversation_store, &headers).await?;
+    if identity.claims.is_none() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let conversation =
+        accessible_conversation(&conversation_store, &identity, &conversation_id).await?;
+    if conversation.user_id != identity.user_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    match conversation_store
+        .set_message_feedback(
+            &conversation_id,
+            &message_id,
+            payload.rating,
+            payload.comment.as_deref(),
+        )
+        .await
+    {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to save message feedback: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
-/// 获取用户的对话列表
+/// 获取用户在当前工作区的对话列表
 pub async fn get_user_conversations(
     State((_, _, conversation_store)): State<AppState>,
+    headers: HeaderMap,
     Path(user_id): Path<String>,
     Query(pagination): Query<PaginationQuery>,
-) -> ResponseJson<UserConversationsResponse> {
+) -> Result<ResponseJson<UserConversationsResponse>, StatusCode> {
+    let identity = caller(&conversation_store, &headers).await?;
+    let user_id = resolve_user_id(&identity, user_id)?;
+
     match conversation_store
-        .get_user_conversations(&user_id, pagination.limit, pagination.offset)
+        .get_user_conversations(
+            &user_id,
+            &identity.workspace,
+            pagination.limit,
+            pagination.offset,
+        )
         .await
     {
         Ok(conversations) => {
             let has_more = conversations.len() as i64 == pagination.limit.unwrap_or(20);
-            ResponseJson(UserConversationsResponse {
+            Ok(ResponseJson(UserConversationsResponse {
                 total: conversations.len() as i64, // 简化实现，实际应该查询总数
                 conversations,
                 has_more,
-            })
+            }))
         }
         Err(e) => {
             error!("Failed to get user conversations: {}", e);
-            ResponseJson(UserConversationsResponse {
-                total: 0,
-                conversations: Vec::new(),
-                has_more: false,
-            })
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
-/// 获取用户交互统计
+/// 获取用户在当前工作区的交互统计
 pub async fn get_user_interaction_stats(
     State((_, _, conversation_store)): State<AppState>,
+    headers: HeaderMap,
     Path(user_id): Path<String>,
-) -> ResponseJson<Option<UserInteractionStats>> {
+) -> Result<ResponseJson<UserInteractionStats>, StatusCode> {
+    let identity = caller(&conversation_store, &headers).await?;
+    let user_id = resolve_user_id(&identity, user_id)?;
+
     match conversation_store
-        .get_user_interaction_stats(&user_id)
+        .get_user_interaction_stats(&user_id, &identity.workspace)
         .await
     {
-        Ok(stats) => ResponseJson(Some(stats)),
+        Ok(stats) => Ok(ResponseJson(stats)),
         Err(e) => {
             error!("Failed to get user interaction stats: {}", e);
-            ResponseJson(None)
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
@@ -256,15 +394,21 @@ pub struct CleanupRequest {
     pub days_to_keep: i64,
 }
 
-/// 获取所有对话（管理员功能）
+/// 获取当前工作区的所有对话（管理员功能）
 pub async fn get_all_conversations(
     State((_, _, conversation_store)): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Query(pagination): Query<AdminPaginationQuery>,
 ) -> ResponseJson<UserConversationsResponse> {
     let search_param = pagination.search.as_deref();
 
     match conversation_store
-        .get_all_conversations(pagination.limit, pagination.offset, search_param)
+        .get_all_conversations(
+            &claims.workspace,
+            pagination.limit,
+            pagination.offset,
+            search_param,
+        )
         .await
     {
         Ok(conversations) => {
@@ -286,11 +430,15 @@ pub async fn get_all_conversations(
     }
 }
 
-/// 获取对话统计信息
+/// 获取当前工作区的对话统计信息
 pub async fn get_conversation_stats(
     State((_, _, conversation_store)): State<AppState>,
+    Extension(claims): Extension<Claims>,
 ) -> ResponseJson<Option<ConversationStats>> {
-    match conversation_store.get_conversation_stats().await {
+    match conversation_store
+        .get_conversation_stats(&claims.workspace)
+        .await
+    {
         Ok(stats) => ResponseJson(Some(stats)),
         Err(e) => {
             error!("Failed to get conversation stats: {}", e);
@@ -301,6 +449,8 @@ pub async fn get_conversation_stats(
 
 /// 清理旧对话记录
 pub async fn cleanup_old_conversations(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State((_, _, conversation_store)): State<AppState>,
     Json(payload): Json<CleanupRequest>,
 ) -> ResponseJson<serde_json::Value> {
@@ -317,6 +467,18 @@ pub async fn cleanup_old_conversations(
     {
         Ok(deleted_count) => {
             info!("Cleaned up {} old conversations", deleted_count);
+            record_audit(
+                &claims,
+                addr,
+                AuditAction::ConversationsCleanedUp,
+                "conversations",
+                None,
+                Some(serde_json::json!({
+                    "days_to_keep": payload.days_to_keep,
+                    "deleted_count": deleted_count,
+                })),
+            )
+            .await;
             ResponseJson(serde_json::json!({
                 "success": true,
                 "deleted_count": deleted_count,
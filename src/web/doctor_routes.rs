@@ -0,0 +1,618 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use axum::{
+    Extension, Router,
+    extract::{ConnectInfo, Json, Query},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, post},
+};
+use qdrant_client::qdrant::PointId;
+use rig::providers::openai;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use super::{auth_routes::Claims, knowledge_base_routes::load_handle};
+use crate::{
+    agent::{DEFAULT_KNOWLEDGE_BASE, KnowledgeBaseHandle},
+    db::{AuditAction, Document, DocumentStore, point_id_to_string},
+    utils::FileBackup,
+    web::{AppState, build_chunk_documents, record_audit},
+};
+
+/// 孤立备份（有备份但没有向量数据）的修复方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanBackupAction {
+    /// 从备份重新导入
+    #[default]
+    Restore,
+    /// 删除备份文件
+    Delete,
+    /// 不处理
+    Keep,
+}
+
+/// 检查的知识库，不指定时为默认知识库
+#[derive(Debug, Default, Deserialize)]
+pub struct DoctorQuery {
+    pub knowledge_base: Option<String>,
+}
+
+/// 修复选项
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RepairOptions {
+    pub orphan_backups: OrphanBackupAction,
+}
+
+/// 分块缺失的文档
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkGap {
+    pub base_id: String,
+    pub source: String,
+    pub expected_chunks: u32,
+    pub present: Vec<u32>,
+    pub missing: Vec<u32>,
+}
+
+/// 被多个点重复使用的文档 id
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateId {
+    pub id: String,
+    pub point_ids: Vec<String>,
+}
+
+/// 执行过的修复操作
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairAction {
+    pub target: String,
+    pub action: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// 一致性检查报告
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsistencyReport {
+    pub total_points: usize,
+    pub total_documents: usize,
+    pub total_backups: usize,
+    /// 备份目录未初始化时为 false，此时不检查备份相关问题
+    pub backups_checked: bool,
+    /// payload 无法解析的点
+    pub invalid_points: Vec<String>,
+    /// 有备份但 Qdrant 中没有对应数据
+    pub orphan_backups: Vec<String>,
+    /// Qdrant 中有数据但没有备份
+    pub missing_backups: Vec<String>,
+    pub chunk_gaps: Vec<ChunkGap>,
+    pub duplicate_ids: Vec<DuplicateId>,
+    pub repairs: Vec<RepairAction>,
+}
+
+impl ConsistencyReport {
+    pub fn is_healthy(&self) -> bool {
+        self.invalid_points.is_empty()
+            && self.orphan_backups.is_empty()
+            && self.missing_backups.is_empty()
+            && self.chunk_gaps.is_empty()
+            && self.duplicate_ids.is_empty()
+    }
+}
+
+/// 扫描结果，保留修复时需要的原始数据
+struct Scan {
+    report: ConsistencyReport,
+    invalid_points: Vec<PointId>,
+    duplicate_points: Vec<(String, Vec<(PointId, Document)>)>,
+    documents: BTreeMap<String, Vec<Document>>,
+}
+
+/// 创建一致性检查路由（仅管理员可访问）
+pub fn create_doctor_router() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/doctor", get(check_handler))
+        .route("/api/admin/doctor/repair", post(repair_handler))
+}
+
+fn handle_id(query: &DoctorQuery) -> &str {
+    query
+        .knowledge_base
+        .as_deref()
+        .unwrap_or(DEFAULT_KNOWLEDGE_BASE)
+}
+
+async fn doctor_handle(
+    claims: &Claims,
+    query: &DoctorQuery,
+) -> Result<Arc<KnowledgeBaseHandle>, StatusCode> {
+    load_handle(&claims.workspace, handle_id(query)).await
+}
+
+async fn check_handler(
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<DoctorQuery>,
+) -> Result<ResponseJson<ConsistencyReport>, StatusCode> {
+    let handle = doctor_handle(&claims, &query).await?;
+    check_consistency(&handle.document_store, handle.backup())
+        .await
+        .map(ResponseJson)
+        .map_err(|e| {
+            error!("Consistency check failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// 执行修复，每个从备份重新导入的文档记一条审计日志
+async fn repair_handler(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(query): Query<DoctorQuery>,
+    Json(options): Json<RepairOptions>,
+) -> Result<ResponseJson<ConsistencyReport>, StatusCode> {
+    let handle = doctor_handle(&claims, &query).await?;
+    let embedding_model = {
+        let context = handle.agent.context.read();
+        context.embedding_model.clone()
+    };
+
+    let report = repair_consistency(
+        &handle.document_store,
+        handle.backup(),
+        handle.chunk_size,
+        &embedding_model,
+        &options,
+    )
+    .await
+    .map_err(|e| {
+        error!("Consistency repair failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let restored = report
+        .repairs
+        .iter()
+        .filter(|r| r.success && (r.action == "restore" || r.action == "reingest"));
+    for repair in restored {
+        record_audit(
+            &claims,
+            addr,
+            AuditAction::BackupRestored,
+            &repair.target,
+            None,
+            Some(serde_json::json!({
+                "knowledge_base": handle_id(&query),
+                "action": repair.action,
+            })),
+        )
+        .await;
+    }
+
+    Ok(ResponseJson(report))
+}
+
+/// 检查 Qdrant 与文件备份之间的一致性（只读）
+pub async fn check_consistency(
+    document_store: &DocumentStore,
+    backup: Option<&FileBackup>,
+) -> anyhow::Result<ConsistencyReport> {
+    Ok(scan(document_store, backup).await?.report)
+}
+
+/// 检查并修复一致性问题，返回修复前的问题列表及执行的修复操作
+///
+/// `chunk_size` 为知识库的分块大小，重新导入时按它分块
+pub async fn repair_consistency(
+    document_store: &DocumentStore,
+    backup: Option<&FileBackup>,
+    chunk_size: usize,
+    embedding_model: &openai::EmbeddingModel,
+    options: &RepairOptions,
+) -> anyhow::Result<ConsistencyReport> {
+    let Scan {
+        mut report,
+        invalid_points,
+        duplicate_points,
+        documents,
+    } = scan(document_store, backup).await?;
+    let mut repairs = Vec::new();
+
+    // 1. 删除无法解析的点
+    if !invalid_points.is_empty() {
+        let result = document_store.delete_points(invalid_points).await;
+        repairs.push(repair_action(
+            format!("{} invalid point(s)", report.invalid_points.len()),
+            "delete_points",
+            result,
+        ));
+    }
+
+    // 2. 重复 id 只保留最近更新的点
+    for (id, mut points) in duplicate_points {
+        points.sort_by_key(|(_, doc)| std::cmp::Reverse(doc.updated_at));
+        let stale: Vec<PointId> = points.into_iter().skip(1).map(|(pid, _)| pid).collect();
+        let result = document_store.delete_points(stale).await;
+        repairs.push(repair_action(id, "delete_duplicates", result));
+    }
+
+    if let Some(backup) = backup {
+        // 3. 分块缺失的文档从备份重新导入
+        for gap in &report.chunk_gaps {
+            if !report.missing_backups.contains(&gap.base_id) {
+                let existing = documents
+                    .get(&gap.base_id)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                let result = reingest_from_backup(
+                    document_store,
+                    embedding_model,
+                    backup,
+                    &gap.base_id,
+                    existing,
+                    chunk_size,
+                )
+                .await;
+                repairs.push(repair_action(gap.base_id.clone(), "reingest", result));
+            } else {
+                warn!(
+                    "Cannot repair chunk gaps for {}: no backup available",
+                    gap.base_id
+                );
+                repairs.push(RepairAction {
+                    target: gap.base_id.clone(),
+                    action: "reingest".to_string(),
+                    success: false,
+                    error: Some("No backup available".to_string()),
+                });
+            }
+        }
+
+        // 4. 完整但缺少备份的文档，用现有分块重建备份
+        let gap_ids: HashSet<&str> = report
+            .chunk_gaps
+            .iter()
+            .map(|g| g.base_id.as_str())
+            .collect();
+        for base_id in &report.missing_backups {
+            if gap_ids.contains(base_id.as_str()) {
+                continue;
+            }
+            let Some(chunks) = documents.get(base_id) else {
+                continue;
+            };
+            let result = rebuild_backup(backup, base_id, chunks).await;
+            repairs.push(repair_action(base_id.clone(), "rebuild_backup", result));
+        }
+
+        // 5. 孤立备份
+        for doc_id in &report.orphan_backups {
+            match options.orphan_backups {
+                OrphanBackupAction::Restore => {
+                    let result = reingest_from_backup(
+                        document_store,
+                        embedding_model,
+                        backup,
+                        doc_id,
+                        &[],
+                        chunk_size,
+                    )
+                    .await;
+                    repairs.push(repair_action(doc_id.clone(), "restore", result));
+                }
+                OrphanBackupAction::Delete => {
+                    let result = backup.delete_backup(doc_id).await.map(|_| ());
+                    repairs.push(repair_action(doc_id.clone(), "delete_backup", result));
+                }
+                OrphanBackupAction::Keep => {}
+            }
+        }
+    }
+
+    info!(
+        "🩺 Consistency repair finished: {} action(s), {} failed",
+        repairs.len(),
+        repairs.iter().filter(|r| !r.success).count()
+    );
+
+    report.repairs = repairs;
+    Ok(report)
+}
+
+async fn scan(document_store: &DocumentStore, backup: Option<&FileBackup>) -> anyhow::Result<Scan> {
+    let points = document_store.scroll_all_documents().await?;
+
+    let mut report = ConsistencyReport {
+        total_points: points.len(),
+        ..Default::default()
+    };
+    let mut invalid_points = Vec::new();
+    let mut by_id: BTreeMap<String, Vec<(PointId, Document)>> = BTreeMap::new();
+
+    for (point_id, doc) in points {
+        match doc {
+            Ok(doc) => by_id
+                .entry(doc.id.clone())
+                .or_default()
+                .push((point_id, doc)),
+            Err(e) => {
+                warn!(
+                    "Invalid payload on point {}: {}",
+                    point_id_to_string(&point_id),
+                    e
+                );
+                report.invalid_points.push(point_id_to_string(&point_id));
+                invalid_points.push(point_id);
+            }
+        }
+    }
+
+    // 重复 id
+    let mut duplicate_points = Vec::new();
+    for (id, points) in &by_id {
+        if points.len() > 1 {
+            report.duplicate_ids.push(DuplicateId {
+                id: id.clone(),
+                point_ids: points
+                    .iter()
+                    .map(|(pid, _)| point_id_to_string(pid))
+                    .collect(),
+            });
+            duplicate_points.push((id.clone(), points.clone()));
+        }
+    }
+
+    // 按 base_id 分组（每个 id 只取一个点）
+    let mut documents: BTreeMap<String, Vec<Document>> = BTreeMap::new();
+    for points in by_id.values() {
+        let doc = &points[0].1;
+        documents
+            .entry(doc.base_id.clone())
+            .or_default()
+            .push(doc.clone());
+    }
+    report.total_documents = documents.len();
+
+    for (base_id, chunks) in &mut documents {
+        chunks.sort_by_key(|d| d.chunk_index);
+        if let Some(gap) = find_chunk_gap(base_id, chunks) {
+            report.chunk_gaps.push(gap);
+        }
+    }
+
+    if let Some(backup) = backup {
+        let backups = backup.list_all_backups().await?;
+        let backup_ids: BTreeSet<String> = backups.into_iter().map(|b| b.doc_id).collect();
+        report.total_backups = backup_ids.len();
+        report.backups_checked = true;
+
+        // 更新单个分块时备份以分块 id 保存，因此也接受分块 id
+        report.orphan_backups = backup_ids
+            .iter()
+            .filter(|id| !documents.contains_key(*id) && !by_id.contains_key(*id))
+            .cloned()
+            .collect();
+        report.missing_backups = documents
+            .keys()
+            .filter(|base_id| !backup_ids.contains(*base_id))
+            .cloned()
+            .collect();
+    }
+
+    info!(
+        "🩺 Consistency check: {} points, {} documents, {} backups, healthy={}",
+        report.total_points,
+        report.total_documents,
+        report.total_backups,
+        report.is_healthy()
+    );
+
+    Ok(Scan {
+        report,
+        invalid_points,
+        duplicate_points,
+        documents,
+    })
+}
+
+/// 检查分块文档的 chunk_index 序列是否完整
+fn find_chunk_gap(base_id: &str, chunks: &[Document]) -> Option<ChunkGap> {
+    let present: BTreeSet<u32> = chunks.iter().filter_map(|d| d.chunk_index).collect();
+    if present.is_empty() {
+        return None;
+    }
+
+    let source = chunks[0].source.clone();
+    let max_index = present.iter().next_back().copied().unwrap_or(0);
+    // 以 source 中记录的总块数为准，否则以最大 index 推断
+    let expected_chunks = chunks
+        .iter()
+        .filter_map(Document::total_chunks)
+        .max()
+        .unwrap_or(0)
+        .max(max_index + 1);
+
+    let missing: Vec<u32> = (0..expected_chunks)
+        .filter(|idx| !present.contains(idx))
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+
+    Some(ChunkGap {
+        base_id: base_id.to_string(),
+        source,
+        expected_chunks,
+        present: present.into_iter().collect(),
+        missing,
+    })
+}
+
+/// 从最新备份按知识库的分块大小重新分块，补齐缺失的分块
+///
+/// `existing` 为仍存在的分块：分块与备份一致时只导入缺失的分块，保留现有分块（包括单独修改过的）；
+/// 不一致时整篇替换，先生成全部向量再替换原有的点。单独修改过的分块以分块 id 备份，
+/// 比整篇备份新时使用分块备份的内容
+async fn reingest_from_backup(
+    document_store: &DocumentStore,
+    embedding_model: &openai::EmbeddingModel,
+    backup: &FileBackup,
+    base_id: &str,
+    existing: &[Document],
+    chunk_size: usize,
+) -> anyhow::Result<()> {
+    let backups = backup.list_all_backups().await?;
+    let Some(latest) = backups.iter().find(|b| b.doc_id == base_id) else {
+        return Err(anyhow::anyhow!("Backup not found"));
+    };
+    let (backup_filename, content) = backup
+        .read_backup(base_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Backup not found"))?;
+    let first = existing.first();
+    let filename = first.map_or(backup_filename.as_str(), |doc| doc.filename());
+    let metadata = first.map(|doc| doc.metadata.clone()).unwrap_or_default();
+    let access = first.map(|doc| doc.access.clone()).unwrap_or_default();
+
+    let mut documents: Vec<Document> =
+        build_chunk_documents(base_id, filename, &content, chunk_size)
+            .into_iter()
+            .map(|doc| {
+                doc.with_metadata(metadata.clone())
+                    .with_access(access.clone())
+            })
+            .collect();
+    if documents.is_empty() {
+        return Err(anyhow::anyhow!("Backup content is empty"));
+    }
+
+    let edited: HashSet<&str> = backups
+        .iter()
+        .filter(|b| b.doc_id != base_id && b.created_at > latest.created_at)
+        .map(|b| b.doc_id.as_str())
+        .collect();
+    for doc in &mut documents {
+        if edited.contains(doc.id.as_str())
+            && let Some((_, chunk_content)) = backup.read_backup(&doc.id).await?
+        {
+            doc.content = chunk_content;
+        }
+    }
+
+    let existing_ids: HashSet<&str> = existing.iter().map(|doc| doc.id.as_str()).collect();
+    let aligned = !existing.is_empty()
+        && existing
+            .iter()
+            .all(|doc| doc.total_chunks() == documents[0].total_chunks())
+        && existing_ids
+            .iter()
+            .all(|id| documents.iter().any(|doc| doc.id == *id));
+    if aligned {
+        let missing: Vec<Document> = documents
+            .into_iter()
+            .filter(|doc| !existing_ids.contains(doc.id.as_str()))
+            .collect();
+        let count = missing.len();
+        document_store
+            .add_documents_with_embeddings(missing, embedding_model.clone())
+            .await?;
+        info!(
+            "♻️ Restored {} missing chunk(s) of document {} from backup",
+            count, base_id
+        );
+    } else {
+        document_store
+            .replace_document(
+                &format!("{}_CHUNKED", base_id),
+                documents,
+                embedding_model.clone(),
+            )
+            .await?;
+        info!("♻️ Re-ingested document {} from backup", base_id);
+    }
+    Ok(())
+}
+
+/// 由现有分块内容重建备份
+async fn rebuild_backup(
+    backup: &FileBackup,
+    base_id: &str,
+    chunks: &[Document],
+) -> anyhow::Result<()> {
+    let filename = chunks[0].filename();
+    let content = chunks
+        .iter()
+        .map(|d| d.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    backup.save_backup(base_id, filename, &content).await?;
+    Ok(())
+}
+
+fn repair_action(target: String, action: &str, result: anyhow::Result<()>) -> RepairAction {
+    if let Err(e) = &result {
+        error!("Repair '{}' failed for {}: {}", action, target, e);
+    }
+    RepairAction {
+        target,
+        action: action.to_string(),
+        success: result.is_ok(),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(base_id: &str, idx: u32, total: u32) -> Document {
+        Document::new(
+            format!("{}-{}", base_id, idx),
+            base_id.to_string(),
+            Some(idx),
+            "content".to_string(),
+            format!("guide.md (Part {}/{})", idx + 1, total),
+            chrono::Utc::now(),
+        )
+    }
+
+    #[test]
+    fn test_part_suffix() {
+        let doc = chunk("a", 1, 5);
+        assert_eq!(doc.total_chunks(), Some(5));
+        assert_eq!(doc.filename(), "guide.md");
+
+        let mut doc = chunk("a", 0, 1);
+        doc.source = "notes (Part A).md".to_string();
+        assert_eq!(doc.total_chunks(), None);
+        assert_eq!(doc.filename(), "notes (Part A).md");
+    }
+
+    #[test]
+    fn test_find_chunk_gap() {
+        let complete = vec![chunk("a", 0, 2), chunk("a", 1, 2)];
+        assert!(find_chunk_gap("a", &complete).is_none());
+
+        // 末尾缺失的分块只能通过 source 中的总块数发现
+        let truncated = vec![chunk("b", 0, 4), chunk("b", 2, 4)];
+        let gap = find_chunk_gap("b", &truncated).unwrap();
+        assert_eq!(gap.expected_chunks, 4);
+        assert_eq!(gap.present, vec![0, 2]);
+        assert_eq!(gap.missing, vec![1, 3]);
+
+        let single = vec![Document::new(
+            "c".to_string(),
+            "c".to_string(),
+            None,
+            "content".to_string(),
+            "c.md".to_string(),
+            chrono::Utc::now(),
+        )];
+        assert!(find_chunk_gap("c", &single).is_none());
+    }
+}
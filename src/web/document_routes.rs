@@ -1,8 +1,8 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
     Router,
-    extract::{Json, Multipart, Path, Query, State},
+    extract::{ConnectInfo, Extension, Json, Multipart, Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Json as ResponseJson, Response},
     routing::{delete, get, post, put},
@@ -10,32 +10,49 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, warn};
 
-use crate::utils::DocumentParser;
+use crate::utils::{DocumentParser, FileBackup};
 use crate::{
     agent::RigAgent,
-    db::{Document, DocumentStore},
+    db::{AuditAction, Document, DocumentAccess, DocumentMetadata, DocumentStore},
+    web::{Claims, record_audit, workspace_handle},
 };
 
 // State 类型别名
 pub type AppState = (Arc<RigAgent>, Arc<DocumentStore>);
 
+/// 单个分块的默认最大字节数（知识库可单独配置）
+pub const DEFAULT_CHUNK_SIZE: usize = 8192;
+
 #[derive(Debug, Deserialize)]
 pub struct CreateDocumentRequest {
     pub filename: String,
     pub content: String,
+    #[serde(default)]
+    pub metadata: DocumentMetadata,
+    /// 可见范围，所有者为当前用户
+    #[serde(default)]
+    pub access: DocumentAccess,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateDocumentRequest {
     pub filename: Option<String>,
     pub content: String,
+    /// 不传则保留原有元数据
+    pub metadata: Option<DocumentMetadata>,
+    /// 不传则保留原有可见范围，所有者不会改变
+    pub access: Option<DocumentAccess>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct DocumentResponse {
     pub id: String,
+    /// 分块文档共享的 id
+    pub base_id: String,
     pub filename: String,
     pub content: String,
+    pub metadata: DocumentMetadata,
+    pub access: DocumentAccess,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -57,6 +74,8 @@ pub struct DocumentListItem {
     pub id: String,
     pub filename: String,
     pub preview: String,
+    pub metadata: DocumentMetadata,
+    pub access: DocumentAccess,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -70,14 +89,50 @@ impl From<Document> for DocumentResponse {
     fn from(doc: Document) -> Self {
         DocumentResponse {
             id: doc.id,
+            base_id: doc.base_id,
             filename: doc.source, // 使用 source 作为 filename
             content: doc.content,
+            metadata: doc.metadata,
+            access: doc.access,
             created_at: doc.created_at.to_rfc3339(),
             updated_at: doc.updated_at.to_rfc3339(),
         }
     }
 }
 
+/// 审计日志中的文档摘要
+fn document_summary(doc: &Document) -> serde_json::Value {
+    serde_json::json!({
+        "filename": doc.filename(),
+        "chars": doc.content.chars().count(),
+        "metadata": doc.metadata,
+        "access": doc.access,
+    })
+}
+
+/// 记录新建文档的审计日志
+async fn audit_created_document(
+    claims: &Claims,
+    addr: SocketAddr,
+    filename: &str,
+    doc: &DocumentResponse,
+) {
+    let summary = serde_json::json!({
+        "filename": filename,
+        "metadata": doc.metadata,
+        "access": doc.access,
+    });
+    record_audit(
+        claims,
+        addr,
+        AuditAction::DocumentCreated,
+        doc.base_id.clone(),
+        None,
+        Some(summary),
+    )
+    .await;
+}
+
 /// 创建文档路由 - 查询操作
 pub fn create_document_query_router() -> Router<AppState> {
     Router::new()
@@ -96,12 +151,16 @@ pub fn create_document_mutation_router() -> Router<AppState> {
 }
 
 async fn list_documents(
-    State((_, document_store)): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Query(p): Query<PaginationQuery>,
 ) -> Result<ResponseJson<DocumentListResponse>, StatusCode> {
+    let document_store = workspace_handle(&claims).await?.document_store.clone();
     let limit = p.limit.unwrap_or(20).clamp(1, 1000);
     let offset = p.offset.unwrap_or(0);
-    match document_store.list_documents_paginated(limit, offset).await {
+    match document_store
+        .list_documents_paginated(limit, offset, &claims.viewer())
+        .await
+    {
         Ok((docs, total)) => {
             let documents = docs
                 .into_iter()
@@ -114,6 +173,8 @@ async fn list_documents(
                         id: doc.id,
                         filename: doc.source,
                         preview,
+                        metadata: doc.metadata,
+                        access: doc.access,
                         created_at: doc.created_at.to_rfc3339(),
                         updated_at: doc.updated_at.to_rfc3339(),
                     }
@@ -134,12 +195,16 @@ async fn list_documents(
 }
 
 async fn get_document(
-    State((_, document_store)): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<String>,
 ) -> Result<ResponseJson<DocumentResponse>, StatusCode> {
+    let document_store = workspace_handle(&claims).await?.document_store.clone();
     match document_store.get_document(&id).await {
-        Ok(Some(doc)) => Ok(ResponseJson(DocumentResponse::from(doc))),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        // 不可见的文档按不存在处理，避免泄露 id 是否存在
+        Ok(Some(doc)) if doc.access.can_view(&claims.viewer()) => {
+            Ok(ResponseJson(DocumentResponse::from(doc)))
+        }
+        Ok(_) => Err(StatusCode::NOT_FOUND),
         Err(e) => {
             error!("Failed to get document: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -148,35 +213,110 @@ async fn get_document(
 }
 
 async fn create_document(
-    State((agent, document_store)): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(req): Json<CreateDocumentRequest>,
 ) -> Response {
     info!("Creating document");
+    let handle = match workspace_handle(&claims).await {
+        Ok(handle) => handle,
+        Err(status) => return status.into_response(),
+    };
+
+    let metadata = match req.metadata.normalized() {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                ResponseJson(ErrorResponse {
+                    error: format!("元数据无效: {}", e),
+                }),
+            )
+                .into_response();
+        }
+    };
+    let access = match req.access.normalized() {
+        Ok(access) => access.owned_by(claims.user_id, &claims.sub),
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                ResponseJson(ErrorResponse {
+                    error: format!("访问控制无效: {}", e),
+                }),
+            )
+                .into_response();
+        }
+    };
 
-    process_and_save_document(
-        agent,
-        document_store,
+    match process_and_save_document(
+        handle.agent.clone(),
+        handle.document_store.clone(),
+        handle.backup(),
+        handle.chunk_size,
         &req.filename,
         &req.content,
+        metadata,
+        access,
         "Created",
     )
     .await
-    .map_err(|(status, error)| (status, ResponseJson(ErrorResponse { error })))
-    .into_response()
+    {
+        Ok(response) => {
+            audit_created_document(&claims, addr, &req.filename, &response.0).await;
+            response.into_response()
+        }
+        Err((status, error)) => (status, ResponseJson(ErrorResponse { error })).into_response(),
+    }
 }
 
 async fn update_document(
-    State((agent, document_store)): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Path(id): Path<String>,
     Json(req): Json<UpdateDocumentRequest>,
 ) -> Result<ResponseJson<DocumentResponse>, StatusCode> {
     info!("Updating document");
+    let handle = workspace_handle(&claims).await?;
+    let (agent, document_store) = (&handle.agent, &handle.document_store);
+    let metadata = match req.metadata.map(DocumentMetadata::normalized).transpose() {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            warn!("Invalid document metadata: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+    let access = match req.access.map(DocumentAccess::normalized).transpose() {
+        Ok(access) => access,
+        Err(e) => {
+            warn!("Invalid document access: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+    let viewer = claims.viewer();
+
     match document_store.get_document(&id).await {
+        Ok(Some(doc)) if !doc.access.can_view(&viewer) => Err(StatusCode::NOT_FOUND),
+        Ok(Some(doc)) if !doc.access.can_edit(&viewer) => {
+            warn!("User {} is not allowed to edit document {}", claims.sub, id);
+            Err(StatusCode::FORBIDDEN)
+        }
         Ok(Some(mut doc)) => {
+            let before = document_summary(&doc);
             doc.content = req.content.clone();
             if let Some(filename) = req.filename.clone() {
                 doc.source = filename;
             }
+            let (metadata_changed, access_changed) = (metadata.is_some(), access.is_some());
+            if let Some(metadata) = metadata {
+                doc.metadata = metadata;
+            }
+            if let Some(access) = access {
+                doc.access = DocumentAccess {
+                    owner_id: doc.access.owner_id,
+                    owner: doc.access.owner.take(),
+                    ..access
+                };
+            }
             doc.updated_at = chrono::Utc::now();
 
             // 删除旧文档并添加新文档
@@ -197,8 +337,26 @@ async fn update_document(
                 Ok(_) => {
                     info!("Updated document: {}", doc.id);
 
+                    // 元数据和可见范围属于整个文档，同步到其他分块
+                    if metadata_changed
+                        && let Err(e) = document_store
+                            .set_document_metadata(&doc.base_id, &doc.metadata)
+                            .await
+                    {
+                        error!("Failed to update metadata of other chunks: {}", e);
+                        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                    }
+                    if access_changed
+                        && let Err(e) = document_store
+                            .set_document_access(&doc.base_id, &doc.access)
+                            .await
+                    {
+                        error!("Failed to update access of other chunks: {}", e);
+                        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                    }
+
                     // 保存文件备份
-                    if let Some(backup) = crate::utils::get_file_backup() {
+                    if let Some(backup) = handle.backup() {
                         match backup.save_backup(&doc.id, &doc.source, &doc.content).await {
                             Ok(path) => {
                                 info!("💾 Updated backup to: {:?}", path);
@@ -209,9 +367,15 @@ async fn update_document(
                         }
                     }
 
-                    // 标记agent需要重建以使用更新的文档
-                    agent.set_needs_rebuild(true).await;
-                    info!("Marked agent for rebuild due to updated document");
+                    record_audit(
+                        &claims,
+                        addr,
+                        AuditAction::DocumentUpdated,
+                        doc.base_id.clone(),
+                        Some(before),
+                        Some(document_summary(&doc)),
+                    )
+                    .await;
 
                     Ok(ResponseJson(DocumentResponse::from(doc)))
                 }
@@ -230,12 +394,24 @@ async fn update_document(
 }
 
 async fn delete_document(
-    State((agent, document_store)): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Path(id): Path<String>,
 ) -> Result<StatusCode, StatusCode> {
     info!("Deleting document: {}", id);
+    let handle = workspace_handle(&claims).await?;
+    let document_store = &handle.document_store;
+    let viewer = claims.viewer();
     // 首先检查这个文档是否存在，以及是否是分块文档
     match document_store.get_document(&id).await {
+        Ok(Some(doc)) if !doc.access.can_view(&viewer) => Err(StatusCode::NOT_FOUND),
+        Ok(Some(doc)) if !doc.access.can_edit(&viewer) => {
+            warn!(
+                "User {} is not allowed to delete document {}",
+                claims.sub, id
+            );
+            Err(StatusCode::FORBIDDEN)
+        }
         Ok(Some(doc)) => {
             // 使用结构化字段判断是否为分块文档
             let is_chunked = doc.chunk_index.is_some() || doc.id != doc.base_id;
@@ -252,7 +428,7 @@ async fn delete_document(
                     info!("Deleted document(s) with base ID: {}", backup_id);
 
                     // 删除文件备份
-                    if let Some(backup) = crate::utils::get_file_backup() {
+                    if let Some(backup) = handle.backup() {
                         match backup.delete_backup(&backup_id).await {
                             Ok(count) => {
                                 info!("🗑️  Deleted {} backup file(s) for ID: {}", count, backup_id);
@@ -263,9 +439,15 @@ async fn delete_document(
                         }
                     }
 
-                    // 🔧 标记agent需要重建以排除已删除的文档
-                    agent.set_needs_rebuild(true).await;
-                    info!("Marked agent for rebuild due to document deletion");
+                    record_audit(
+                        &claims,
+                        addr,
+                        AuditAction::DocumentDeleted,
+                        backup_id,
+                        Some(document_summary(&doc)),
+                        None,
+                    )
+                    .await;
 
                     Ok(StatusCode::NO_CONTENT)
                 }
@@ -287,12 +469,19 @@ async fn delete_document(
 }
 
 async fn upload_document(
-    State((agent, document_store)): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     mut multipart: Multipart,
 ) -> Response {
     info!("Uploading document");
+    let handle = match workspace_handle(&claims).await {
+        Ok(handle) => handle,
+        Err(status) => return status.into_response(),
+    };
     let mut filename = String::new();
     let mut file_data = None;
+    let mut metadata = DocumentMetadata::default();
+    let mut access = DocumentAccess::default();
 
     // 读取multipart字段
     loop {
@@ -332,6 +521,44 @@ async fn upload_document(
                     "file" => {
                         file_data = Some(data);
                     }
+                    "metadata" => {
+                        // JSON 格式的文档元数据
+                        metadata = match serde_json::from_slice::<DocumentMetadata>(&data)
+                            .map_err(anyhow::Error::from)
+                            .and_then(DocumentMetadata::normalized)
+                        {
+                            Ok(m) => m,
+                            Err(e) => {
+                                error!("Invalid document metadata: {}", e);
+                                return (
+                                    StatusCode::BAD_REQUEST,
+                                    ResponseJson(ErrorResponse {
+                                        error: format!("元数据无效: {}", e),
+                                    }),
+                                )
+                                    .into_response();
+                            }
+                        };
+                    }
+                    "access" => {
+                        // JSON 格式的可见范围
+                        access = match serde_json::from_slice::<DocumentAccess>(&data)
+                            .map_err(anyhow::Error::from)
+                            .and_then(DocumentAccess::normalized)
+                        {
+                            Ok(a) => a,
+                            Err(e) => {
+                                error!("Invalid document access: {}", e);
+                                return (
+                                    StatusCode::BAD_REQUEST,
+                                    ResponseJson(ErrorResponse {
+                                        error: format!("访问控制无效: {}", e),
+                                    }),
+                                )
+                                    .into_response();
+                            }
+                        };
+                    }
                     _ => {}
                 }
             }
@@ -395,8 +622,23 @@ async fn upload_document(
     );
 
     // 处理文档
-    match process_and_save_document(agent, document_store, &filename, &content, "Uploaded").await {
-        Ok(response) => response.into_response(),
+    match process_and_save_document(
+        handle.agent.clone(),
+        handle.document_store.clone(),
+        handle.backup(),
+        handle.chunk_size,
+        &filename,
+        &content,
+        metadata,
+        access.owned_by(claims.user_id, &claims.sub),
+        "Uploaded",
+    )
+    .await
+    {
+        Ok(response) => {
+            audit_created_document(&claims, addr, &filename, &response.0).await;
+            response.into_response()
+        }
         Err(status) => {
             error!("Failed to upload document: {}", status.1);
             (status.0, ResponseJson(ErrorResponse { error: status.1 })).into_response()
@@ -406,17 +648,13 @@ async fn upload_document(
 
 #[allow(dead_code)]
 async fn reset_documents(
-    State((agent, document_store)): State<AppState>,
+    State((_, document_store)): State<AppState>,
 ) -> Result<StatusCode, StatusCode> {
     info!("Resetting document store");
     match document_store.reset_table().await {
         Ok(_) => {
             info!("Successfully reset document store");
 
-            // 标记agent需要重建
-            agent.set_needs_rebuild(true).await;
-            info!("Marked agent for rebuild due to document store reset");
-
             Ok(StatusCode::OK)
         }
         Err(e) => {
@@ -427,11 +665,16 @@ async fn reset_documents(
 }
 
 /// 处理并保存文档（包含分块、embedding、备份）
-async fn process_and_save_document(
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn process_and_save_document(
     agent: Arc<RigAgent>,
     document_store: Arc<DocumentStore>,
+    backup: Option<&FileBackup>,
+    chunk_size: usize,
     filename: &str,
     content: &str,
+    metadata: DocumentMetadata,
+    access: DocumentAccess,
     action: &str, // "Created" 或 "Uploaded"
 ) -> Result<ResponseJson<DocumentResponse>, (StatusCode, String)> {
     // 检查文件是否为空
@@ -440,10 +683,16 @@ async fn process_and_save_document(
         return Err((StatusCode::BAD_REQUEST, "文件内容不能为空".to_string()));
     }
 
-    // 将文档内容分块处理
-    const MAX_CHUNK_SIZE: usize = 8192;
-    let chunks = chunk_document(content, MAX_CHUNK_SIZE);
-    let total_chunks = chunks.len();
+    // 将文档内容分块处理，为每个块创建一个Document
+    let base_id = nanoid::nanoid!();
+    let documents: Vec<Document> = build_chunk_documents(&base_id, filename, content, chunk_size)
+        .into_iter()
+        .map(|doc| {
+            doc.with_metadata(metadata.clone())
+                .with_access(access.clone())
+        })
+        .collect();
+    let total_chunks = documents.len();
 
     // 双重检查：确保chunks不为空
     if total_chunks == 0 {
@@ -456,39 +705,6 @@ async fn process_and_save_document(
 
     info!("Split document '{}' into {} chunks", filename, total_chunks);
 
-    // 为每个块创建一个Document
-    let base_id = nanoid::nanoid!();
-    let documents: Vec<Document> = chunks
-        .into_iter()
-        .enumerate()
-        .map(|(idx, chunk_content)| {
-            let source = if total_chunks > 1 {
-                format!("{} (Part {}/{})", filename, idx + 1, total_chunks)
-            } else {
-                filename.to_string()
-            };
-            let id = if total_chunks == 1 {
-                base_id.clone()
-            } else {
-                format!("{}-{}", base_id, idx)
-            };
-            let timestamp = chrono::Utc::now();
-            Document {
-                id,
-                base_id: base_id.clone(),
-                chunk_index: if total_chunks == 1 {
-                    None
-                } else {
-                    Some(idx as u32)
-                },
-                content: chunk_content,
-                source,
-                created_at: timestamp,
-                updated_at: timestamp,
-            }
-        })
-        .collect();
-
     // 获取 embedding model 从 agent context
     let embedding_model = {
         let context = agent.context.read();
@@ -509,7 +725,7 @@ async fn process_and_save_document(
             );
 
             // 保存文件备份
-            if let Some(backup) = crate::utils::get_file_backup() {
+            if let Some(backup) = backup {
                 match backup.save_backup(&base_id, filename, content).await {
                     Ok(path) => {
                         info!("💾 Saved backup to: {:?}", path);
@@ -520,13 +736,6 @@ async fn process_and_save_document(
                 }
             }
 
-            // 标记agent需要重建以使用新文档
-            agent.set_needs_rebuild(true).await;
-            info!(
-                "Marked agent for rebuild due to {} document",
-                action.to_lowercase()
-            );
-
             Ok(ResponseJson(DocumentResponse::from(documents[0].clone())))
         }
         Err(e) => {
@@ -539,6 +748,52 @@ async fn process_and_save_document(
     }
 }
 
+/// 将文档内容分块并构建 Document 列表（共享同一个 base_id）
+///
+/// 单块文档的 id 即 base_id；多块文档的 id 为 `{base_id}-{idx}`，source 带 `(Part i/N)` 后缀
+pub(crate) fn build_chunk_documents(
+    base_id: &str,
+    filename: &str,
+    content: &str,
+    chunk_size: usize,
+) -> Vec<Document> {
+    let chunks = chunk_document(content, chunk_size);
+    let total_chunks = chunks.len();
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(idx, chunk_content)| {
+            let source = if total_chunks > 1 {
+                format!("{} (Part {}/{})", filename, idx + 1, total_chunks)
+            } else {
+                filename.to_string()
+            };
+            let id = if total_chunks == 1 {
+                base_id.to_string()
+            } else {
+                format!("{}-{}", base_id, idx)
+            };
+            let timestamp = chrono::Utc::now();
+            Document {
+                id,
+                base_id: base_id.to_string(),
+                chunk_index: if total_chunks == 1 {
+                    None
+                } else {
+                    Some(idx as u32)
+                },
+                content: chunk_content,
+                source,
+                created_at: timestamp,
+                updated_at: timestamp,
+                metadata: DocumentMetadata::default(),
+                access: DocumentAccess::default(),
+            }
+        })
+        .collect()
+}
+
 /// 智能分块文本，尝试在句子边界处分割，保持表格完整性
 ///
 /// 这个函数将大文档分成小块，避免超过embedding模型的token限制
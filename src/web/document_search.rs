@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use axum::{
+    Router,
+    extract::{Extension, Query},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::get,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    db::{Document, DocumentFilter},
+    web::{AppState, Claims, knowledge_base_handle},
+};
+
+/// 关键词检索参与融合的最大候选数（按关键词得分取前 N 个）
+const KEYWORD_CANDIDATES: usize = 200;
+/// 混合检索 RRF 融合常数
+const RRF_K: f64 = 60.0;
+/// 片段最大长度（字符）
+const SNIPPET_CHARS: usize = 160;
+
+/// 检索模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    Semantic,
+    Keyword,
+    #[default]
+    Hybrid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DocumentSearchQuery {
+    pub q: String,
+    pub mode: Option<SearchMode>,
+    pub limit: Option<usize>,
+    /// source 包含的文本
+    pub source: Option<String>,
+    /// 更新时间下限（RFC3339 或 YYYY-MM-DD）
+    pub from: Option<String>,
+    /// 更新时间上限（RFC3339 或 YYYY-MM-DD，包含当天）
+    pub to: Option<String>,
+    /// 检索的知识库，不传时为工作区的默认知识库
+    pub knowledge_base: Option<String>,
+}
+
+/// 命中的分块
+#[derive(Debug, Serialize)]
+pub struct DocumentSearchHit {
+    pub id: String,
+    pub chunk_index: Option<u32>,
+    pub source: String,
+    pub score: f64,
+    pub semantic_score: Option<f64>,
+    pub keyword_score: Option<f64>,
+    /// HTML 转义后的片段，关键词以 <mark> 标记
+    pub snippet: String,
+    pub updated_at: String,
+}
+
+/// 按 base_id 分组的检索结果
+#[derive(Debug, Serialize)]
+pub struct DocumentSearchGroup {
+    pub base_id: String,
+    pub filename: String,
+    pub score: f64,
+    pub hits: Vec<DocumentSearchHit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DocumentSearchResponse {
+    pub query: String,
+    pub mode: SearchMode,
+    pub total_hits: usize,
+    pub groups: Vec<DocumentSearchGroup>,
+}
+
+/// 创建文档检索路由（需要 document.read 权限，与文档查询路由一起挂载）
+pub fn create_document_search_router() -> Router<AppState> {
+    Router::new().route("/api/documents/search", get(search_documents))
+}
+
+async fn search_documents(
+    Extension(claims): Extension<Claims>,
+    Query(req): Query<DocumentSearchQuery>,
+) -> Result<ResponseJson<DocumentSearchResponse>, StatusCode> {
+    let handle = knowledge_base_handle(&claims, req.knowledge_base.as_deref()).await?;
+    let (agent, document_store) = (&handle.agent, &handle.document_store);
+    let query = req.q.trim().to_string();
+    if query.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mode = req.mode.unwrap_or_default();
+    let limit = req.limit.unwrap_or(10).clamp(1, 50);
+    let filter = DocumentFilter {
+        source: req.source,
+        updated_from: parse_date_param(req.from.as_deref(), false)?,
+        updated_to: parse_date_param(req.to.as_deref(), true)?,
+        viewer: claims.viewer(),
+    };
+    let terms = search_terms(&query);
+
+    info!("Searching documents ({:?}): {}", mode, query);
+
+    // 语义检索结果
+    let semantic = if mode != SearchMode::Keyword {
+        let embedding_model = {
+            let context = agent.context.read();
+            context.embedding_model.clone()
+        };
+        document_store
+            .semantic_search(&embedding_model, &query, limit * 2, &filter)
+            .await
+            .map_err(|e| {
+                error!("Semantic search failed: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+    } else {
+        Vec::new()
+    };
+
+    // 关键词检索结果
+    let keyword = if mode != SearchMode::Semantic {
+        document_store
+            .keyword_search(&terms, KEYWORD_CANDIDATES, &filter, |doc| {
+                keyword_score(&doc.content, &terms)
+            })
+            .await
+            .map_err(|e| {
+                error!("Keyword search failed: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+    } else {
+        Vec::new()
+    };
+
+    let hits = merge_hits(mode, semantic, keyword, &terms, limit);
+    let total_hits = hits.len();
+    let groups = group_hits(hits);
+
+    Ok(ResponseJson(DocumentSearchResponse {
+        query,
+        mode,
+        total_hits,
+        groups,
+    }))
+}
+
+/// 合并两路检索结果
+///
+/// 混合模式使用 Reciprocal Rank Fusion，不依赖两路分数的量纲
+fn merge_hits(
+    mode: SearchMode,
+    semantic: Vec<(f64, Document)>,
+    keyword: Vec<(f64, Document)>,
+    terms: &[String],
+    limit: usize,
+) -> Vec<(DocumentSearchHit, Document)> {
+    struct Candidate {
+        doc: Document,
+        semantic: Option<(usize, f64)>,
+        keyword: Option<(usize, f64)>,
+    }
+
+    let mut candidates: HashMap<String, Candidate> = HashMap::new();
+    for (rank, (score, doc)) in semantic.into_iter().enumerate() {
+        candidates
+            .entry(doc.id.clone())
+            .or_insert_with(|| Candidate {
+                doc,
+                semantic: None,
+                keyword: None,
+            })
+            .semantic = Some((rank, score));
+    }
+    for (rank, (score, doc)) in keyword.into_iter().enumerate() {
+        candidates
+            .entry(doc.id.clone())
+            .or_insert_with(|| Candidate {
+                doc,
+                semantic: None,
+                keyword: None,
+            })
+            .keyword = Some((rank, score));
+    }
+
+    let mut hits: Vec<(DocumentSearchHit, Document)> = candidates
+        .into_values()
+        .map(|c| {
+            let score = match mode {
+                SearchMode::Semantic => c.semantic.map(|(_, s)| s).unwrap_or_default(),
+                SearchMode::Keyword => c.keyword.map(|(_, s)| s).unwrap_or_default(),
+                SearchMode::Hybrid => [c.semantic, c.keyword]
+                    .iter()
+                    .flatten()
+                    .map(|(rank, _)| 1.0 / (RRF_K + *rank as f64 + 1.0))
+                    .sum(),
+            };
+            let hit = DocumentSearchHit {
+                id: c.doc.id.clone(),
+                chunk_index: c.doc.chunk_index,
+                source: c.doc.source.clone(),
+                score,
+                semantic_score: c.semantic.map(|(_, s)| s),
+                keyword_score: c.keyword.map(|(_, s)| s),
+                snippet: highlight_snippet(&c.doc.content, terms, SNIPPET_CHARS),
+                updated_at: c.doc.updated_at.to_rfc3339(),
+            };
+            (hit, c.doc)
+        })
+        .collect();
+
+    hits.sort_by(|a, b| b.0.score.total_cmp(&a.0.score));
+    hits.truncate(limit);
+    hits
+}
+
+/// 按 base_id 分组，组的分数取组内最高分
+fn group_hits(hits: Vec<(DocumentSearchHit, Document)>) -> Vec<DocumentSearchGroup> {
+    let mut groups: Vec<DocumentSearchGroup> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    // hits 已按分数倒序，组按首次出现顺序即为按最高分排序
+    for (hit, doc) in hits {
+        match index.get(&doc.base_id) {
+            Some(&idx) => groups[idx].hits.push(hit),
+            None => {
+                index.insert(doc.base_id.clone(), groups.len());
+                groups.push(DocumentSearchGroup {
+                    base_id: doc.base_id.clone(),
+                    filename: doc.filename().to_string(),
+                    score: hit.score,
+                    hits: vec![hit],
+                });
+            }
+        }
+    }
+
+    groups
+}
+
+/// 解析日期参数，仅有日期时 `end_of_day` 决定取当天开始还是结束
+pub(crate) fn parse_date_param(
+    value: Option<&str>,
+    end_of_day: bool,
+) -> Result<Option<DateTime<Utc>>, StatusCode> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(Some(datetime.with_timezone(&Utc)));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| StatusCode::BAD_REQUEST)?;
+    let datetime = if end_of_day {
+        date.and_hms_milli_opt(23, 59, 59, 999)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(datetime.map(|dt| dt.and_utc()))
+}
+
+/// 大小写折叠（逐字符，保持字符位置一一对应）
+fn fold_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// 将查询拆分为去重后的关键词
+fn search_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for term in query.split_whitespace() {
+        let term: String = term.chars().map(fold_char).collect();
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms.truncate(10);
+    terms
+}
+
+/// 关键词得分：各关键词饱和词频的平均值，范围 [0, 1)
+fn keyword_score(content: &str, terms: &[String]) -> f64 {
+    if terms.is_empty() {
+        return 0.0;
+    }
+
+    let folded: String = content.chars().map(fold_char).collect();
+    let total: f64 = terms
+        .iter()
+        .map(|term| {
+            let tf = folded.matches(term.as_str()).count() as f64;
+            tf / (tf + 1.2)
+        })
+        .sum();
+    total / terms.len() as f64
+}
+
+/// 生成以首个命中为中心的片段，关键词以 <mark> 包裹，其余内容做 HTML 转义
+fn highlight_snippet(content: &str, terms: &[String], max_chars: usize) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let folded: Vec<char> = chars.iter().map(|c| fold_char(*c)).collect();
+    let terms: Vec<Vec<char>> = terms
+        .iter()
+        .map(|t| t.chars().collect::<Vec<char>>())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    // 找到所有不重叠的命中区间（优先最长匹配）
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < folded.len() {
+        let matched = terms
+            .iter()
+            .filter(|t| folded[i..].starts_with(t))
+            .map(|t| t.len())
+            .max();
+        match matched {
+            Some(len) => {
+                ranges.push((i, i + len));
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+
+    let start = ranges
+        .first()
+        .map(|(s, _)| s.saturating_sub(max_chars / 4))
+        .unwrap_or(0);
+    let end = (start + max_chars).min(chars.len());
+
+    let mut snippet = String::with_capacity(max_chars + 32);
+    if start > 0 {
+        snippet.push_str("...");
+    }
+    let mut ranges = ranges.into_iter().peekable();
+    let mut open_until = None;
+    for (idx, c) in chars.iter().enumerate().take(end).skip(start) {
+        while ranges.peek().is_some_and(|(_, e)| *e <= idx) {
+            ranges.next();
+        }
+        if open_until.is_none()
+            && let Some(&(s, e)) = ranges.peek()
+            && s <= idx
+        {
+            snippet.push_str("<mark>");
+            open_until = Some(e);
+        }
+
+        match c {
+            '&' => snippet.push_str("&amp;"),
+            '<' => snippet.push_str("&lt;"),
+            '>' => snippet.push_str("&gt;"),
+            '"' => snippet.push_str("&quot;"),
+            '\'' => snippet.push_str("&#39;"),
+            '\n' | '\r' => snippet.push(' '),
+            _ => snippet.push(*c),
+        }
+
+        if open_until.is_some_and(|e| e == idx + 1) {
+            snippet.push_str("</mark>");
+            open_until = None;
+        }
+    }
+    if open_until.is_some() {
+        snippet.push_str("</mark>");
+    }
+    if end < chars.len() {
+        snippet.push_str("...");
+    }
+
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_score() {
+        let terms = search_terms("Rust rust 向量");
+        assert_eq!(terms, vec!["rust", "向量"]);
+        assert_eq!(keyword_score("nothing here", &terms), 0.0);
+        assert!(keyword_score("Rust 向量 rust", &terms) > keyword_score("Rust only", &terms));
+    }
+
+    #[test]
+    fn test_highlight_snippet() {
+        let terms = search_terms("qdrant");
+        assert_eq!(
+            highlight_snippet("Use <Qdrant> & more", &terms, 160),
+            "Use &lt;<mark>Qdrant</mark>&gt; &amp; more"
+        );
+
+        let content = format!("{}Qdrant{}", "a".repeat(100), "b".repeat(100));
+        let snippet = highlight_snippet(&content, &terms, 20);
+        assert!(snippet.starts_with("..."));
+        assert!(snippet.ends_with("..."));
+        assert!(snippet.contains("<mark>Qdrant</mark>"));
+
+        assert_eq!(highlight_snippet("短文本", &[], 160), "短文本");
+    }
+
+    #[test]
+    fn test_parse_date_param() {
+        let from = parse_date_param(Some("2024-03-01"), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(from.to_rfc3339(), "2024-03-01T00:00:00+00:00");
+        let to = parse_date_param(Some("2024-03-01"), true).unwrap().unwrap();
+        assert!(to > from);
+        assert!(parse_date_param(Some("yesterday"), false).is_err());
+        assert!(parse_date_param(None, false).unwrap().is_none());
+    }
+}
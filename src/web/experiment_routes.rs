@@ -0,0 +1,267 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Extension, Path, State},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::auth_routes::{AppError, Claims, require_permission};
+use crate::{
+    agent::RigAgent,
+    config::get_config,
+    db::{
+        AuditAction, ConversationStore, DocumentStore, Experiment, ExperimentStore, ExperimentUnit,
+        ExperimentVariant, ExperimentVariantStats, Permission, get_experiment_store,
+        get_preamble_store, validate_variants,
+    },
+    web::record_audit,
+};
+
+type AppState = (Arc<RigAgent>, Arc<DocumentStore>, Arc<ConversationStore>);
+
+#[derive(Debug, Deserialize)]
+pub struct CreateExperimentRequest {
+    pub name: String,
+    /// 按用户（默认）或按对话分组
+    #[serde(default)]
+    pub unit: ExperimentUnit,
+    pub variants: Vec<ExperimentVariant>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExperimentReport {
+    pub experiment: Experiment,
+    pub variants: Vec<ExperimentVariantStats>,
+}
+
+/// 创建 A/B 实验路由（experiment.manage），作用于当前工作区
+pub fn create_experiment_router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/admin/experiments",
+            get(list_experiments).post(create_experiment),
+        )
+        .route(
+            "/api/admin/experiments/{id}",
+            get(get_experiment).delete(delete_experiment),
+        )
+        .route("/api/admin/experiments/{id}/stop", post(stop_experiment))
+        .route(
+            "/api/admin/experiments/{id}/report",
+            get(get_experiment_report),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            Permission::ExperimentManage,
+            require_permission,
+        ))
+}
+
+fn experiment_store() -> Result<&'static ExperimentStore, AppError> {
+    get_experiment_store()
+        .map(Arc::as_ref)
+        .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Experiment store not initialized")))
+}
+
+async fn load_experiment(claims: &Claims, id: i64) -> Result<Experiment, AppError> {
+    experiment_store()?
+        .get(&claims.workspace, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Experiment not found".to_string()))
+}
+
+/// 审计日志中的实验摘要
+fn experiment_summary(experiment: &Experiment) -> serde_json::Value {
+    serde_json::json!({
+        "name": experiment.name,
+        "unit": experiment.unit,
+        "status": experiment.status,
+        "variants": experiment.variants,
+    })
+}
+
+/// 变体不能绕过 preamble 审批和聊天接口的模型白名单
+///
+/// preamble 版本必须存在；需要审批时只能使用当前生效的版本或审批发布的版本。
+/// 模型必须在 `chat.allowed_models` 中
+async fn validate_variant_sources(
+    workspace: &str,
+    variant: &ExperimentVariant,
+) -> Result<(), AppError> {
+    if let Some(version) = variant.preamble_version {
+        let store = get_preamble_store().ok_or_else(|| {
+            AppError::BadRequest("Preamble versions are not available".to_string())
+        })?;
+        if store.get_version(workspace, version).await?.is_none() {
+            return Err(AppError::BadRequest(format!(
+                "Variant {} references unknown preamble version {}",
+                variant.id, version
+            )));
+        }
+        let approved = !get_config().auth.preamble_require_approval
+            || store.live_version(workspace).await? == Some(version)
+            || store.is_published_draft(workspace, version).await?;
+        if !approved {
+            return Err(AppError::BadRequest(format!(
+                "Variant {} references preamble version {} which was not approved",
+                variant.id, version
+            )));
+        }
+    }
+    if let Some(model) = &variant.model
+        && !get_config()
+            .chat
+            .allowed_models
+            .iter()
+            .any(|m| m == model.trim())
+    {
+        return Err(AppError::BadRequest(format!(
+            "Variant {} model {} is not in the allowed models",
+            variant.id, model
+        )));
+    }
+    Ok(())
+}
+
+async fn list_experiments(
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<Vec<Experiment>>, AppError> {
+    Ok(Json(experiment_store()?.list(&claims.workspace).await?))
+}
+
+/// 创建并开始实验，工作区已有运行中的实验时返回 409
+async fn create_experiment(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(req): Json<CreateExperimentRequest>,
+) -> Result<(StatusCode, Json<Experiment>), AppError> {
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("Name is required".to_string()));
+    }
+    validate_variants(&req.variants).map_err(AppError::BadRequest)?;
+    for variant in &req.variants {
+        validate_variant_sources(&claims.workspace, variant).await?;
+    }
+
+    let store = experiment_store()?;
+    if let Some(running) = store.running(&claims.workspace).await? {
+        return Err(AppError::Conflict(format!(
+            "Experiment {} is already running",
+            running.id
+        )));
+    }
+    let experiment = store
+        .create(&claims.workspace, name, req.unit, req.variants, &claims.sub)
+        .await?;
+
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::ExperimentCreated,
+        format!("experiment:{}", experiment.id),
+        None,
+        Some(experiment_summary(&experiment)),
+    )
+    .await;
+    info!(
+        "🧪 {} started experiment {} ({})",
+        claims.sub, experiment.id, experiment.name
+    );
+    Ok((StatusCode::CREATED, Json(experiment)))
+}
+
+async fn get_experiment(
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+) -> Result<Json<Experiment>, AppError> {
+    Ok(Json(load_experiment(&claims, id).await?))
+}
+
+/// 停止实验，之后的聊天恢复使用工作区当前的配置
+async fn stop_experiment(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<i64>,
+) -> Result<Json<Experiment>, AppError> {
+    let store = experiment_store()?;
+    let before = load_experiment(&claims, id).await?;
+    if !store.stop(&claims.workspace, id).await? {
+        return Err(AppError::Conflict("Experiment is not running".to_string()));
+    }
+    let experiment = load_experiment(&claims, id).await?;
+
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::ExperimentStopped,
+        format!("experiment:{}", id),
+        Some(experiment_summary(&before)),
+        Some(experiment_summary(&experiment)),
+    )
+    .await;
+    Ok(Json(experiment))
+}
+
+/// 删除实验，已记录在消息中的变体不受影响
+async fn delete_experiment(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let experiment = load_experiment(&claims, id).await?;
+    experiment_store()?.delete(&claims.workspace, id).await?;
+
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::ExperimentDeleted,
+        format!("experiment:{}", id),
+        Some(experiment_summary(&experiment)),
+        None,
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 各变体的对话数、转人工率、平均对话长度和评分
+async fn get_experiment_report(
+    State((_, _, conversation_store)): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<i64>,
+) -> Result<Json<ExperimentReport>, AppError> {
+    let experiment = load_experiment(&claims, id).await?;
+    let stats = conversation_store
+        .experiment_report(&claims.workspace, id)
+        .await?;
+
+    // 没有数据的变体也列出
+    let variants = experiment
+        .variants
+        .iter()
+        .map(|variant| {
+            stats
+                .iter()
+                .find(|s| s.variant == variant.id)
+                .cloned()
+                .unwrap_or_else(|| ExperimentVariantStats {
+                    variant: variant.id.clone(),
+                    conversations: 0,
+                    escalated_conversations: 0,
+                    escalation_rate: 0.0,
+                    avg_messages: 0.0,
+                    ratings: 0,
+                    avg_rating: None,
+                })
+        })
+        .collect();
+
+    Ok(Json(ExperimentReport {
+        experiment,
+        variants,
+    }))
+}
@@ -0,0 +1,658 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    Router,
+    extract::{ConnectInfo, Extension, Json, Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Json as ResponseJson, Response},
+    routing::{delete, get},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    agent::{
+        DEFAULT_KNOWLEDGE_BASE, KnowledgeBaseHandle, KnowledgeBaseRegistry, get_knowledge_bases,
+        validate_preamble,
+    },
+    db::{
+        AuditAction, CreateKnowledgeBaseRequest, DocumentAccess, DocumentMetadata, KnowledgeBase,
+        PreambleDraft, UpdateKnowledgeBaseRequest, get_preamble_store, preamble_key,
+    },
+    web::{
+        AppState, Claims, CreateDocumentRequest, DEFAULT_CHUNK_SIZE, DocumentListItem,
+        DocumentListResponse, ErrorResponse, PreambleChange, preamble_scope,
+        process_and_save_document, record_audit, save_preamble,
+    },
+};
+
+/// 分块大小允许范围（字节）
+const CHUNK_SIZE_RANGE: std::ops::RangeInclusive<i64> = 512..=32 * 1024;
+
+/// 知识库详情（含 preamble）
+#[derive(Debug, Serialize)]
+pub struct KnowledgeBaseResponse {
+    #[serde(flatten)]
+    pub knowledge_base: KnowledgeBase,
+    pub is_default: bool,
+    pub preamble: String,
+    /// 需要审批时请求中的 preamble 提交为草稿，发布后才生效
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preamble_draft: Option<PreambleDraft>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KnowledgeBaseListResponse {
+    pub knowledge_bases: Vec<KnowledgeBaseResponse>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct DeleteKnowledgeBaseQuery {
+    /// 同时删除 Qdrant 集合中的全部文档
+    pub purge: bool,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct PaginationQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+/// 创建知识库管理路由（仅管理员可访问）
+pub fn create_knowledge_base_router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/admin/knowledge-bases",
+            get(list_knowledge_bases).post(create_knowledge_base),
+        )
+        .route(
+            "/api/admin/knowledge-bases/{id}",
+            get(get_knowledge_base)
+                .put(update_knowledge_base)
+                .delete(delete_knowledge_base),
+        )
+        .route(
+            "/api/admin/knowledge-bases/{id}/documents",
+            get(list_knowledge_base_documents).post(create_knowledge_base_document),
+        )
+        .route(
+            "/api/admin/knowledge-bases/{id}/documents/{doc_id}",
+            delete(delete_knowledge_base_document),
+        )
+}
+
+fn registry() -> Result<&'static KnowledgeBaseRegistry, StatusCode> {
+    get_knowledge_bases().ok_or_else(|| {
+        error!("Knowledge base registry is not initialized");
+        StatusCode::SERVICE_UNAVAILABLE
+    })
+}
+
+/// 知识库 id 只允许小写字母、数字、`-` 和 `_`，用于集合名和目录名
+fn is_valid_id(id: &str) -> bool {
+    (1..=64).contains(&id.len())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        && id != DEFAULT_KNOWLEDGE_BASE
+}
+
+/// 集合名只允许字母、数字、`-` 和 `_`
+fn is_valid_collection_name(name: &str) -> bool {
+    (1..=128).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn validate_params(
+    chunk_size: Option<i64>,
+    temperature: Option<f64>,
+    preamble: Option<&str>,
+) -> Result<(), StatusCode> {
+    if chunk_size.is_some_and(|size| !CHUNK_SIZE_RANGE.contains(&size))
+        || temperature.is_some_and(|t| !(0.0..=2.0).contains(&t))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if let Some(Err(e)) = preamble.map(validate_preamble) {
+        warn!("Invalid knowledge base preamble: {}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+/// 工作区默认知识库的只读描述（默认工作区来自环境变量配置）
+async fn default_knowledge_base(
+    registry: &KnowledgeBaseRegistry,
+    workspace: &str,
+) -> KnowledgeBaseResponse {
+    let config = registry.app_config_for_workspace(workspace);
+    let preamble = tokio::fs::read_to_string(&config.preamble_file)
+        .await
+        .unwrap_or_default();
+    let now = chrono::Utc::now();
+
+    KnowledgeBaseResponse {
+        knowledge_base: KnowledgeBase {
+            id: DEFAULT_KNOWLEDGE_BASE.to_string(),
+            workspace_id: workspace.to_string(),
+            name: "Default".to_string(),
+            description: String::new(),
+            collection_name: config.qdrant.collection_name.clone(),
+            chunk_size: DEFAULT_CHUNK_SIZE as i64,
+            temperature: Some(config.temperature),
+            openai_model: Some(config.openai_model.clone()),
+            created_at: now,
+            updated_at: now,
+        },
+        is_default: true,
+        preamble,
+        preamble_draft: None,
+    }
+}
+
+async fn to_response(
+    registry: &KnowledgeBaseRegistry,
+    knowledge_base: KnowledgeBase,
+) -> KnowledgeBaseResponse {
+    let preamble = registry
+        .load_preamble(&knowledge_base.id)
+        .await
+        .unwrap_or_default();
+    KnowledgeBaseResponse {
+        knowledge_base,
+        is_default: false,
+        preamble,
+        preamble_draft: None,
+    }
+}
+
+/// 获取当前工作区中的知识库记录，属于其他工作区时视为不存在
+async fn stored_knowledge_base(
+    registry: &KnowledgeBaseRegistry,
+    workspace: &str,
+    id: &str,
+) -> Result<KnowledgeBase, StatusCode> {
+    match registry.store().get(id).await {
+        Ok(Some(kb)) if kb.workspace_id == workspace => Ok(kb),
+        Ok(_) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to get knowledge base {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn list_knowledge_bases(
+    Extension(claims): Extension<Claims>,
+) -> Result<ResponseJson<KnowledgeBaseListResponse>, StatusCode> {
+    let registry = registry()?;
+    let stored = registry
+        .store()
+        .list(&claims.workspace)
+        .await
+        .map_err(|e| {
+            error!("Failed to list knowledge bases: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut knowledge_bases = vec![default_knowledge_base(registry, &claims.workspace).await];
+    for knowledge_base in stored {
+        knowledge_bases.push(to_response(registry, knowledge_base).await);
+    }
+
+    Ok(ResponseJson(KnowledgeBaseListResponse { knowledge_bases }))
+}
+
+async fn get_knowledge_base(
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<ResponseJson<KnowledgeBaseResponse>, StatusCode> {
+    let registry = registry()?;
+    if id == DEFAULT_KNOWLEDGE_BASE {
+        return Ok(ResponseJson(
+            default_knowledge_base(registry, &claims.workspace).await,
+        ));
+    }
+
+    let knowledge_base = stored_knowledge_base(registry, &claims.workspace, &id).await?;
+    Ok(ResponseJson(to_response(registry, knowledge_base).await))
+}
+
+async fn create_knowledge_base(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(req): Json<CreateKnowledgeBaseRequest>,
+) -> Result<(StatusCode, ResponseJson<KnowledgeBaseResponse>), StatusCode> {
+    let registry = registry()?;
+    let id = req.id.trim().to_string();
+    if !is_valid_id(&id) || req.name.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    validate_params(req.chunk_size, req.temperature, req.preamble.as_deref())?;
+
+    let collection_name = req
+        .collection_name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| format!("{}_{}", registry.config().qdrant.collection_name, id));
+    if !is_valid_collection_name(&collection_name) {
+        warn!("Invalid collection name: {:?}", collection_name);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // 集合不能与任何工作区的默认知识库或其他知识库共用（id 全局唯一）
+    let existing = registry.store().list_all().await.map_err(|e| {
+        error!("Failed to list knowledge bases: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if existing.iter().any(|kb| kb.id == id) {
+        return Err(StatusCode::CONFLICT);
+    }
+    let workspaces = registry.workspaces().list().await.map_err(|e| {
+        error!("Failed to list workspaces: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if workspaces.iter().any(|ws| {
+        registry
+            .app_config_for_workspace(&ws.id)
+            .qdrant
+            .collection_name
+            == collection_name
+    }) || existing
+        .iter()
+        .any(|kb| kb.collection_name == collection_name)
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let now = chrono::Utc::now();
+    let knowledge_base = KnowledgeBase {
+        id: id.clone(),
+        workspace_id: claims.workspace.clone(),
+        name: req.name.trim().to_string(),
+        description: req.description.unwrap_or_default(),
+        collection_name,
+        chunk_size: req.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE as i64),
+        temperature: req.temperature,
+        openai_model: req.openai_model.filter(|m| !m.trim().is_empty()),
+        created_at: now,
+        updated_at: now,
+    };
+
+    registry
+        .store()
+        .create(&knowledge_base)
+        .await
+        .map_err(|e| {
+            error!("Failed to create knowledge base {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::KnowledgeBaseCreated,
+        &id,
+        None,
+        serde_json::to_value(&knowledge_base).ok(),
+    )
+    .await;
+    info!(
+        "📚 Created knowledge base {} (collection: {})",
+        id, knowledge_base.collection_name
+    );
+
+    let preamble_draft = match &req.preamble {
+        Some(preamble) => save_knowledge_base_preamble(&claims, addr, &id, preamble).await?,
+        None => None,
+    };
+    let mut response = to_response(registry, knowledge_base).await;
+    response.preamble_draft = preamble_draft;
+    Ok((StatusCode::CREATED, ResponseJson(response)))
+}
+
+/// 修改知识库；preamble 和 `/api/preamble` 一样记录版本，需要审批时提交草稿并返回 202
+async fn update_knowledge_base(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateKnowledgeBaseRequest>,
+) -> Result<(StatusCode, ResponseJson<KnowledgeBaseResponse>), StatusCode> {
+    let registry = registry()?;
+    if id == DEFAULT_KNOWLEDGE_BASE {
+        // 默认知识库通过环境变量和 /api/preamble 管理
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    validate_params(
+        req.chunk_size,
+        req.temperature.flatten(),
+        req.preamble.as_deref(),
+    )?;
+
+    let mut knowledge_base = stored_knowledge_base(registry, &claims.workspace, &id).await?;
+    let before = serde_json::to_value(&knowledge_base).ok();
+
+    if let Some(name) = req.name.filter(|n| !n.trim().is_empty()) {
+        knowledge_base.name = name.trim().to_string();
+    }
+    if let Some(description) = req.description {
+        knowledge_base.description = description;
+    }
+    if let Some(chunk_size) = req.chunk_size {
+        knowledge_base.chunk_size = chunk_size;
+    }
+    if let Some(temperature) = req.temperature {
+        knowledge_base.temperature = temperature;
+    }
+    if let Some(model) = req.openai_model {
+        knowledge_base.openai_model = model
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty());
+    }
+    knowledge_base.updated_at = chrono::Utc::now();
+
+    registry
+        .store()
+        .update(&knowledge_base)
+        .await
+        .map_err(|e| {
+            error!("Failed to update knowledge base {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // 模型参数直接更新到已加载的 agent，下一次对话生效；分块大小变化时才重建
+    match registry.loaded(&id) {
+        Some(handle) if handle.chunk_size == knowledge_base.chunk_size.max(1) as usize => {
+            handle
+                .agent
+                .update_generation(&registry.app_config_for(&knowledge_base));
+        }
+        _ => registry.evict(&id),
+    }
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::KnowledgeBaseUpdated,
+        &id,
+        before,
+        serde_json::to_value(&knowledge_base).ok(),
+    )
+    .await;
+    let preamble_draft = match &req.preamble {
+        Some(preamble) => save_knowledge_base_preamble(&claims, addr, &id, preamble).await?,
+        None => None,
+    };
+    info!("📚 Updated knowledge base {}", id);
+
+    let status = match preamble_draft {
+        Some(_) => StatusCode::ACCEPTED,
+        None => StatusCode::OK,
+    };
+    let mut response = to_response(registry, knowledge_base).await;
+    response.preamble_draft = preamble_draft;
+    Ok((status, ResponseJson(response)))
+}
+
+/// 按 preamble 的版本和审批流程保存知识库的 preamble，需要审批时返回提交的草稿
+async fn save_knowledge_base_preamble(
+    claims: &Claims,
+    addr: SocketAddr,
+    id: &str,
+    content: &str,
+) -> Result<Option<PreambleDraft>, StatusCode> {
+    let scope = preamble_scope(claims, Some(id)).await?;
+    match save_preamble(claims, addr, &scope, content, None).await? {
+        PreambleChange::Saved(_) => Ok(None),
+        PreambleChange::Proposed(draft) => Ok(Some(draft)),
+    }
+}
+
+async fn delete_knowledge_base(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    Query(query): Query<DeleteKnowledgeBaseQuery>,
+) -> Result<StatusCode, StatusCode> {
+    let registry = registry()?;
+    if id == DEFAULT_KNOWLEDGE_BASE {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let knowledge_base = stored_knowledge_base(registry, &claims.workspace, &id).await?;
+
+    if query.purge {
+        let handle = load_handle(&claims.workspace, &id).await?;
+        if let Err(e) = handle.document_store.reset_table().await {
+            error!("Failed to purge collection of knowledge base {}: {}", id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        info!("🗑️  Purged collection of knowledge base {}", id);
+    }
+
+    match registry.store().delete(&id).await {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to delete knowledge base {}: {}", id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    registry.evict(&id);
+    if let Err(e) = registry.remove_files(&id).await {
+        warn!("⚠️ Failed to remove files of knowledge base {}: {}", id, e);
+    }
+    if let Err(e) = registry.remove_backups(&id).await {
+        warn!(
+            "⚠️ Failed to remove backups of knowledge base {}: {}",
+            id, e
+        );
+    }
+    if let Some(store) = get_preamble_store()
+        && let Err(e) = store
+            .delete_preamble(&preamble_key(&claims.workspace, Some(&id)))
+            .await
+    {
+        warn!(
+            "⚠️ Failed to remove preamble versions of knowledge base {}: {}",
+            id, e
+        );
+    }
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::KnowledgeBaseDeleted,
+        &id,
+        serde_json::to_value(&knowledge_base).ok(),
+        Some(serde_json::json!({ "purge": query.purge })),
+    )
+    .await;
+    info!("🗑️  Deleted knowledge base {}", id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 当前请求所在工作区的默认知识库（agent、集合、备份目录和分块大小）
+pub(crate) async fn workspace_handle(
+    claims: &Claims,
+) -> Result<Arc<KnowledgeBaseHandle>, StatusCode> {
+    match registry()?.workspace(&claims.workspace).await {
+        Ok(Some(handle)) => Ok(handle),
+        Ok(None) => {
+            warn!("Workspace {} no longer exists", claims.workspace);
+            Err(StatusCode::FORBIDDEN)
+        }
+        Err(e) => {
+            error!("Failed to load workspace {}: {}", claims.workspace, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// 按 `knowledge_base` 参数选择当前工作区的知识库，不传或为 `default` 时为工作区的默认知识库
+pub(crate) async fn knowledge_base_handle(
+    claims: &Claims,
+    knowledge_base: Option<&str>,
+) -> Result<Arc<KnowledgeBaseHandle>, StatusCode> {
+    match knowledge_base
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && *id != DEFAULT_KNOWLEDGE_BASE)
+    {
+        Some(id) => load_handle(&claims.workspace, id).await,
+        None => workspace_handle(claims).await,
+    }
+}
+
+pub(crate) async fn load_handle(
+    workspace: &str,
+    id: &str,
+) -> Result<Arc<KnowledgeBaseHandle>, StatusCode> {
+    match registry()?.get(workspace, Some(id)).await {
+        Ok(Some(handle)) => Ok(handle),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to load knowledge base {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn list_knowledge_base_documents(
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Query(p): Query<PaginationQuery>,
+) -> Result<ResponseJson<DocumentListResponse>, StatusCode> {
+    let handle = load_handle(&claims.workspace, &id).await?;
+    let limit = p.limit.unwrap_or(20).clamp(1, 1000);
+    let offset = p.offset.unwrap_or(0);
+
+    let (docs, total) = handle
+        .document_store
+        .list_documents_paginated(limit, offset, &claims.viewer())
+        .await
+        .map_err(|e| {
+            error!("Failed to list documents of knowledge base {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let documents = docs
+        .into_iter()
+        .map(|doc| {
+            let mut preview: String = doc.content.chars().take(160).collect();
+            if preview.len() < doc.content.len() {
+                preview.push_str("...");
+            }
+            DocumentListItem {
+                id: doc.id,
+                filename: doc.source,
+                preview,
+                metadata: doc.metadata,
+                access: doc.access,
+                created_at: doc.created_at.to_rfc3339(),
+                updated_at: doc.updated_at.to_rfc3339(),
+            }
+        })
+        .collect();
+
+    Ok(ResponseJson(DocumentListResponse {
+        documents,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+async fn create_knowledge_base_document(
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(req): Json<CreateDocumentRequest>,
+) -> Response {
+    let handle = match load_handle(&claims.workspace, &id).await {
+        Ok(handle) => handle,
+        Err(status) => return status.into_response(),
+    };
+
+    let metadata = match DocumentMetadata::normalized(req.metadata) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                ResponseJson(ErrorResponse {
+                    error: format!("元数据无效: {}", e),
+                }),
+            )
+                .into_response();
+        }
+    };
+    let access = match DocumentAccess::normalized(req.access) {
+        Ok(access) => access.owned_by(claims.user_id, &claims.sub),
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                ResponseJson(ErrorResponse {
+                    error: format!("访问控制无效: {}", e),
+                }),
+            )
+                .into_response();
+        }
+    };
+
+    info!("Creating document in knowledge base {}", id);
+    process_and_save_document(
+        handle.agent.clone(),
+        handle.document_store.clone(),
+        handle.backup(),
+        handle.chunk_size,
+        &req.filename,
+        &req.content,
+        metadata,
+        access,
+        "Created",
+    )
+    .await
+    .map_err(|(status, error)| (status, ResponseJson(ErrorResponse { error })))
+    .into_response()
+}
+
+async fn delete_knowledge_base_document(
+    Extension(claims): Extension<Claims>,
+    Path((id, doc_id)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let handle = load_handle(&claims.workspace, &id).await?;
+
+    let doc = match handle.document_store.get_document(&doc_id).await {
+        Ok(Some(doc)) => doc,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to get document: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    // 与 /api/documents 相同：分块文档整体删除
+    let delete_id = if doc.chunk_index.is_some() || doc.id != doc.base_id {
+        format!("{}_CHUNKED", doc.base_id)
+    } else {
+        doc.id.clone()
+    };
+
+    if let Err(e) = handle.document_store.delete_document(&delete_id).await {
+        error!("Failed to delete document: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    if let Some(backup) = handle.backup()
+        && let Err(e) = backup.delete_backup(&doc.base_id).await
+    {
+        warn!("⚠️ Failed to delete backup for ID {}: {}", doc.base_id, e);
+    }
+
+    info!(
+        "Deleted document {} from knowledge base {}",
+        doc.base_id, id
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,17 +1,43 @@
+mod api_key_routes;
+mod audit_routes;
 mod auth_routes;
+mod backup_routes;
 mod chat_route;
 mod conversation_routes;
+mod doctor_routes;
 mod document_routes;
+mod document_search;
+mod experiment_routes;
+mod knowledge_base_routes;
+mod oidc_routes;
+mod playground_routes;
 mod preamble_routes;
+mod rate_limit;
 mod root;
+mod settings_routes;
 mod state;
+mod totp_routes;
 mod user_routes;
+mod workspace_routes;
 
+pub use api_key_routes::*;
+pub use audit_routes::*;
 pub use auth_routes::*;
+pub use backup_routes::*;
 pub use chat_route::*;
 pub use conversation_routes::*;
+pub use doctor_routes::*;
 pub use document_routes::*;
+pub use document_search::*;
+pub use experiment_routes::*;
+pub use knowledge_base_routes::*;
+pub use oidc_routes::*;
+pub use playground_routes::*;
 pub use preamble_routes::*;
+pub use rate_limit::*;
 pub use root::*;
+pub use settings_routes::*;
 pub use state::*;
+pub use totp_routes::*;
 pub use user_routes::*;
+pub use workspace_routes::*;
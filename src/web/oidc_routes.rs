@@ -0,0 +1,1076 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header, jwk::JwkSet};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use super::auth_routes::{
+    AppError, JwtUtil, LoginResponse, UserAppState, issue_tokens, select_workspace,
+};
+use crate::{
+    config::{OidcSection, get_config},
+    db::{
+        AuthEventKind, CreateUserRequest, DEFAULT_WORKSPACE, NewAuthEvent, UpdateUserRequest, User,
+        UserRole, UserStore, WorkspaceStore, normalize_display_name,
+    },
+};
+
+/// 同时映射到多个角色时的优先级，靠前的优先
+const ROLE_PRIORITY: [UserRole; 6] = [
+    UserRole::Admin,
+    UserRole::KnowledgeAdmin,
+    UserRole::Editor,
+    UserRole::SupportAgent,
+    UserRole::User,
+    UserRole::Viewer,
+];
+
+/// OIDC 登录的 state 有效期（秒）
+const PENDING_LOGIN_TTL: i64 = 600;
+
+/// 登录结果交换码的有效期（秒），登录页拿到后立即换取
+const LOGIN_RESULT_TTL: i64 = 60;
+
+/// 保存 state 的 cookie，回调时校验 state 来自发起登录的同一个浏览器
+const STATE_COOKIE: &str = "rig_oidc_state";
+
+/// OIDC 单点登录配置
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    /// 身份提供方地址，用于发现配置和校验 id_token 的 iss
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// 回调地址，需与身份提供方登记的一致，如 https://example.com/api/auth/oidc/callback
+    pub redirect_url: String,
+    pub scopes: String,
+    /// 作为本地用户名的 claim
+    pub username_claim: String,
+    /// 角色来源 claim，支持 `realm_access.roles` 这样的嵌套路径
+    pub role_claim: String,
+    /// 角色 claim 中包含其中任一值时映射为管理员
+    pub admin_values: Vec<String>,
+    /// 角色 claim 取值到本地角色的映射
+    pub role_values: BTreeMap<String, UserRole>,
+    /// 同步为用户分组的 claim，不配置时不同步
+    pub groups_claim: Option<String>,
+    /// 接受的 id_token 签名算法，不在其中的 token 一律拒绝，不以 token header 为准
+    pub id_token_algorithms: Vec<Algorithm>,
+}
+
+impl OidcConfig {
+    /// 从统一配置读取，未配置 OIDC_ISSUER 或 OIDC_CLIENT_ID 时不启用
+    pub fn current() -> Option<Self> {
+        Self::from_config(&get_config().oidc)
+    }
+
+    pub fn from_config(config: &OidcSection) -> Option<Self> {
+        let (Some(issuer), Some(client_id)) = (&config.issuer, &config.client_id) else {
+            return None;
+        };
+
+        Some(Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: client_id.clone(),
+            client_secret: config.client_secret.clone(),
+            redirect_url: config.redirect_url.clone(),
+            scopes: config.scopes.clone(),
+            username_claim: config.username_claim.clone(),
+            role_claim: config.role_claim.clone(),
+            admin_values: config.admin_values.clone(),
+            role_values: config.role_values.clone(),
+            groups_claim: config.groups_claim.clone(),
+            id_token_algorithms: config
+                .id_token_algorithms
+                .iter()
+                .filter_map(|alg| match alg.trim().parse() {
+                    Ok(alg) => Some(alg),
+                    Err(_) => {
+                        warn!("Ignoring unknown OIDC id_token algorithm {:?}", alg);
+                        None
+                    }
+                })
+                .collect(),
+        })
+    }
+}
+
+/// 身份提供方的发现文档（只取用到的字段）
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: Option<String>,
+}
+
+/// 授权码换取的 token（只取 id_token）
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// 等待回调的登录请求
+#[derive(Debug, Clone)]
+struct PendingLogin {
+    nonce: String,
+    /// PKCE code_verifier，换取 token 时提交
+    code_verifier: String,
+    workspace: Option<String>,
+    created_at: i64,
+}
+
+/// 发起登录得到的授权地址和需写入浏览器 cookie 的 state
+#[derive(Debug)]
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+}
+
+/// 从 id_token 映射得到的外部身份
+#[derive(Debug, Clone, PartialEq)]
+pub struct OidcIdentity {
+    pub subject: String,
+    pub username: String,
+    /// `name` claim，作为显示名称
+    pub display_name: Option<String>,
+    /// 角色 claim 未映射到任何角色时为 None，保留本地分配的角色
+    pub role: Option<UserRole>,
+    pub groups: Option<Vec<String>>,
+}
+
+/// OIDC 授权码登录客户端
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: Mutex<Option<ProviderMetadata>>,
+    jwks: Mutex<Option<JwkSet>>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+    /// 等待登录页领取的登录结果：交换码 -> (结果, 创建时间)
+    results: Mutex<HashMap<String, (OidcLogin, i64)>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> anyhow::Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to build OIDC HTTP client")?;
+
+        Ok(Self {
+            config,
+            http,
+            metadata: Mutex::new(None),
+            jwks: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+            results: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 获取发现文档，成功后缓存
+    async fn metadata(&self) -> anyhow::Result<ProviderMetadata> {
+        if let Some(metadata) = self.metadata.lock().clone() {
+            return Ok(metadata);
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", self.config.issuer);
+        let metadata: ProviderMetadata = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("Failed to fetch OIDC discovery document from {}", url))?
+            .json()
+            .await
+            .context("Invalid OIDC discovery document")?;
+        if metadata.issuer.trim_end_matches('/') != self.config.issuer {
+            anyhow::bail!("OIDC issuer mismatch: {}", metadata.issuer);
+        }
+
+        *self.metadata.lock() = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    /// 生成跳转到身份提供方的授权地址，并记录 state、nonce 和 PKCE code_verifier
+    pub async fn authorization_url(
+        &self,
+        workspace: Option<String>,
+    ) -> anyhow::Result<AuthorizationRequest> {
+        let metadata = self.metadata().await?;
+        let state = nanoid::nanoid!(32);
+        let nonce = nanoid::nanoid!(32);
+        // nanoid 的字母表都是 PKCE 允许的字符
+        let code_verifier = nanoid::nanoid!(64);
+        let code_challenge = pkce_challenge(&code_verifier);
+        let now = Utc::now().timestamp();
+
+        {
+            let mut pending = self.pending.lock();
+            pending.retain(|_, login| now - login.created_at < PENDING_LOGIN_TTL);
+            pending.insert(
+                state.clone(),
+                PendingLogin {
+                    nonce: nonce.clone(),
+                    code_verifier,
+                    workspace,
+                    created_at: now,
+                },
+            );
+        }
+
+        let url = reqwest::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .context("Invalid authorization endpoint")?;
+        Ok(AuthorizationRequest {
+            url: url.to_string(),
+            state,
+        })
+    }
+
+    /// 取出 state 对应的登录请求（只能使用一次）
+    fn take_pending(&self, state: &str) -> Option<PendingLogin> {
+        self.pending
+            .lock()
+            .remove(state)
+            .filter(|login| Utc::now().timestamp() - login.created_at < PENDING_LOGIN_TTL)
+    }
+
+    /// 暂存登录结果，返回登录页用来领取结果的一次性交换码
+    fn store_result(&self, login: OidcLogin) -> String {
+        let code = nanoid::nanoid!(32);
+        let now = Utc::now().timestamp();
+        let mut results = self.results.lock();
+        results.retain(|_, (_, created_at)| now - *created_at < LOGIN_RESULT_TTL);
+        results.insert(code.clone(), (login, now));
+        code
+    }
+
+    /// 领取登录结果（只能使用一次）
+    fn take_result(&self, code: &str) -> Option<OidcLogin> {
+        self.results
+            .lock()
+            .remove(code)
+            .filter(|(_, created_at)| Utc::now().timestamp() - created_at < LOGIN_RESULT_TTL)
+            .map(|(login, _)| login)
+    }
+
+    /// 用授权码换取 id_token 并校验，返回映射后的身份
+    async fn exchange_code(
+        &self,
+        code: &str,
+        pending: &PendingLogin,
+    ) -> anyhow::Result<OidcIdentity> {
+        let metadata = self.metadata().await?;
+        let response: TokenResponse = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+                ("code_verifier", pending.code_verifier.as_str()),
+            ])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Failed to exchange authorization code")?
+            .json()
+            .await
+            .context("Invalid token response")?;
+
+        let claims = self.verify_id_token(&metadata, &response.id_token).await?;
+        if claims.get("nonce").and_then(Value::as_str) != Some(pending.nonce.as_str()) {
+            anyhow::bail!("id_token nonce mismatch");
+        }
+        self.map_claims(&claims)
+    }
+
+    /// 校验 id_token 的签名、iss、aud 和有效期
+    ///
+    /// 签名算法必须在配置的 `id_token_algorithms` 中；HS* 算法使用 client secret 校验，
+    /// 其他算法使用身份提供方的 JWKS
+    async fn verify_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        token: &str,
+    ) -> anyhow::Result<serde_json::Map<String, Value>> {
+        let header = decode_header(token).context("Invalid id_token header")?;
+        if !self.config.id_token_algorithms.contains(&header.alg) {
+            anyhow::bail!("id_token signed with unexpected algorithm {:?}", header.alg);
+        }
+        let key = match header.alg {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                if self.config.client_secret.is_empty() {
+                    anyhow::bail!("HMAC signed id_token requires OIDC_CLIENT_SECRET");
+                }
+                DecodingKey::from_secret(self.config.client_secret.as_bytes())
+            }
+            _ => self.jwk_key(metadata, header.kid.as_deref()).await?,
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = self.config.id_token_algorithms.clone();
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        let data = decode::<serde_json::Map<String, Value>>(token, &key, &validation)
+            .context("Invalid id_token")?;
+        Ok(data.claims)
+    }
+
+    /// 按 kid 查找签名公钥，找不到时重新拉取 JWKS（身份提供方可能已轮换密钥）
+    async fn jwk_key(
+        &self,
+        metadata: &ProviderMetadata,
+        kid: Option<&str>,
+    ) -> anyhow::Result<DecodingKey> {
+        let find = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None => jwks.keys.first().cloned(),
+        };
+
+        if let Some(jwk) = self.jwks.lock().as_ref().and_then(find) {
+            return Ok(DecodingKey::from_jwk(&jwk)?);
+        }
+
+        let jwks_uri = metadata
+            .jwks_uri
+            .as_deref()
+            .context("OIDC provider has no jwks_uri")?;
+        let jwks: JwkSet = self
+            .http
+            .get(jwks_uri)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Failed to fetch JWKS")?
+            .json()
+            .await
+            .context("Invalid JWKS")?;
+        let jwk = find(&jwks).context("No matching key in JWKS")?;
+        *self.jwks.lock() = Some(jwks);
+
+        Ok(DecodingKey::from_jwk(&jwk)?)
+    }
+
+    /// 将 id_token claims 映射为本地身份
+    fn map_claims(&self, claims: &serde_json::Map<String, Value>) -> anyhow::Result<OidcIdentity> {
+        let subject = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .context("id_token has no sub claim")?
+            .to_string();
+        let username = claim_values(claims, &self.config.username_claim)
+            .into_iter()
+            .next()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .with_context(|| format!("id_token has no {} claim", self.config.username_claim))?;
+
+        let display_name = claims
+            .get("name")
+            .and_then(Value::as_str)
+            .and_then(normalize_display_name);
+
+        // 映射到多个角色时取权限最高的
+        let role = claim_values(claims, &self.config.role_claim)
+            .iter()
+            .filter_map(|v| {
+                if self.config.admin_values.contains(v) {
+                    Some(UserRole::Admin)
+                } else {
+                    self.config.role_values.get(v).cloned()
+                }
+            })
+            .min_by_key(|role| ROLE_PRIORITY.iter().position(|r| r == role));
+        let groups = self
+            .config
+            .groups_claim
+            .as_deref()
+            .map(|claim| claim_values(claims, claim));
+
+        Ok(OidcIdentity {
+            subject,
+            username,
+            display_name,
+            role,
+            groups,
+        })
+    }
+}
+
+/// PKCE S256：code_verifier 的 SHA-256 摘要，按 base64url（无填充）编码
+fn pkce_challenge(code_verifier: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let digest = Sha256::digest(code_verifier.as_bytes());
+    let mut encoded = String::with_capacity(43);
+    for chunk in digest.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (u32::from(*b) << (16 - 8 * i)));
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// 从 Cookie 请求头中读取指定 cookie
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (key, value) = pair.trim().split_once('=')?;
+            (key == name).then_some(value)
+        })
+}
+
+/// 按点分路径读取 claim，字符串视为单个值，数组取其中的字符串
+fn claim_values(claims: &serde_json::Map<String, Value>, path: &str) -> Vec<String> {
+    let mut parts = path.split('.');
+    let Some(mut value) = parts.next().and_then(|first| claims.get(first)) else {
+        return Vec::new();
+    };
+    for part in parts {
+        match value.get(part) {
+            Some(next) => value = next,
+            None => return Vec::new(),
+        }
+    }
+
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 找到外部身份绑定的用户，首次登录时即时创建
+///
+/// 分组以身份提供方为准，每次登录同步；角色仅在 claim 映射到角色时同步，
+/// 否则保留本地分配的角色。不会自动绑定同名的本地账号
+async fn provision_user(
+    user_store: &UserStore,
+    workspace_store: &WorkspaceStore,
+    issuer: &str,
+    identity: OidcIdentity,
+) -> Result<User, AppError> {
+    if let Some(user) = user_store
+        .get_user_by_identity(issuer, &identity.subject)
+        .await?
+    {
+        let role = identity.role.filter(|role| *role != user.role);
+        let role_changed = role.is_some();
+        let groups_changed = identity.groups.as_ref().is_some_and(|g| *g != user.groups);
+        let name_changed =
+            identity.display_name.is_some() && identity.display_name != user.display_name;
+        if !role_changed && !groups_changed && !name_changed {
+            return Ok(user);
+        }
+
+        info!(
+            "Syncing OIDC user {}: role {} -> {}",
+            user.username,
+            user.role,
+            role.as_ref().unwrap_or(&user.role)
+        );
+        let user = user_store
+            .update_user(
+                user.id,
+                UpdateUserRequest {
+                    display_name: name_changed.then_some(identity.display_name).flatten(),
+                    password: None,
+                    status: None,
+                    role,
+                    groups: identity.groups,
+                    must_change_password: None,
+                },
+            )
+            .await?;
+        return Ok(user);
+    }
+
+    if user_store
+        .get_user_by_username(&identity.username)
+        .await?
+        .is_some()
+    {
+        warn!(
+            "OIDC login for {} conflicts with an existing local user",
+            identity.username
+        );
+        return Err(AppError::Forbidden(
+            "Username already belongs to a local account".to_string(),
+        ));
+    }
+
+    // 单点登录用户没有本地密码，使用随机密码占位
+    let user = user_store
+        .create_user(CreateUserRequest {
+            username: identity.username,
+            display_name: identity.display_name,
+            password: nanoid::nanoid!(32),
+            role: Some(identity.role.unwrap_or(UserRole::User)),
+            status: Some(1),
+            groups: identity.groups,
+            must_change_password: None,
+        })
+        .await?;
+    user_store
+        .link_identity(issuer, &identity.subject, user.id)
+        .await?;
+    workspace_store
+        .add_member(DEFAULT_WORKSPACE, user.id)
+        .await?;
+
+    info!(
+        "Provisioned OIDC user {} (id: {}, role: {})",
+        user.username, user.id, user.role
+    );
+    Ok(user)
+}
+
+/// OIDC 路由状态，未配置 OIDC 时客户端为 None
+pub type OidcAppState = (Arc<UserStore>, Arc<WorkspaceStore>, Option<Arc<OidcClient>>);
+
+/// 单点登录是否可用
+#[derive(Debug, Serialize)]
+pub struct OidcConfigResponse {
+    pub enabled: bool,
+}
+
+/// 发起单点登录的参数
+#[derive(Debug, Deserialize)]
+pub struct OidcLoginQuery {
+    /// 登录后进入的工作区
+    pub workspace: Option<String>,
+}
+
+/// 身份提供方回调参数
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+async fn oidc_config_handler(
+    State((_, _, client)): State<OidcAppState>,
+) -> Json<OidcConfigResponse> {
+    Json(OidcConfigResponse {
+        enabled: client.is_some(),
+    })
+}
+
+/// 单点登录的结果
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum OidcLogin {
+    Tokens(LoginResponse),
+    /// 已启用两步验证，需在登录页提交验证码，用票据换取 token
+    MfaRequired {
+        #[serde(rename = "mfa_ticket")]
+        ticket: String,
+        username: String,
+    },
+}
+
+/// 领取单点登录结果的请求
+#[derive(Debug, Deserialize)]
+pub struct OidcTokenRequest {
+    pub code: String,
+}
+
+/// 设置或清除保存 state 的 cookie，回调地址为 https 时加上 Secure
+fn state_cookie(client: &OidcClient, state: &str, max_age: i64) -> String {
+    let secure = if client.config.redirect_url.starts_with("https://") {
+        "; Secure"
+    } else {
+        ""
+    };
+    format!(
+        "{}={}; Path=/api/auth/oidc; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        STATE_COOKIE, state, max_age, secure
+    )
+}
+
+/// 跳转到身份提供方登录，state 同时写入 cookie，防止登录 CSRF
+async fn oidc_login_handler(
+    State((_, _, client)): State<OidcAppState>,
+    Query(query): Query<OidcLoginQuery>,
+) -> Result<Response, AppError> {
+    let client = client.ok_or_else(|| AppError::NotFound("OIDC is not configured".to_string()))?;
+    let request = client.authorization_url(query.workspace).await?;
+    Ok((
+        [(
+            header::SET_COOKIE,
+            state_cookie(&client, &request.state, PENDING_LOGIN_TTL),
+        )],
+        Redirect::to(&request.url),
+    )
+        .into_response())
+}
+
+/// 身份提供方回调：校验后暂存登录结果，只把一次性交换码通过 URL fragment 交给登录页
+///
+/// token 不出现在地址栏和浏览器历史中，登录页用交换码调用 `POST /api/auth/oidc/token` 领取
+async fn oidc_callback_handler(
+    State((user_store, workspace_store, client)): State<OidcAppState>,
+    headers: HeaderMap,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Response {
+    let Some(client) = client else {
+        return AppError::NotFound("OIDC is not configured".to_string()).into_response();
+    };
+    let clear_cookie = [(header::SET_COOKIE, state_cookie(&client, "", 0))];
+
+    let browser_state = cookie(&headers, STATE_COOKIE);
+    match complete_login(&client, &user_store, &workspace_store, query, browser_state).await {
+        Ok(login) => {
+            let fragment = url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("sso_code", &client.store_result(login))
+                .finish();
+            (clear_cookie, Redirect::to(&format!("/login#{}", fragment))).into_response()
+        }
+        Err(err) => {
+            let message = match err {
+                AppError::BadRequest(msg)
+                | AppError::Unauthorized(msg)
+                | AppError::Forbidden(msg)
+                | AppError::NotFound(msg)
+                | AppError::TooManyRequests(msg) => msg,
+                AppError::Internal(err) => {
+                    warn!("OIDC login failed: {:?}", err);
+                    "Single sign-on failed".to_string()
+                }
+            };
+            let fragment = url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("error", &message)
+                .finish();
+            (clear_cookie, Redirect::to(&format!("/login#{}", fragment))).into_response()
+        }
+    }
+}
+
+/// 用回调得到的一次性交换码领取 token（或两步验证票据）
+async fn oidc_token_handler(
+    State((_, _, client)): State<OidcAppState>,
+    Json(req): Json<OidcTokenRequest>,
+) -> Result<Json<OidcLogin>, AppError> {
+    let client = client.ok_or_else(|| AppError::NotFound("OIDC is not configured".to_string()))?;
+    client
+        .take_result(&req.code)
+        .map(Json)
+        .ok_or_else(|| AppError::Unauthorized("Login request expired".to_string()))
+}
+
+/// 完成授权码登录：校验 state，换取并校验 id_token，即时创建用户后签发 token
+///
+/// `browser_state` 为浏览器 cookie 中保存的 state，必须与回调参数一致；
+/// 已启用两步验证的用户只得到两步验证票据
+async fn complete_login(
+    client: &OidcClient,
+    user_store: &UserStore,
+    workspace_store: &WorkspaceStore,
+    query: OidcCallbackQuery,
+    browser_state: Option<&str>,
+) -> Result<OidcLogin, AppError> {
+    if let Some(error) = query.error {
+        return Err(AppError::Unauthorized(
+            query.error_description.unwrap_or(error),
+        ));
+    }
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return Err(AppError::BadRequest("Missing code or state".to_string()));
+    };
+    if browser_state != Some(state.as_str()) {
+        warn!("OIDC callback state does not match the browser cookie");
+        return Err(AppError::Unauthorized(
+            "Login was not started from this browser".to_string(),
+        ));
+    }
+    let pending = client
+        .take_pending(&state)
+        .ok_or_else(|| AppError::Unauthorized("Login request expired".to_string()))?;
+
+    let identity = client.exchange_code(&code, &pending).await?;
+    let user = provision_user(user_store, workspace_store, &client.config.issuer, identity).await?;
+    if user.status != 1 {
+        warn!("OIDC login rejected for disabled user: {}", user.username);
+        return Err(AppError::Forbidden("User is disabled".to_string()));
+    }
+
+    let workspace =
+        select_workspace(workspace_store, user.id, pending.workspace.as_deref()).await?;
+    if user_store.totp_status(user.id).await?.enabled {
+        info!("OIDC login of {} awaits two-factor code", user.username);
+        return Ok(OidcLogin::MfaRequired {
+            ticket: JwtUtil::new().generate_mfa_ticket(user.id, &workspace)?,
+            username: user.username,
+        });
+    }
+    info!("OIDC login successful for user: {}", user.username);
+    user_store
+        .record_auth_event(NewAuthEvent {
+            kind: AuthEventKind::LoginSucceeded,
+            username: &user.username,
+            user_id: Some(user.id),
+            ip: None,
+            detail: Some(format!("oidc: {}", client.config.issuer)),
+        })
+        .await;
+    Ok(OidcLogin::Tokens(
+        issue_tokens(user_store, user, &workspace, None).await?,
+    ))
+}
+
+/// 创建 OIDC 单点登录路由
+pub fn create_oidc_router((user_store, workspace_store): UserAppState) -> Router {
+    let client = OidcConfig::current().and_then(|config| match OidcClient::new(config) {
+        Ok(client) => Some(Arc::new(client)),
+        Err(err) => {
+            warn!("OIDC disabled: {:?}", err);
+            None
+        }
+    });
+    if let Some(client) = &client {
+        info!(
+            "OIDC single sign-on enabled (issuer: {})",
+            client.config.issuer
+        );
+    }
+
+    Router::new()
+        .route("/api/auth/oidc/config", get(oidc_config_handler))
+        .route("/api/auth/oidc/login", get(oidc_login_handler))
+        .route("/api/auth/oidc/callback", get(oidc_callback_handler))
+        .route("/api/auth/oidc/token", post(oidc_token_handler))
+        .with_state((user_store, workspace_store, client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TOTP_STEP_SECONDS;
+    use jsonwebtoken::{EncodingKey, Header, encode};
+
+    const CLIENT_ID: &str = "rig-rag";
+    const CLIENT_SECRET: &str = "mock-secret";
+
+    /// 模拟身份提供方当前登录的用户：(subject, roles)
+    type MockUser = Arc<Mutex<(String, Vec<String>)>>;
+
+    /// 本地模拟身份提供方：授权时把 nonce 作为授权码返回，换取 token 时签入 id_token
+    async fn start_mock_idp(user: MockUser) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let discovery = serde_json::json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "jwks_uri": format!("{}/jwks", issuer),
+        });
+        let token_issuer = issuer.clone();
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(move || async move { Json(discovery) }),
+            )
+            .route(
+                "/authorize",
+                get(|Query(q): Query<HashMap<String, String>>| async move {
+                    assert_eq!(q["code_challenge_method"], "S256");
+                    Redirect::to(&format!(
+                        "{}?code={}&state={}",
+                        q["redirect_uri"], q["nonce"], q["state"]
+                    ))
+                }),
+            )
+            .route(
+                "/token",
+                axum::routing::post(
+                    move |axum::Form(form): axum::Form<HashMap<String, String>>| async move {
+                        assert_eq!(form["client_secret"], CLIENT_SECRET);
+                        assert_eq!(form["code_verifier"].len(), 64);
+                        let (subject, roles) = user.lock().clone();
+                        let claims = serde_json::json!({
+                            "iss": token_issuer,
+                            "aud": CLIENT_ID,
+                            "sub": subject,
+                            "preferred_username": "alice",
+                            "nonce": form["code"],
+                            "roles": roles,
+                            "exp": Utc::now().timestamp() + 300,
+                        });
+                        let id_token = encode(
+                            &Header::default(),
+                            &claims,
+                            &EncodingKey::from_secret(CLIENT_SECRET.as_bytes()),
+                        )
+                        .unwrap();
+                        Json(serde_json::json!({ "id_token": id_token, "token_type": "Bearer" }))
+                    },
+                ),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        issuer
+    }
+
+    fn tokens(login: OidcLogin) -> LoginResponse {
+        match login {
+            OidcLogin::Tokens(response) => response,
+            OidcLogin::MfaRequired { .. } => panic!("unexpected two-factor challenge"),
+        }
+    }
+
+    /// 走一遍浏览器跳转：授权地址 -> 模拟身份提供方 -> 回调参数
+    async fn authorize(client: &OidcClient) -> OidcCallbackQuery {
+        let url = client.authorization_url(None).await.unwrap().url;
+        let http = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let response = http.get(&url).send().await.unwrap();
+        let location = response.headers()["location"].to_str().unwrap();
+        let params: HashMap<String, String> = reqwest::Url::parse(location)
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect();
+
+        OidcCallbackQuery {
+            code: params.get("code").cloned(),
+            state: params.get("state").cloned(),
+            error: None,
+            error_description: None,
+        }
+    }
+
+    /// 模拟带着发起登录时写入的 state cookie 回调
+    async fn callback(
+        client: &OidcClient,
+        user_store: &UserStore,
+        workspace_store: &WorkspaceStore,
+        query: OidcCallbackQuery,
+    ) -> Result<OidcLogin, AppError> {
+        let state = query.state.clone();
+        complete_login(client, user_store, workspace_store, query, state.as_deref()).await
+    }
+
+    #[test]
+    fn test_pkce_challenge() {
+        // RFC 7636 附录 B 的示例
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            "theme=dark; rig_oidc_state=abc".parse().unwrap(),
+        );
+        assert_eq!(cookie(&headers, STATE_COOKIE), Some("abc"));
+        assert_eq!(cookie(&headers, "missing"), None);
+    }
+
+    #[tokio::test]
+    async fn test_login_with_mock_identity_provider() {
+        let db = std::env::temp_dir().join(format!("oidc-{}.db", nanoid::nanoid!(8)));
+        let url = format!("sqlite:{}?mode=rwc", db.display());
+        let user_store = UserStore::new(&url).await.unwrap();
+        let workspace_store = WorkspaceStore::new(&url).await.unwrap();
+
+        let mock_user: MockUser = Arc::new(Mutex::new((
+            "alice-sub".to_string(),
+            vec!["rag-admin".to_string()],
+        )));
+        let client = OidcClient::new(OidcConfig {
+            issuer: start_mock_idp(mock_user.clone()).await,
+            client_id: CLIENT_ID.to_string(),
+            client_secret: CLIENT_SECRET.to_string(),
+            redirect_url: "http://localhost:3000/api/auth/oidc/callback".to_string(),
+            scopes: "openid profile".to_string(),
+            username_claim: "preferred_username".to_string(),
+            role_claim: "roles".to_string(),
+            admin_values: vec!["rag-admin".to_string()],
+            role_values: BTreeMap::from([
+                ("rag-user".to_string(), UserRole::User),
+                ("rag-editor".to_string(), UserRole::Editor),
+            ]),
+            groups_claim: None,
+            id_token_algorithms: vec![Algorithm::HS256],
+        })
+        .unwrap();
+
+        // state 与浏览器 cookie 不一致时拒绝（登录 CSRF）
+        let query = authorize(&client).await;
+        assert!(
+            complete_login(&client, &user_store, &workspace_store, query, Some("other"))
+                .await
+                .is_err()
+        );
+
+        let query = authorize(&client).await;
+        let state = query.state.clone();
+        let login = callback(&client, &user_store, &workspace_store, query)
+            .await
+            .unwrap();
+        // 登录页用一次性交换码领取结果
+        let code = client.store_result(login);
+        let response = tokens(client.take_result(&code).unwrap());
+        assert!(client.take_result(&code).is_none());
+        assert_eq!(response.username, "alice");
+        assert_eq!(response.role, UserRole::Admin);
+        assert_eq!(response.workspace, DEFAULT_WORKSPACE);
+        assert!(response.mfa_enrollment_required);
+        let claims = crate::web::JwtUtil::new()
+            .verify_token(&response.token)
+            .unwrap();
+        assert_eq!(claims.role, UserRole::Admin);
+
+        // state 只能使用一次
+        let replay = OidcCallbackQuery {
+            code: Some("anything".to_string()),
+            state,
+            error: None,
+            error_description: None,
+        };
+        assert!(
+            callback(&client, &user_store, &workspace_store, replay)
+                .await
+                .is_err()
+        );
+
+        // 再次登录复用同一个用户，角色按身份提供方同步
+        mock_user.lock().1 = vec!["rag-user".to_string()];
+        let query = authorize(&client).await;
+        let response = tokens(
+            callback(&client, &user_store, &workspace_store, query)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(response.role, UserRole::User);
+        assert_eq!(user_store.list_users().await.unwrap().len(), 1);
+
+        // 管理员手动分配的细分角色在 claim 未映射到角色时保留
+        let alice = user_store
+            .get_user_by_username("alice")
+            .await
+            .unwrap()
+            .unwrap();
+        user_store
+            .update_user(
+                alice.id,
+                UpdateUserRequest {
+                    display_name: None,
+                    password: None,
+                    status: None,
+                    role: Some(UserRole::KnowledgeAdmin),
+                    groups: None,
+                    must_change_password: None,
+                },
+            )
+            .await
+            .unwrap();
+        for roles in [vec![], vec!["unmapped".to_string()]] {
+            mock_user.lock().1 = roles;
+            let query = authorize(&client).await;
+            let response = tokens(
+                callback(&client, &user_store, &workspace_store, query)
+                    .await
+                    .unwrap(),
+            );
+            assert_eq!(response.role, UserRole::KnowledgeAdmin);
+        }
+
+        // 映射到多个角色时取权限最高的
+        mock_user.lock().1 = vec!["rag-user".to_string(), "rag-editor".to_string()];
+        let query = authorize(&client).await;
+        let response = tokens(
+            callback(&client, &user_store, &workspace_store, query)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(response.role, UserRole::Editor);
+
+        // 启用两步验证后只得到票据，不直接签发 token
+        let secret = user_store.start_totp_enrollment(alice.id).await.unwrap();
+        let code =
+            crate::utils::totp_code(&secret, Utc::now().timestamp() / TOTP_STEP_SECONDS).unwrap();
+        let recovery_codes = user_store.confirm_totp(alice.id, &code).await.unwrap();
+        assert_eq!(
+            recovery_codes.unwrap().len(),
+            crate::db::RECOVERY_CODE_COUNT
+        );
+        let query = authorize(&client).await;
+        match callback(&client, &user_store, &workspace_store, query)
+            .await
+            .unwrap()
+        {
+            OidcLogin::MfaRequired { ticket, username } => {
+                assert_eq!(username, "alice");
+                let claims = JwtUtil::new().verify_mfa_ticket(&ticket).unwrap();
+                assert_eq!(claims.mfa_user_id, alice.id);
+            }
+            OidcLogin::Tokens(_) => panic!("expected two-factor challenge"),
+        }
+        // 同一验证码不能重复使用
+        assert!(
+            user_store
+                .verify_second_factor(alice.id, &code)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // 不同 subject 的同名用户不会绑定到已有账号
+        mock_user.lock().0 = "mallory-sub".to_string();
+        let query = authorize(&client).await;
+        assert!(
+            callback(&client, &user_store, &workspace_store, query)
+                .await
+                .is_err()
+        );
+
+        let _ = std::fs::remove_file(db);
+    }
+}
@@ -0,0 +1,103 @@
+use axum::{
+    Router,
+    extract::{Extension, Json},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::post,
+};
+use rig::completion::Message;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::{
+    agent::{PreambleVariables, PromptInspection, language_tag},
+    db::{DocumentViewer, MessageRole, MetadataFilter},
+    web::{AppState, Claims, workspace_handle},
+};
+
+/// 历史消息，role 为 user 或 assistant
+#[derive(Debug, Deserialize)]
+pub struct PlaygroundMessage {
+    pub role: MessageRole,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaygroundRequest {
+    pub question: String,
+    #[serde(default)]
+    pub history: Vec<PlaygroundMessage>,
+    /// 临时覆盖 preamble，不会保存
+    pub preamble: Option<String>,
+    #[serde(default)]
+    pub filters: MetadataFilter,
+    /// 按未登录聊天用户的可见范围检索，默认使用当前管理员身份
+    #[serde(default)]
+    pub anonymous: bool,
+    /// preamble 中 `user_language` 变量的取值
+    pub language: Option<String>,
+}
+
+/// 创建检索调试路由（仅管理员可访问）
+pub fn create_playground_router() -> Router<AppState> {
+    Router::new().route("/api/admin/playground", post(run_playground))
+}
+
+async fn run_playground(
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<PlaygroundRequest>,
+) -> Result<ResponseJson<PromptInspection>, StatusCode> {
+    let question = req.question.trim();
+    if question.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let history = req
+        .history
+        .into_iter()
+        .map(|msg| match msg.role {
+            MessageRole::User => Ok(Message::user(msg.content)),
+            MessageRole::Assistant => Ok(Message::assistant(msg.content)),
+            MessageRole::System => Err(StatusCode::BAD_REQUEST),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    info!("🧪 Playground question: {}", question);
+
+    let viewer = if req.anonymous {
+        DocumentViewer::Anonymous
+    } else {
+        claims.viewer()
+    };
+    let user_language = match req.language.as_deref().map(str::trim) {
+        Some(language) if !language.is_empty() => {
+            Some(language_tag(language).ok_or(StatusCode::BAD_REQUEST)?)
+        }
+        _ => None,
+    };
+    let variables = PreambleVariables {
+        user_name: (!req.anonymous)
+            .then(|| claims.name.clone().unwrap_or_else(|| claims.sub.clone())),
+        user_language,
+        knowledge_base: Some(claims.workspace.clone()),
+        workspace: Some(claims.workspace.clone()),
+    };
+    let inspection = workspace_handle(&claims)
+        .await?
+        .agent
+        .inspect(
+            question,
+            history,
+            req.preamble,
+            &req.filters,
+            &viewer,
+            &variables,
+        )
+        .await
+        .map_err(|e| {
+            error!("Playground inspection failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(ResponseJson(inspection))
+}
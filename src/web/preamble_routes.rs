@@ -1,105 +1,840 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
     Router,
-    extract::{Json, State},
+    extract::{ConnectInfo, Extension, Json, Path, Query},
     http::StatusCode,
-    response::Json as ResponseJson,
-    routing::{get, put},
+    response::{IntoResponse, Json as ResponseJson, Response},
+    routing::{get, post, put},
 };
 use serde::{Deserialize, Serialize};
+use similar::TextDiff;
 use tokio::fs;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use crate::{agent::RigAgent, db::DocumentStore};
+use crate::{
+    agent::{DEFAULT_KNOWLEDGE_BASE, KnowledgeBaseHandle, RigAgent, validate_preamble},
+    config::get_config,
+    db::{
+        AuditAction, DocumentStore, PreambleDraft, PreambleDraftStatus, PreambleStore,
+        PreambleVersion, PublishDraftOutcome, get_preamble_store, preamble_key,
+    },
+    web::{Claims, ErrorResponse, knowledge_base_handle, record_audit},
+};
 
 // State 类型别名
 type AppState = (Arc<RigAgent>, Arc<DocumentStore>);
 
+/// 审计日志中保留的 preamble 前缀长度（字符）
+const AUDIT_PREVIEW_CHARS: usize = 500;
+
 #[derive(Debug, Deserialize)]
 pub struct UpdatePreambleRequest {
     pub content: String,
-    pub secret_key: Option<String>,
+    /// 版本说明
+    pub comment: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct PreambleResponse {
     pub content: String,
+    /// 当前生效的版本号，尚未保存过版本时为空
+    pub version: Option<i64>,
+    /// 修改是否需要另一位管理员审批后发布
+    pub require_approval: bool,
     pub updated_at: String,
 }
 
+/// 版本列表项，不含正文
+#[derive(Debug, Serialize)]
+pub struct PreambleVersionSummary {
+    pub version: i64,
+    pub author: String,
+    pub comment: Option<String>,
+    pub chars: usize,
+    /// 是否为当前生效的版本
+    pub live: bool,
+    pub created_at: String,
+}
+
+/// 操作的知识库，不传时为工作区的默认知识库
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PreambleScopeQuery {
+    pub knowledge_base: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreambleDiffQuery {
+    pub from: i64,
+    /// 不传则与当前生效的版本比较
+    pub to: Option<i64>,
+    pub knowledge_base: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreambleDiffResponse {
+    pub from: i64,
+    pub to: i64,
+    /// unified diff 格式
+    pub diff: String,
+}
+
+/// 回滚请求
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PreambleVersionRequest {
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreambleDraftQuery {
+    /// 不传时只列出待审批的草稿
+    pub status: Option<PreambleDraftStatus>,
+    pub knowledge_base: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreambleDraftResponse {
+    #[serde(flatten)]
+    pub draft: PreambleDraft,
+    /// 当前用户能否发布：草稿待审批、不是自己提交的且基于当前生效版本
+    pub can_publish: bool,
+    /// 提交后生效版本已变化，需要重新提交
+    pub outdated: bool,
+    /// 相对当前生效版本的 unified diff
+    pub diff: String,
+}
+
 /// 创建 Preamble 路由 - 查询操作（所有登录用户可访问）
 pub fn create_preamble_query_router() -> Router<AppState> {
     Router::new().route("/api/preamble", get(get_preamble))
 }
 
-/// 创建 Preamble 路由 - 修改操作（仅管理员可访问）
+/// 创建 Preamble 路由 - 修改和版本管理（需要 preamble.write）
 pub fn create_preamble_mutation_router() -> Router<AppState> {
-    Router::new().route("/api/preamble", put(update_preamble))
+    Router::new()
+        .route("/api/preamble", put(update_preamble))
+        .route("/api/preamble/versions", get(list_preamble_versions))
+        .route(
+            "/api/preamble/versions/{version}",
+            get(get_preamble_version),
+        )
+        .route("/api/preamble/diff", get(diff_preamble_versions))
+        .route(
+            "/api/preamble/versions/{version}/rollback",
+            post(rollback_preamble),
+        )
+        .route("/api/preamble/versions/{version}/pin", post(pin_preamble))
+        .route("/api/preamble/drafts", get(list_preamble_drafts))
+        .route(
+            "/api/preamble/drafts/{id}/publish",
+            post(publish_preamble_draft),
+        )
+        .route(
+            "/api/preamble/drafts/{id}/reject",
+            post(reject_preamble_draft),
+        )
+}
+
+/// 是否需要双人审批：`auth.preamble_require_approval` 开启时修改只生成草稿，
+/// 由另一位拥有 preamble.write 权限的用户发布
+fn approval_required() -> bool {
+    get_config().auth.preamble_require_approval
+}
+
+fn preamble_response(
+    content: String,
+    version: Option<i64>,
+    updated_at: chrono::DateTime<chrono::Utc>,
+) -> PreambleResponse {
+    PreambleResponse {
+        content,
+        version,
+        require_approval: approval_required(),
+        updated_at: updated_at.to_rfc3339(),
+    }
+}
+
+fn preamble_store() -> Result<&'static PreambleStore, StatusCode> {
+    get_preamble_store().map(Arc::as_ref).ok_or_else(|| {
+        error!("Preamble store is not initialized");
+        StatusCode::SERVICE_UNAVAILABLE
+    })
+}
+
+/// 本次请求操作的 preamble：版本存储中的 key 和所属知识库
+pub(crate) struct PreambleScope {
+    pub key: String,
+    pub handle: Arc<KnowledgeBaseHandle>,
+}
+
+/// 按 `knowledge_base` 参数确定操作的知识库，不传时为工作区的默认知识库
+pub(crate) async fn preamble_scope(
+    claims: &Claims,
+    knowledge_base: Option<&str>,
+) -> Result<PreambleScope, StatusCode> {
+    let knowledge_base = knowledge_base
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && *id != DEFAULT_KNOWLEDGE_BASE);
+    let handle = knowledge_base_handle(claims, knowledge_base).await?;
+    Ok(PreambleScope {
+        key: preamble_key(&claims.workspace, knowledge_base),
+        handle,
+    })
 }
 
 async fn get_preamble(
-    State((agent, _)): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<PreambleScopeQuery>,
 ) -> Result<ResponseJson<PreambleResponse>, StatusCode> {
-    // 从 agent context 获取 preamble，因为 Qdrant 主要用于向量存储
-    let context = agent.context.read();
-    Ok(ResponseJson(PreambleResponse {
-        content: context.preamble.clone(),
-        updated_at: chrono::Utc::now().to_rfc3339(),
-    }))
+    // 从知识库 agent context 获取 preamble，因为 Qdrant 主要用于向量存储
+    let scope = preamble_scope(&claims, query.knowledge_base.as_deref()).await?;
+    let version = match get_preamble_store() {
+        Some(store) => store.live_version(&scope.key).await.map_err(|e| {
+            error!("Failed to get live preamble version: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        None => None,
+    };
+    let content = scope.handle.agent.context.read().preamble.clone();
+    Ok(ResponseJson(preamble_response(
+        content,
+        version,
+        chrono::Utc::now(),
+    )))
 }
 
+/// 保存新版本（需要审批时提交草稿，返回 202），preamble 中有未知变量时返回 400 和错误信息
 async fn update_preamble(
-    State((agent, _)): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(query): Query<PreambleScopeQuery>,
     Json(req): Json<UpdatePreambleRequest>,
+) -> Response {
+    if let Err(error) = validate_preamble(&req.content) {
+        return (
+            StatusCode::BAD_REQUEST,
+            ResponseJson(ErrorResponse { error }),
+        )
+            .into_response();
+    }
+    let scope = match preamble_scope(&claims, query.knowledge_base.as_deref()).await {
+        Ok(scope) => scope,
+        Err(status) => return status.into_response(),
+    };
+    match save_preamble(&claims, addr, &scope, &req.content, req.comment.as_deref()).await {
+        Ok(PreambleChange::Saved(version)) => ResponseJson(preamble_response(
+            version.content,
+            Some(version.version),
+            version.created_at,
+        ))
+        .into_response(),
+        Ok(PreambleChange::Proposed(draft)) => {
+            (StatusCode::ACCEPTED, ResponseJson(draft)).into_response()
+        }
+        Err(status) => status.into_response(),
+    }
+}
+
+/// 保存 preamble 的结果：直接生效的新版本，或需要审批时提交的草稿
+pub(crate) enum PreambleChange {
+    Saved(PreambleVersion),
+    Proposed(PreambleDraft),
+}
+
+/// 按审批设置保存 preamble，调用方负责先校验内容
+pub(crate) async fn save_preamble(
+    claims: &Claims,
+    addr: SocketAddr,
+    scope: &PreambleScope,
+    content: &str,
+    comment: Option<&str>,
+) -> Result<PreambleChange, StatusCode> {
+    let store = preamble_store()?;
+    if approval_required() {
+        let draft = propose_draft(store, claims, addr, scope, content, comment).await?;
+        return Ok(PreambleChange::Proposed(draft));
+    }
+
+    let previous = ensure_initial_version(store, scope).await?;
+    let (version, preamble_file) =
+        save_and_apply(store, claims, scope, &previous, content, comment).await?;
+
+    record_audit(
+        claims,
+        addr,
+        AuditAction::PreambleUpdated,
+        preamble_file,
+        Some(preamble_summary(&previous)),
+        Some(preamble_summary(&version)),
+    )
+    .await;
+    Ok(PreambleChange::Saved(version))
+}
+
+/// 列出全部版本
+async fn list_preamble_versions(
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<PreambleScopeQuery>,
+) -> Result<ResponseJson<Vec<PreambleVersionSummary>>, StatusCode> {
+    let store = preamble_store()?;
+    let scope = preamble_scope(&claims, query.knowledge_base.as_deref()).await?;
+    let internal = |e: anyhow::Error| {
+        error!("Failed to list preamble versions: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let live = store.live_version(&scope.key).await.map_err(internal)?;
+    let versions = store.list_versions(&scope.key).await.map_err(internal)?;
+
+    Ok(ResponseJson(
+        versions
+            .into_iter()
+            .map(|v| PreambleVersionSummary {
+                live: Some(v.version) == live,
+                chars: v.content.chars().count(),
+                version: v.version,
+                author: v.author,
+                comment: v.comment,
+                created_at: v.created_at.to_rfc3339(),
+            })
+            .collect(),
+    ))
+}
+
+async fn get_preamble_version(
+    Extension(claims): Extension<Claims>,
+    Path(version): Path<i64>,
+    Query(query): Query<PreambleScopeQuery>,
+) -> Result<ResponseJson<PreambleVersion>, StatusCode> {
+    let scope = preamble_scope(&claims, query.knowledge_base.as_deref()).await?;
+    Ok(ResponseJson(
+        load_version(preamble_store()?, &scope, version).await?,
+    ))
+}
+
+/// 比较两个版本
+async fn diff_preamble_versions(
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<PreambleDiffQuery>,
+) -> Result<ResponseJson<PreambleDiffResponse>, StatusCode> {
+    let store = preamble_store()?;
+    let scope = preamble_scope(&claims, query.knowledge_base.as_deref()).await?;
+    let to = match query.to {
+        Some(to) => to,
+        None => store
+            .live_version(&scope.key)
+            .await
+            .map_err(|e| {
+                error!("Failed to get live preamble version: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .ok_or(StatusCode::NOT_FOUND)?,
+    };
+    let old = load_version(store, &scope, query.from).await?;
+    let new = load_version(store, &scope, to).await?;
+
+    Ok(ResponseJson(PreambleDiffResponse {
+        from: old.version,
+        to: new.version,
+        diff: unified_diff(
+            &old.content,
+            &new.content,
+            &format!("v{}", old.version),
+            &format!("v{}", new.version),
+        ),
+    }))
+}
+
+/// 以历史版本的内容保存一个新版本并生效，需要审批时以该内容提交草稿
+async fn rollback_preamble(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(version): Path<i64>,
+    Query(query): Query<PreambleScopeQuery>,
+    Json(req): Json<PreambleVersionRequest>,
+) -> Result<Response, StatusCode> {
+    let store = preamble_store()?;
+    let scope = preamble_scope(&claims, query.knowledge_base.as_deref()).await?;
+    let target = load_version(store, &scope, version).await?;
+    let comment = req
+        .comment
+        .unwrap_or_else(|| format!("Rollback to v{}", target.version));
+    if approval_required() {
+        let draft = propose_draft(
+            store,
+            &claims,
+            addr,
+            &scope,
+            &target.content,
+            Some(&comment),
+        )
+        .await?;
+        return Ok((StatusCode::ACCEPTED, ResponseJson(draft)).into_response());
+    }
+
+    let previous = ensure_initial_version(store, &scope).await?;
+    let (saved, preamble_file) = save_and_apply(
+        store,
+        &claims,
+        &scope,
+        &previous,
+        &target.content,
+        Some(&comment),
+    )
+    .await?;
+    info!(
+        "⏪ {} rolled back preamble {} to v{} (saved as v{})",
+        claims.sub, scope.key, target.version, saved.version
+    );
+
+    let mut after = preamble_summary(&saved);
+    after["rollback_from"] = target.version.into();
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::PreambleUpdated,
+        preamble_file,
+        Some(preamble_summary(&previous)),
+        Some(after),
+    )
+    .await;
+
+    Ok(ResponseJson(preamble_response(
+        saved.content,
+        Some(saved.version),
+        saved.created_at,
+    ))
+    .into_response())
+}
+
+/// 指定生效的版本，不产生新版本；需要审批时不可用（返回 409），应通过回滚提交草稿
+async fn pin_preamble(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(version): Path<i64>,
+    Query(query): Query<PreambleScopeQuery>,
 ) -> Result<ResponseJson<PreambleResponse>, StatusCode> {
-    // 验证秘密密钥
-    let required_secret =
-        std::env::var("PREAMBLE_SECRET_KEY").unwrap_or_else(|_| "aaa111===".to_string());
+    if approval_required() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let store = preamble_store()?;
+    let scope = preamble_scope(&claims, query.knowledge_base.as_deref()).await?;
+    let target = load_version(store, &scope, version).await?;
+    let previous = ensure_initial_version(store, &scope).await?;
+    store
+        .pin_version(&scope.key, target.version)
+        .await
+        .map_err(|e| {
+            error!("Failed to pin preamble version: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let preamble_file = apply_or_restore(store, &scope, &previous, &target.content).await?;
+    info!(
+        "📌 {} pinned preamble {} to v{}",
+        claims.sub, scope.key, target.version
+    );
 
-    let provided_secret = req.secret_key.as_deref().unwrap_or("");
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::PreamblePinned,
+        preamble_file,
+        Some(preamble_summary(&previous)),
+        Some(preamble_summary(&target)),
+    )
+    .await;
 
-    if provided_secret != required_secret {
-        error!("❌ Preamble update failed: invalid secret key");
+    Ok(ResponseJson(preamble_response(
+        target.content,
+        Some(target.version),
+        chrono::Utc::now(),
+    )))
+}
+
+/// 列出草稿及其相对当前生效版本的差异
+async fn list_preamble_drafts(
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<PreambleDraftQuery>,
+) -> Result<ResponseJson<Vec<PreambleDraftResponse>>, StatusCode> {
+    let store = preamble_store()?;
+    let scope = preamble_scope(&claims, query.knowledge_base.as_deref()).await?;
+    let status = query.status.unwrap_or(PreambleDraftStatus::Pending);
+    let drafts = store
+        .list_drafts(&scope.key, Some(status))
+        .await
+        .map_err(|e| {
+            error!("Failed to list preamble drafts: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let live_version = store.live_version(&scope.key).await.map_err(|e| {
+        error!("Failed to get live preamble version: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let live = match live_version {
+        Some(version) => {
+            let live = load_version(store, &scope, version).await?;
+            (format!("v{}", live.version), live.content)
+        }
+        None => {
+            let content = scope.handle.agent.context.read().preamble.clone();
+            ("current".to_string(), content)
+        }
+    };
+
+    Ok(ResponseJson(
+        drafts
+            .into_iter()
+            .map(|draft| {
+                let outdated = draft.status == PreambleDraftStatus::Pending
+                    && (draft.base_version.is_none() || draft.base_version != live_version);
+                PreambleDraftResponse {
+                    can_publish: draft.status == PreambleDraftStatus::Pending
+                        && draft.author_id != claims.user_id
+                        && !outdated,
+                    outdated,
+                    diff: unified_diff(
+                        &live.1,
+                        &draft.content,
+                        &live.0,
+                        &format!("draft {}", draft.id),
+                    ),
+                    draft,
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// 发布草稿：保存为新版本并立即生效，提交者本人不能发布；
+/// 提交后生效版本已变化（其他草稿发布或回滚）时返回 409，需基于当前版本重新提交
+async fn publish_preamble_draft(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<i64>,
+    Query(query): Query<PreambleScopeQuery>,
+) -> Result<ResponseJson<PreambleResponse>, StatusCode> {
+    let store = preamble_store()?;
+    let scope = preamble_scope(&claims, query.knowledge_base.as_deref()).await?;
+    let draft = load_draft(store, &scope, id).await?;
+    if draft.status != PreambleDraftStatus::Pending {
+        return Err(StatusCode::CONFLICT);
+    }
+    if draft.author_id == claims.user_id {
+        warn!(
+            "🚫 {} tried to publish own preamble draft #{}",
+            claims.sub, id
+        );
         return Err(StatusCode::FORBIDDEN);
     }
 
-    info!("✅ Secret key verified, updating preamble");
+    let previous = ensure_initial_version(store, &scope).await?;
+    let version = match store
+        .publish_draft(&scope.key, id, &claims.sub)
+        .await
+        .map_err(|e| {
+            error!("Failed to publish preamble draft {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })? {
+        PublishDraftOutcome::Published(version) => version,
+        PublishDraftOutcome::NotPending => return Err(StatusCode::CONFLICT),
+        PublishDraftOutcome::Outdated { live_version } => {
+            warn!(
+                "Preamble draft #{} for {} is based on version {:?} but {:?} is live, resubmission required",
+                id, scope.key, draft.base_version, live_version
+            );
+            return Err(StatusCode::CONFLICT);
+        }
+    };
+    let preamble_file = apply_or_restore(store, &scope, &previous, &version.content).await?;
+    info!(
+        "🚀 {} published preamble draft #{} by {} for {} as v{}",
+        claims.sub, id, draft.author, scope.key, version.version
+    );
 
-    // 更新 agent context 中的 preamble 并持久化到文件
-    {
-        let mut context = agent.context.write();
+    let mut after = preamble_summary(&version);
+    after["draft_id"] = id.into();
+    after["proposed_by"] = draft.author.into();
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::PreambleUpdated,
+        preamble_file,
+        Some(preamble_summary(&previous)),
+        Some(after),
+    )
+    .await;
 
-        context.preamble = req.content.clone();
-        context.needs_rebuild = true; // 标记需要重建agent
+    Ok(ResponseJson(preamble_response(
+        version.content,
+        Some(version.version),
+        version.created_at,
+    )))
+}
+
+/// 拒绝草稿，提交者本人可以用它撤回
+async fn reject_preamble_draft(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<i64>,
+    Query(query): Query<PreambleScopeQuery>,
+) -> Result<ResponseJson<PreambleDraft>, StatusCode> {
+    let store = preamble_store()?;
+    let scope = preamble_scope(&claims, query.knowledge_base.as_deref()).await?;
+    load_draft(store, &scope, id).await?;
+    let rejected = store
+        .reject_draft(&scope.key, id, &claims.sub)
+        .await
+        .map_err(|e| {
+            error!("Failed to reject preamble draft {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if !rejected {
+        return Err(StatusCode::CONFLICT);
     }
+    let draft = load_draft(store, &scope, id).await?;
+    info!(
+        "❎ {} rejected preamble draft #{} for {}",
+        claims.sub, id, scope.key
+    );
+
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::PreambleDraftRejected,
+        draft_target(id),
+        None,
+        Some(draft_summary(&draft)),
+    )
+    .await;
+
+    Ok(ResponseJson(draft))
+}
+
+/// 提交草稿，等待另一位用户发布
+async fn propose_draft(
+    store: &PreambleStore,
+    claims: &Claims,
+    addr: SocketAddr,
+    scope: &PreambleScope,
+    content: &str,
+    comment: Option<&str>,
+) -> Result<PreambleDraft, StatusCode> {
+    let comment = comment.map(str::trim).filter(|c| !c.is_empty());
+    // 记录提交时的生效版本，发布前生效版本变化时需要重新提交
+    let base = ensure_initial_version(store, scope).await?;
+    let draft = store
+        .create_draft(
+            &scope.key,
+            content,
+            &claims.sub,
+            claims.user_id,
+            comment,
+            base.version,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to save preamble draft: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    info!(
+        "📝 {} proposed preamble draft #{} for {}",
+        claims.sub, draft.id, scope.key
+    );
+
+    let mut summary = draft_summary(&draft);
+    summary["preamble"] = scope.key.clone().into();
+    record_audit(
+        claims,
+        addr,
+        AuditAction::PreambleDraftProposed,
+        draft_target(draft.id),
+        None,
+        Some(summary),
+    )
+    .await;
+    Ok(draft)
+}
+
+async fn load_draft(
+    store: &PreambleStore,
+    scope: &PreambleScope,
+    id: i64,
+) -> Result<PreambleDraft, StatusCode> {
+    store
+        .get_draft(&scope.key, id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get preamble draft {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn load_version(
+    store: &PreambleStore,
+    scope: &PreambleScope,
+    version: i64,
+) -> Result<PreambleVersion, StatusCode> {
+    store
+        .get_version(&scope.key, version)
+        .await
+        .map_err(|e| {
+            error!("Failed to get preamble version {}: {}", version, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
 
-    // 先同步保存到文件，确保持久化成功
-    save_preamble_to_file(&req.content).await.map_err(|e| {
-        error!("Failed to save preamble to file: {}", e);
+/// 先保存新版本，再写入 agent 和文件；写入失败时恢复之前的生效版本
+async fn save_and_apply(
+    store: &PreambleStore,
+    claims: &Claims,
+    scope: &PreambleScope,
+    previous: &PreambleVersion,
+    content: &str,
+    comment: Option<&str>,
+) -> Result<(PreambleVersion, String), StatusCode> {
+    let comment = comment.map(str::trim).filter(|c| !c.is_empty());
+    let version = store
+        .create_version(&scope.key, content, &claims.sub, comment)
+        .await
+        .map_err(|e| {
+            error!("Failed to save preamble version: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let preamble_file = apply_or_restore(store, scope, previous, &version.content).await?;
+    Ok((version, preamble_file))
+}
+
+/// 返回当前生效的版本；还没有任何版本时（升级前的 preamble 文件）先把当前内容保存为第 1 版
+async fn ensure_initial_version(
+    store: &PreambleStore,
+    scope: &PreambleScope,
+) -> Result<PreambleVersion, StatusCode> {
+    let live = store.live_version(&scope.key).await.map_err(|e| {
+        error!("Failed to get live preamble version: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
+    if let Some(live) = live {
+        return load_version(store, scope, live).await;
+    }
 
-    info!("✅ Preamble updated in memory and saved to file, agent will be rebuilt on next chat");
+    let current = scope.handle.agent.context.read().preamble.clone();
+    store
+        .create_version(
+            &scope.key,
+            &current,
+            "system",
+            Some("Imported from preamble file"),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to import current preamble: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
 
-    Ok(ResponseJson(PreambleResponse {
-        content: req.content,
-        updated_at: chrono::Utc::now().to_rfc3339(),
-    }))
+/// 应用已设为生效的版本，失败时把生效版本恢复为 previous，agent 和文件保持原样
+async fn apply_or_restore(
+    store: &PreambleStore,
+    scope: &PreambleScope,
+    previous: &PreambleVersion,
+    content: &str,
+) -> Result<String, StatusCode> {
+    let result = apply_preamble(&scope.handle, content).await;
+    if result.is_err()
+        && let Err(e) = store.pin_version(&scope.key, previous.version).await
+    {
+        error!(
+            "Failed to restore live preamble {} to v{}: {}",
+            scope.key, previous.version, e
+        );
+    }
+    result
 }
 
-/// 保存 Preamble 到文件
-async fn save_preamble_to_file(content: &str) -> Result<(), std::io::Error> {
-    let preamble_path =
-        std::env::var("PREAMBLE_FILE").unwrap_or_else(|_| "data/preamble.md".to_string());
+/// 把 preamble 持久化到文件后更新知识库 agent context 中的 preamble，返回文件路径
+async fn apply_preamble(handle: &KnowledgeBaseHandle, content: &str) -> Result<String, StatusCode> {
+    let preamble_file = handle.agent.context.read().preamble_file.clone();
+
+    // 先保存到文件，失败时内存中的 preamble 保持不变
+    save_preamble_to_file(&preamble_file, content)
+        .await
+        .map_err(|e| {
+            error!("Failed to save preamble to file: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // 每次请求都用 context 中的 preamble 构建 agent，无需重建
+    handle.agent.context.write().preamble = content.to_string();
+    info!("✅ Preamble saved to file and updated in memory, effective from next chat");
+    Ok(preamble_file)
+}
 
+/// 审计日志中的 preamble 摘要
+fn preamble_summary(preamble: &PreambleVersion) -> serde_json::Value {
+    serde_json::json!({
+        "version": preamble.version,
+        "chars": preamble.content.chars().count(),
+        "preview": preamble.content.chars().take(AUDIT_PREVIEW_CHARS).collect::<String>(),
+    })
+}
+
+/// 审计日志中草稿的 target
+fn draft_target(id: i64) -> String {
+    format!("preamble_draft:{}", id)
+}
+
+/// 审计日志中的草稿摘要
+fn draft_summary(draft: &PreambleDraft) -> serde_json::Value {
+    serde_json::json!({
+        "draft_id": draft.id,
+        "author": draft.author,
+        "comment": draft.comment,
+        "base_version": draft.base_version,
+        "chars": draft.content.chars().count(),
+        "preview": draft.content.chars().take(AUDIT_PREVIEW_CHARS).collect::<String>(),
+    })
+}
+
+/// 按行比较两段内容，生成 unified diff
+fn unified_diff(old: &str, new: &str, old_header: &str, new_header: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_header, new_header)
+        .to_string()
+}
+
+/// 保存 Preamble 到文件
+async fn save_preamble_to_file(preamble_path: &str, content: &str) -> Result<(), std::io::Error> {
     // 确保目录存在
-    if let Some(parent) = std::path::Path::new(&preamble_path).parent() {
+    if let Some(parent) = std::path::Path::new(preamble_path).parent() {
         fs::create_dir_all(parent).await?;
     }
 
     // 写入文件
-    fs::write(&preamble_path, content).await?;
+    fs::write(preamble_path, content).await?;
     info!("Preamble saved to file: {}", preamble_path);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let diff = unified_diff(
+            "You are helpful.\nAnswer in English.\n",
+            "You are helpful.\nAnswer in Chinese.\n",
+            "v1",
+            "v2",
+        );
+        assert!(diff.starts_with("--- v1\n+++ v2\n"));
+        assert!(diff.contains("-Answer in English.\n+Answer in Chinese.\n"));
+    }
+}
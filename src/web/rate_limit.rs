@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use parking_lot::Mutex;
+use tower_governor::{
+    GovernorError,
+    key_extractor::{KeyExtractor, PeerIpKeyExtractor},
+};
+
+use crate::{
+    config::get_config,
+    db::{API_KEY_PREFIX, get_settings_store},
+    web::{CHAT_SESSION_HEADER, JwtUtil, bearer_token},
+};
+
+/// 聊天接口的限流键：登录用户按用户 id，匿名访客按会话 id，都没有时按 IP
+///
+/// 这里只校验签名，不查库；没有会话的请求（包括新建会话）仍按 IP 限流，
+/// 不能靠反复申请新会话绕过限制。API key 在这里无法校验，按 IP 限流，
+/// 避免随意构造的 key 各自得到一个令牌桶；key 自身的每分钟上限在认证时另行检查
+#[derive(Debug, Clone, Copy)]
+pub struct ChatRateLimitKeyExtractor;
+
+impl KeyExtractor for ChatRateLimitKeyExtractor {
+    type Key = String;
+
+    fn extract<T>(&self, req: &Request<T>) -> Result<Self::Key, GovernorError> {
+        if let Ok(Some(token)) = bearer_token(req.headers()) {
+            if token.starts_with(API_KEY_PREFIX) {
+                return ip_key(req);
+            }
+            if let Ok(claims) = JwtUtil::new().verify_token(token) {
+                return Ok(format!("user:{}", claims.user_id));
+            }
+        }
+
+        // 内置聊天组件通过路径参数获取历史
+        let session = req
+            .headers()
+            .get(CHAT_SESSION_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .or_else(|| req.uri().path().strip_prefix("/api/history/"));
+        if let Some(session) = session
+            && let Ok(session) = JwtUtil::new().verify_chat_session(session.trim())
+        {
+            return Ok(format!("visitor:{}", session.sid));
+        }
+
+        ip_key(req)
+    }
+}
+
+fn ip_key<T>(req: &Request<T>) -> Result<String, GovernorError> {
+    PeerIpKeyExtractor
+        .extract(req)
+        .map(|ip| format!("ip:{}", ip))
+}
+
+/// 聊天接口每个限流键的令牌桶：剩余请求数和上次计算的时间
+#[derive(Default)]
+struct ChatBuckets {
+    buckets: HashMap<String, (f64, Instant)>,
+    /// 下次允许清理的时间，避免桶数一直超过阈值时每个请求都遍历全部桶
+    next_prune: Option<Instant>,
+}
+
+static CHAT_BUCKETS: OnceLock<Mutex<ChatBuckets>> = OnceLock::new();
+
+/// 令牌桶超过这个数量时清理已经回满的桶
+const CHAT_BUCKET_PRUNE_THRESHOLD: usize = 10_000;
+
+/// 两次清理之间的最短间隔
+const CHAT_BUCKET_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 聊天接口的频率限制，每隔 chat_replenish_seconds 秒恢复一次额度，最多累积 chat_burst 次
+///
+/// 参数在每次请求时从运行时设置读取，修改后立即生效
+pub async fn chat_rate_limit(req: Request, next: Next) -> Response {
+    let (replenish_seconds, burst) = get_settings_store()
+        .map(|store| {
+            let settings = store.settings();
+            (settings.chat_replenish_seconds, settings.chat_burst)
+        })
+        .unwrap_or_else(|| {
+            let chat = &get_config().chat;
+            (chat.replenish_seconds, chat.burst)
+        });
+    let Ok(key) = ChatRateLimitKeyExtractor.extract(&req) else {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Unable to identify client",
+        )
+            .into_response();
+    };
+
+    if let Err(wait) = take_chat_token(&key, replenish_seconds, burst) {
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            format!("Too Many Requests! Wait for {}s", wait.as_secs().max(1)),
+        )
+            .into_response();
+        if let Ok(value) = wait.as_secs().max(1).to_string().parse() {
+            response.headers_mut().insert("retry-after", value);
+        }
+        return response;
+    }
+    next.run(req).await
+}
+
+/// 从限流键的令牌桶中取一个令牌，没有时返回需要等待的时间
+fn take_chat_token(key: &str, replenish_seconds: u64, burst: u32) -> Result<(), Duration> {
+    let now = Instant::now();
+    let interval = replenish_seconds.max(1) as f64;
+    let capacity = f64::from(burst.max(1));
+    let mut state = CHAT_BUCKETS.get_or_init(Default::default).lock();
+
+    if state.buckets.len() > CHAT_BUCKET_PRUNE_THRESHOLD
+        && state.next_prune.is_none_or(|next| now >= next)
+    {
+        state.buckets.retain(|_, (tokens, updated)| {
+            *tokens + now.duration_since(*updated).as_secs_f64() / interval < capacity
+        });
+        state.next_prune = Some(now + CHAT_BUCKET_PRUNE_INTERVAL);
+    }
+
+    let (tokens, updated) = state
+        .buckets
+        .entry(key.to_string())
+        .or_insert((capacity, now));
+    *tokens = (*tokens + now.duration_since(*updated).as_secs_f64() / interval).min(capacity);
+    *updated = now;
+    if *tokens < 1.0 {
+        return Err(Duration::from_secs_f64((1.0 - *tokens) * interval));
+    }
+    *tokens -= 1.0;
+    Ok(())
+}
+
+/// 当前小时内每个 IP 新建的匿名聊天会话数，进入新的一小时时清空
+#[derive(Default)]
+struct SessionWindow {
+    hour: i64,
+    counts: HashMap<IpAddr, u32>,
+}
+
+static CHAT_SESSION_WINDOW: OnceLock<Mutex<SessionWindow>> = OnceLock::new();
+
+/// 记录一次新建匿名会话，超过每个 IP 每小时的上限时返回 false（按自然小时计数）
+///
+/// 不带会话的聊天请求会签发新会话，单靠请求频率限制无法阻止同一 IP 批量创建访客
+pub fn take_chat_session(ip: IpAddr, limit_per_hour: u32) -> bool {
+    let hour = Utc::now().timestamp() / 3600;
+    let mut window = CHAT_SESSION_WINDOW.get_or_init(Default::default).lock();
+    if window.hour != hour {
+        window.hour = hour;
+        window.counts.clear();
+    }
+    let count = window.counts.entry(ip).or_insert(0);
+    if *count >= limit_per_hour {
+        return false;
+    }
+    *count += 1;
+    true
+}
+
+/// 每个 API key 当前分钟的请求数
+static API_KEY_WINDOWS: OnceLock<Mutex<HashMap<String, (i64, u32)>>> = OnceLock::new();
+
+/// 记录一次 API key 请求，超过每分钟上限时返回 false（按自然分钟计数）
+pub fn check_api_key_rate(key_id: &str, limit_per_minute: u32) -> bool {
+    let minute = Utc::now().timestamp() / 60;
+    let mut windows = API_KEY_WINDOWS.get_or_init(Default::default).lock();
+    let (window, count) = windows.entry(key_id.to_string()).or_insert((minute, 0));
+    if *window != minute {
+        *window = minute;
+        *count = 0;
+    }
+    if *count >= limit_per_minute {
+        return false;
+    }
+    *count += 1;
+    true
+}
@@ -3,17 +3,20 @@ use std::sync::Arc;
 use axum::{
     Router,
     extract::Path,
-    http::{HeaderValue, Method},
+    http::{HeaderValue, Method, request::Parts},
     middleware,
     response::Html,
     routing::get,
 };
-use tower_governor::{GovernorLayer, governor::GovernorConfigBuilder};
-use tower_http::cors::CorsLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
 use crate::{
     agent::RigAgent,
-    db::{ConversationStore, DocumentStore, UserStore},
+    config::get_config,
+    db::{
+        ConversationStore, DocumentStore, Permission, RuntimeSettings, UserStore, WorkspaceStore,
+        get_settings_store,
+    },
     web::*,
 };
 
@@ -21,34 +24,33 @@ pub async fn create_router(
     agent: Arc<RigAgent>,
     document_store: Arc<DocumentStore>,
     user_store: Arc<UserStore>,
+    workspace_store: Arc<WorkspaceStore>,
 ) -> Router {
     // 初始化对话存储
     let conversation_store = Arc::new(
-        ConversationStore::from_env()
+        ConversationStore::from_config()
             .await
             .expect("Failed to initialize conversation store"),
     );
-    let server_url = "*";
+    // 允许的来源按运行时设置判断，修改后立即生效
     let cors = CorsLayer::new()
-        .allow_origin(server_url.parse::<HeaderValue>().unwrap())
+        .allow_origin(AllowOrigin::predicate(allow_cors_origin))
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_headers(vec![
             axum::http::header::CONTENT_TYPE,
             axum::http::header::AUTHORIZATION,
+            axum::http::HeaderName::from_static(CHAT_SESSION_HEADER),
         ]);
 
-    // 配置频率限制
-    let chat_governor_conf = Arc::new(
-        GovernorConfigBuilder::default()
-            .per_second(3)
-            .burst_size(10)
-            .finish()
-            .unwrap(),
-    );
-
-    // 用户管理和认证路由（独立state）
-    let auth_user_router =
-        create_auth_router(user_store.clone()).merge(create_user_router(user_store));
+    // 用户管理、工作区和认证路由（独立state）
+    let user_state = (user_store, workspace_store);
+    let auth_user_router = create_auth_router(user_state.clone())
+        .merge(create_user_router(user_state.clone()))
+        .merge(create_workspace_router(user_state.clone()))
+        .merge(create_oidc_router(user_state.clone()))
+        .merge(create_totp_router(user_state.clone()))
+        .merge(create_api_key_router(user_state))
+        .merge(create_audit_router());
 
     // 公开路由（不需要认证）
     let public_router = Router::new()
@@ -57,42 +59,76 @@ pub async fn create_router(
         .route("/admin", get(serve_admin))
         .route("/static/{*file}", get(static_file));
 
-    // 需要登录即可访问的用户路由（所有用户）
-    let user_query_router = Router::new()
+    // 文档查询和检索（document.read）
+    let document_read_router = Router::new()
         .merge(crate::web::create_document_query_router())
+        .merge(crate::web::create_document_search_router())
         .merge(crate::web::create_preamble_query_router())
+        .route_layer(middleware::from_fn_with_state(
+            Permission::DocumentRead,
+            require_permission,
+        ));
+
+    // 文档修改（document.write）
+    let document_write_router = Router::new()
         .merge(crate::web::create_document_mutation_router())
         .layer(tower_http::limit::RequestBodyLimitLayer::new(
             10 * 1024 * 1024,
         )) // 文档上传限制
-        .route_layer(middleware::from_fn(require_user_auth_middleware));
+        .route_layer(middleware::from_fn_with_state(
+            Permission::DocumentWrite,
+            require_permission,
+        ));
 
-    // 需要Admin权限的修改路由
-    let admin_mutation_router = Router::new()
-        .merge(crate::web::create_preamble_mutation_router())
+    // 备份、一致性检查和运行时设置只针对默认工作区（system.manage）
+    let default_workspace_router = Router::new()
+        .merge(crate::web::create_backup_router())
+        .merge(crate::web::create_doctor_router())
+        .merge(crate::web::create_settings_router())
+        .route_layer(middleware::from_fn(require_default_workspace_middleware))
+        .route_layer(middleware::from_fn_with_state(
+            Permission::SystemManage,
+            require_permission,
+        ));
+
+    // 知识库管理和检索调试（knowledge_base.manage）
+    let knowledge_base_router = Router::new()
+        .merge(crate::web::create_playground_router())
+        .merge(crate::web::create_knowledge_base_router())
         .layer(tower_http::limit::RequestBodyLimitLayer::new(
             10 * 1024 * 1024,
         )) // 文档上传限制
-        .route_layer(middleware::from_fn(require_admin_auth_middleware));
+        .route_layer(middleware::from_fn_with_state(
+            Permission::KnowledgeBaseManage,
+            require_permission,
+        ));
+
+    // 管理路由，各自要求对应的权限
+    let admin_mutation_router = Router::new()
+        .merge(crate::web::create_preamble_mutation_router().route_layer(
+            middleware::from_fn_with_state(Permission::PreambleWrite, require_permission),
+        ))
+        .merge(default_workspace_router)
+        .merge(knowledge_base_router);
 
     // 分别创建不同状态的路由
     let chat_router = create_chat_router()
         .layer(tower_http::limit::RequestBodyLimitLayer::new(10 * 1024)) // 聊天消息限制为10KB
-        .layer(GovernorLayer::new(chat_governor_conf)) // 基于IP的频率限制
+        .layer(middleware::from_fn(chat_rate_limit)) // 按登录用户或匿名访客计数，没有身份时按 IP
         .with_state((
             agent.clone(),
             document_store.clone(),
             conversation_store.clone(),
         ));
 
-    let conversation_router = create_conversation_router().with_state((
-        agent.clone(),
-        document_store.clone(),
-        conversation_store,
-    ));
+    let conversation_router = create_conversation_router()
+        .merge(create_admin_conversation_router())
+        .merge(create_experiment_router())
+        .with_state((agent.clone(), document_store.clone(), conversation_store));
 
-    let user_query_router_with_state =
-        user_query_router.with_state((agent.clone(), document_store.clone()));
+    let document_router_with_state = document_read_router
+        .merge(document_write_router)
+        .with_state((agent.clone(), document_store.clone()));
 
     let admin_mutation_router_with_state =
         admin_mutation_router.with_state((agent, document_store));
@@ -102,11 +138,21 @@ pub async fn create_router(
         .merge(auth_user_router)
         .merge(chat_router)
         .merge(conversation_router)
-        .merge(user_query_router_with_state)
+        .merge(document_router_with_state)
         .merge(admin_mutation_router_with_state)
         .layer(cors)
 }
 
+fn allow_cors_origin(origin: &HeaderValue, _: &Parts) -> bool {
+    let Ok(origin) = origin.to_str() else {
+        return false;
+    };
+    match get_settings_store() {
+        Some(store) => store.settings().allows_origin(origin),
+        None => RuntimeSettings::from_config(get_config()).allows_origin(origin),
+    }
+}
+
 async fn serve_index() -> Result<Html<String>, axum::http::StatusCode> {
     let file_content = std::fs::read_to_string("static/index.html")
         .map_err(|_| axum::http::StatusCode::NOT_FOUND)?;
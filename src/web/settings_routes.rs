@@ -0,0 +1,161 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Extension},
+    routing::get,
+};
+use serde::Deserialize;
+use tracing::{error, info};
+
+use super::auth_routes::{AppError, Claims};
+use crate::{
+    agent::{RigAgent, get_knowledge_bases},
+    db::{
+        AuditAction, DocumentStore, RuntimeSettings, SettingsStore, StoredSettings,
+        get_settings_store,
+    },
+    web::record_audit,
+};
+
+type AppState = (Arc<RigAgent>, Arc<DocumentStore>);
+
+/// 修改运行时设置，未提供的字段保持不变
+#[derive(Debug, Deserialize)]
+pub struct UpdateSettingsRequest {
+    pub openai_model: Option<String>,
+    pub temperature: Option<f64>,
+    /// 为 0 时取消限制，检索集合中的全部文档
+    pub top_k: Option<usize>,
+    pub chat_replenish_seconds: Option<u64>,
+    pub chat_burst: Option<u32>,
+    pub cors_origins: Option<Vec<String>>,
+}
+
+impl UpdateSettingsRequest {
+    fn apply(self, mut settings: RuntimeSettings) -> RuntimeSettings {
+        if let Some(model) = self.openai_model {
+            settings.openai_model = model.trim().to_string();
+        }
+        if let Some(temperature) = self.temperature {
+            settings.temperature = temperature;
+        }
+        if let Some(top_k) = self.top_k {
+            settings.top_k = (top_k > 0).then_some(top_k);
+        }
+        if let Some(seconds) = self.chat_replenish_seconds {
+            settings.chat_replenish_seconds = seconds;
+        }
+        if let Some(burst) = self.chat_burst {
+            settings.chat_burst = burst;
+        }
+        if let Some(origins) = self.cors_origins {
+            settings.cors_origins = origins.iter().map(|o| o.trim().to_string()).collect();
+        }
+        settings
+    }
+}
+
+/// 创建运行时设置路由，由调用方限制为默认工作区的 system.manage
+pub fn create_settings_router() -> Router<AppState> {
+    Router::new().route(
+        "/api/admin/settings",
+        get(get_settings)
+            .put(update_settings)
+            .delete(reset_settings),
+    )
+}
+
+fn settings_store() -> Result<&'static SettingsStore, AppError> {
+    get_settings_store()
+        .map(Arc::as_ref)
+        .ok_or_else(|| AppError::Internal(anyhow::anyhow!("Settings store not initialized")))
+}
+
+/// 把模型参数应用到已加载的 agent，下一次聊天请求生效
+async fn apply_generation(settings: &RuntimeSettings) -> Result<(), AppError> {
+    if let Some(registry) = get_knowledge_bases() {
+        registry
+            .update_generation(&settings.openai_model, settings.temperature, settings.top_k)
+            .await?;
+    }
+    Ok(())
+}
+
+/// 应用新设置，失败时把已保存的设置和 agent 恢复为之前的值
+async fn apply_or_restore(
+    store: &SettingsStore,
+    previous: StoredSettings,
+    settings: &RuntimeSettings,
+) -> Result<(), AppError> {
+    let Err(e) = apply_generation(settings).await else {
+        return Ok(());
+    };
+    error!(
+        "Failed to apply runtime settings, restoring previous settings: {:?}",
+        e
+    );
+    if let Err(restore_err) = apply_generation(&previous.settings).await {
+        error!("Failed to re-apply previous settings: {:?}", restore_err);
+    }
+    if let Err(restore_err) = store.restore(previous).await {
+        error!("Failed to restore previous settings: {}", restore_err);
+    }
+    Err(e)
+}
+
+async fn get_settings() -> Result<Json<StoredSettings>, AppError> {
+    Ok(Json(settings_store()?.current()))
+}
+
+/// 校验并保存设置，模型、检索、限流和 CORS 立即生效
+async fn update_settings(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(req): Json<UpdateSettingsRequest>,
+) -> Result<Json<StoredSettings>, AppError> {
+    let store = settings_store()?;
+    let previous = store.current();
+    let before = previous.settings.clone();
+    let settings = req.apply(before.clone());
+    settings.validate().map_err(AppError::BadRequest)?;
+
+    let stored = store.save(settings.clone(), &claims.sub).await?;
+    apply_or_restore(store, previous, &settings).await?;
+
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::SettingsUpdated,
+        "settings",
+        serde_json::to_value(&before).ok(),
+        serde_json::to_value(&settings).ok(),
+    )
+    .await;
+    info!("⚙️ {} updated runtime settings", claims.sub);
+    Ok(Json(stored))
+}
+
+/// 删除已保存的设置，恢复为环境变量的值
+async fn reset_settings(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Result<Json<StoredSettings>, AppError> {
+    let store = settings_store()?;
+    let previous = store.current();
+    let before = previous.settings.clone();
+    let stored = store.reset().await?;
+    apply_or_restore(store, previous, &stored.settings).await?;
+
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::SettingsUpdated,
+        "settings",
+        serde_json::to_value(&before).ok(),
+        serde_json::to_value(&stored.settings).ok(),
+    )
+    .await;
+    info!("⚙️ {} reset runtime settings", claims.sub);
+    Ok(Json(stored))
+}
@@ -0,0 +1,242 @@
+use std::net::SocketAddr;
+
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Extension, Path, State},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::auth_routes::{
+    AppError, Claims, LoginResponse, UserAppState, issue_tokens, require_permission,
+    require_user_auth_middleware, verify_second_factor,
+};
+use crate::{
+    db::{AuditAction, AuthEventKind, NewAuthEvent, Permission, TotpStatus, UserStore},
+    utils::totp_provisioning_uri,
+    web::{ensure_manageable, record_audit},
+};
+
+/// 验证器 App 中显示的签发方名称
+const TOTP_ISSUER: &str = "rig-rag";
+
+/// 开始绑定的响应，密钥确认前不生效
+#[derive(Debug, Serialize)]
+pub struct TotpSetupResponse {
+    pub secret: String,
+    /// otpauth URI，可生成二维码供验证器 App 扫描
+    pub otpauth_uri: String,
+}
+
+/// 验证码请求，`code` 为 6 位验证码或恢复码
+#[derive(Debug, Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+/// 绑定成功的响应：恢复码只在此时返回一次；此前的登录全部失效，附带新的 token
+#[derive(Debug, Serialize)]
+pub struct TotpConfirmResponse {
+    pub recovery_codes: Vec<String>,
+    #[serde(flatten)]
+    pub tokens: LoginResponse,
+}
+
+/// 重新生成的恢复码
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// 创建两步验证路由：当前用户自助管理，管理员可重置成员的两步验证
+pub fn create_totp_router(state: UserAppState) -> Router {
+    let user_routes = Router::new()
+        .route("/api/auth/totp", get(totp_status_handler))
+        .route("/api/auth/totp/setup", post(totp_setup_handler))
+        .route("/api/auth/totp/confirm", post(totp_confirm_handler))
+        .route(
+            "/api/auth/totp/recovery-codes",
+            post(regenerate_recovery_codes_handler),
+        )
+        .route("/api/auth/totp/disable", post(totp_disable_handler))
+        .route_layer(middleware::from_fn(require_user_auth_middleware))
+        .with_state(state.clone());
+
+    let admin_routes = Router::new()
+        .route("/api/users/{id}/totp/reset", post(reset_user_totp_handler))
+        .route_layer(middleware::from_fn_with_state(
+            Permission::UserManage,
+            require_permission,
+        ))
+        .with_state(state);
+
+    user_routes.merge(admin_routes)
+}
+
+async fn totp_status_handler(
+    Extension(claims): Extension<Claims>,
+    State((user_store, _)): State<UserAppState>,
+) -> Result<Json<TotpStatus>, AppError> {
+    Ok(Json(user_store.totp_status(claims.user_id).await?))
+}
+
+/// 生成新的 TOTP 密钥，重复调用会替换尚未确认的密钥
+async fn totp_setup_handler(
+    Extension(claims): Extension<Claims>,
+    State((user_store, _)): State<UserAppState>,
+) -> Result<Json<TotpSetupResponse>, AppError> {
+    if user_store.totp_status(claims.user_id).await?.enabled {
+        return Err(AppError::BadRequest(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let secret = user_store.start_totp_enrollment(claims.user_id).await?;
+    Ok(Json(TotpSetupResponse {
+        otpauth_uri: totp_provisioning_uri(TOTP_ISSUER, &claims.sub, &secret),
+        secret,
+    }))
+}
+
+/// 用验证器 App 中的验证码确认绑定
+async fn totp_confirm_handler(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((user_store, _)): State<UserAppState>,
+    Json(req): Json<TotpCodeRequest>,
+) -> Result<Json<TotpConfirmResponse>, AppError> {
+    let recovery_codes = user_store
+        .confirm_totp(claims.user_id, &req.code)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Invalid verification code".to_string()))?;
+    user_store
+        .record_auth_event(NewAuthEvent {
+            kind: AuthEventKind::MfaEnabled,
+            username: &claims.sub,
+            user_id: Some(claims.user_id),
+            ip: Some(addr.ip().to_string()),
+            detail: None,
+        })
+        .await;
+
+    let tokens = reissue_tokens(&user_store, &claims).await?;
+    Ok(Json(TotpConfirmResponse {
+        recovery_codes,
+        tokens,
+    }))
+}
+
+/// 重新生成恢复码，需提交当前的验证码
+async fn regenerate_recovery_codes_handler(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((user_store, _)): State<UserAppState>,
+    Json(req): Json<TotpCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AppError> {
+    ensure_second_factor(&user_store, &claims, &req.code, addr).await?;
+    let recovery_codes = user_store.regenerate_recovery_codes(claims.user_id).await?;
+    info!("User {} regenerated recovery codes", claims.sub);
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// 关闭两步验证，需提交当前的验证码或恢复码；其他设备上的登录全部失效
+///
+/// 要求管理员启用两步验证时，管理员关闭后新签发的 token 只能访问认证接口
+async fn totp_disable_handler(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((user_store, _)): State<UserAppState>,
+    Json(req): Json<TotpCodeRequest>,
+) -> Result<Json<LoginResponse>, AppError> {
+    ensure_second_factor(&user_store, &claims, &req.code, addr).await?;
+    user_store.disable_totp(claims.user_id).await?;
+    user_store.revoke_user_sessions(claims.user_id).await?;
+    user_store
+        .record_auth_event(NewAuthEvent {
+            kind: AuthEventKind::MfaDisabled,
+            username: &claims.sub,
+            user_id: Some(claims.user_id),
+            ip: Some(addr.ip().to_string()),
+            detail: None,
+        })
+        .await;
+    info!("User {} disabled two-factor authentication", claims.sub);
+
+    Ok(Json(reissue_tokens(&user_store, &claims).await?))
+}
+
+/// 管理员重置成员的两步验证（丢失设备且没有恢复码时），该用户的登录全部失效
+async fn reset_user_totp_handler(
+    Path(id): Path<i64>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((user_store, workspace_store)): State<UserAppState>,
+) -> Result<StatusCode, AppError> {
+    ensure_manageable(&workspace_store, &claims, id).await?;
+    let user = user_store
+        .get_user_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    if user_store.disable_totp(id).await? {
+        user_store.revoke_user_sessions(id).await?;
+        user_store
+            .record_auth_event(NewAuthEvent {
+                kind: AuthEventKind::MfaDisabled,
+                username: &user.username,
+                user_id: Some(id),
+                ip: None,
+                detail: Some(format!("reset by {}", claims.sub)),
+            })
+            .await;
+        info!(
+            "{} reset two-factor authentication of user {}",
+            claims.sub, id
+        );
+        record_audit(
+            &claims,
+            addr,
+            AuditAction::UserMfaReset,
+            id.to_string(),
+            None,
+            None,
+        )
+        .await;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 敏感操作前再次校验第二因素，锁定期间直接拒绝
+async fn ensure_second_factor(
+    user_store: &UserStore,
+    claims: &Claims,
+    code: &str,
+    addr: SocketAddr,
+) -> Result<(), AppError> {
+    if !user_store.totp_status(claims.user_id).await?.enabled {
+        return Err(AppError::BadRequest(
+            "Two-factor authentication is not enabled".to_string(),
+        ));
+    }
+    let user = user_store
+        .get_user_by_id(claims.user_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))?;
+    verify_second_factor(user_store, &user, code, Some(addr.ip().to_string())).await?;
+    Ok(())
+}
+
+/// 按用户当前状态为原工作区重新签发 token
+async fn reissue_tokens(
+    user_store: &UserStore,
+    claims: &Claims,
+) -> Result<LoginResponse, AppError> {
+    let user = user_store
+        .get_user_by_id(claims.user_id)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))?;
+    issue_tokens(user_store, user, &claims.workspace, None).await
+}
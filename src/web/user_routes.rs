@@ -1,18 +1,23 @@
-use std::sync::Arc;
+use std::net::SocketAddr;
 
 use axum::{
     Json, Router,
-    extract::{Extension, Path, State},
+    extract::{ConnectInfo, Extension, Path, Query, State},
+    http::StatusCode,
     middleware,
-    routing::get,
+    routing::{get, post},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tracing::info;
 
-use super::auth_routes::{AppError, Claims, require_user_auth_middleware};
+use super::auth_routes::{AppError, Claims, UserAppState, require_user_auth_middleware};
 use crate::{
-    db::{CreateUserRequest, UpdateUserRequest, User, UserStore},
-    web::require_admin_auth_middleware,
+    db::{
+        AuditAction, AuthEvent, AuthEventKind, CreateUserRequest, DEFAULT_WORKSPACE, NewAuthEvent,
+        PasswordPolicy, Permission, UpdateUserRequest, User, UserRole, WorkspaceStore,
+        validate_display_name,
+    },
+    web::{record_audit, require_permission},
 };
 
 /// 用户响应
@@ -20,8 +25,10 @@ use crate::{
 pub struct UserResponse {
     pub id: i64,
     pub username: String,
+    pub display_name: Option<String>,
     pub role: String,
     pub status: i32,
+    pub must_change_password: bool,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -31,19 +38,76 @@ impl From<User> for UserResponse {
         Self {
             id: user.id,
             username: user.username,
+            display_name: user.display_name,
             role: user.role.to_string(),
             status: user.status,
+            must_change_password: user.must_change_password,
             created_at: user.created_at.to_rfc3339(),
             updated_at: user.updated_at.to_rfc3339(),
         }
     }
 }
 
-/// 列出所有用户
+/// 审计日志中的用户摘要
+fn user_summary(user: &User) -> serde_json::Value {
+    serde_json::json!({
+        "username": user.username,
+        "display_name": user.display_name,
+        "role": user.role,
+        "status": user.status,
+        "groups": user.groups,
+        "must_change_password": user.must_change_password,
+    })
+}
+
+/// 确认用户属于当前工作区，否则视为不存在
+async fn ensure_member(
+    workspace_store: &WorkspaceStore,
+    claims: &Claims,
+    user_id: i64,
+) -> Result<(), AppError> {
+    if workspace_store
+        .is_member(&claims.workspace, user_id)
+        .await?
+    {
+        Ok(())
+    } else {
+        Err(AppError::NotFound("User not found".to_string()))
+    }
+}
+
+/// 确认可以修改用户的全局属性（密码、角色、状态、两步验证、登录会话）
+///
+/// 用户必须属于当前工作区；非默认工作区的管理员只能修改仅属于本工作区的用户，
+/// 否则会影响到其他工作区
+pub(crate) async fn ensure_manageable(
+    workspace_store: &WorkspaceStore,
+    claims: &Claims,
+    user_id: i64,
+) -> Result<(), AppError> {
+    ensure_member(workspace_store, claims, user_id).await?;
+    if claims.workspace == DEFAULT_WORKSPACE {
+        return Ok(());
+    }
+    let other_workspace = workspace_store
+        .list_for_user(user_id)
+        .await?
+        .iter()
+        .any(|w| w.id != claims.workspace);
+    if other_workspace {
+        return Err(AppError::Forbidden(
+            "User also belongs to other workspaces".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// 列出当前工作区的用户
 async fn list_users_handler(
-    State(user_store): State<Arc<UserStore>>,
+    Extension(claims): Extension<Claims>,
+    State((_, workspace_store)): State<UserAppState>,
 ) -> Result<Json<Vec<UserResponse>>, AppError> {
-    let users = user_store.list_users().await?;
+    let users = workspace_store.list_members(&claims.workspace).await?;
     let response: Vec<UserResponse> = users.into_iter().map(UserResponse::from).collect();
     Ok(Json(response))
 }
@@ -51,7 +115,7 @@ async fn list_users_handler(
 /// 获取当前用户信息
 async fn get_current_user_handler(
     Extension(claims): Extension<Claims>,
-    State(user_store): State<Arc<UserStore>>,
+    State((user_store, _)): State<UserAppState>,
 ) -> Result<Json<UserResponse>, AppError> {
     let user = user_store
         .get_user_by_id(claims.user_id)
@@ -64,8 +128,10 @@ async fn get_current_user_handler(
 /// 获取指定用户信息
 async fn get_user_handler(
     Path(id): Path<i64>,
-    State(user_store): State<Arc<UserStore>>,
+    Extension(claims): Extension<Claims>,
+    State((user_store, workspace_store)): State<UserAppState>,
 ) -> Result<Json<UserResponse>, AppError> {
+    ensure_member(&workspace_store, &claims, id).await?;
     let user = user_store
         .get_user_by_id(id)
         .await?
@@ -74,46 +140,215 @@ async fn get_user_handler(
     Ok(Json(UserResponse::from(user)))
 }
 
-/// 创建用户
+/// 创建用户，并加入当前工作区
 async fn create_user_handler(
-    State(user_store): State<Arc<UserStore>>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((user_store, workspace_store)): State<UserAppState>,
     Json(req): Json<CreateUserRequest>,
 ) -> Result<Json<UserResponse>, AppError> {
-    info!("Creating new user: {}", req.username);
+    info!(
+        "Creating new user: {} (workspace: {})",
+        req.username, claims.workspace
+    );
+    PasswordPolicy::current()
+        .validate(&req.username, &req.password)
+        .map_err(AppError::BadRequest)?;
+    if let Some(name) = req.display_name.as_deref() {
+        validate_display_name(name).map_err(AppError::BadRequest)?;
+    }
     let user = user_store.create_user(req).await?;
+    workspace_store
+        .add_member(&claims.workspace, user.id)
+        .await?;
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::UserCreated,
+        user.id.to_string(),
+        None,
+        Some(user_summary(&user)),
+    )
+    .await;
     Ok(Json(UserResponse::from(user)))
 }
 
 /// 更新用户
 async fn update_user_handler(
     Path(id): Path<i64>,
-    State(user_store): State<Arc<UserStore>>,
-    Json(req): Json<UpdateUserRequest>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((user_store, workspace_store)): State<UserAppState>,
+    Json(mut req): Json<UpdateUserRequest>,
 ) -> Result<Json<UserResponse>, AppError> {
+    ensure_manageable(&workspace_store, &claims, id).await?;
     info!("Updating user with id: {}", id);
+    let current = user_store
+        .get_user_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    let password_reset = match req.password.as_deref() {
+        Some(password) => {
+            PasswordPolicy::current()
+                .validate(&current.username, password)
+                .map_err(AppError::BadRequest)?;
+            true
+        }
+        None => false,
+    };
+    // 管理员重置的密码默认要求用户下次登录后修改，除非显式传入 false
+    if password_reset && req.must_change_password.is_none() {
+        req.must_change_password = Some(true);
+    }
+    if let Some(name) = req.display_name.as_deref() {
+        validate_display_name(name).map_err(AppError::BadRequest)?;
+    }
+
     let user = user_store.update_user(id, req).await?;
+    if password_reset {
+        user_store
+            .record_auth_event(NewAuthEvent {
+                kind: AuthEventKind::PasswordReset,
+                username: &user.username,
+                user_id: Some(user.id),
+                ip: None,
+                detail: Some(format!("by {}", claims.sub)),
+            })
+            .await;
+    }
+
+    let mut after = user_summary(&user);
+    after["password_reset"] = password_reset.into();
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::UserUpdated,
+        id.to_string(),
+        Some(user_summary(&current)),
+        Some(after),
+    )
+    .await;
     Ok(Json(UserResponse::from(user)))
 }
 
-/// 删除用户
+/// 强制用户在所有设备上退出登录，并吊销其创建的 API key
+async fn revoke_user_sessions_handler(
+    Path(id): Path<i64>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((user_store, workspace_store)): State<UserAppState>,
+) -> Result<StatusCode, AppError> {
+    ensure_manageable(&workspace_store, &claims, id).await?;
+    user_store.revoke_user_sessions(id).await?;
+    user_store.revoke_user_api_keys(id).await?;
+    info!("{} revoked all sessions of user {}", claims.sub, id);
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::UserSessionsRevoked,
+        id.to_string(),
+        None,
+        None,
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 认证事件查询参数
+#[derive(Debug, Deserialize)]
+pub struct AuthEventsQuery {
+    pub limit: Option<i64>,
+}
+
+/// 查询用户的登录、锁定和密码修改记录
+async fn list_auth_events_handler(
+    Path(id): Path<i64>,
+    Query(query): Query<AuthEventsQuery>,
+    Extension(claims): Extension<Claims>,
+    State((user_store, workspace_store)): State<UserAppState>,
+) -> Result<Json<Vec<AuthEvent>>, AppError> {
+    ensure_member(&workspace_store, &claims, id).await?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    Ok(Json(user_store.list_auth_events(id, limit).await?))
+}
+
+/// 将用户移出当前工作区，不再属于任何工作区时删除用户
 async fn delete_user_handler(
     Path(id): Path<i64>,
-    State(user_store): State<Arc<UserStore>>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((user_store, workspace_store)): State<UserAppState>,
 ) -> Result<Json<serde_json::Value>, AppError> {
+    ensure_member(&workspace_store, &claims, id).await?;
+    let user = user_store
+        .get_user_by_id(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    let mut before = user_summary(&user);
+    let remaining = workspace_store.remove_member(&claims.workspace, id).await?;
+    if remaining > 0 {
+        info!(
+            "Removed user {} from workspace {} ({} workspace(s) left)",
+            id, claims.workspace, remaining
+        );
+        before["remaining_workspaces"] = remaining.into();
+        record_audit(
+            &claims,
+            addr,
+            AuditAction::UserDeleted,
+            id.to_string(),
+            Some(before),
+            None,
+        )
+        .await;
+        return Ok(Json(serde_json::json!({
+            "message": "User removed from workspace"
+        })));
+    }
+
     info!("Deleting user with id: {}", id);
     user_store.delete_user(id).await?;
+    record_audit(
+        &claims,
+        addr,
+        AuditAction::UserDeleted,
+        id.to_string(),
+        Some(before),
+        None,
+    )
+    .await;
     Ok(Json(serde_json::json!({
         "message": "User deleted successfully"
     })))
 }
 
+/// 角色及其权限
+#[derive(Debug, Serialize)]
+pub struct RoleResponse {
+    pub role: UserRole,
+    pub permissions: &'static [Permission],
+}
+
+/// 列出可分配的角色，通过更新用户的 `role` 字段分配
+async fn list_roles_handler() -> Json<Vec<RoleResponse>> {
+    Json(
+        UserRole::ALL
+            .into_iter()
+            .map(|role| RoleResponse {
+                permissions: role.permissions(),
+                role,
+            })
+            .collect(),
+    )
+}
+
 /// 创建用户管理路由
-pub fn create_user_router(user_store: Arc<UserStore>) -> Router {
+pub fn create_user_router(state: UserAppState) -> Router {
     // 需要认证的路由
     let authenticated_routes = Router::new()
         .route("/api/users/me", get(get_current_user_handler))
         .route_layer(middleware::from_fn(require_user_auth_middleware))
-        .with_state(user_store.clone());
+        .with_state(state.clone());
 
     // 需要admin权限的路由
     let admin_routes = Router::new()
@@ -127,8 +362,17 @@ pub fn create_user_router(user_store: Arc<UserStore>) -> Router {
                 .put(update_user_handler)
                 .delete(delete_user_handler),
         )
-        .route_layer(middleware::from_fn(require_admin_auth_middleware))
-        .with_state(user_store);
+        .route(
+            "/api/users/{id}/revoke-sessions",
+            post(revoke_user_sessions_handler),
+        )
+        .route("/api/users/{id}/auth-events", get(list_auth_events_handler))
+        .route("/api/roles", get(list_roles_handler))
+        .route_layer(middleware::from_fn_with_state(
+            Permission::UserManage,
+            require_permission,
+        ))
+        .with_state(state);
 
     authenticated_routes.merge(admin_routes)
 }
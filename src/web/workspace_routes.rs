@@ -0,0 +1,323 @@
+use std::net::SocketAddr;
+
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Extension, Path, Query, State},
+    http::StatusCode,
+    middleware,
+    routing::{delete, get, put},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::auth_routes::{AppError, Claims, UserAppState, require_user_auth_middleware};
+use crate::{
+    agent::get_knowledge_bases,
+    db::{
+        AuditAction, ConversationStore, CreateWorkspaceRequest, DEFAULT_WORKSPACE, Permission,
+        Workspace, get_experiment_store, get_preamble_store,
+    },
+    web::{DeleteKnowledgeBaseQuery, UserResponse, record_audit_in, require_permission},
+};
+
+/// 当前用户的工作区列表
+#[derive(Debug, Serialize)]
+pub struct MyWorkspacesResponse {
+    pub current: String,
+    pub workspaces: Vec<Workspace>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameWorkspaceRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddMemberRequest {
+    pub user_id: i64,
+}
+
+/// 创建工作区路由
+///
+/// 新建、删除和列出全部工作区仅限默认工作区的管理员；
+/// 改名和成员管理允许该工作区自己的管理员
+pub fn create_workspace_router(state: UserAppState) -> Router {
+    let authenticated_routes = Router::new()
+        .route("/api/workspaces", get(list_my_workspaces))
+        .route_layer(middleware::from_fn(require_user_auth_middleware))
+        .with_state(state.clone());
+
+    let admin_routes = Router::new()
+        .route(
+            "/api/admin/workspaces",
+            get(list_workspaces).post(create_workspace),
+        )
+        .route(
+            "/api/admin/workspaces/{id}",
+            put(rename_workspace).delete(delete_workspace),
+        )
+        .route(
+            "/api/admin/workspaces/{id}/members",
+            get(list_members).post(add_member),
+        )
+        .route(
+            "/api/admin/workspaces/{id}/members/{user_id}",
+            delete(remove_member),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            Permission::WorkspaceManage,
+            require_permission,
+        ))
+        .with_state(state);
+
+    authenticated_routes.merge(admin_routes)
+}
+
+/// 工作区 id 用于集合名和目录名，规则与知识库 id 相同
+fn is_valid_id(id: &str) -> bool {
+    (1..=64).contains(&id.len())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+fn require_default_workspace(claims: &Claims) -> Result<(), AppError> {
+    if claims.workspace == DEFAULT_WORKSPACE {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(
+            "Only available in the default workspace".to_string(),
+        ))
+    }
+}
+
+/// 默认工作区的管理员可管理所有工作区，其他管理员只能管理当前工作区
+fn require_manage(claims: &Claims, id: &str) -> Result<(), AppError> {
+    if claims.workspace == DEFAULT_WORKSPACE || claims.workspace == id {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(
+            "Not allowed to manage this workspace".to_string(),
+        ))
+    }
+}
+
+async fn list_my_workspaces(
+    Extension(claims): Extension<Claims>,
+    State((_, workspace_store)): State<UserAppState>,
+) -> Result<Json<MyWorkspacesResponse>, AppError> {
+    let workspaces = workspace_store.list_for_user(claims.user_id).await?;
+    Ok(Json(MyWorkspacesResponse {
+        current: claims.workspace,
+        workspaces,
+    }))
+}
+
+async fn list_workspaces(
+    Extension(claims): Extension<Claims>,
+    State((_, workspace_store)): State<UserAppState>,
+) -> Result<Json<Vec<Workspace>>, AppError> {
+    require_default_workspace(&claims)?;
+    Ok(Json(workspace_store.list().await?))
+}
+
+async fn create_workspace(
+    Extension(claims): Extension<Claims>,
+    State((_, workspace_store)): State<UserAppState>,
+    Json(req): Json<CreateWorkspaceRequest>,
+) -> Result<(StatusCode, Json<Workspace>), AppError> {
+    require_default_workspace(&claims)?;
+
+    let id = req.id.trim().to_string();
+    let name = req.name.trim().to_string();
+    if !is_valid_id(&id) || name.is_empty() {
+        return Err(AppError::BadRequest(
+            "Invalid workspace id or name".to_string(),
+        ));
+    }
+    if workspace_store.get(&id).await?.is_some() {
+        return Err(AppError::BadRequest("Workspace already exists".to_string()));
+    }
+
+    let workspace = workspace_store
+        .create(CreateWorkspaceRequest { id, name })
+        .await?;
+    // 创建者自动成为成员，便于切换过去继续配置
+    workspace_store
+        .add_member(&workspace.id, claims.user_id)
+        .await?;
+
+    info!("🏢 {} created workspace {}", claims.sub, workspace.id);
+    Ok((StatusCode::CREATED, Json(workspace)))
+}
+
+async fn rename_workspace(
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    State((_, workspace_store)): State<UserAppState>,
+    Json(req): Json<RenameWorkspaceRequest>,
+) -> Result<Json<Workspace>, AppError> {
+    require_manage(&claims, &id)?;
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(
+            "Workspace name is required".to_string(),
+        ));
+    }
+
+    if !workspace_store.rename(&id, name).await? {
+        return Err(AppError::NotFound("Workspace not found".to_string()));
+    }
+    let workspace = workspace_store
+        .get(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Workspace not found".to_string()))?;
+    Ok(Json(workspace))
+}
+
+/// 删除工作区，`purge=true` 时同时清空其集合和知识库
+async fn delete_workspace(
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Query(query): Query<DeleteKnowledgeBaseQuery>,
+    State((_, workspace_store)): State<UserAppState>,
+) -> Result<StatusCode, AppError> {
+    require_default_workspace(&claims)?;
+    if id == DEFAULT_WORKSPACE {
+        return Err(AppError::BadRequest(
+            "Cannot delete the default workspace".to_string(),
+        ));
+    }
+    if workspace_store.get(&id).await?.is_none() {
+        return Err(AppError::NotFound("Workspace not found".to_string()));
+    }
+
+    let registry = get_knowledge_bases().ok_or_else(|| {
+        AppError::Internal(anyhow::anyhow!(
+            "Knowledge base registry is not initialized"
+        ))
+    })?;
+
+    for knowledge_base in registry.store().list(&id).await? {
+        if query.purge
+            && let Some(handle) = registry.get(&id, Some(&knowledge_base.id)).await?
+        {
+            handle.document_store.reset_table().await?;
+        }
+        registry.store().delete(&knowledge_base.id).await?;
+        if let Err(e) = registry.remove_files(&knowledge_base.id).await {
+            warn!(
+                "⚠️ Failed to remove files of knowledge base {}: {}",
+                knowledge_base.id, e
+            );
+        }
+    }
+    if query.purge
+        && let Some(handle) = registry.workspace(&id).await?
+    {
+        handle.document_store.reset_table().await?;
+        info!("🗑️  Purged collection of workspace {}", id);
+    }
+
+    workspace_store.delete(&id).await?;
+    registry.evict_workspace(&id);
+    let dir = registry.workspace_dir(&id);
+    if dir.exists()
+        && let Err(e) = tokio::fs::remove_dir_all(&dir).await
+    {
+        warn!("⚠️ Failed to remove files of workspace {}: {}", id, e);
+    }
+    if let Some(preamble_store) = get_preamble_store() {
+        preamble_store.delete_workspace(&id).await?;
+    }
+    if let Some(experiment_store) = get_experiment_store() {
+        experiment_store.delete_workspace(&id).await?;
+    }
+    let conversations = ConversationStore::from_config()
+        .await?
+        .delete_workspace(&id)
+        .await?;
+    info!(
+        "🗑️  Deleted {} conversation(s) of workspace {}",
+        conversations, id
+    );
+
+    info!("🗑️  {} deleted workspace {}", claims.sub, id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_members(
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    State((_, workspace_store)): State<UserAppState>,
+) -> Result<Json<Vec<UserResponse>>, AppError> {
+    require_manage(&claims, &id)?;
+    let members = workspace_store.list_members(&id).await?;
+    Ok(Json(members.into_iter().map(UserResponse::from).collect()))
+}
+
+/// 把已有用户加入工作区，仅限默认工作区的管理员
+///
+/// 角色和密码是全局的，其他工作区的管理员加入别人的账号后就能修改它，
+/// 因此只能通过创建用户添加新成员
+async fn add_member(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    State((user_store, workspace_store)): State<UserAppState>,
+    Json(req): Json<AddMemberRequest>,
+) -> Result<StatusCode, AppError> {
+    require_default_workspace(&claims)?;
+    if workspace_store.get(&id).await?.is_none() {
+        return Err(AppError::NotFound("Workspace not found".to_string()));
+    }
+    let Some(user) = user_store.get_user_by_id(req.user_id).await? else {
+        return Err(AppError::NotFound("User not found".to_string()));
+    };
+
+    workspace_store.add_member(&id, req.user_id).await?;
+    record_audit_in(
+        &id,
+        &claims,
+        addr,
+        AuditAction::WorkspaceMemberAdded,
+        req.user_id.to_string(),
+        None,
+        Some(serde_json::json!({ "username": user.username })),
+    )
+    .await;
+    info!("Added user {} to workspace {}", req.user_id, id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 移除成员，用户至少要保留一个工作区
+async fn remove_member(
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path((id, user_id)): Path<(String, i64)>,
+    State((_, workspace_store)): State<UserAppState>,
+) -> Result<StatusCode, AppError> {
+    require_manage(&claims, &id)?;
+    if !workspace_store.is_member(&id, user_id).await? {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+    if workspace_store.list_for_user(user_id).await?.len() <= 1 {
+        return Err(AppError::BadRequest(
+            "User must belong to at least one workspace".to_string(),
+        ));
+    }
+
+    workspace_store.remove_member(&id, user_id).await?;
+    record_audit_in(
+        &id,
+        &claims,
+        addr,
+        AuditAction::WorkspaceMemberRemoved,
+        user_id.to_string(),
+        None,
+        None,
+    )
+    .await;
+    info!("Removed user {} from workspace {}", user_id, id);
+    Ok(StatusCode::NO_CONTENT)
+}
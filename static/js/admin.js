@@ -1 +1 @@
-const API_BASE="";let authToken=localStorage.getItem("authToken"),currentUserRole=null,currentDocumentId=null,currentPage=0,pageSize=20,totalDocuments=0;function getAuthHeaders(){return{"Content-Type":"application/json",Authorization:`Bearer ${authToken}`}}async function checkAuth(){if(!authToken)return window.location.href="/login",!1;try{const e=await fetch("/api/auth/verify",{method:"POST",headers:getAuthHeaders()});if(!e.ok)throw new Error("Token invalid");const t=await e.json();currentUserRole=t.role,localStorage.setItem("userRole",t.role);const o=document.getElementById("userInfo");return o&&(o.textContent=`👤 ${t.sub} (${t.role})`),updateUIPermissions(),!0}catch(e){return console.error("Auth check failed:",e),localStorage.removeItem("authToken"),localStorage.removeItem("username"),localStorage.removeItem("userRole"),window.location.href="/login",!1}}function hasSpecialAccess(){return new URLSearchParams(window.location.search).has("cody")}function updateUIPermissions(){const e="admin"===currentUserRole,t=hasSpecialAccess(),o=document.querySelector('.nav-item[onclick*="preamble"]'),n=document.querySelector('.nav-item[onclick*="users"]');o&&(o.style.display=t?"block":"none"),n&&(n.style.display=e?"block":"none")}function logout(){localStorage.removeItem("authToken"),localStorage.removeItem("username"),localStorage.removeItem("userRole"),window.location.href="/login"}function showSection(e){const t="admin"===currentUserRole,o=hasSpecialAccess();if("preamble"===e&&!t&&!o)return void showAlert("您没有权限访问此功能","error");if("users"===e&&!t)return void showAlert("您没有权限访问此功能","error");document.querySelectorAll(".section").forEach(e=>{e.classList.remove("active")}),document.querySelectorAll(".nav-item").forEach(e=>{e.classList.remove("active")}),document.getElementById(e).classList.add("active");document.querySelectorAll(".nav-item").forEach(t=>{const o=t.getAttribute("onclick");o&&o.includes(`'${e}'`)&&t.classList.add("active")});const n=new URL(window.location.href).search;switch(n?history.replaceState(null,null,`${n}#${e}`):history.replaceState(null,null,`#${e}`),e){case"documents":loadDocuments();break;case"preamble":loadPreamble();break;case"upload":document.getElementById("createDocumentForm").reset();break;case"conversations":loadConversations();break;case"users":loadUsers();break}}function loadSectionFromHash(){let e=window.location.hash.substring(1);e&&["documents","preamble","upload","users","conversations"].includes(e)?showSection(e):showSection("documents")}function showAlert(e,t="success"){const o=document.getElementById("alertContainer"),n=document.createElement("div");n.className="alert alert-"+("error"===t?"error":"success"),n.textContent=e,o.appendChild(n),setTimeout(()=>{n.remove()},3e3)}function showSecretKeyModal(e){const t=document.createElement("div");t.className="modal-backdrop",t.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);display:flex;justify-content:center;align-items:center;z-index:1000",t.innerHTML='<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:400px;"><h3 style="margin-bottom:20px;">🔑 验证</h3><form id="secretKeyForm"><div class="form-group"><input type="password" id="secretKeyInput" class="form-control" placeholder="请输入密钥" required autofocus></div><div><button type="submit" class="btn btn-primary">✅ 确认</button><button type="button" class="btn btn-secondary modal-cancel-btn">❌ 取消</button></div></form></div>';t.querySelector(".modal-cancel-btn").addEventListener("click",()=>t.remove()),t.addEventListener("click",e=>{e.target===t&&t.remove()}),t.querySelector(".modal-content").addEventListener("click",e=>{e.stopPropagation()}),t.querySelector("#secretKeyForm").addEventListener("submit",o=>{o.preventDefault();const n=document.getElementById("secretKeyInput").value.trim();t.remove(),e(n)}),document.body.appendChild(t),setTimeout(()=>{document.getElementById("secretKeyInput").focus()},100)}async function loadDocuments(e=0){const t=document.getElementById("documentsLoading"),o=document.getElementById("documentsList"),n=document.getElementById("pagination");t.style.display="block",o.innerHTML="",n.style.display="none";try{const n=e*pageSize,r=await fetch(`/api/documents?limit=${pageSize}&offset=${n}`,{headers:getAuthHeaders()});if(!r.ok)throw new Error("获取文档列表失败");const a=await r.json();if(t.style.display="none",currentPage=e,totalDocuments=a.total,0===a.documents.length)return void(o.innerHTML='<p style="text-align: center; color: #6c757d; padding: 40px;">暂无文档，请先上传一些文档。</p>');a.documents.forEach(e=>{const t=createDocumentElement(e);o.appendChild(t)}),updatePaginationControls()}catch(e){t.style.display="none",showAlert(e.message,"error"),o.innerHTML='<p style="text-align: center; color: #dc3545; padding: 40px;">加载文档失败，请检查网络连接。</p>'}}function createDocumentElement(e){const t=document.createElement("div");t.className="document-item";const o=new Date(e.created_at).toLocaleString("zh-CN"),n=new Date(e.updated_at).toLocaleString("zh-CN");return t.innerHTML=`<h3>${escapeHtml(e.filename)}</h3><div class="document-meta"> 📅 创建时间: ${o} | 🔄 更新时间: ${n} </div><p>${escapeHtml(e.preview)}</p><div class="document-actions"><button class="btn btn-primary" onclick="editDocument('${e.id}')">✏️ 编辑</button><button class="btn btn-secondary" onclick="viewDocument('${e.id}')">👁️ 查看</button><button class="btn btn-danger" onclick="deleteDocument('${e.id}', '${escapeHtml(e.filename)}')">🗑️ 删除</button></div>`,t}function updatePaginationControls(){const e=document.getElementById("pagination"),t=document.getElementById("prevPage"),o=document.getElementById("nextPage"),n=document.getElementById("pageInfo");if(totalDocuments<=pageSize)return void(e.style.display="none");e.style.display="block";const r=Math.ceil(totalDocuments/pageSize),a=currentPage+1;n.textContent=`第 ${a} 页，共 ${r} 页 (${totalDocuments} 个文档)`,t.disabled=0===currentPage,o.disabled=currentPage>=r-1}function goToPreviousPage(){currentPage>0&&loadDocuments(currentPage-1)}function goToNextPage(){const e=Math.ceil(totalDocuments/pageSize);currentPage<e-1&&loadDocuments(currentPage+1)}async function editDocument(e){try{const t=await fetch(`/api/documents/${e}`,{headers:getAuthHeaders()});if(!t.ok)throw new Error("获取文档详情失败");const o=createEditModal(await t.json());document.body.appendChild(o)}catch(e){showAlert(e.message,"error")}}function createEditModal(e){const t=document.createElement("div");t.className="modal-backdrop",t.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);display:flex;justify-content:center;align-items:center;z-index:1000",t.innerHTML=`<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:800px;max-height:85%;overflow-y:auto;position:relative;"><button class="floating-close-btn" style="position:absolute;top:10px;right:10px;background:rgba(0, 0, 0, 0.7);color:white;border:none;border-radius:50%;width:32px;height:32px;display:flex;align-items:center;justify-content:center;cursor:pointer;z-index:1001;opacity:0.8;transition:opacity 0.3s ease;" title="关闭"> ❌ </button><h3 style="margin-bottom:20px;">✏️ 编辑文档</h3><form id="editDocumentForm"><div class="form-group"><label>文件名：</label><input type="text" id="editFilename" class="form-control" value="${escapeHtml(e.filename)}" required></div><div class="form-group"><label>文档内容：</label><textarea id="editContent" class="form-control" rows="22" required>${escapeHtml(e.content)}</textarea></div><div><button type="submit" class="btn btn-primary">💾 保存</button><button type="button" class="btn btn-secondary modal-cancel-btn">❌ 取消</button></div></form></div>`;t.querySelector(".modal-cancel-btn").addEventListener("click",()=>t.remove());return t.querySelector(".floating-close-btn").addEventListener("click",()=>t.remove()),t.addEventListener("click",e=>{e.target===t&&t.remove()}),t.querySelector(".modal-content").addEventListener("click",e=>{e.stopPropagation()}),t.querySelector("#editDocumentForm").addEventListener("submit",async o=>{o.preventDefault();const n=document.getElementById("editFilename").value.trim(),r=document.getElementById("editContent").value.trim();if(n&&r)try{if(!(await fetch(`/api/documents/${e.id}`,{method:"PUT",headers:getAuthHeaders(),body:JSON.stringify({filename:n,content:r})})).ok)throw new Error("更新文档失败");showAlert("文档更新成功！"),t.remove(),loadDocuments()}catch(e){showAlert(e.message,"error")}else showAlert("请填写完整信息","error")}),t}async function viewDocument(e){try{const t=await fetch(`/api/documents/${e}`,{headers:getAuthHeaders()});if(!t.ok)throw new Error("获取文档详情失败");const o=await t.json(),n=document.createElement("div");n.className="modal-backdrop",n.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);display:flex;justify-content:center;align-items:center;z-index:1000";const r=new Date(o.created_at).toLocaleString("zh-CN"),a=new Date(o.updated_at).toLocaleString("zh-CN");n.innerHTML=`<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:800px;max-height:80%;overflow:hidden;position:relative;display:flex;flex-direction:column;"><button class="floating-close-btn" style="position:absolute;top:10px;right:10px;background:rgba(0, 0, 0, 0.7);color:white;border:none;border-radius:50%;width:32px;height:32px;display:flex;align-items:center;justify-content:center;cursor:pointer;z-index:1001;opacity:0.8;transition:opacity 0.3s ease;" title="关闭"> ❌ </button><h3 style="margin-bottom:20px;">👁️ ${escapeHtml(o.filename)}</h3><div style="margin-bottom:20px;color:#6c757d;font-size:0.9rem;"> 📅 创建时间:${r}<br> 🔄 更新时间:${a}</div><div style="flex:1;overflow-y:auto;margin:0 -10px;padding:0 10px;"><div style="background:#f8f9fa;padding:20px;border-radius:10px;margin-bottom:20px;white-space:pre-wrap;font-family:monospace;font-size:1rem;">${escapeHtml(o.content)}</div></div></div>`;n.querySelector(".floating-close-btn").addEventListener("click",()=>n.remove()),n.addEventListener("click",e=>{e.target===n&&n.remove()}),n.querySelector(".modal-content").addEventListener("click",e=>{e.stopPropagation()}),document.body.appendChild(n)}catch(e){showAlert(e.message,"error")}}async function deleteDocument(e,t){if(confirm(`确定要删除文档 "${t}" 吗？此操作不可恢复。`))try{if(!(await fetch(`/api/documents/${e}`,{method:"DELETE",headers:getAuthHeaders()})).ok)throw new Error("删除文档失败");showAlert("文档删除成功！"),loadDocuments()}catch(e){showAlert(e.message,"error")}}async function resetDocuments(){if(confirm("⚠️ 确定要重置文档存储吗？\n\n这将删除所有文档和向量索引，此操作不可恢复！\n\n建议在schema更改或数据损坏时使用。")&&confirm("🚨 最后确认：这将永久删除所有文档数据！\n\n确定要继续吗？"))try{if(!(await fetch("/api/documents/reset",{method:"POST",headers:getAuthHeaders()})).ok)throw new Error("重置文档存储失败");showAlert("✅ 文档存储重置成功！请重新上传文档。","success"),loadDocuments()}catch(e){showAlert(e.message,"error")}}async function loadPreamble(){const e=document.getElementById("preambleLoading"),t=document.getElementById("preambleForm");e.style.display="block",t.style.display="none";try{const o=await fetch("/api/preamble",{headers:getAuthHeaders()});if(!o.ok)throw new Error("获取Preamble配置失败");const n=await o.json();document.getElementById("preambleContent").value=n.content,e.style.display="none",t.style.display="block"}catch(t){e.style.display="none",showAlert(t.message,"error")}}function validateFileType(e){const t="."+e.name.split(".").pop().toLowerCase();return!![".txt",".md",".json",".csv",".pdf",".docx",".xlsx"].includes(t)||(showAlert("不支持的文件类型，请选择 .txt, .md, .json, .csv, .pdf, .docx, .xlsx 文件","error"),!1)}function handleFileSelect(e){const t=e.target.files[0];t&&validateFileType(t)&&uploadDocument(t)}async function uploadDocument(e){try{const t=new FormData;t.append("filename",e.name),t.append("file",e);const o={Authorization:`Bearer ${authToken}`},n=await fetch("/api/documents/upload",{method:"POST",headers:o,body:t});if(!n.ok){const e=await n.json().catch(()=>({}));throw new Error(e.error||e.message||"上传文档失败")}showAlert("文档上传成功！"),document.getElementById("fileInput").value="",document.getElementById("documents").classList.contains("active")&&loadDocuments()}catch(e){showAlert(e.message,"error")}}function escapeHtml(e){const t={"&":"&amp;","<":"&lt;",">":"&gt;",'"':"&quot;","'":"&#039;"};return e.replace(/[&<>"']/g,e=>t[e])}function setupFileDragAndDrop(){const e=document.querySelector(".file-upload");function t(e){e.preventDefault(),e.stopPropagation()}function o(){e.style.background="#f8f9ff",e.style.borderColor="#667eea",e.style.transform="scale(1.02)"}function n(){e.style.background="",e.style.borderColor="",e.style.transform=""}e&&(["dragenter","dragover","dragleave","drop"].forEach(o=>{e.addEventListener(o,t,!1),document.body.addEventListener(o,t,!1)}),["dragenter","dragover"].forEach(t=>{e.addEventListener(t,o,!1)}),["dragleave","drop"].forEach(t=>{e.addEventListener(t,n,!1)}),e.addEventListener("drop",function(e){const t=e.dataTransfer.files;t.length>0&&function(e){const t=e[0];if(!validateFileType(t))return;uploadDocument(t)}(t)},!1))}async function loadUsers(){const e=document.getElementById("usersLoading"),t=document.getElementById("usersList");e.style.display="block",t.innerHTML="";try{const o=await fetch("/api/users",{headers:getAuthHeaders()});if(!o.ok)throw new Error("获取用户列表失败");const n=await o.json();if(e.style.display="none",0===n.length)return void(t.innerHTML='<p style="text-align: center; color: #6c757d; padding: 40px;">暂无用户。</p>');const r=document.createElement("div");r.style.cssText="background: #f8f9fa; border-radius: 10px; overflow: hidden;",r.innerHTML='<table style="width:100%;border-collapse:collapse;"><thead><tr style="background:#667eea;color:white;"><th style="padding:15px;text-align:left;">用户名</th><th style="padding:15px;text-align:left;">角色</th><th style="padding:15px;text-align:left;">状态</th><th style="padding:15px;text-align:left;">创建时间</th><th style="padding:15px;text-align:left;">更新时间</th><th style="padding:15px;text-align:center;">操作</th></tr></thead><tbody id="usersTableBody"></tbody></table>',t.appendChild(r);const a=document.getElementById("usersTableBody");n.forEach((e,t)=>{const o=document.createElement("tr");o.style.cssText=`background:${t%2==0?"white":"#f8f9fa"};border-bottom:1px solid #e9ecef`;const n=new Date(e.created_at).toLocaleString("zh-CN"),r=new Date(e.updated_at).toLocaleString("zh-CN"),s="admin"===e.role?"👑 管理员":"👤 用户",i=1===e.status?'<span style="background: #28a745; color: white; padding: 4px 12px; border-radius: 12px; font-size: 0.85rem;">✅ 启用</span>':'<span style="background: #dc3545; color: white; padding: 4px 12px; border-radius: 12px; font-size: 0.85rem;">❌ 禁用</span>';o.innerHTML=`<td style="padding:15px;font-weight:600;">${escapeHtml(e.username)}</td><td style="padding:15px;">${s}</td><td style="padding:15px;">${i}</td><td style="padding:15px;color:#6c757d;font-size:0.9rem;">${n}</td><td style="padding:15px;color:#6c757d;font-size:0.9rem;">${r}</td><td style="padding:15px;text-align:center;"><button class="btn btn-primary" onclick="editUser(${e.id})" style="padding:6px 12px;font-size:0.85rem;margin-right:5px;"> ✏️ 编辑 </button><button class="btn btn-danger" onclick="deleteUser(${e.id}, '${escapeHtml(e.username)}')" style="padding:6px 12px;font-size:0.85rem;"> 🗑️ 删除 </button></td>`,a.appendChild(o)})}catch(o){e.style.display="none",showAlert(o.message,"error"),t.innerHTML='<p style="text-align: center; color: #dc3545; padding: 40px;">加载用户失败。</p>'}}function showCreateUserModal(){const e=document.createElement("div");e.className="modal-backdrop",e.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);display:flex;justify-content:center;align-items:center;z-index:1000",e.innerHTML='<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:500px;"><h3 style="margin-bottom:20px;">➕ 创建新用户</h3><form id="createUserForm"><div class="form-group"><label>用户名：</label><input type="text" id="newUsername" class="form-control" placeholder="请输入用户名" required></div><div class="form-group"><label>密码：</label><input type="password" id="newPassword" class="form-control" placeholder="请输入密码" required></div><div class="form-group"><label>角色：</label><select id="newRole" class="form-control"><option value="user">👤 普通用户</option><option value="admin">👑 管理员</option></select></div><div class="form-group"><label>状态：</label><select id="newStatus" class="form-control"><option value="1" selected>✅ 启用</option><option value="0">❌ 禁用</option></select></div><div><button type="submit" class="btn btn-primary">💾 创建</button><button type="button" class="btn btn-secondary modal-cancel-btn">❌ 取消</button></div></form></div>',e.querySelector(".modal-cancel-btn").addEventListener("click",()=>e.remove()),e.addEventListener("click",t=>{t.target===e&&e.remove()}),e.querySelector("#createUserForm").addEventListener("submit",async t=>{t.preventDefault();const o=document.getElementById("newUsername").value.trim(),n=document.getElementById("newPassword").value,r=document.getElementById("newRole").value,a=parseInt(document.getElementById("newStatus").value);if(o&&n)try{const t=await fetch("/api/users",{method:"POST",headers:getAuthHeaders(),body:JSON.stringify({username:o,password:n,role:r,status:a})});if(!t.ok){const e=await t.json();throw new Error(e.error||"创建用户失败")}showAlert("用户创建成功！"),e.remove(),loadUsers()}catch(e){showAlert(e.message,"error")}else showAlert("请填写完整信息","error")}),document.body.appendChild(e)}async function editUser(e){try{const t=await fetch(`/api/users/${e}`,{headers:getAuthHeaders()});if(!t.ok)throw new Error("获取用户信息失败");const o=await t.json(),n=document.createElement("div");n.className="modal-backdrop",n.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);display:flex;justify-content:center;align-items:center;z-index:1000",n.innerHTML=`<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:500px;"><h3 style="margin-bottom:20px;">✏️ 编辑用户:${escapeHtml(o.username)}</h3><form id="editUserForm"><div class="form-group"><label>新密码（留空则不修改）：</label><input type="password" id="editPassword" class="form-control" placeholder="请输入新密码"></div><div class="form-group"><label>角色：</label><select id="editRole" class="form-control"><option value="user" ${"user"===o.role?"selected":""}>👤 普通用户</option><option value="admin" ${"admin"===o.role?"selected":""}>👑 管理员</option></select></div><div class="form-group"><label>状态：</label><select id="editStatus" class="form-control"><option value="1" ${1===o.status?"selected":""}>✅ 启用</option><option value="0" ${0===o.status?"selected":""}>❌ 禁用</option></select></div><div><button type="submit" class="btn btn-primary">💾 保存</button><button type="button" class="btn btn-secondary modal-cancel-btn">❌ 取消</button></div></form></div>`,n.querySelector(".modal-cancel-btn").addEventListener("click",()=>n.remove()),n.addEventListener("click",e=>{e.target===n&&n.remove()}),n.querySelector("#editUserForm").addEventListener("submit",async t=>{t.preventDefault();const r=document.getElementById("editPassword").value.trim(),a=document.getElementById("editRole").value,s=parseInt(document.getElementById("editStatus").value),i={};if(r&&(i.password=r),a!==o.role&&(i.role=a),s!==o.status&&(i.status=s),0!==Object.keys(i).length)try{const t=await fetch(`/api/users/${e}`,{method:"PUT",headers:getAuthHeaders(),body:JSON.stringify(i)});if(!t.ok){const e=await t.json();throw new Error(e.error||"更新用户失败")}showAlert("用户更新成功！"),n.remove(),loadUsers()}catch(e){showAlert(e.message,"error")}else showAlert("没有需要更新的内容","error")}),document.body.appendChild(n)}catch(e){showAlert(e.message,"error")}}async function deleteUser(e,t){if(confirm(`确定要删除用户 "${t}" 吗？此操作不可恢复。`))try{const t=await fetch(`/api/users/${e}`,{method:"DELETE",headers:getAuthHeaders()});if(!t.ok){const e=await t.json();throw new Error(e.error||"删除用户失败")}showAlert("用户删除成功！"),loadUsers()}catch(e){showAlert(e.message,"error")}}document.addEventListener("DOMContentLoaded",async function(){await checkAuth()&&(document.getElementById("preambleForm").addEventListener("submit",async function(e){e.preventDefault();const t=document.getElementById("preambleContent").value.trim();t?showSecretKeyModal(async e=>{if(e)try{const o=await fetch("/api/preamble",{method:"PUT",headers:getAuthHeaders(),body:JSON.stringify({content:t,secret_key:e})});if(403===o.status)return void showAlert("❌ 验证失败，无权限保存配置","error");if(!o.ok)throw new Error("保存Preamble配置失败");showAlert("✅ Preamble配置保存成功！")}catch(e){showAlert(e.message,"error")}else showAlert("请输入验证密钥","error")}):showAlert("请输入Preamble内容","error")}),document.getElementById("createDocumentForm").addEventListener("submit",async function(e){e.preventDefault();const t=document.getElementById("documentFilename").value.trim(),o=document.getElementById("documentContent").value.trim();if(t&&o)try{if(!(await fetch("/api/documents",{method:"POST",headers:getAuthHeaders(),body:JSON.stringify({filename:t,content:o})})).ok)throw new Error("创建文档失败");showAlert("文档创建成功！"),this.reset()}catch(e){showAlert(e.message,"error")}else showAlert("请填写完整信息","error")}),document.getElementById("prevPage").addEventListener("click",goToPreviousPage),document.getElementById("nextPage").addEventListener("click",goToNextPage),setupFileDragAndDrop(),window.addEventListener("hashchange",loadSectionFromHash),loadSectionFromHash())});let conversationCurrentPage=0,conversationPageSize=20,conversationTotal=0,conversationSearchQuery="",messageCurrentPage=0,messagePageSize=50;async function loadConversations(){const e=document.getElementById("conversationsLoading"),t=document.getElementById("conversationsList"),o=document.getElementById("conversationStats");e.style.display="block",t.innerHTML="",o.style.display="none";try{let e=`/api/admin/conversations?limit=${conversationPageSize}&offset=${conversationCurrentPage*conversationPageSize}`;conversationSearchQuery&&(e+=`&search=${encodeURIComponent(conversationSearchQuery)}`);const t=await fetch(e,{headers:getAuthHeaders()});if(!t.ok)throw new Error(`HTTP error! status: ${t.status}`);const o=await t.json();await loadConversationStats(),displayConversations(o.conversations),updateConversationPagination(o.total,o.has_more)}catch(e){console.error("Error loading conversations:",e),showAlert("加载对话记录失败: "+e.message,"error"),t.innerHTML='<div style="text-align:center;padding:40px;color:#6c757d;"><p>暂无对话记录</p><p>或者需要管理员权限才能查看</p></div>'}finally{e.style.display="none"}}async function loadConversationStats(){try{const e=await fetch("/api/admin/conversations/stats",{headers:getAuthHeaders()});if(!e.ok)throw new Error(`HTTP error! status: ${e.status}`);const t=await e.json();t&&(updateConversationStats(t),document.getElementById("conversationStats").style.display="grid")}catch(e){console.error("Error loading conversation stats:",e)}}function updateConversationStats(e){document.getElementById("totalConversations").textContent=e.total_conversations||0,document.getElementById("activeConversations").textContent=e.active_conversations||0,document.getElementById("totalMessages").textContent=e.total_messages||0,document.getElementById("todayConversations").textContent=e.today_conversations||0}function displayConversations(e){const t=document.getElementById("conversationsList");e&&0!==e.length?t.innerHTML=e.map(e=>`<div class="document-item" style="border-left-color:${getConversationStatusColor(e.status)};"><div style="display:flex;justify-content:space-between;align-items:flex-start;margin-bottom:10px;"><h3 style="margin:0;color:#495057;">对话 #${e.id.substring(0,8)}...</h3><span class="badge" style="background-color:${getConversationStatusColor(e.status)};color:white;padding:4px 8px;border-radius:4px;font-size:0.8rem;"> ${getConversationStatusText(e.status)}</span></div><div class="document-meta"><strong>用户ID:</strong> ${e.user_id}<br><strong>创建时间:</strong> ${formatDateTime(e.created_at)}<br><strong>最后消息:</strong> ${e.updated_at?formatDateTime(e.updated_at):"无"}<br> ${e.title?`<strong>标题:</strong> ${e.title}<br>`:""} </div><div class="document-actions"><button class="btn btn-primary" onclick="viewConversationDetails('${e.id}')"> 👁️ 查看详情 </button><button class="btn btn-secondary" onclick="viewConversationMessages('${e.id}')"> 💬 查看消息 </button> ${"active"===e.status?`\n                    <button class="btn btn-warning" onclick="closeConversation('${e.id}')">\n                        🔒 关闭对话\n                    </button>\n                `:""} <button class="btn btn-danger" onclick="deleteConversation('${e.id}')"> 🗑️ 删除 </button></div></div>`).join(""):t.innerHTML='<div style="text-align:center;padding:40px;color:#6c757d;"><p>暂无对话记录</p></div>'}function getConversationStatusColor(e){switch(e){case"active":return"#28a745";case"closed":return"#6c757d";case"escalated":return"#ffc107";default:return"#6c757d"}}function getConversationStatusText(e){switch(e){case"active":return"活跃";case"closed":return"已关闭";case"escalated":return"已升级";default:return"未知"}}function formatDateTime(e){return new Date(e).toLocaleString("zh-CN",{year:"numeric",month:"2-digit",day:"2-digit",hour:"2-digit",minute:"2-digit",second:"2-digit"})}async function viewConversationDetails(e){try{const t=await fetch(`/api/conversation/${e}`,{headers:getAuthHeaders()});if(!t.ok)throw new Error(`HTTP error! status: ${t.status}`);const o=await t.json(),n=document.createElement("div");n.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);z-index:1000;display:flex;align-items:center;justify-content:center",n.innerHTML=`<div style="background:white;padding:20px;border-radius:10px;max-width:600px;width:90%;max-height:80%;overflow-y:auto;"><h3>对话详情</h3><div style="margin-bottom:15px;"><strong>对话ID:</strong> ${o.id}<br><strong>用户ID:</strong> ${o.user_id}<br><strong>状态:</strong> ${getConversationStatusText(o.status)}<br><strong>创建时间:</strong> ${formatDateTime(o.created_at)}<br><strong>更新时间:</strong> ${formatDateTime(o.updated_at)}<br> ${o.title?`<strong>标题:</strong> ${o.title}<br>`:""}\n                    ${o.metadata?`<strong>元数据:</strong> <pre style="background: #f8f9fa; padding: 10px; border-radius: 5px; overflow-x: auto;">${JSON.stringify(o.metadata,null,2)}</pre>`:""}</div><div style="text-align:right;"><button class="btn btn-secondary" onclick="this.closest('.modal').remove()">关闭</button></div></div>`,n.className="modal",document.body.appendChild(n),n.addEventListener("click",e=>{e.target===n&&n.remove()})}catch(e){console.error("Error viewing conversation details:",e),showAlert("查看对话详情失败: "+e.message,"error")}}async function viewConversationMessages(e){messageCurrentPage=0,await loadConversationMessages(e,0)}async function loadConversationMessages(e,t){try{const o=t*messagePageSize,n=await fetch(`/api/conversation/${e}/messages?limit=${messagePageSize}&offset=${o}`,{headers:getAuthHeaders()});if(!n.ok)throw new Error(`HTTP error! status: ${n.status}`);const r=await n.json();messageCurrentPage=t;const a=r.length===messagePageSize;let s=document.getElementById("conversationMessagesModal");s||(s=document.createElement("div"),s.id="conversationMessagesModal",s.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);z-index:1000;display:flex;align-items:center;justify-content:center",document.body.appendChild(s),s.addEventListener("click",e=>{e.target===s&&s.remove()}));const i=r.length>0?r.map(e=>`<div style="margin-bottom:15px;padding:10px;border-radius:5px;background:${"user"===e.role?"#e3f2fd":"#f5f5f5"};"><div style="font-weight:bold;margin-bottom:5px;color:${"user"===e.role?"#1976d2":"#666"};"> ${"user"===e.role?"👤 用户":"🤖 助手"}- ${formatDateTime(e.created_at)}</div><div style="white-space:pre-wrap;">${escapeHtml(e.content)}</div> ${e.metadata?`<div style="font-size: 0.8rem; color: #666; margin-top: 5px;">元数据: ${escapeHtml(JSON.stringify(e.metadata))}</div>`:""}\n            </div>\n        `).join(""):'<p style="text-align: center; color: #666;">暂无消息</p>',c=r.length>0?`<div style="display:flex;justify-content:center;align-items:center;gap:10px;margin-top:15px;"><button class="btn btn-secondary" id="messagePrevPage" style="padding:8px 16px;font-size:0.9rem;${0===t?"visibility:hidden;":""}">⬅️ 上一页</button><span style="color:#6c757d;font-size:1.0rem;">第 ${t+1}页</span><button class="btn btn-secondary" id="messageNextPage" style="padding:8px 16px;font-size:0.9rem;${a?"":"visibility:hidden;"}">下一页 ➡️</button></div>`:"";s.innerHTML=`<div style="background:white;padding:20px;border-radius:10px;max-width:900px;width:90%;max-height:85%;overflow-y:auto;position:relative;"><button style="position:absolute;top:10px;right:10px;background:rgba(0,0,0,0.7);color:white;border:none;border-radius:50%;width:32px;height:32px;cursor:pointer;z-index:1001;" onclick="document.getElementById('conversationMessagesModal').remove()">❌</button><h3 style="margin-bottom:15px;">💬 对话消息历史</h3><div style="max-height:500px;overflow-y:auto;border:1px solid #ddd;padding:15px;border-radius:5px;background:#fafafa;"> ${i}</div> ${c}</div>`;const d=s.querySelector("#messagePrevPage"),l=s.querySelector("#messageNextPage");d&&(d.onclick=()=>{messageCurrentPage>0&&loadConversationMessages(e,messageCurrentPage-1)}),l&&(l.onclick=()=>{a&&loadConversationMessages(e,messageCurrentPage+1)})}catch(e){console.error("Error viewing conversation messages:",e),showAlert("查看对话消息失败: "+e.message,"error")}}async function closeConversation(e){if(confirm("确定要关闭这个对话吗？"))try{const t=await fetch(`/api/conversation/${e}`,{method:"PUT",headers:getAuthHeaders(),body:JSON.stringify({status:"closed"})});if(!t.ok)throw new Error(`HTTP error! status: ${t.status}`);showAlert("对话已关闭","success"),loadConversations()}catch(e){console.error("Error closing conversation:",e),showAlert("关闭对话失败: "+e.message,"error")}}async function deleteConversation(e){if(confirm("确定要删除这个对话吗？此操作不可恢复！"))try{const t=await fetch(`/api/conversation/${e}`,{method:"DELETE",headers:getAuthHeaders()});if(!t.ok)throw new Error(`HTTP error! status: ${t.status}`);showAlert("对话已删除","success"),loadConversations()}catch(e){console.error("Error deleting conversation:",e),showAlert("删除对话失败: "+e.message,"error")}}function searchConversations(){const e=document.getElementById("conversationSearch");conversationSearchQuery=e.value.trim(),conversationCurrentPage=0,loadConversations()}function updateConversationPagination(e,t){conversationTotal=e;const o=document.getElementById("conversationPagination"),n=document.getElementById("conversationPrevPage"),r=document.getElementById("conversationNextPage"),a=document.getElementById("conversationPageInfo");if(e<=conversationPageSize)return void(o.style.display="none");o.style.display="block";const s=conversationCurrentPage*conversationPageSize+1,i=Math.min((conversationCurrentPage+1)*conversationPageSize,e);a.textContent=`第 ${s}-${i} 条，共 ${e} 条`,n.disabled=0===conversationCurrentPage,r.disabled=!t,n.onclick=()=>{conversationCurrentPage>0&&(conversationCurrentPage--,loadConversations())},r.onclick=()=>{t&&(conversationCurrentPage++,loadConversations())}}async function cleanupOldConversations(){const e=prompt("请输入要保留的天数（超过此天数的已关闭对话将被删除）:","30");if(!e)return;const t=parseInt(e);if(isNaN(t)||t<1)showAlert("请输入有效的天数（大于0）","error");else if(confirm(`确定要删除 ${t} 天前的已关闭对话记录吗？\n\n此操作不可恢复！`))try{const e=await fetch("/api/admin/conversations/cleanup",{method:"POST",headers:getAuthHeaders(),body:JSON.stringify({days_to_keep:t})});if(!e.ok)throw new Error(`HTTP error! status: ${e.status}`);const o=await e.json();o.success?(showAlert(o.message,"success"),loadConversations()):showAlert("清理失败: "+o.error,"error")}catch(e){console.error("Error cleaning up conversations:",e),showAlert("清理对话记录失败: "+e.message,"error")}}function showCleanupDialog(){const e=document.createElement("div");e.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);z-index:1000;display:flex;align-items:center;justify-content:center",e.innerHTML='<div style="background:white;padding:20px;border-radius:10px;max-width:500px;width:90%;"><h3>🗑️ 清理旧对话记录</h3><div style="margin:20px 0;"><p>此功能将删除超过指定天数的已关闭对话记录。</p><p style="color:#dc3545;font-weight:bold;">⚠️ 警告：此操作不可恢复！</p><div style="margin:20px 0;"><label for="cleanupDays" style="display:block;margin-bottom:5px;font-weight:bold;"> 保留天数： </label><input type="number" id="cleanupDays" value="30" min="1" max="365" style="width:100%;padding:8px;border:1px solid #ddd;border-radius:4px;"><small style="color:#666;">超过此天数的已关闭对话将被永久删除</small></div><div style="background:#f8f9fa;padding:15px;border-radius:5px;margin:15px 0;"><h4 style="margin:0 0 10px 0;color:#495057;">清理规则：</h4><ul style="margin:0;padding-left:20px;color:#6c757d;"><li>只删除状态为"已关闭"的对话</li><li>活跃对话和已升级对话不会被删除</li><li>相关消息记录也会被一并删除</li></ul></div></div><div style="text-align:right;display:flex;gap:10px;justify-content:flex-end;"><button class="btn btn-secondary" onclick="this.closest(\'.modal\').remove()">取消</button><button class="btn btn-danger" onclick="executeCleanup(this.closest(\'.modal\'))">确认清理</button></div></div>',e.className="modal",document.body.appendChild(e),e.addEventListener("click",t=>{t.target===e&&e.remove()})}async function executeCleanup(e){const t=e.querySelector("#cleanupDays"),o=parseInt(t.value);if(isNaN(o)||o<1)showAlert("请输入有效的天数（大于0）","error");else{e.remove();try{const e=await fetch("/api/admin/conversations/cleanup",{method:"POST",headers:getAuthHeaders(),body:JSON.stringify({days_to_keep:o})});if(!e.ok)throw new Error(`HTTP error! status: ${e.status}`);const t=await e.json();t.success?(showAlert(t.message,"success"),loadConversations()):showAlert("清理失败: "+t.error,"error")}catch(e){console.error("Error cleaning up conversations:",e),showAlert("清理对话记录失败: "+e.message,"error")}}}
\ No newline at end of file
+const API_BASE="";let authToken=localStorage.getItem("authToken"),currentUserRole=null,currentPermissions=[];const ROLE_LABELS={admin:"👑 超级管理员",knowledge_admin:"📚 知识库管理员",support_agent:"🎧 客服",editor:"✏️ 编辑",user:"👤 普通用户",viewer:"👁️ 只读"};function hasPermission(e){return currentPermissions.includes(e)}function roleOptions(e){return Object.entries(ROLE_LABELS).map(([t,o])=>`<option value="${t}" ${t===e?"selected":""}>${o}</option>`).join("")}let currentDocumentId=null,currentPage=0,pageSize=20,totalDocuments=0;function getAuthHeaders(){return{"Content-Type":"application/json",Authorization:`Bearer ${authToken}`}}async function checkAuth(){if(!authToken)return window.location.href="/login",!1;try{const e=await fetch("/api/auth/verify",{method:"POST",headers:getAuthHeaders()});if(!e.ok)throw new Error("Token invalid");const t=await e.json();if(t.must_change_password||t.mfa_enrollment_required)return window.location.href="/login",!1;currentUserRole=t.role,currentPermissions=t.permissions||[],localStorage.setItem("userRole",t.role);const o=document.getElementById("userInfo");return o&&(o.textContent=`👤 ${t.sub} (${t.role})`),updateUIPermissions(),!0}catch(e){return console.error("Auth check failed:",e),localStorage.removeItem("authToken"),localStorage.removeItem("username"),localStorage.removeItem("userRole"),window.location.href="/login",!1}}function hasSpecialAccess(){return new URLSearchParams(window.location.search).has("cody")}function updateUIPermissions(){const e=hasPermission("user.manage"),t=hasSpecialAccess(),o=document.querySelector('.nav-item[onclick*="preamble"]'),n=document.querySelector('.nav-item[onclick*="users"]');o&&(o.style.display=t?"block":"none"),n&&(n.style.display=e?"block":"none")}async function logout(){await window.rigAuth.logout(),window.location.href="/login"}function showSection(e){const o=hasSpecialAccess();if("preamble"===e&&!hasPermission("preamble.write")&&!o)return void showAlert("您没有权限访问此功能","error");if("users"===e&&!hasPermission("user.manage"))return void showAlert("您没有权限访问此功能","error");document.querySelectorAll(".section").forEach(e=>{e.classList.remove("active")}),document.querySelectorAll(".nav-item").forEach(e=>{e.classList.remove("active")}),document.getElementById(e).classList.add("active");document.querySelectorAll(".nav-item").forEach(t=>{const o=t.getAttribute("onclick");o&&o.includes(`'${e}'`)&&t.classList.add("active")});const n=new URL(window.location.href).search;switch(n?history.replaceState(null,null,`${n}#${e}`):history.replaceState(null,null,`#${e}`),e){case"documents":loadDocuments();break;case"preamble":loadPreamble();break;case"upload":document.getElementById("createDocumentForm").reset();break;case"conversations":loadConversations();break;case"users":loadUsers();break}}function loadSectionFromHash(){let e=window.location.hash.substring(1);e&&["documents","preamble","upload","users","conversations"].includes(e)?showSection(e):showSection("documents")}function showAlert(e,t="success"){const o=document.getElementById("alertContainer"),n=document.createElement("div");n.className="alert alert-"+("error"===t?"error":"success"),n.textContent=e,o.appendChild(n),setTimeout(()=>{n.remove()},3e3)}async function loadDocuments(e=0){const t=document.getElementById("documentsLoading"),o=document.getElementById("documentsList"),n=document.getElementById("pagination");t.style.display="block",o.innerHTML="",n.style.display="none";try{const n=e*pageSize,r=await fetch(`/api/documents?limit=${pageSize}&offset=${n}`,{headers:getAuthHeaders()});if(!r.ok)throw new Error("获取文档列表失败");const a=await r.json();if(t.style.display="none",currentPage=e,totalDocuments=a.total,0===a.documents.length)return void(o.innerHTML='<p style="text-align: center; color: #6c757d; padding: 40px;">暂无文档，请先上传一些文档。</p>');a.documents.forEach(e=>{const t=createDocumentElement(e);o.appendChild(t)}),updatePaginationControls()}catch(e){t.style.display="none",showAlert(e.message,"error"),o.innerHTML='<p style="text-align: center; color: #dc3545; padding: 40px;">加载文档失败，请检查网络连接。</p>'}}function createDocumentElement(e){const t=document.createElement("div");t.className="document-item";const o=new Date(e.created_at).toLocaleString("zh-CN"),n=new Date(e.updated_at).toLocaleString("zh-CN");return t.innerHTML=`<h3>${escapeHtml(e.filename)}</h3><div class="document-meta"> 📅 创建时间: ${o} | 🔄 更新时间: ${n} </div><p>${escapeHtml(e.preview)}</p><div class="document-actions"><button class="btn btn-primary" onclick="editDocument('${e.id}')">✏️ 编辑</button><button class="btn btn-secondary" onclick="viewDocument('${e.id}')">👁️ 查看</button><button class="btn btn-danger" onclick="deleteDocument('${e.id}', '${escapeHtml(e.filename)}')">🗑️ 删除</button></div>`,t}function updatePaginationControls(){const e=document.getElementById("pagination"),t=document.getElementById("prevPage"),o=document.getElementById("nextPage"),n=document.getElementById("pageInfo");if(totalDocuments<=pageSize)return void(e.style.display="none");e.style.display="block";const r=Math.ceil(totalDocuments/pageSize),a=currentPage+1;n.textContent=`第 ${a} 页，共 ${r} 页 (${totalDocuments} 个文档)`,t.disabled=0===currentPage,o.disabled=currentPage>=r-1}function goToPreviousPage(){currentPage>0&&loadDocuments(currentPage-1)}function goToNextPage(){const e=Math.ceil(totalDocuments/pageSize);currentPage<e-1&&loadDocuments(currentPage+1)}async function editDocument(e){try{const t=await fetch(`/api/documents/${e}`,{headers:getAuthHeaders()});if(!t.ok)throw new Error("获取文档详情失败");const o=createEditModal(await t.json());document.body.appendChild(o)}catch(e){showAlert(e.message,"error")}}function createEditModal(e){const t=document.createElement("div");t.className="modal-backdrop",t.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);display:flex;justify-content:center;align-items:center;z-index:1000",t.innerHTML=`<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:800px;max-height:85%;overflow-y:auto;position:relative;"><button class="floating-close-btn" style="position:absolute;top:10px;right:10px;background:rgba(0, 0, 0, 0.7);color:white;border:none;border-radius:50%;width:32px;height:32px;display:flex;align-items:center;justify-content:center;cursor:pointer;z-index:1001;opacity:0.8;transition:opacity 0.3s ease;" title="关闭"> ❌ </button><h3 style="margin-bottom:20px;">✏️ 编辑文档</h3><form id="editDocumentForm"><div class="form-group"><label>文件名：</label><input type="text" id="editFilename" class="form-control" value="${escapeHtml(e.filename)}" required></div><div class="form-group"><label>文档内容：</label><textarea id="editContent" class="form-control" rows="22" required>${escapeHtml(e.content)}</textarea></div><div><button type="submit" class="btn btn-primary">💾 保存</button><button type="button" class="btn btn-secondary modal-cancel-btn">❌ 取消</button></div></form></div>`;t.querySelector(".modal-cancel-btn").addEventListener("click",()=>t.remove());return t.querySelector(".floating-close-btn").addEventListener("click",()=>t.remove()),t.addEventListener("click",e=>{e.target===t&&t.remove()}),t.querySelector(".modal-content").addEventListener("click",e=>{e.stopPropagation()}),t.querySelector("#editDocumentForm").addEventListener("submit",async o=>{o.preventDefault();const n=document.getElementById("editFilename").value.trim(),r=document.getElementById("editContent").value.trim();if(n&&r)try{if(!(await fetch(`/api/documents/${e.id}`,{method:"PUT",headers:getAuthHeaders(),body:JSON.stringify({filename:n,content:r})})).ok)throw new Error("更新文档失败");showAlert("文档更新成功！"),t.remove(),loadDocuments()}catch(e){showAlert(e.message,"error")}else showAlert("请填写完整信息","error")}),t}async function viewDocument(e){try{const t=await fetch(`/api/documents/${e}`,{headers:getAuthHeaders()});if(!t.ok)throw new Error("获取文档详情失败");const o=await t.json(),n=document.createElement("div");n.className="modal-backdrop",n.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);display:flex;justify-content:center;align-items:center;z-index:1000";const r=new Date(o.created_at).toLocaleString("zh-CN"),a=new Date(o.updated_at).toLocaleString("zh-CN");n.innerHTML=`<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:800px;max-height:80%;overflow:hidden;position:relative;display:flex;flex-direction:column;"><button class="floating-close-btn" style="position:absolute;top:10px;right:10px;background:rgba(0, 0, 0, 0.7);color:white;border:none;border-radius:50%;width:32px;height:32px;display:flex;align-items:center;justify-content:center;cursor:pointer;z-index:1001;opacity:0.8;transition:opacity 0.3s ease;" title="关闭"> ❌ </button><h3 style="margin-bottom:20px;">👁️ ${escapeHtml(o.filename)}</h3><div style="margin-bottom:20px;color:#6c757d;font-size:0.9rem;"> 📅 创建时间:${r}<br> 🔄 更新时间:${a}</div><div style="flex:1;overflow-y:auto;margin:0 -10px;padding:0 10px;"><div style="background:#f8f9fa;padding:20px;border-radius:10px;margin-bottom:20px;white-space:pre-wrap;font-family:monospace;font-size:1rem;">${escapeHtml(o.content)}</div></div></div>`;n.querySelector(".floating-close-btn").addEventListener("click",()=>n.remove()),n.addEventListener("click",e=>{e.target===n&&n.remove()}),n.querySelector(".modal-content").addEventListener("click",e=>{e.stopPropagation()}),document.body.appendChild(n)}catch(e){showAlert(e.message,"error")}}async function deleteDocument(e,t){if(confirm(`确定要删除文档 "${t}" 吗？此操作不可恢复。`))try{if(!(await fetch(`/api/documents/${e}`,{method:"DELETE",headers:getAuthHeaders()})).ok)throw new Error("删除文档失败");showAlert("文档删除成功！"),loadDocuments()}catch(e){showAlert(e.message,"error")}}async function resetDocuments(){if(confirm("⚠️ 确定要重置文档存储吗？\n\n这将删除所有文档和向量索引，此操作不可恢复！\n\n建议在schema更改或数据损坏时使用。")&&confirm("🚨 最后确认：这将永久删除所有文档数据！\n\n确定要继续吗？"))try{if(!(await fetch("/api/documents/reset",{method:"POST",headers:getAuthHeaders()})).ok)throw new Error("重置文档存储失败");showAlert("✅ 文档存储重置成功！请重新上传文档。","success"),loadDocuments()}catch(e){showAlert(e.message,"error")}}async function loadPreamble(){const e=document.getElementById("preambleLoading"),t=document.getElementById("preambleForm");e.style.display="block",t.style.display="none";try{const o=await fetch("/api/preamble",{headers:getAuthHeaders()});if(!o.ok)throw new Error("获取Preamble配置失败");const n=await o.json();document.getElementById("preambleContent").value=n.content,document.getElementById("preambleApprovalHint").style.display=n.require_approval?"block":"none",e.style.display="none",t.style.display="block",loadPreambleDrafts(),loadPreambleVersions()}catch(t){e.style.display="none",showAlert(t.message,"error")}}async function loadPreambleVersions(){const e=document.getElementById("preambleVersions");try{const t=await fetch("/api/preamble/versions",{headers:getAuthHeaders()});if(!t.ok)return void(e.innerHTML="");const o=await t.json();if(0===o.length)return void(e.innerHTML='<p style="color: #6c757d;">暂无历史版本，保存后开始记录。</p>');const n=o.map(e=>`<tr style="border-bottom:1px solid #e9ecef;"><td style="padding:10px;font-weight:600;">v${e.version}</td><td style="padding:10px;">${escapeHtml(e.author)}</td><td style="padding:10px;">${escapeHtml(e.comment||"")}</td><td style="padding:10px;">${new Date(e.created_at).toLocaleString("zh-CN")}</td><td style="padding:10px;">${e.live?'<span style="color: #28a745; font-weight: 600;">✅ 当前版本</span>':`<button class="btn btn-secondary" onclick="showPreambleDiff(${e.version})">🔍 差异</button> <button class="btn btn-primary" onclick="rollbackPreamble(${e.version})">⏪ 回滚</button>`}</td></tr>`).join("");e.innerHTML=`<h3 style="margin-bottom:15px;">🕘 历史版本</h3><table style="width:100%;border-collapse:collapse;"><thead><tr style="background:#f8f9fa;text-align:left;"><th style="padding:10px;">版本</th><th style="padding:10px;">作者</th><th style="padding:10px;">说明</th><th style="padding:10px;">时间</th><th style="padding:10px;">操作</th></tr></thead><tbody>${n}</tbody></table>`}catch(t){e.innerHTML=""}}function showDiffModal(e,t){const o=document.createElement("div");o.className="modal-backdrop",o.style.cssText="position: fixed; top: 0; left: 0; width: 100%; height: 100%; background: rgba(0,0,0,0.5); display: flex; justify-content: center; align-items: center; z-index: 1000;",o.innerHTML=`<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:900px;"><h3 style="margin-bottom:20px;">🔍 ${escapeHtml(e)}</h3><pre style="max-height:60vh;overflow:auto;background:#f8f9fa;padding:15px;border-radius:8px;white-space:pre-wrap;">${escapeHtml(t||"内容相同")}</pre><button type="button" class="btn btn-secondary modal-cancel-btn">关闭</button></div>`,o.querySelector(".modal-cancel-btn").addEventListener("click",()=>o.remove()),o.addEventListener("click",e=>{e.target===o&&o.remove()}),document.body.appendChild(o)}async function showPreambleDiff(e){try{const t=await fetch(`/api/preamble/diff?from=${e}`,{headers:getAuthHeaders()});if(!t.ok)throw new Error("获取版本差异失败");const o=await t.json();showDiffModal(`v${o.from} → v${o.to}`,o.diff)}catch(e){showAlert(e.message,"error")}}async function rollbackPreamble(e){if(confirm(`确定要回滚到 v${e} 吗？`))try{const t=await fetch(`/api/preamble/versions/${e}/rollback`,{method:"POST",headers:getAuthHeaders(),body:JSON.stringify({})});if(403===t.status)return void showAlert("❌ 无权限回滚配置","error");if(!t.ok)throw new Error("回滚Preamble失败");if(202===t.status)return showAlert(`📝 已提交回滚到 v${e} 的草稿，等待其他管理员发布`),void loadPreambleDrafts();showAlert(`✅ 已回滚到 v${e}`),loadPreamble()}catch(e){showAlert(e.message,"error")}}let preambleDrafts={};async function loadPreambleDrafts(){const e=document.getElementById("preambleDrafts");try{const t=await fetch("/api/preamble/drafts",{headers:getAuthHeaders()});if(!t.ok)return void(e.innerHTML="");const o=await t.json();if(preambleDrafts=Object.fromEntries(o.map(e=>[e.id,e])),0===o.length)return void(e.innerHTML="");const n=o.map(e=>`<tr style="border-bottom:1px solid #e9ecef;"><td style="padding:10px;font-weight:600;">#${e.id}</td><td style="padding:10px;">${escapeHtml(e.author)}</td><td style="padding:10px;">${escapeHtml(e.comment||"")}</td><td style="padding:10px;">${new Date(e.created_at).toLocaleString("zh-CN")}</td><td style="padding:10px;"><button class="btn btn-secondary" onclick="showDraftDiff(${e.id})">🔍 差异</button> ${e.can_publish?`<button class="btn btn-primary" onclick="publishPreambleDraft(${e.id})">🚀 发布</button>`:""} <button class="btn btn-danger" onclick="rejectPreambleDraft(${e.id})">${e.can_publish?"❎ 拒绝":"↩️ 撤回"}</button></td></tr>`).join("");e.innerHTML=`<h3 style="margin-bottom:15px;">📝 待审批草稿</h3><table style="width:100%;border-collapse:collapse;"><thead><tr style="background:#f8f9fa;text-align:left;"><th style="padding:10px;">草稿</th><th style="padding:10px;">提交者</th><th style="padding:10px;">说明</th><th style="padding:10px;">时间</th><th style="padding:10px;">操作</th></tr></thead><tbody>${n}</tbody></table>`}catch(t){e.innerHTML=""}}function showDraftDiff(e){const t=preambleDrafts[e];t&&showDiffModal(`草稿 #${e}`,t.diff)}async function publishPreambleDraft(e){if(confirm(`确定要发布草稿 #${e} 吗？发布后立即生效。`))try{const t=await fetch(`/api/preamble/drafts/${e}/publish`,{method:"POST",headers:getAuthHeaders()});if(403===t.status)return void showAlert("❌ 不能发布自己提交的草稿","error");if(!t.ok)throw new Error("发布草稿失败");showAlert(`✅ 草稿 #${e} 已发布`),loadPreamble()}catch(e){showAlert(e.message,"error")}}async function rejectPreambleDraft(e){if(confirm(`确定要拒绝草稿 #${e} 吗？`))try{if(!(await fetch(`/api/preamble/drafts/${e}/reject`,{method:"POST",headers:getAuthHeaders()})).ok)throw new Error("拒绝草稿失败");showAlert(`✅ 草稿 #${e} 已拒绝`),loadPreambleDrafts()}catch(e){showAlert(e.message,"error")}}function validateFileType(e){const t="."+e.name.split(".").pop().toLowerCase();return!![".txt",".md",".json",".csv",".pdf",".docx",".xlsx"].includes(t)||(showAlert("不支持的文件类型，请选择 .txt, .md, .json, .csv, .pdf, .docx, .xlsx 文件","error"),!1)}function handleFileSelect(e){const t=e.target.files[0];t&&validateFileType(t)&&uploadDocument(t)}async function uploadDocument(e){try{const t=new FormData;t.append("filename",e.name),t.append("file",e);const o={Authorization:`Bearer ${authToken}`},n=await fetch("/api/documents/upload",{method:"POST",headers:o,body:t});if(!n.ok){const e=await n.json().catch(()=>({}));throw new Error(e.error||e.message||"上传文档失败")}showAlert("文档上传成功！"),document.getElementById("fileInput").value="",document.getElementById("documents").classList.contains("active")&&loadDocuments()}catch(e){showAlert(e.message,"error")}}function escapeHtml(e){const t={"&":"&amp;","<":"&lt;",">":"&gt;",'"':"&quot;","'":"&#039;"};return e.replace(/[&<>"']/g,e=>t[e])}function setupFileDragAndDrop(){const e=document.querySelector(".file-upload");function t(e){e.preventDefault(),e.stopPropagation()}function o(){e.style.background="#f8f9ff",e.style.borderColor="#667eea",e.style.transform="scale(1.02)"}function n(){e.style.background="",e.style.borderColor="",e.style.transform=""}e&&(["dragenter","dragover","dragleave","drop"].forEach(o=>{e.addEventListener(o,t,!1),document.body.addEventListener(o,t,!1)}),["dragenter","dragover"].forEach(t=>{e.addEventListener(t,o,!1)}),["dragleave","drop"].forEach(t=>{e.addEventListener(t,n,!1)}),e.addEventListener("drop",function(e){const t=e.dataTransfer.files;t.length>0&&function(e){const t=e[0];if(!validateFileType(t))return;uploadDocument(t)}(t)},!1))}async function loadUsers(){const e=document.getElementById("usersLoading"),t=document.getElementById("usersList");e.style.display="block",t.innerHTML="";try{const o=await fetch("/api/users",{headers:getAuthHeaders()});if(!o.ok)throw new Error("获取用户列表失败");const n=await o.json();if(e.style.display="none",0===n.length)return void(t.innerHTML='<p style="text-align: center; color: #6c757d; padding: 40px;">暂无用户。</p>');const r=document.createElement("div");r.style.cssText="background: #f8f9fa; border-radius: 10px; overflow: hidden;",r.innerHTML='<table style="width:100%;border-collapse:collapse;"><thead><tr style="background:#667eea;color:white;"><th style="padding:15px;text-align:left;">用户名</th><th style="padding:15px;text-align:left;">角色</th><th style="padding:15px;text-align:left;">状态</th><th style="padding:15px;text-align:left;">创建时间</th><th style="padding:15px;text-align:left;">更新时间</th><th style="padding:15px;text-align:center;">操作</th></tr></thead><tbody id="usersTableBody"></tbody></table>',t.appendChild(r);const a=document.getElementById("usersTableBody");n.forEach((e,t)=>{const o=document.createElement("tr");o.style.cssText=`background:${t%2==0?"white":"#f8f9fa"};border-bottom:1px solid #e9ecef`;const n=new Date(e.created_at).toLocaleString("zh-CN"),r=new Date(e.updated_at).toLocaleString("zh-CN"),s=ROLE_LABELS[e.role]||e.role,i=1===e.status?'<span style="background: #28a745; color: white; padding: 4px 12px; border-radius: 12px; font-size: 0.85rem;">✅ 启用</span>':'<span style="background: #dc3545; color: white; padding: 4px 12px; border-radius: 12px; font-size: 0.85rem;">❌ 禁用</span>';o.innerHTML=`<td style="padding:15px;font-weight:600;">${escapeHtml(e.username)}</td><td style="padding:15px;">${s}</td><td style="padding:15px;">${i}</td><td style="padding:15px;color:#6c757d;font-size:0.9rem;">${n}</td><td style="padding:15px;color:#6c757d;font-size:0.9rem;">${r}</td><td style="padding:15px;text-align:center;"><button class="btn btn-primary" onclick="editUser(${e.id})" style="padding:6px 12px;font-size:0.85rem;margin-right:5px;"> ✏️ 编辑 </button><button class="btn btn-danger" onclick="deleteUser(${e.id}, '${escapeHtml(e.username)}')" style="padding:6px 12px;font-size:0.85rem;"> 🗑️ 删除 </button></td>`,a.appendChild(o)})}catch(o){e.style.display="none",showAlert(o.message,"error"),t.innerHTML='<p style="text-align: center; color: #dc3545; padding: 40px;">加载用户失败。</p>'}}function showCreateUserModal(){const e=document.createElement("div");e.className="modal-backdrop",e.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);display:flex;justify-content:center;align-items:center;z-index:1000",e.innerHTML='<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:500px;"><h3 style="margin-bottom:20px;">➕ 创建新用户</h3><form id="createUserForm"><div class="form-group"><label>用户名：</label><input type="text" id="newUsername" class="form-control" placeholder="请输入用户名" required></div><div class="form-group"><label>密码：</label><input type="password" id="newPassword" class="form-control" placeholder="请输入密码" required></div><div class="form-group"><label>角色：</label><select id="newRole" class="form-control">'+roleOptions("viewer")+'</select></div><div class="form-group"><label>状态：</label><select id="newStatus" class="form-control"><option value="1" selected>✅ 启用</option><option value="0">❌ 禁用</option></select></div><div><button type="submit" class="btn btn-primary">💾 创建</button><button type="button" class="btn btn-secondary modal-cancel-btn">❌ 取消</button></div></form></div>',e.querySelector(".modal-cancel-btn").addEventListener("click",()=>e.remove()),e.addEventListener("click",t=>{t.target===e&&e.remove()}),e.querySelector("#createUserForm").addEventListener("submit",async t=>{t.preventDefault();const o=document.getElementById("newUsername").value.trim(),n=document.getElementById("newPassword").value,r=document.getElementById("newRole").value,a=parseInt(document.getElementById("newStatus").value);if(o&&n)try{const t=await fetch("/api/users",{method:"POST",headers:getAuthHeaders(),body:JSON.stringify({username:o,password:n,role:r,status:a})});if(!t.ok){const e=await t.json();throw new Error(e.error||"创建用户失败")}showAlert("用户创建成功！"),e.remove(),loadUsers()}catch(e){showAlert(e.message,"error")}else showAlert("请填写完整信息","error")}),document.body.appendChild(e)}async function editUser(e){try{const t=await fetch(`/api/users/${e}`,{headers:getAuthHeaders()});if(!t.ok)throw new Error("获取用户信息失败");const o=await t.json(),n=document.createElement("div");n.className="modal-backdrop",n.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);display:flex;justify-content:center;align-items:center;z-index:1000",n.innerHTML=`<div class="modal-content" style="background:white;padding:30px;border-radius:15px;width:90%;max-width:500px;"><h3 style="margin-bottom:20px;">✏️ 编辑用户:${escapeHtml(o.username)}</h3><form id="editUserForm"><div class="form-group"><label>新密码（留空则不修改）：</label><input type="password" id="editPassword" class="form-control" placeholder="请输入新密码"></div><div class="form-group"><label>角色：</label><select id="editRole" class="form-control">${roleOptions(o.role)}</select></div><div class="form-group"><label>状态：</label><select id="editStatus" class="form-control"><option value="1" ${1===o.status?"selected":""}>✅ 启用</option><option value="0" ${0===o.status?"selected":""}>❌ 禁用</option></select></div><div><button type="submit" class="btn btn-primary">💾 保存</button><button type="button" class="btn btn-secondary modal-cancel-btn">❌ 取消</button></div></form></div>`,n.querySelector(".modal-cancel-btn").addEventListener("click",()=>n.remove()),n.addEventListener("click",e=>{e.target===n&&n.remove()}),n.querySelector("#editUserForm").addEventListener("submit",async t=>{t.preventDefault();const r=document.getElementById("editPassword").value.trim(),a=document.getElementById("editRole").value,s=parseInt(document.getElementById("editStatus").value),i={};if(r&&(i.password=r),a!==o.role&&(i.role=a),s!==o.status&&(i.status=s),0!==Object.keys(i).length)try{const t=await fetch(`/api/users/${e}`,{method:"PUT",headers:getAuthHeaders(),body:JSON.stringify(i)});if(!t.ok){const e=await t.json();throw new Error(e.error||"更新用户失败")}showAlert("用户更新成功！"),n.remove(),loadUsers()}catch(e){showAlert(e.message,"error")}else showAlert("没有需要更新的内容","error")}),document.body.appendChild(n)}catch(e){showAlert(e.message,"error")}}async function deleteUser(e,t){if(confirm(`确定要删除用户 "${t}" 吗？此操作不可恢复。`))try{const t=await fetch(`/api/users/${e}`,{method:"DELETE",headers:getAuthHeaders()});if(!t.ok){const e=await t.json();throw new Error(e.error||"删除用户失败")}showAlert("用户删除成功！"),loadUsers()}catch(e){showAlert(e.message,"error")}}document.addEventListener("DOMContentLoaded",async function(){await checkAuth()&&(document.getElementById("preambleForm").addEventListener("submit",async function(e){e.preventDefault();const t=document.getElementById("preambleContent").value.trim(),c=document.getElementById("preambleComment").value.trim();if(t)try{const o=await fetch("/api/preamble",{method:"PUT",headers:getAuthHeaders(),body:JSON.stringify({content:t,comment:c||null})});if(403===o.status)return void showAlert("❌ 无权限保存配置","error");if(400===o.status){const d=await o.json().catch(()=>({}));return void showAlert(`❌ ${d.error||"Preamble内容无效"}`,"error")}if(!o.ok)throw new Error("保存Preamble配置失败");if(document.getElementById("preambleComment").value="",202===o.status)return showAlert("📝 已提交草稿，等待其他管理员发布"),void loadPreambleDrafts();showAlert("✅ Preamble配置保存成功！"),loadPreambleVersions()}catch(e){showAlert(e.message,"error")}else showAlert("请输入Preamble内容","error")}),document.getElementById("createDocumentForm").addEventListener("submit",async function(e){e.preventDefault();const t=document.getElementById("documentFilename").value.trim(),o=document.getElementById("documentContent").value.trim();if(t&&o)try{if(!(await fetch("/api/documents",{method:"POST",headers:getAuthHeaders(),body:JSON.stringify({filename:t,content:o})})).ok)throw new Error("创建文档失败");showAlert("文档创建成功！"),this.reset()}catch(e){showAlert(e.message,"error")}else showAlert("请填写完整信息","error")}),document.getElementById("prevPage").addEventListener("click",goToPreviousPage),document.getElementById("nextPage").addEventListener("click",goToNextPage),setupFileDragAndDrop(),window.addEventListener("hashchange",loadSectionFromHash),loadSectionFromHash())});let conversationCurrentPage=0,conversationPageSize=20,conversationTotal=0,conversationSearchQuery="",messageCurrentPage=0,messagePageSize=50;async function loadConversations(){const e=document.getElementById("conversationsLoading"),t=document.getElementById("conversationsList"),o=document.getElementById("conversationStats");e.style.display="block",t.innerHTML="",o.style.display="none";try{let e=`/api/admin/conversations?limit=${conversationPageSize}&offset=${conversationCurrentPage*conversationPageSize}`;conversationSearchQuery&&(e+=`&search=${encodeURIComponent(conversationSearchQuery)}`);const t=await fetch(e,{headers:getAuthHeaders()});if(!t.ok)throw new Error(`HTTP error! status: ${t.status}`);const o=await t.json();await loadConversationStats(),displayConversations(o.conversations),updateConversationPagination(o.total,o.has_more)}catch(e){console.error("Error loading conversations:",e),showAlert("加载对话记录失败: "+e.message,"error"),t.innerHTML='<div style="text-align:center;padding:40px;color:#6c757d;"><p>暂无对话记录</p><p>或者需要管理员权限才能查看</p></div>'}finally{e.style.display="none"}}async function loadConversationStats(){try{const e=await fetch("/api/admin/conversations/stats",{headers:getAuthHeaders()});if(!e.ok)throw new Error(`HTTP error! status: ${e.status}`);const t=await e.json();t&&(updateConversationStats(t),document.getElementById("conversationStats").style.display="grid")}catch(e){console.error("Error loading conversation stats:",e)}}function updateConversationStats(e){document.getElementById("totalConversations").textContent=e.total_conversations||0,document.getElementById("activeConversations").textContent=e.active_conversations||0,document.getElementById("totalMessages").textContent=e.total_messages||0,document.getElementById("todayConversations").textContent=e.today_conversations||0}function displayConversations(e){const t=document.getElementById("conversationsList");e&&0!==e.length?t.innerHTML=e.map(e=>`<div class="document-item" style="border-left-color:${getConversationStatusColor(e.status)};"><div style="display:flex;justify-content:space-between;align-items:flex-start;margin-bottom:10px;"><h3 style="margin:0;color:#495057;">对话 #${e.id.substring(0,8)}...</h3><span class="badge" style="background-color:${getConversationStatusColor(e.status)};color:white;padding:4px 8px;border-radius:4px;font-size:0.8rem;"> ${getConversationStatusText(e.status)}</span></div><div class="document-meta"><strong>用户ID:</strong> ${e.user_id}<br><strong>创建时间:</strong> ${formatDateTime(e.created_at)}<br><strong>最后消息:</strong> ${e.updated_at?formatDateTime(e.updated_at):"无"}<br> ${e.title?`<strong>标题:</strong> ${e.title}<br>`:""} </div><div class="document-actions"><button class="btn btn-primary" onclick="viewConversationDetails('${e.id}')"> 👁️ 查看详情 </button><button class="btn btn-secondary" onclick="viewConversationMessages('${e.id}')"> 💬 查看消息 </button> ${"active"===e.status?`\n                    <button class="btn btn-warning" onclick="closeConversation('${e.id}')">\n                        🔒 关闭对话\n                    </button>\n                `:""} <button class="btn btn-danger" onclick="deleteConversation('${e.id}')"> 🗑️ 删除 </button></div></div>`).join(""):t.innerHTML='<div style="text-align:center;padding:40px;color:#6c757d;"><p>暂无对话记录</p></div>'}function getConversationStatusColor(e){switch(e){case"active":return"#28a745";case"closed":return"#6c757d";case"escalated":return"#ffc107";default:return"#6c757d"}}function getConversationStatusText(e){switch(e){case"active":return"活跃";case"closed":return"已关闭";case"escalated":return"已升级";default:return"未知"}}function formatDateTime(e){return new Date(e).toLocaleString("zh-CN",{year:"numeric",month:"2-digit",day:"2-digit",hour:"2-digit",minute:"2-digit",second:"2-digit"})}async function viewConversationDetails(e){try{const t=await fetch(`/api/conversation/${e}`,{headers:getAuthHeaders()});if(!t.ok)throw new Error(`HTTP error! status: ${t.status}`);const o=await t.json(),n=document.createElement("div");n.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);z-index:1000;display:flex;align-items:center;justify-content:center",n.innerHTML=`<div style="background:white;padding:20px;border-radius:10px;max-width:600px;width:90%;max-height:80%;overflow-y:auto;"><h3>对话详情</h3><div style="margin-bottom:15px;"><strong>对话ID:</strong> ${o.id}<br><strong>用户ID:</strong> ${o.user_id}<br><strong>状态:</strong> ${getConversationStatusText(o.status)}<br><strong>创建时间:</strong> ${formatDateTime(o.created_at)}<br><strong>更新时间:</strong> ${formatDateTime(o.updated_at)}<br> ${o.title?`<strong>标题:</strong> ${o.title}<br>`:""}\n                    ${o.metadata?`<strong>元数据:</strong> <pre style="background: #f8f9fa; padding: 10px; border-radius: 5px; overflow-x: auto;">${JSON.stringify(o.metadata,null,2)}</pre>`:""}</div><div style="text-align:right;"><button class="btn btn-secondary" onclick="this.closest('.modal').remove()">关闭</button></div></div>`,n.className="modal",document.body.appendChild(n),n.addEventListener("click",e=>{e.target===n&&n.remove()})}catch(e){console.error("Error viewing conversation details:",e),showAlert("查看对话详情失败: "+e.message,"error")}}async function viewConversationMessages(e){messageCurrentPage=0,await loadConversationMessages(e,0)}async function loadConversationMessages(e,t){try{const o=t*messagePageSize,n=await fetch(`/api/conversation/${e}/messages?limit=${messagePageSize}&offset=${o}`,{headers:getAuthHeaders()});if(!n.ok)throw new Error(`HTTP error! status: ${n.status}`);const r=await n.json();messageCurrentPage=t;const a=r.length===messagePageSize;let s=document.getElementById("conversationMessagesModal");s||(s=document.createElement("div"),s.id="conversationMessagesModal",s.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);z-index:1000;display:flex;align-items:center;justify-content:center",document.body.appendChild(s),s.addEventListener("click",e=>{e.target===s&&s.remove()}));const i=r.length>0?r.map(e=>`<div style="margin-bottom:15px;padding:10px;border-radius:5px;background:${"user"===e.role?"#e3f2fd":"#f5f5f5"};"><div style="font-weight:bold;margin-bottom:5px;color:${"user"===e.role?"#1976d2":"#666"};"> ${"user"===e.role?"👤 用户":"🤖 助手"}- ${formatDateTime(e.created_at)}</div><div style="white-space:pre-wrap;">${escapeHtml(e.content)}</div> ${e.metadata?`<div style="font-size: 0.8rem; color: #666; margin-top: 5px;">元数据: ${escapeHtml(JSON.stringify(e.metadata))}</div>`:""}\n            </div>\n        `).join(""):'<p style="text-align: center; color: #666;">暂无消息</p>',c=r.length>0?`<div style="display:flex;justify-content:center;align-items:center;gap:10px;margin-top:15px;"><button class="btn btn-secondary" id="messagePrevPage" style="padding:8px 16px;font-size:0.9rem;${0===t?"visibility:hidden;":""}">⬅️ 上一页</button><span style="color:#6c757d;font-size:1.0rem;">第 ${t+1}页</span><button class="btn btn-secondary" id="messageNextPage" style="padding:8px 16px;font-size:0.9rem;${a?"":"visibility:hidden;"}">下一页 ➡️</button></div>`:"";s.innerHTML=`<div style="background:white;padding:20px;border-radius:10px;max-width:900px;width:90%;max-height:85%;overflow-y:auto;position:relative;"><button style="position:absolute;top:10px;right:10px;background:rgba(0,0,0,0.7);color:white;border:none;border-radius:50%;width:32px;height:32px;cursor:pointer;z-index:1001;" onclick="document.getElementById('conversationMessagesModal').remove()">❌</button><h3 style="margin-bottom:15px;">💬 对话消息历史</h3><div style="max-height:500px;overflow-y:auto;border:1px solid #ddd;padding:15px;border-radius:5px;background:#fafafa;"> ${i}</div> ${c}</div>`;const d=s.querySelector("#messagePrevPage"),l=s.querySelector("#messageNextPage");d&&(d.onclick=()=>{messageCurrentPage>0&&loadConversationMessages(e,messageCurrentPage-1)}),l&&(l.onclick=()=>{a&&loadConversationMessages(e,messageCurrentPage+1)})}catch(e){console.error("Error viewing conversation messages:",e),showAlert("查看对话消息失败: "+e.message,"error")}}async function closeConversation(e){if(confirm("确定要关闭这个对话吗？"))try{const t=await fetch(`/api/conversation/${e}`,{method:"PUT",headers:getAuthHeaders(),body:JSON.stringify({status:"closed"})});if(!t.ok)throw new Error(`HTTP error! status: ${t.status}`);showAlert("对话已关闭","success"),loadConversations()}catch(e){console.error("Error closing conversation:",e),showAlert("关闭对话失败: "+e.message,"error")}}async function deleteConversation(e){if(confirm("确定要删除这个对话吗？此操作不可恢复！"))try{const t=await fetch(`/api/conversation/${e}`,{method:"DELETE",headers:getAuthHeaders()});if(!t.ok)throw new Error(`HTTP error! status: ${t.status}`);showAlert("对话已删除","success"),loadConversations()}catch(e){console.error("Error deleting conversation:",e),showAlert("删除对话失败: "+e.message,"error")}}function searchConversations(){const e=document.getElementById("conversationSearch");conversationSearchQuery=e.value.trim(),conversationCurrentPage=0,loadConversations()}function updateConversationPagination(e,t){conversationTotal=e;const o=document.getElementById("conversationPagination"),n=document.getElementById("conversationPrevPage"),r=document.getElementById("conversationNextPage"),a=document.getElementById("conversationPageInfo");if(e<=conversationPageSize)return void(o.style.display="none");o.style.display="block";const s=conversationCurrentPage*conversationPageSize+1,i=Math.min((conversationCurrentPage+1)*conversationPageSize,e);a.textContent=`第 ${s}-${i} 条，共 ${e} 条`,n.disabled=0===conversationCurrentPage,r.disabled=!t,n.onclick=()=>{conversationCurrentPage>0&&(conversationCurrentPage--,loadConversations())},r.onclick=()=>{t&&(conversationCurrentPage++,loadConversations())}}async function cleanupOldConversations(){const e=prompt("请输入要保留的天数（超过此天数的已关闭对话将被删除）:","30");if(!e)return;const t=parseInt(e);if(isNaN(t)||t<1)showAlert("请输入有效的天数（大于0）","error");else if(confirm(`确定要删除 ${t} 天前的已关闭对话记录吗？\n\n此操作不可恢复！`))try{const e=await fetch("/api/admin/conversations/cleanup",{method:"POST",headers:getAuthHeaders(),body:JSON.stringify({days_to_keep:t})});if(!e.ok)throw new Error(`HTTP error! status: ${e.status}`);const o=await e.json();o.success?(showAlert(o.message,"success"),loadConversations()):showAlert("清理失败: "+o.error,"error")}catch(e){console.error("Error cleaning up conversations:",e),showAlert("清理对话记录失败: "+e.message,"error")}}function showCleanupDialog(){const e=document.createElement("div");e.style.cssText="position:fixed;top:0;left:0;width:100%;height:100%;background:rgba(0,0,0,0.5);z-index:1000;display:flex;align-items:center;justify-content:center",e.innerHTML='<div style="background:white;padding:20px;border-radius:10px;max-width:500px;width:90%;"><h3>🗑️ 清理旧对话记录</h3><div style="margin:20px 0;"><p>此功能将删除超过指定天数的已关闭对话记录。</p><p style="color:#dc3545;font-weight:bold;">⚠️ 警告：此操作不可恢复！</p><div style="margin:20px 0;"><label for="cleanupDays" style="display:block;margin-bottom:5px;font-weight:bold;"> 保留天数： </label><input type="number" id="cleanupDays" value="30" min="1" max="365" style="width:100%;padding:8px;border:1px solid #ddd;border-radius:4px;"><small style="color:#666;">超过此天数的已关闭对话将被永久删除</small></div><div style="background:#f8f9fa;padding:15px;border-radius:5px;margin:15px 0;"><h4 style="margin:0 0 10px 0;color:#495057;">清理规则：</h4><ul style="margin:0;padding-left:20px;color:#6c757d;"><li>只删除状态为"已关闭"的对话</li><li>活跃对话和已升级对话不会被删除</li><li>相关消息记录也会被一并删除</li></ul></div></div><div style="text-align:right;display:flex;gap:10px;justify-content:flex-end;"><button class="btn btn-secondary" onclick="this.closest(\'.modal\').remove()">取消</button><button class="btn btn-danger" onclick="executeCleanup(this.closest(\'.modal\'))">确认清理</button></div></div>',e.className="modal",document.body.appendChild(e),e.addEventListener("click",t=>{t.target===e&&e.remove()})}async function executeCleanup(e){const t=e.querySelector("#cleanupDays"),o=parseInt(t.value);if(isNaN(o)||o<1)showAlert("请输入有效的天数（大于0）","error");else{e.remove();try{const e=await fetch("/api/admin/conversations/cleanup",{method:"POST",headers:getAuthHeaders(),body:JSON.stringify({days_to_keep:o})});if(!e.ok)throw new Error(`HTTP error! status: ${e.status}`);const t=await e.json();t.success?(showAlert(t.message,"success"),loadConversations()):showAlert("清理失败: "+t.error,"error")}catch(e){console.error("Error cleaning up conversations:",e),showAlert("清理对话记录失败: "+e.message,"error")}}}
\ No newline at end of file
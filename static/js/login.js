@@ -1 +1 @@
-function showAlert(e,t){const o=document.getElementById("alertBox");o.textContent=e,o.className=`alert alert-${t} show`,setTimeout(()=>{o.classList.remove("show")},3e3)}document.getElementById("loginForm").addEventListener("submit",async e=>{e.preventDefault();const t=document.getElementById("username").value.trim(),o=document.getElementById("password").value,n=document.getElementById("loginBtn");document.getElementById("alertBox");if(t&&o){n.disabled=!0,n.innerHTML='<span class="loading-spinner"></span>登录中...';try{const e=await fetch("/api/auth/login",{method:"POST",headers:{"Content-Type":"application/json"},body:JSON.stringify({username:t,password:o})}),r=await e.json();e.ok?(localStorage.setItem("authToken",r.token),localStorage.setItem("username",r.username),localStorage.setItem("userRole",r.role),showAlert("登录成功！正在跳转...","success"),setTimeout(()=>{window.location.href="/admin"},500)):(showAlert(r.error||"登录失败，请检查用户名和密码","error"),n.disabled=!1,n.innerHTML="登录")}catch(e){console.error("Login error:",e),showAlert("网络错误，请稍后重试","error"),n.disabled=!1,n.innerHTML="登录"}}else showAlert("请填写用户名和密码","error")}),window.addEventListener("DOMContentLoaded",()=>{const e=localStorage.getItem("authToken");e&&fetch("/api/auth/verify",{method:"POST",headers:{"Content-Type":"application/json",Authorization:`Bearer ${e}`}}).then(e=>{e.ok&&(window.location.href="/admin")}).catch(e=>{console.error("Token verification error:",e)})}),document.getElementById("password").addEventListener("keypress",e=>{"Enter"===e.key&&(e.preventDefault(),document.getElementById("loginForm").dispatchEvent(new Event("submit")))});
\ No newline at end of file
+const MFA_CODE_REQUIRED="Two-factor code required";function showPasswordForm(e){document.getElementById("loginForm").style.display="none",document.getElementById("ssoBtn").style.display="none",document.getElementById("passwordForm").style.display="block",document.getElementById("currentPassword").value=e||"",document.getElementById(e?"newPassword":"currentPassword").focus()}async function showEnrollForm(){document.getElementById("loginForm").style.display="none",document.getElementById("ssoBtn").style.display="none",document.getElementById("enrollForm").style.display="block";try{const e=await fetch("/api/auth/totp/setup",{method:"POST",headers:{Authorization:`Bearer ${localStorage.getItem("authToken")}`}}),t=await e.json();if(!e.ok)return void showAlert(t.error||"生成两步验证密钥失败","error");document.getElementById("totpSecret").value=t.secret,document.getElementById("totpUri").href=t.otpauth_uri,document.getElementById("enrollCode").focus()}catch(e){console.error("TOTP setup error:",e),showAlert("网络错误，请稍后重试","error")}}function showMfaForm(e){document.getElementById("loginForm").style.display="none",document.getElementById("ssoBtn").style.display="none",document.getElementById("mfaForm").style.display="block",document.getElementById("mfaForm").dataset.ticket=e,document.getElementById("mfaCode").focus()}function showAlert(e,t){const o=document.getElementById("alertBox");o.textContent=e,o.className=`alert alert-${t} show`,setTimeout(()=>{o.classList.remove("show")},3e3)}function handleSsoCallback(){if(!window.location.hash)return!1;const e=new URLSearchParams(window.location.hash.slice(1));return history.replaceState(null,"",window.location.pathname),e.get("error")?(showAlert(e.get("error"),"error"),!1):!!e.get("sso_code")&&(completeSsoLogin(e.get("sso_code")),!0)}async function completeSsoLogin(e){try{const t=await fetch("/api/auth/oidc/token",{method:"POST",headers:{"Content-Type":"application/json"},body:JSON.stringify({code:e})}),o=await t.json();if(!t.ok)return showAlert(o.error||"单点登录失败","error"),void loadSsoConfig();if(o.mfa_ticket)return void showMfaForm(o.mfa_ticket);rigAuth.saveTokens(o),window.location.href="/admin"}catch(e){console.error("SSO login error:",e),showAlert("网络错误，请稍后重试","error")}}async function loadSsoConfig(){try{const e=await fetch("/api/auth/oidc/config");(await e.json()).enabled&&(document.getElementById("ssoBtn").style.display="block")}catch(e){console.error("SSO config error:",e)}}document.getElementById("loginForm").addEventListener("submit",async e=>{e.preventDefault();const t=document.getElementById("username").value.trim(),o=document.getElementById("password").value,r=document.getElementById("otp").value.trim(),n=document.getElementById("loginBtn");document.getElementById("alertBox");if(t&&o){n.disabled=!0,n.innerHTML='<span class="loading-spinner"></span>登录中...';try{const e=await fetch("/api/auth/login",{method:"POST",headers:{"Content-Type":"application/json"},body:JSON.stringify({username:t,password:o,otp:r||void 0})}),s=await e.json();if(e.ok){if(localStorage.setItem("authToken",s.token),localStorage.setItem("refreshToken",s.refresh_token),localStorage.setItem("username",s.username),localStorage.setItem("userRole",s.role),s.must_change_password)return void showPasswordForm(o);if(s.mfa_enrollment_required)return void showEnrollForm();showAlert("登录成功！正在跳转...","success"),setTimeout(()=>{window.location.href="/admin"},500)}else s.error===MFA_CODE_REQUIRED?(document.getElementById("otpGroup").style.display="block",document.getElementById("otp").focus(),showAlert("请输入两步验证码","error"),n.disabled=!1,n.innerHTML="登录"):(showAlert(s.error||"登录失败，请检查用户名和密码","error"),n.disabled=!1,n.innerHTML="登录")}catch(e){console.error("Login error:",e),showAlert("网络错误，请稍后重试","error"),n.disabled=!1,n.innerHTML="登录"}}else showAlert("请填写用户名和密码","error")}),document.getElementById("passwordForm").addEventListener("submit",async e=>{e.preventDefault();const t=document.getElementById("currentPassword").value,o=document.getElementById("newPassword").value,n=document.getElementById("confirmPassword").value,r=document.getElementById("passwordBtn");if(o===n){r.disabled=!0;try{const e=await fetch("/api/auth/password",{method:"POST",headers:{"Content-Type":"application/json",Authorization:`Bearer ${localStorage.getItem("authToken")}`},body:JSON.stringify({current_password:t,new_password:o})}),n=await e.json();if(e.ok){if(rigAuth.saveTokens(n),n.mfa_enrollment_required)return document.getElementById("passwordForm").style.display="none",void showEnrollForm();showAlert("密码已修改！正在跳转...","success"),setTimeout(()=>{window.location.href="/admin"},500)}else showAlert(n.error||"修改密码失败","error"),r.disabled=!1}catch(e){console.error("Change password error:",e),showAlert("网络错误，请稍后重试","error"),r.disabled=!1}}else showAlert("两次输入的新密码不一致","error")}),document.getElementById("enrollForm").addEventListener("submit",async e=>{e.preventDefault();const t=document.getElementById("enrollCode").value.trim(),o=document.getElementById("enrollBtn");o.disabled=!0;try{const e=await fetch("/api/auth/totp/confirm",{method:"POST",headers:{"Content-Type":"application/json",Authorization:`Bearer ${localStorage.getItem("authToken")}`},body:JSON.stringify({code:t})}),n=await e.json();e.ok?(rigAuth.saveTokens(n),document.getElementById("enrollForm").style.display="none",document.getElementById("recoveryCodes").textContent=n.recovery_codes.join("\n"),document.getElementById("recoveryBox").style.display="block"):(showAlert(n.error||"验证码错误","error"),o.disabled=!1)}catch(e){console.error("TOTP confirm error:",e),showAlert("网络错误，请稍后重试","error"),o.disabled=!1}}),document.getElementById("recoveryDoneBtn").addEventListener("click",()=>{window.location.href="/admin"}),document.getElementById("mfaForm").addEventListener("submit",async e=>{e.preventDefault();const t=document.getElementById("mfaForm"),o=document.getElementById("mfaCode").value.trim(),n=document.getElementById("mfaBtn");n.disabled=!0;try{const e=await fetch("/api/auth/login/mfa",{method:"POST",headers:{"Content-Type":"application/json"},body:JSON.stringify({ticket:t.dataset.ticket,otp:o})}),r=await e.json();e.ok?(rigAuth.saveTokens(r),showAlert("登录成功！正在跳转...","success"),setTimeout(()=>{window.location.href="/admin"},500)):(showAlert(r.error||"验证码错误","error"),n.disabled=!1)}catch(e){console.error("MFA login error:",e),showAlert("网络错误，请稍后重试","error"),n.disabled=!1}}),window.addEventListener("DOMContentLoaded",()=>{if(handleSsoCallback())return;loadSsoConfig();const e=localStorage.getItem("authToken");e&&fetch("/api/auth/verify",{method:"POST",headers:{"Content-Type":"application/json",Authorization:`Bearer ${e}`}}).then(e=>e.ok?e.json():null).then(e=>{e&&(e.must_change_password?showPasswordForm(""):e.mfa_enrollment_required?showEnrollForm():window.location.href="/admin")}).catch(e=>{console.error("Token verification error:",e)})}),document.getElementById("password").addEventListener("keypress",e=>{"Enter"===e.key&&(e.preventDefault(),document.getElementById("loginForm").dispatchEvent(new Event("submit")))});
\ No newline at end of file